@@ -8,8 +8,9 @@ use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::json_types::{ValidAccountId, U128};
 use near_sdk::{
     assert_one_yocto, env, near_bindgen, 
-    AccountId, Balance, PromiseResult, StorageUsage,
+    AccountId, Balance, Gas, PromiseOrValue, PromiseResult, StorageUsage,
 };
+use crate::events::{Event, WithdrawEvent};
 use crate::legacy::AccountV1;
 use crate::utils::{ext_self, GAS_FOR_FT_TRANSFER, GAS_FOR_RESOLVE_TRANSFER};
 use crate::*;
@@ -39,6 +40,15 @@ const ACC_ID_AS_CLT_KEY_STORAGE: StorageUsage = ACC_ID_AS_KEY_STORAGE + 1;
 pub const INIT_ACCOUNT_STORAGE: StorageUsage =
     ACC_ID_AS_CLT_KEY_STORAGE + 1 + U128_STORAGE + U32_STORAGE + U32_STORAGE + U64_STORAGE;
 
+/// Storage charged per token in `tokens`: collection key prefix + token id key + balance.
+pub const TOKEN_STORAGE: StorageUsage = KEY_PREFIX_ACC + ACC_ID_AS_KEY_STORAGE + U128_STORAGE;
+/// Storage charged per token in `legacy_tokens`: token id key + balance.
+pub const LEGACY_TOKEN_STORAGE: StorageUsage = ACC_ID_AS_KEY_STORAGE + U128_STORAGE;
+
+/// Bytes the runtime actually uses to register an account with max length id,
+/// measured by `test_storage_measured_account_bytes`. Default for minimal storage deposit.
+pub const MEASURED_ACCOUNT_STORAGE: StorageUsage = 729;
+
 #[derive(BorshDeserialize, BorshSerialize)]
 pub enum VAccount {
     V1(AccountV1),
@@ -62,6 +72,22 @@ impl From<Account> for VAccount {
 }
 
 
+/// Logs change of account's $NEAR storage balance with the reason, to make storage accounting traceable.
+pub(crate) fn log_near_amount_change(
+    account_id: &AccountId,
+    reason: &str,
+    prev_near_amount: Balance,
+    near_amount: Balance,
+) {
+    env::log(
+        format!(
+            "Account {} storage balance changed by {}: {} -> {}",
+            account_id, reason, prev_near_amount, near_amount
+        )
+        .as_bytes(),
+    );
+}
+
 /// Account deposits information and storage cost.
 #[derive(BorshSerialize, BorshDeserialize)]
 pub struct Account {
@@ -107,8 +133,13 @@ impl Account {
     }
 
     /// Deposit amount to the balance of given token,
-    /// if given token not register and not enough storage, deposit fails 
-    pub(crate) fn deposit_with_storage_check(&mut self, token: &AccountId, amount: Balance) -> bool { 
+    /// if given token not register and not enough storage at `byte_price`, deposit fails 
+    pub(crate) fn deposit_with_storage_check(
+        &mut self,
+        token: &AccountId,
+        amount: Balance,
+        byte_price: Balance,
+    ) -> bool { 
         if let Some(balance) = self.tokens.get(token) {
             // token has been registered, just add without storage check, 
             let new_balance = balance + amount;
@@ -121,7 +152,7 @@ impl Account {
         } else {
             // check storage after insert, if fail should unregister the token
             self.tokens.insert(token, &(amount));
-            if self.storage_usage() <= self.near_amount {
+            if self.storage_usage(byte_price) <= self.near_amount {
                 true
             } else {
                 self.tokens.remove(token);
@@ -159,18 +190,18 @@ impl Account {
 
     // [AUDIT_01]
     /// Returns amount of $NEAR necessary to cover storage used by this data structure.
-    pub fn storage_usage(&self) -> Balance {
+    pub fn storage_usage(&self, byte_price: Balance) -> Balance {
         (INIT_ACCOUNT_STORAGE + 
-            self.legacy_tokens.len() as u64 * (ACC_ID_AS_KEY_STORAGE + U128_STORAGE) + 
-            self.tokens.len() as u64 * (KEY_PREFIX_ACC + ACC_ID_AS_KEY_STORAGE + U128_STORAGE)
+            self.legacy_tokens.len() as u64 * LEGACY_TOKEN_STORAGE + 
+            self.tokens.len() as u64 * TOKEN_STORAGE
         ) as u128
-            * env::storage_byte_cost()
+            * byte_price
     }
 
     /// Returns how much NEAR is available for storage.
-    pub fn storage_available(&self) -> Balance {
+    pub fn storage_available(&self, byte_price: Balance) -> Balance {
         // [AUDIT_01] avoid math overflow
-        let locked = self.storage_usage();
+        let locked = self.storage_usage(byte_price);
         if self.near_amount > locked {
             self.near_amount - locked
         } else {
@@ -178,20 +209,6 @@ impl Account {
         }
     }
 
-    /// Asserts there is sufficient amount of $NEAR to cover storage usage.
-    pub fn assert_storage_usage(&self) {
-        assert!(
-            self.storage_usage() <= self.near_amount,
-            "{}",
-            ERR11_INSUFFICIENT_STORAGE
-        );
-    }
-
-    /// Returns minimal account deposit storage usage possible.
-    pub fn min_storage_usage() -> Balance {
-        INIT_ACCOUNT_STORAGE as Balance * env::storage_byte_cost()
-    }
-
     /// Registers given token and set balance to 0.
     pub(crate) fn register(&mut self, token_ids: &Vec<ValidAccountId>) {
         for token_id in token_ids {
@@ -220,6 +237,9 @@ impl Contract {
     pub fn register_tokens(&mut self, token_ids: Vec<ValidAccountId>) {
         assert_one_yocto();
         self.assert_contract_running();
+        for token_id in &token_ids {
+            self.assert_token_not_banned(token_id.as_ref());
+        }
         let sender_id = env::predecessor_account_id();
         let mut account = self.internal_unwrap_account(&sender_id);
         account.register(&token_ids);
@@ -243,27 +263,72 @@ impl Contract {
     /// Withdraws given token from the deposits of given user.
     /// Optional unregister will try to remove record of this token from AccountDeposit for given user.
     /// Unregister will fail if the left over balance is non 0.
+    /// For accounts with withdraw delay creates pending withdrawal instead, unregister is not allowed then.
+    /// Amount must not be below min withdrawal of the token.
     #[payable]
     pub fn withdraw(
         &mut self,
         token_id: ValidAccountId,
         amount: U128,
         unregister: Option<bool>,
-    ) -> Promise {
+    ) -> PromiseOrValue<()> {
         assert_one_yocto();
         self.assert_contract_running();
         let token_id: AccountId = token_id.into();
         let amount: u128 = amount.into();
         assert!(amount > 0, "{}", ERR29_ILLEGAL_WITHDRAW_AMOUNT);
+        self.assert_min_withdrawal(&token_id, amount);
         let sender_id = env::predecessor_account_id();
         let mut account = self.internal_unwrap_account(&sender_id);
         // Note: subtraction and deregistration will be reverted if the promise fails.
         account.withdraw(&token_id, amount);
         if unregister == Some(true) {
+            assert!(
+                !self.is_withdraw_delayed(&sender_id),
+                "{}",
+                ERR184_UNREGISTER_WITH_WITHDRAW_DELAY
+            );
             account.unregister(&token_id);
         }
         self.internal_save_account(&sender_id, account);
-        self.internal_send_tokens(&sender_id, &token_id, amount)
+        self.internal_withdraw_tokens(&sender_id, &token_id, amount)
+    }
+
+    /// Withdraws whole balances of given tokens, or of all tokens of the account if None.
+    /// Zero balances and balances below min withdrawal of the token are skipped, tokens stay
+    /// registered. Every token is sent with its own callback returning the balance on failure,
+    /// so the call must have enough gas for all transfers.
+    #[payable]
+    pub fn withdraw_all(&mut self, token_ids: Option<Vec<ValidAccountId>>) {
+        assert_one_yocto();
+        self.assert_contract_running();
+        let sender_id = env::predecessor_account_id();
+        let mut account = self.internal_unwrap_account(&sender_id);
+        let token_ids: Vec<AccountId> = match token_ids {
+            Some(token_ids) => token_ids.into_iter().map(|token_id| token_id.into()).collect(),
+            None => account.get_tokens(),
+        };
+        let mut withdrawals = vec![];
+        for token_id in token_ids {
+            let amount = account.get_balance(&token_id).expect(ERR21_TOKEN_NOT_REG);
+            if amount == 0 || amount < self.internal_min_withdrawal(&token_id) {
+                continue;
+            }
+            account.withdraw(&token_id, amount);
+            withdrawals.push((token_id, amount));
+        }
+        if !self.is_withdraw_delayed(&sender_id) {
+            assert!(
+                env::prepaid_gas() - env::used_gas()
+                    >= withdrawals.len() as Gas * (GAS_FOR_FT_TRANSFER + GAS_FOR_RESOLVE_TRANSFER),
+                "{}",
+                ERR396_NOT_ENOUGH_GAS_FOR_WITHDRAWALS
+            );
+        }
+        self.internal_save_account(&sender_id, account);
+        for (token_id, amount) in withdrawals {
+            self.internal_withdraw_tokens(&sender_id, &token_id, amount);
+        }
     }
 
     #[private]
@@ -279,43 +344,15 @@ impl Contract {
             "{}",
             ERR25_CALLBACK_POST_WITHDRAW_INVALID
         );
+        self.internal_resolve_tokens(&token_id, amount.0);
         match env::promise_result(0) {
             PromiseResult::NotReady => unreachable!(),
-            PromiseResult::Successful(_) => {}
+            PromiseResult::Successful(_) => {
+                self.internal_record_withdraw_result(&token_id, true);
+            }
             PromiseResult::Failed => {
-                // This reverts the changes from withdraw function.
-                // If account doesn't exit, deposits to the owner's account as lostfound.
-                let mut failed = false;
-                if let Some(mut account) = self.internal_get_account(&sender_id) {
-                    if account.deposit_with_storage_check(&token_id, amount.0) {
-                        // cause storage already checked, here can directly save
-                        self.accounts.insert(&sender_id, &account.into());
-                    } else {
-                        // we can ensure that internal_get_account here would NOT cause a version upgrade, 
-                        // cause it is callback, the account must be the current version or non-exist,
-                        // so, here we can just leave it without insert, won't cause storage collection inconsistency.
-                        env::log(
-                            format!(
-                                "Account {} has not enough storage. Depositing to owner.",
-                                sender_id
-                            )
-                            .as_bytes(),
-                        );
-                        failed = true;
-                    }
-                } else {
-                    env::log(
-                        format!(
-                            "Account {} is not registered. Depositing to owner.",
-                            sender_id
-                        )
-                        .as_bytes(),
-                    );
-                    failed = true;
-                }
-                if failed {
-                    self.internal_lostfound(&token_id, amount.0);
-                }
+                self.internal_record_withdraw_result(&token_id, false);
+                self.internal_return_failed_withdrawal(&token_id, &sender_id, amount.0);
             }
         };
     }
@@ -325,18 +362,88 @@ impl Contract {
 
     /// Checks that account has enough storage to be stored and saves it into collection.
     /// This should be only place to directly use `self.accounts`.
+    /// Storage price may have risen since the account paid for its storage, so coverage at
+    /// the current price is only required once the account takes more storage.
     pub(crate) fn internal_save_account(&mut self, account_id: &AccountId, account: Account) {
-        account.assert_storage_usage();
+        let byte_price = self.internal_storage_byte_price();
+        let usage = account.storage_usage(byte_price);
+        if usage > account.near_amount {
+            let prev_usage = self
+                .internal_get_account(account_id)
+                .map_or(0, |prev| prev.storage_usage(byte_price));
+            assert!(usage <= prev_usage, "{}", ERR11_INSUFFICIENT_STORAGE);
+            log!(
+                "Account {} storage is not covered at current price, {} more needed",
+                account_id,
+                usage - account.near_amount
+            );
+        }
         self.accounts.insert(&account_id, &account.into());
+        self.account_index.insert(account_id);
+        self.internal_record_activity(account_id);
+    }
+
+    /// Returns amount of failed withdrawal to the account as a claim or deposit, or to its lost
+    /// tokens. Called in callbacks, so never panics.
+    pub(crate) fn internal_return_failed_withdrawal(
+        &mut self,
+        token_id: &AccountId,
+        sender_id: &AccountId,
+        amount: Balance,
+    ) {
+        let amount = U128(self.internal_receive_tokens(token_id, amount));
+        // Keeps the amount as a claim of the account, see `claim_failed_withdrawal`.
+        // Amounts of unregistered accounts are kept as lost tokens, see `claim_lost_tokens`.
+        if self.internal_get_account(sender_id).is_some()
+            && self.internal_add_failed_withdrawal(sender_id, token_id, amount.0)
+        {
+            return;
+        }
+        // With too many claims, this reverts the changes from withdraw function.
+        // If account doesn't exit, keeps the amount as its lost tokens.
+        let mut failed = false;
+        if let Some(mut account) = self.internal_get_account(sender_id) {
+            if account.deposit_with_storage_check(
+                token_id,
+                amount.0,
+                self.internal_storage_byte_price(),
+            ) {
+                // cause storage already checked, here can directly save
+                self.accounts.insert(sender_id, &account.into());
+            } else {
+                // we can ensure that internal_get_account here would NOT cause a version upgrade, 
+                // cause it is callback, the account must be the current version or non-exist,
+                // so, here we can just leave it without insert, won't cause storage collection inconsistency.
+                env::log(
+                    format!(
+                        "Account {} has not enough storage.",
+                        sender_id
+                    )
+                    .as_bytes(),
+                );
+                failed = true;
+            }
+        } else {
+            env::log(
+                format!(
+                    "Account {} is not registered.",
+                    sender_id
+                )
+                .as_bytes(),
+            );
+            failed = true;
+        }
+        if failed {
+            self.internal_add_lost_tokens(sender_id, token_id, amount.0);
+        }
     }
 
-    /// save token to owner account as lostfound, no need to care about storage
-    /// only global whitelisted token can be stored in lost-found
     pub(crate) fn internal_lostfound(&mut self, token_id: &AccountId, amount: u128) {
         if self.whitelisted_tokens.contains(token_id) {
             let mut lostfound = self.internal_unwrap_or_default_account(&self.owner_id);
             lostfound.deposit(token_id, amount);
             self.accounts.insert(&self.owner_id, &lostfound.into());
+            self.account_index.insert(&self.owner_id);
         } else {
             env::panic("ERR: non-whitelisted token can NOT deposit into lost-found.".as_bytes());
         }
@@ -349,21 +456,25 @@ impl Contract {
     /// This should be used when it's known that storage is prepaid.
     pub(crate) fn internal_register_account(&mut self, account_id: &AccountId, amount: Balance) {
         let mut account = self.internal_unwrap_or_default_account(&account_id);
+        let prev_near_amount = account.near_amount;
         account.near_amount += amount;
+        log_near_amount_change(account_id, "storage_deposit", prev_near_amount, account.near_amount);
         self.internal_save_account(&account_id, account);
     }
 
     /// storage withdraw
     pub(crate) fn internal_storage_withdraw(&mut self, account_id: &AccountId, amount: Balance) -> u128 {
         let mut account = self.internal_unwrap_account(&account_id);
-        let available = account.storage_available();
+        let available = account.storage_available(self.internal_storage_byte_price());
         assert!(available > 0, "ERR_NO_STORAGE_CAN_WITHDRAW");
         let mut withdraw_amount = amount;
         if amount == 0 {
             withdraw_amount = available;
         }
         assert!(withdraw_amount <= available, "ERR_STORAGE_WITHDRAW_TOO_MUCH");
+        let prev_near_amount = account.near_amount;
         account.near_amount -= withdraw_amount;
+        log_near_amount_change(account_id, "storage_withdraw", prev_near_amount, account.near_amount);
         self.internal_save_account(&account_id, account);
         withdraw_amount
     }
@@ -416,21 +527,46 @@ impl Contract {
 
     /// Sends given amount to given user and if it fails, returns it back to user's balance.
     /// Tokens must already be subtracted from internal balance.
+    /// For tokens in shares mode `amount` is in shares, the transfer is of their token value.
     pub(crate) fn internal_send_tokens(
-        &self,
+        &mut self,
+        sender_id: &AccountId,
+        token_id: &AccountId,
+        amount: Balance,
+    ) -> Promise {
+        self.internal_send_tokens_after(None, sender_id, token_id, amount)
+    }
+
+    /// Same as `internal_send_tokens`, but the transfer waits for `after` promise if given.
+    /// Chaining a promise with the result of `internal_send_tokens` would replace what its
+    /// callback waits for, so the chain is built here.
+    pub(crate) fn internal_send_tokens_after(
+        &mut self,
+        after: Option<Promise>,
         sender_id: &AccountId,
         token_id: &AccountId,
         amount: Balance,
     ) -> Promise {
-        ext_fungible_token::ft_transfer(
+        let amount = self.internal_release_tokens(token_id, amount);
+        Event::Withdraw(vec![WithdrawEvent {
+            account_id: sender_id.clone(),
+            token_id: token_id.clone(),
+            amount: U128(amount),
+        }])
+        .emit();
+        let transfer = ext_fungible_token::ft_transfer(
             sender_id.clone(),
             U128(amount),
             None,
             token_id,
             1,
             GAS_FOR_FT_TRANSFER,
-        )
-        .then(ext_self::exchange_callback_post_withdraw(
+        );
+        let transfer = match after {
+            Some(after) => after.then(transfer),
+            None => transfer,
+        };
+        transfer.then(ext_self::exchange_callback_post_withdraw(
             token_id.clone(),
             sender_id.clone(),
             U128(amount),
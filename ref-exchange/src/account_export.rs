@@ -0,0 +1,158 @@
+//! Account export / import for migration to a successor deployment.
+//!
+//! `export_account` produces a Borsh snapshot of account's deposits and pool shares, together
+//! with the token amounts the shares are worth. The successor, configured with this exchange as
+//! its migration peer, credits the snapshot to the account with `import_account`, called by the
+//! peer itself or by the owner. Pool shares are imported as deposits of the tokens they were
+//! worth, the underlying tokens are moved to the successor in bulk instead of per account.
+
+use near_sdk::json_types::Base64VecU8;
+
+use crate::*;
+
+/// Pool shares of the account and the amounts of pool tokens they are worth.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct SharesSnapshot {
+    pub pool_id: u64,
+    pub shares: Balance,
+    pub amounts: Vec<(AccountId, Balance)>,
+}
+
+/// Balances of an account at the exchange it is exported from.
+/// Amounts are internal, i.e. shares of the exchange's holdings for tokens in shares mode.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct AccountSnapshot {
+    pub exchange_id: AccountId,
+    pub account_id: AccountId,
+    /// Storage balance of the account, importer is expected to attach it.
+    pub near_amount: Balance,
+    pub deposits: Vec<(AccountId, Balance)>,
+    pub shares: Vec<SharesSnapshot>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug))]
+pub struct AccountExport {
+    /// Borsh serialized `AccountSnapshot`.
+    pub snapshot: Base64VecU8,
+    /// Sha256 of the snapshot, checked on import.
+    pub proof: Base64VecU8,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Sets exchange accounts are imported from, None disables imports. Only can be called by owner.
+    #[payable]
+    pub fn set_migration_peer(&mut self, peer_id: Option<ValidAccountId>) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.migration_peer = peer_id.map(|peer_id| peer_id.into());
+        log!("Migration peer set to {:?}", self.migration_peer);
+    }
+
+    pub fn get_migration_peer(&self) -> Option<AccountId> {
+        self.migration_peer.clone()
+    }
+
+    /// Returns snapshot of deposits and pool shares of the account.
+    pub fn export_account(&self, account_id: ValidAccountId) -> AccountExport {
+        let account = self.internal_unwrap_account(account_id.as_ref());
+        let deposits = account
+            .get_tokens()
+            .into_iter()
+            .map(|token_id| {
+                let amount = account.get_balance(&token_id).unwrap();
+                (token_id, amount)
+            })
+            .collect();
+        let mut shares = vec![];
+        for pool_id in 0..self.pools.len() {
+            let pool = self.pools.get(pool_id).unwrap();
+            let pool_shares = pool.share_balances(account_id.as_ref());
+            if pool_shares > 0 {
+                shares.push(SharesSnapshot {
+                    pool_id,
+                    shares: pool_shares,
+                    amounts: pool
+                        .tokens()
+                        .iter()
+                        .cloned()
+                        .zip(pool.predict_remove_liquidity(pool_shares))
+                        .collect(),
+                });
+            }
+        }
+        let snapshot = AccountSnapshot {
+            exchange_id: env::current_account_id(),
+            account_id: account_id.into(),
+            near_amount: account.near_amount,
+            deposits,
+            shares,
+        }
+        .try_to_vec()
+        .unwrap();
+        AccountExport {
+            proof: env::sha256(&snapshot).into(),
+            snapshot: snapshot.into(),
+        }
+    }
+
+    /// Credits exported snapshot of an account from the migration peer, attached deposit
+    /// is added to the account's storage balance. Only can be called by owner or migration peer,
+    /// each account can be imported once.
+    #[payable]
+    pub fn import_account(&mut self, snapshot: Base64VecU8, proof: Base64VecU8) {
+        self.assert_contract_running();
+        let peer_id = self
+            .migration_peer
+            .clone()
+            .expect(ERR170_NO_MIGRATION_PEER);
+        assert!(
+            env::predecessor_account_id() == self.owner_id
+                || env::predecessor_account_id() == peer_id,
+            "{}",
+            ERR100_NOT_ALLOWED
+        );
+        assert_eq!(
+            env::sha256(&snapshot.0),
+            proof.0,
+            "{}",
+            ERR171_INVALID_ACCOUNT_SNAPSHOT
+        );
+        let snapshot =
+            AccountSnapshot::try_from_slice(&snapshot.0).expect(ERR171_INVALID_ACCOUNT_SNAPSHOT);
+        assert_eq!(
+            snapshot.exchange_id, peer_id,
+            "{}",
+            ERR171_INVALID_ACCOUNT_SNAPSHOT
+        );
+        assert!(
+            self.imported_accounts.insert(&snapshot.account_id),
+            "{}",
+            ERR172_ACCOUNT_ALREADY_IMPORTED
+        );
+
+        let account_id = snapshot.account_id;
+        let mut account = self.internal_unwrap_or_default_account(&account_id);
+        let prev_near_amount = account.near_amount;
+        account.near_amount += env::attached_deposit();
+        log_near_amount_change(
+            &account_id,
+            "import_account",
+            prev_near_amount,
+            account.near_amount,
+        );
+        let amounts = snapshot.deposits.into_iter().chain(
+            snapshot
+                .shares
+                .into_iter()
+                .flat_map(|shares| shares.amounts.into_iter()),
+        );
+        for (token_id, amount) in amounts {
+            account.deposit(&token_id, amount);
+        }
+        self.internal_save_account(&account_id, account);
+        log!("Account {} imported from {}", account_id, peer_id);
+    }
+}
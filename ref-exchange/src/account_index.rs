@@ -0,0 +1,105 @@
+//! Enumerable index of registered accounts.
+//!
+//! Accounts are added to the index whenever they are saved and removed on unregister, so the
+//! number of accounts and their ids can be queried for adoption metrics and storage audits, and
+//! auditors can page through account states to reconstruct total liabilities per token.
+//! Accounts registered before the index existed are added when they are saved next or by owner
+//! with `index_accounts`. Index entry is included in `MEASURED_ACCOUNT_STORAGE`, the default
+//! minimal storage deposit.
+
+use std::collections::HashMap;
+
+use crate::pagination::Page;
+use crate::*;
+
+/// Max number of account ids `index_accounts` takes in one call.
+pub const MAX_INDEX_ACCOUNTS_BATCH: usize = 100;
+
+/// Storage balance and deposits of an account.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug, PartialEq))]
+pub struct AccountState {
+    pub account_id: AccountId,
+    /// Storage deposit of the account and the part of it locked by used storage.
+    pub near_amount: U128,
+    pub storage_usage: U128,
+    /// Balances of registered tokens, same as `get_deposits`.
+    pub deposits: HashMap<AccountId, U128>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Adds given registered accounts to the index, unregistered ones are ignored.
+    /// Returns number of newly indexed accounts. Only can be called by owner.
+    #[payable]
+    pub fn index_accounts(&mut self, account_ids: Vec<ValidAccountId>) -> u64 {
+        assert_one_yocto();
+        self.assert_owner();
+        assert!(
+            account_ids.len() <= MAX_INDEX_ACCOUNTS_BATCH,
+            "{}",
+            ERR230_TOO_MANY_ACCOUNTS
+        );
+        let mut indexed = 0;
+        for account_id in account_ids {
+            if self.accounts.contains_key(account_id.as_ref())
+                && self.account_index.insert(account_id.as_ref())
+            {
+                indexed += 1;
+            }
+        }
+        log!("Indexed {} accounts", indexed);
+        indexed
+    }
+
+    /// Returns number of indexed accounts.
+    pub fn get_number_of_accounts(&self) -> u64 {
+        self.account_index.len()
+    }
+
+    /// Returns ids of indexed accounts, meant for operational tooling of owner.
+    pub fn get_account_ids(&self, from_index: u64, limit: u64) -> Vec<AccountId> {
+        let keys = self.account_index.as_vector();
+        (from_index..std::cmp::min(from_index + limit, keys.len()))
+            .map(|index| keys.get(index).unwrap())
+            .collect()
+    }
+
+    /// Returns page of `limit` indexed account ids after the cursor.
+    pub fn get_account_ids_page(&self, cursor: Option<String>, limit: u64) -> Page<AccountId> {
+        let keys = self.account_index.as_vector();
+        Page::from_index(cursor, limit, keys.len(), |index| keys.get(index).unwrap())
+    }
+
+    /// Returns storage balance and deposits of the account, None if it is not registered.
+    pub fn get_account_storage_state(&self, account_id: ValidAccountId) -> Option<AccountState> {
+        self.internal_account_state(account_id.as_ref())
+    }
+
+    /// Returns states of `limit` indexed accounts from `from_index`.
+    pub fn get_accounts(&self, from_index: u64, limit: u64) -> Vec<AccountState> {
+        self.get_account_ids(from_index, limit)
+            .into_iter()
+            .filter_map(|account_id| self.internal_account_state(&account_id))
+            .collect()
+    }
+}
+
+impl Contract {
+    fn internal_account_state(&self, account_id: &AccountId) -> Option<AccountState> {
+        self.internal_get_account(account_id).map(|account| AccountState {
+            account_id: account_id.clone(),
+            near_amount: U128(account.near_amount),
+            storage_usage: U128(account.storage_usage(self.internal_storage_byte_price())),
+            deposits: account
+                .get_tokens()
+                .into_iter()
+                .map(|token_id| {
+                    let amount = account.get_balance(&token_id).unwrap();
+                    (token_id, U128(amount))
+                })
+                .collect(),
+        })
+    }
+}
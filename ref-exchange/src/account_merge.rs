@@ -0,0 +1,198 @@
+//! Merging of an account into another one.
+//!
+//! The source account requests a merge with `merge_account_into`, the target confirms it with
+//! `confirm_account_merge`. On confirmation all deposits, LP shares and storage balance of the
+//! source move to the target within the same call, as do its claims of failed withdrawals,
+//! quarantined amounts and lost tokens, so none are left behind under an unregistered account.
+//! A log is emitted per moved asset and the source is unregistered. Accounts with pending
+//! withdrawals, flash loans, share loans or share locks can't be merged. Neither can accounts with
+//! a withdraw delay, their funds would skip it in the target, the delay has to be disabled first.
+//! Pending requests are covered by the exchange.
+
+use std::collections::HashMap;
+
+use crate::account_deposit::log_near_amount_change;
+use crate::*;
+
+/// Adds `source` amounts by token to `target`, logging each of them.
+fn merge_amounts(
+    kind: &str,
+    source_id: &AccountId,
+    target_id: &AccountId,
+    source: HashMap<AccountId, Balance>,
+    target: &mut HashMap<AccountId, Balance>,
+) {
+    for (token_id, amount) in source {
+        log!(
+            "Merged {} {} of {} from {} to {}",
+            kind,
+            amount,
+            token_id,
+            source_id,
+            target_id
+        );
+        *target.entry(token_id).or_insert(0) += amount;
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Requests merging of the caller's account into `target_account_id`, replacing a previous
+    /// request. Takes effect once the target confirms it.
+    #[payable]
+    pub fn merge_account_into(&mut self, target_account_id: ValidAccountId) {
+        assert_one_yocto();
+        self.assert_contract_running();
+        let sender_id = env::predecessor_account_id();
+        assert_ne!(
+            &sender_id,
+            target_account_id.as_ref(),
+            "{}",
+            ERR303_MERGE_INTO_SELF
+        );
+        self.internal_unwrap_account(&sender_id);
+        self.internal_unwrap_account(target_account_id.as_ref());
+        self.account_merges
+            .insert(&sender_id, target_account_id.as_ref());
+        log!(
+            "Account {} requested merge into {}",
+            sender_id,
+            target_account_id.as_ref()
+        );
+    }
+
+    /// Cancels merge request of the caller.
+    #[payable]
+    pub fn cancel_account_merge(&mut self) {
+        assert_one_yocto();
+        let sender_id = env::predecessor_account_id();
+        self.account_merges
+            .remove(&sender_id)
+            .expect(ERR300_NO_MERGE_REQUEST);
+        log!("Account {} cancelled merge", sender_id);
+    }
+
+    /// Moves everything of `source_account_id` that requested merge into the caller's account.
+    /// Attached deposit covers registration of the caller in pools it had no shares of.
+    #[payable]
+    pub fn confirm_account_merge(&mut self, source_account_id: ValidAccountId) {
+        self.assert_contract_running();
+        assert!(
+            env::attached_deposit() > 0,
+            "Requires attached deposit of at least 1 yoctoNEAR"
+        );
+        let prev_storage = env::storage_usage();
+        let target_id = env::predecessor_account_id();
+        let source_id: AccountId = source_account_id.into();
+        assert_eq!(
+            self.account_merges.remove(&source_id),
+            Some(target_id.clone()),
+            "{}",
+            ERR300_NO_MERGE_REQUEST
+        );
+        assert!(
+            self.pending_withdrawals
+                .get(&source_id)
+                .filter(|pending| !pending.is_empty())
+                .is_none(),
+            "{}",
+            ERR302_MERGE_PENDING_WITHDRAWALS
+        );
+        self.assert_no_flash_loan(&source_id);
+        assert!(
+            !self.is_withdraw_delayed(&source_id),
+            "{}",
+            ERR304_MERGE_WITHDRAW_DELAY
+        );
+
+        for pool_id in 0..self.pools.len() {
+            let mut pool = self.pools.get(pool_id).unwrap();
+            let shares = pool.share_balances(&source_id);
+            if shares == 0 {
+                continue;
+            }
+            assert!(
+                !self.share_loans.contains_key(&(source_id.clone(), pool_id)),
+                "{}",
+                ERR301_MERGE_SHARE_LOAN
+            );
+            assert!(
+                !self.share_locks.contains_key(&(source_id.clone(), pool_id)),
+                "{}",
+                ERR416_MERGE_SHARE_LOCKS
+            );
+            if !pool.share_is_registered(&target_id) {
+                pool.share_register(&target_id);
+            }
+            pool.share_transfer(&source_id, &target_id, shares);
+            self.pools.replace(pool_id, &pool);
+            log!(
+                "Merged {} shares of pool {} from {} to {}",
+                shares,
+                pool_id,
+                source_id,
+                target_id
+            );
+        }
+
+        let mut source = self.internal_unwrap_account(&source_id);
+        let mut target = self.internal_unwrap_account(&target_id);
+        for token_id in source.get_tokens() {
+            let amount = source.get_balance(&token_id).unwrap();
+            source.withdraw(&token_id, amount);
+            source.unregister(&token_id);
+            target.deposit(&token_id, amount);
+            log!(
+                "Merged {} of {} from {} to {}",
+                amount,
+                token_id,
+                source_id,
+                target_id
+            );
+        }
+        let prev_near_amount = target.near_amount;
+        target.near_amount += source.near_amount;
+        log_near_amount_change(&source_id, "account_merge", source.near_amount, 0);
+        log_near_amount_change(
+            &target_id,
+            "account_merge",
+            prev_near_amount,
+            target.near_amount,
+        );
+        if let Some(claims) = self.failed_withdrawals.remove(&source_id) {
+            let mut target_claims = self.failed_withdrawals.get(&target_id).unwrap_or_default();
+            merge_amounts("failed withdrawal", &source_id, &target_id, claims, &mut target_claims);
+            self.failed_withdrawals.insert(&target_id, &target_claims);
+        }
+        if let Some(quarantined) = self.quarantined_amounts.remove(&source_id) {
+            let mut target_quarantined =
+                self.quarantined_amounts.get(&target_id).unwrap_or_default();
+            merge_amounts(
+                "quarantined",
+                &source_id,
+                &target_id,
+                quarantined,
+                &mut target_quarantined,
+            );
+            self.quarantined_amounts
+                .insert(&target_id, &target_quarantined);
+        }
+        if let Some(lost) = self.lost_tokens.remove(&source_id) {
+            let mut target_lost = self.lost_tokens.get(&target_id).unwrap_or_default();
+            merge_amounts("lost", &source_id, &target_id, lost, &mut target_lost);
+            self.lost_tokens.insert(&target_id, &target_lost);
+        }
+        self.accounts.remove(&source_id);
+        self.account_index.remove(&source_id);
+        self.account_activity.remove(&source_id);
+        self.dust_flags.remove(&source_id);
+        self.internal_save_account(&target_id, target);
+        self.internal_check_storage(prev_storage);
+        log!("Account {} merged into {}", source_id, target_id);
+    }
+
+    /// Returns account the given account requested to merge into.
+    pub fn get_account_merge(&self, account_id: ValidAccountId) -> Option<AccountId> {
+        self.account_merges.get(account_id.as_ref())
+    }
+}
@@ -1,6 +1,7 @@
 use crate::errors::ERR41_WRONG_ACTION_RESULT;
 use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::{env, json_types::U128, AccountId, Balance};
+use near_sdk::json_types::{ValidAccountId, WrappedTimestamp, U128};
+use near_sdk::{env, AccountId, Balance};
 
 /// Single swap action.
 #[derive(Serialize, Deserialize)]
@@ -18,6 +19,33 @@ pub struct SwapAction {
     pub token_out: AccountId,
     /// Required minimum amount of token_out.
     pub min_amount_out: U128,
+    /// Optional quote id from `get_return_quote`, swap fails if the pool changed too much since.
+    #[serde(default)]
+    pub quote_id: Option<String>,
+    /// Optional block timestamp in nanoseconds, swap fails if executed after it.
+    #[serde(default)]
+    pub deadline: Option<WrappedTimestamp>,
+    /// Optional max drop of the pool's spot price of token_in in token_out caused by the swap,
+    /// in bps of `FEE_DIVISOR`.
+    #[serde(default)]
+    pub max_price_impact_bps: Option<u32>,
+}
+
+/// Swaps along a route, e.g. returned by `get_best_route`.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RoutedSwapAction {
+    /// Pools of the route in order of hops.
+    pub pool_ids: Vec<u64>,
+    /// Tokens of the route from input to output, one more than pools.
+    pub tokens: Vec<AccountId>,
+    /// Amount to exchange, if None it takes amount_out from previous step.
+    pub amount_in: Option<U128>,
+    /// Required minimum amount of the last token of the route.
+    pub min_amount_out: U128,
+    /// Optional block timestamp in nanoseconds, swap fails if executed after it.
+    #[serde(default)]
+    pub deadline: Option<WrappedTimestamp>,
 }
 
 /// Single action. Allows to execute sequence of various actions initiated by an account.
@@ -26,6 +54,28 @@ pub struct SwapAction {
 #[serde(untagged)]
 pub enum Action {
     Swap(SwapAction),
+    RoutedSwap(RoutedSwapAction),
+}
+
+/// Message parameters to receive via token function call.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(untagged)]
+pub enum TokenReceiverMessage {
+    /// Alternative to deposit + execute actions call.
+    Execute {
+        referral_id: Option<ValidAccountId>,
+        /// List of sequential actions.
+        actions: Vec<Action>,
+    },
+    /// Deposit to inner account of another registered account, e.g. from an omnibus wallet.
+    /// Must stay before `Deposit`, which ignores unknown fields.
+    DepositTo {
+        receiver_id: ValidAccountId,
+        memo: Option<String>,
+    },
+    /// Deposit like empty message, memo is passed into the deposit log and receipt.
+    Deposit { memo: String },
 }
 
 impl Action {
@@ -35,6 +85,15 @@ impl Action {
             Action::Swap(swap_action) => {
                 vec![swap_action.token_in.clone(), swap_action.token_out.clone()]
             }
+            Action::RoutedSwap(routed_swap_action) => routed_swap_action.tokens.clone(),
+        }
+    }
+
+    /// Returns token this action outputs.
+    pub fn output_token(&self) -> Option<AccountId> {
+        match self {
+            Action::Swap(swap_action) => Some(swap_action.token_out.clone()),
+            Action::RoutedSwap(routed_swap_action) => routed_swap_action.tokens.last().cloned(),
         }
     }
 }
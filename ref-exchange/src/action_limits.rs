@@ -0,0 +1,99 @@
+//! Limits of actions per call and tokens per pool.
+//!
+//! Owner can tune how many actions a single call executes, how many swaps a route chains, each
+//! taking the output of the previous one, and how many tokens a stable pool has, within hard
+//! ceilings keeping calls under the gas limit. The limits are part of `metadata`, so routers
+//! can adapt to each deployment. Existing pools are not affected by changes of the limits.
+
+use crate::*;
+
+/// Hard ceiling of actions per call.
+pub const MAX_ACTIONS_CEILING: u32 = 32;
+
+/// Hard ceiling of swaps in a route.
+pub const MAX_ROUTE_HOPS_CEILING: u32 = 8;
+
+/// Hard ceiling of tokens in a pool.
+pub const MAX_POOL_TOKENS_CEILING: u32 = 10;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct ActionLimits {
+    pub max_actions: u32,
+    /// Max swaps chained by taking output of the previous swap as input.
+    pub max_route_hops: u32,
+    pub max_pool_tokens: u32,
+}
+
+impl Default for ActionLimits {
+    fn default() -> Self {
+        Self {
+            max_actions: MAX_ACTIONS_CEILING,
+            max_route_hops: MAX_ROUTE_HOPS_CEILING,
+            max_pool_tokens: MAX_POOL_TOKENS_CEILING,
+        }
+    }
+}
+
+impl ActionLimits {
+    pub fn assert_actions(&self, actions: &[Action]) {
+        assert!(
+            actions.len() as u32 <= self.max_actions,
+            "{}",
+            ERR360_TOO_MANY_ACTIONS
+        );
+        let mut hops = 0;
+        for action in actions {
+            match action {
+                Action::Swap(swap_action) => {
+                    hops = if swap_action.amount_in.is_some() { 1 } else { hops + 1 };
+                }
+                Action::RoutedSwap(routed_swap_action) => {
+                    let route_hops = routed_swap_action.pool_ids.len() as u32;
+                    hops = if routed_swap_action.amount_in.is_some() {
+                        route_hops
+                    } else {
+                        hops + route_hops
+                    };
+                }
+            }
+            assert!(hops <= self.max_route_hops, "{}", ERR361_ROUTE_TOO_LONG);
+        }
+    }
+
+    pub fn assert_pool_tokens(&self, num_tokens: usize) {
+        assert!(
+            num_tokens as u32 <= self.max_pool_tokens,
+            "{}",
+            ERR362_TOO_MANY_POOL_TOKENS
+        );
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Sets limits of actions and pool tokens within hard ceilings. Only can be called by owner.
+    #[payable]
+    pub fn set_action_limits(&mut self, limits: ActionLimits) {
+        assert_one_yocto();
+        self.assert_owner();
+        assert!(
+            limits.max_actions > 0
+                && limits.max_actions <= MAX_ACTIONS_CEILING
+                && limits.max_route_hops > 0
+                && limits.max_route_hops <= MAX_ROUTE_HOPS_CEILING
+                && limits.max_pool_tokens >= 2
+                && limits.max_pool_tokens <= MAX_POOL_TOKENS_CEILING,
+            "{}",
+            ERR363_INVALID_ACTION_LIMITS
+        );
+        log!(
+            "Action limits set: max actions {}, max route hops {}, max pool tokens {}",
+            limits.max_actions,
+            limits.max_route_hops,
+            limits.max_pool_tokens
+        );
+        self.action_limits = limits;
+    }
+}
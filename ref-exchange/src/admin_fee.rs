@@ -1,4 +1,38 @@
-use near_sdk::{env, AccountId};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, AccountId, Balance};
+
+/// Referral fee applied to swaps of at least `min_amount_in` of the input token.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct ReferralFeeTier {
+    pub min_amount_in: U128,
+    /// Basis points of the fee for referrer.
+    pub referral_fee: u32,
+}
+
+/// Split of the pool fee between the exchange and the referral, in bps of the total fee.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct PoolAdminFees {
+    pub exchange_fee: u32,
+    /// Default referral fee, referrer tiers and referral fee tiers still apply on top.
+    pub referral_fee: u32,
+}
+
+/// Token the pool fee of a swap is taken in.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub enum FeeSide {
+    /// Fee is deducted from the amount in before pricing.
+    Input,
+    /// Fee is deducted from the priced amount out, stable pools always take it this way.
+    Output,
+}
 
 /// Maintain information about fees.
 pub struct AdminFees {
@@ -8,6 +42,8 @@ pub struct AdminFees {
     pub referral_fee: u32,
     pub exchange_id: AccountId,
     pub referral_id: Option<AccountId>,
+    /// Side simple pools take the fee on.
+    pub fee_side: FeeSide,
 }
 
 impl AdminFees {
@@ -17,10 +53,29 @@ impl AdminFees {
             exchange_id: env::current_account_id(),
             referral_fee: 0,
             referral_id: None,
+            fee_side: FeeSide::Input,
         }
     }
 
     pub fn zero() -> Self {
         Self::new(0)
     }
+
+    /// Sets referral fee of the largest tier `amount_in` reaches, tiers are sorted by size.
+    /// Keeps default referral fee if trade is smaller than all tiers.
+    pub fn with_referral_tiers(mut self, tiers: &[ReferralFeeTier], amount_in: Balance) -> Self {
+        if let Some(tier) = tiers
+            .iter()
+            .rev()
+            .find(|tier| amount_in >= tier.min_amount_in.0)
+        {
+            self.referral_fee = tier.referral_fee;
+        }
+        self
+    }
+
+    pub fn with_fee_side(mut self, fee_side: FeeSide) -> Self {
+        self.fee_side = fee_side;
+        self
+    }
 }
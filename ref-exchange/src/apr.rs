@@ -0,0 +1,190 @@
+//! Realized LP fee APR of pools from on-chain data.
+//!
+//! Pools take a checkpoint of their value per share and input volumes on swaps, at most once per
+//! `POOL_CHECKPOINT_INTERVAL`, keeping the last `MAX_POOL_CHECKPOINTS`.
+//! Value per share is price independent (geometric mean of amounts for simple pools, sum of
+//! comparable amounts for stable pools) and only grows with fees left to LPs, so its growth
+//! since a checkpoint annualized is the APR LPs actually received. The checkpointed values per
+//! share are also exposed as share price history, so e.g. a vault can show the growth it charges
+//! fees on. Weighted pools and simple pools of more than two tokens aren't checkpointed yet.
+
+use near_sdk::json_types::{WrappedDuration, WrappedTimestamp};
+use near_sdk::Timestamp;
+
+use crate::utils::{integer_sqrt, FEE_DIVISOR, U256};
+use crate::*;
+
+/// Min time between checkpoints of a pool, 1 day in nano sec.
+pub const POOL_CHECKPOINT_INTERVAL: Timestamp = 24 * 3600 * 1_000_000_000;
+
+/// Number of checkpoints kept per pool.
+pub const MAX_POOL_CHECKPOINTS: usize = 30;
+
+const YEAR: Timestamp = 365 * 24 * 3600 * 1_000_000_000;
+
+const SHARE_VALUE_PRECISION: u128 = 1_000_000_000_000_000_000;
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct PoolCheckpoint {
+    pub timestamp: Timestamp,
+    /// Price independent value of a share in `SHARE_VALUE_PRECISION`.
+    pub share_value: Balance,
+    /// Input volumes of the pool tokens.
+    pub volumes: Vec<Balance>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug))]
+pub struct PoolApr {
+    /// Checkpoint the APR is computed from.
+    pub from_timestamp: WrappedTimestamp,
+    pub to_timestamp: WrappedTimestamp,
+    /// Annualized growth of value per share, in bps of `FEE_DIVISOR`.
+    pub apr: u64,
+    /// Total swap fees charged in the pool tokens since the checkpoint, including exchange and referral fees.
+    pub fees: Vec<U128>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug, PartialEq))]
+pub struct SharePricePoint {
+    pub timestamp: WrappedTimestamp,
+    /// Price independent value of a share in `SHARE_VALUE_PRECISION` of 10^18.
+    pub share_value: U128,
+}
+
+impl From<&PoolCheckpoint> for SharePricePoint {
+    fn from(checkpoint: &PoolCheckpoint) -> Self {
+        Self {
+            timestamp: checkpoint.timestamp.into(),
+            share_value: U128(checkpoint.share_value),
+        }
+    }
+}
+
+/// Returns price independent value of a pool share, None for empty pool.
+fn pool_share_value(pool: &Pool) -> Option<Balance> {
+    let (value, shares_total_supply) = match pool {
+        Pool::SimplePool(pool) if pool.amounts.len() == 2 => {
+            let product = pool
+                .amounts
+                .iter()
+                .fold(U256::one(), |product, amount| product * U256::from(*amount));
+            (integer_sqrt(product), pool.shares_total_supply)
+        }
+        Pool::StableSwapPool(pool) => (
+            pool.c_amounts
+                .iter()
+                .fold(U256::zero(), |sum, amount| sum + U256::from(*amount)),
+            pool.shares_total_supply,
+        ),
+        Pool::SimplePool(_) | Pool::WeightedPool(_) => return None,
+    };
+    if shares_total_supply == 0 {
+        return None;
+    }
+    Some((value * U256::from(SHARE_VALUE_PRECISION) / U256::from(shares_total_supply)).as_u128())
+}
+
+fn pool_checkpoint(pool: &Pool) -> Option<PoolCheckpoint> {
+    pool_share_value(pool).map(|share_value| PoolCheckpoint {
+        timestamp: env::block_timestamp(),
+        share_value,
+        volumes: pool
+            .get_volumes()
+            .iter()
+            .map(|volume| volume.input.0)
+            .collect(),
+    })
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Returns realized LP fee APR of the pool since the first checkpoint within last `window` nano sec,
+    /// or the latest checkpoint if there is none within the window. None if the pool has no checkpoints yet.
+    pub fn get_pool_apr(&self, pool_id: u64, window: WrappedDuration) -> Option<PoolApr> {
+        let pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+        let current = pool_checkpoint(&pool)?;
+        let checkpoints = self.pool_checkpoints.get(&pool_id)?;
+        let window_start = current.timestamp.saturating_sub(window.0);
+        let from = checkpoints
+            .iter()
+            .find(|checkpoint| checkpoint.timestamp >= window_start)
+            .or_else(|| checkpoints.last())?;
+        let elapsed = current.timestamp - from.timestamp;
+        if elapsed == 0 || from.share_value == 0 {
+            return None;
+        }
+        let growth = current.share_value.saturating_sub(from.share_value);
+        let apr = (U256::from(growth) * U256::from(FEE_DIVISOR) * U256::from(YEAR)
+            / (U256::from(from.share_value) * U256::from(elapsed)))
+        .as_u64();
+        let fees = current
+            .volumes
+            .iter()
+            .zip(from.volumes.iter())
+            .map(|(volume, prev_volume)| {
+                U128(
+                    (U256::from(volume - prev_volume) * U256::from(pool.get_fee())
+                        / U256::from(FEE_DIVISOR))
+                    .as_u128(),
+                )
+            })
+            .collect();
+        Some(PoolApr {
+            from_timestamp: from.timestamp.into(),
+            to_timestamp: current.timestamp.into(),
+            apr,
+            fees,
+        })
+    }
+
+    /// Returns value per share of the pool at its checkpoints within last `window` nano sec,
+    /// oldest first, followed by the current value. Empty if the pool has no value.
+    pub fn get_pool_share_price_history(
+        &self,
+        pool_id: u64,
+        window: WrappedDuration,
+    ) -> Vec<SharePricePoint> {
+        let pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+        let current = match pool_checkpoint(&pool) {
+            Some(current) => current,
+            None => return vec![],
+        };
+        let window_start = current.timestamp.saturating_sub(window.0);
+        let mut history: Vec<SharePricePoint> = self
+            .pool_checkpoints
+            .get(&pool_id)
+            .unwrap_or_default()
+            .iter()
+            .filter(|checkpoint| {
+                checkpoint.timestamp >= window_start && checkpoint.timestamp < current.timestamp
+            })
+            .map(SharePricePoint::from)
+            .collect();
+        history.push(SharePricePoint::from(&current));
+        history
+    }
+}
+
+impl Contract {
+    /// Takes a checkpoint of the pool if the latest one is older than `POOL_CHECKPOINT_INTERVAL`.
+    /// Checkpoints are covered by the exchange.
+    pub(crate) fn internal_checkpoint_pool(&mut self, pool_id: u64, pool: &Pool) {
+        let mut checkpoints = self.pool_checkpoints.get(&pool_id).unwrap_or_default();
+        if let Some(last) = checkpoints.last() {
+            if env::block_timestamp() < last.timestamp + POOL_CHECKPOINT_INTERVAL {
+                return;
+            }
+        }
+        if let Some(checkpoint) = pool_checkpoint(pool) {
+            checkpoints.push(checkpoint);
+            if checkpoints.len() > MAX_POOL_CHECKPOINTS {
+                checkpoints.remove(0);
+            }
+            self.pool_checkpoints.insert(&pool_id, &checkpoints);
+        }
+    }
+}
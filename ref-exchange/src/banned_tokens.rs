@@ -0,0 +1,53 @@
+//! Tokens banned by owner.
+//!
+//! The whitelist only decides which tokens accounts can use without registering them, anyone
+//! can still register a token and deposit it, or create a pool of it. Owner bans known malicious
+//! tokens: deposits of them are refunded, they can't be registered and pools of them can't be
+//! created. Banned tokens are removed from the whitelist. Existing deposits and pools are not
+//! affected, so accounts can still withdraw and remove liquidity.
+
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// Bans the tokens and removes them from the whitelist. Only can be called by owner.
+    #[payable]
+    pub fn ban_tokens(&mut self, tokens: Vec<ValidAccountId>) {
+        assert_one_yocto();
+        self.assert_owner();
+        for token in tokens {
+            self.banned_tokens.insert(token.as_ref());
+            self.whitelisted_tokens.remove(token.as_ref());
+            log!("Token {} banned", token);
+        }
+    }
+
+    /// Lifts ban of the tokens. Only can be called by owner.
+    #[payable]
+    pub fn unban_tokens(&mut self, tokens: Vec<ValidAccountId>) {
+        assert_one_yocto();
+        self.assert_owner();
+        for token in tokens {
+            self.banned_tokens.remove(token.as_ref());
+            log!("Token {} unbanned", token);
+        }
+    }
+
+    pub fn get_banned_tokens(&self) -> Vec<AccountId> {
+        self.banned_tokens.to_vec()
+    }
+
+    pub fn is_token_banned(&self, token_id: ValidAccountId) -> bool {
+        self.banned_tokens.contains(token_id.as_ref())
+    }
+}
+
+impl Contract {
+    pub(crate) fn assert_token_not_banned(&self, token_id: &AccountId) {
+        assert!(
+            !self.banned_tokens.contains(token_id),
+            "{}",
+            ERR423_TOKEN_BANNED
+        );
+    }
+}
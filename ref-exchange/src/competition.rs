@@ -0,0 +1,267 @@
+//! Time-boxed trading competitions.
+//!
+//! Owner creates a competition window over a set of pools and funds its reward budget
+//! from the owner's inner account. Swaps within the window through the pools in scope
+//! add the traded amount of `volume_token` to the account's volume. After the window closes,
+//! accounts claim their part of the reward proportional to their volume to their inner accounts.
+
+use near_sdk::json_types::WrappedTimestamp;
+use near_sdk::Timestamp;
+
+use crate::pagination::Page;
+use crate::utils::U256;
+use crate::*;
+
+/// Max number of competitions that haven't ended yet, bounds gas added to every swap.
+pub const MAX_OPEN_COMPETITIONS: usize = 5;
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct Competition {
+    /// Pools swaps through which are counted.
+    pub pool_ids: Vec<u64>,
+    /// Token the volume is measured in, must be in every pool in scope.
+    pub volume_token: AccountId,
+    pub start: Timestamp,
+    pub end: Timestamp,
+    pub reward_token: AccountId,
+    pub reward_amount: Balance,
+    /// Sum of volumes of all accounts.
+    pub total_volume: Balance,
+    /// Rewards already claimed or refunded.
+    pub claimed: Balance,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug))]
+pub struct CompetitionInfo {
+    pub id: u64,
+    pub pool_ids: Vec<u64>,
+    pub volume_token: AccountId,
+    pub start: WrappedTimestamp,
+    pub end: WrappedTimestamp,
+    pub reward_token: AccountId,
+    pub reward_amount: U128,
+    pub total_volume: U128,
+    pub claimed: U128,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Creates competition over `pool_ids` between `start` and `end`, funding `reward_amount`
+    /// of `reward_token` from owner's inner account. Only can be called by owner.
+    /// Returns id of the competition.
+    #[payable]
+    pub fn create_competition(
+        &mut self,
+        pool_ids: Vec<u64>,
+        volume_token: ValidAccountId,
+        start: WrappedTimestamp,
+        end: WrappedTimestamp,
+        reward_token: ValidAccountId,
+        reward_amount: U128,
+    ) -> u64 {
+        assert_one_yocto();
+        self.assert_owner();
+        let volume_token: AccountId = volume_token.into();
+        let reward_token: AccountId = reward_token.into();
+        assert!(
+            !pool_ids.is_empty()
+                && start.0 < end.0
+                && end.0 > env::block_timestamp()
+                && reward_amount.0 > 0,
+            "{}",
+            ERR150_INVALID_COMPETITION
+        );
+        for pool_id in pool_ids.iter() {
+            let pool = self.pools.get(*pool_id).expect("ERR_NO_POOL");
+            assert!(
+                pool.tokens().contains(&volume_token),
+                "{}",
+                ERR150_INVALID_COMPETITION
+            );
+        }
+        let now = env::block_timestamp();
+        let competitions = &self.competitions;
+        self.open_competitions
+            .retain(|id| competitions.get(*id).unwrap().end > now);
+        assert!(
+            self.open_competitions.len() < MAX_OPEN_COMPETITIONS,
+            "{}",
+            ERR151_TOO_MANY_COMPETITIONS
+        );
+
+        let owner_id = env::predecessor_account_id();
+        let mut account = self.internal_unwrap_account(&owner_id);
+        account.withdraw(&reward_token, reward_amount.0);
+        self.internal_save_account(&owner_id, account);
+
+        let id = self.competitions.len();
+        self.competitions.push(&Competition {
+            pool_ids,
+            volume_token,
+            start: start.0,
+            end: end.0,
+            reward_token: reward_token.clone(),
+            reward_amount: reward_amount.0,
+            total_volume: 0,
+            claimed: 0,
+        });
+        self.open_competitions.push(id);
+        log!(
+            "Competition {} created: {} of {} between {} and {}",
+            id,
+            reward_amount.0,
+            reward_token,
+            start.0,
+            end.0
+        );
+        id
+    }
+
+    /// Claims caller's part of the reward of ended competition to caller's inner account.
+    /// Returns claimed amount.
+    #[payable]
+    pub fn claim_competition_reward(&mut self, competition_id: u64) -> U128 {
+        assert_one_yocto();
+        self.assert_contract_running();
+        let sender_id = env::predecessor_account_id();
+        let mut competition = self.internal_get_ended_competition(competition_id);
+        let volume = self
+            .competition_volumes
+            .remove(&(competition_id, sender_id.clone()))
+            .expect(ERR153_NO_COMPETITION_REWARD);
+        let amount = (U256::from(competition.reward_amount) * U256::from(volume)
+            / U256::from(competition.total_volume))
+        .as_u128();
+        competition.claimed += amount;
+        self.competitions.replace(competition_id, &competition);
+
+        let mut account = self.internal_unwrap_account(&sender_id);
+        account.deposit(&competition.reward_token, amount);
+        self.internal_save_account(&sender_id, account);
+        log!(
+            "Competition {} reward claimed by {}: {} of {}",
+            competition_id,
+            sender_id,
+            amount,
+            competition.reward_token
+        );
+        U128(amount)
+    }
+
+    /// Returns reward budget of ended competition without any volume to owner's inner account.
+    /// Only can be called by owner.
+    #[payable]
+    pub fn refund_competition_reward(&mut self, competition_id: u64) {
+        assert_one_yocto();
+        self.assert_owner();
+        let mut competition = self.internal_get_ended_competition(competition_id);
+        assert!(
+            competition.total_volume == 0 && competition.claimed == 0,
+            "{}",
+            ERR153_NO_COMPETITION_REWARD
+        );
+        competition.claimed = competition.reward_amount;
+        self.competitions.replace(competition_id, &competition);
+
+        let owner_id = env::predecessor_account_id();
+        let mut account = self.internal_unwrap_account(&owner_id);
+        account.deposit(&competition.reward_token, competition.reward_amount);
+        self.internal_save_account(&owner_id, account);
+        log!(
+            "Competition {} reward refunded: {} of {}",
+            competition_id,
+            competition.reward_amount,
+            competition.reward_token
+        );
+    }
+
+    /// Returns `limit` competitions starting from `from_index`.
+    pub fn get_competitions(&self, from_index: u64, limit: u64) -> Vec<CompetitionInfo> {
+        (from_index..std::cmp::min(from_index + limit, self.competitions.len()))
+            .map(|id| self.internal_get_competition_info(id))
+            .collect()
+    }
+
+    /// Returns page of `limit` competitions after the cursor.
+    pub fn get_competitions_page(&self, cursor: Option<String>, limit: u64) -> Page<CompetitionInfo> {
+        Page::from_index(cursor, limit, self.competitions.len(), |id| {
+            self.internal_get_competition_info(id)
+        })
+    }
+
+    /// Returns volume of the account in the competition, 0 after the reward is claimed.
+    pub fn get_competition_volume(&self, competition_id: u64, account_id: ValidAccountId) -> U128 {
+        U128(
+            self.competition_volumes
+                .get(&(competition_id, account_id.into()))
+                .unwrap_or(0),
+        )
+    }
+}
+
+impl Contract {
+    /// Adds volume of the swap to all running competitions with the pool in scope.
+    /// Volume records are covered by the exchange and removed on claim.
+    pub(crate) fn internal_record_competition_volume(
+        &mut self,
+        account_id: &AccountId,
+        pool_id: u64,
+        token_in: &AccountId,
+        amount_in: Balance,
+        token_out: &AccountId,
+        amount_out: Balance,
+    ) {
+        let now = env::block_timestamp();
+        for id in self.open_competitions.clone() {
+            let mut competition = self.competitions.get(id).unwrap();
+            if now < competition.start
+                || now >= competition.end
+                || !competition.pool_ids.contains(&pool_id)
+            {
+                continue;
+            }
+            let volume = if token_in == &competition.volume_token {
+                amount_in
+            } else if token_out == &competition.volume_token {
+                amount_out
+            } else {
+                continue;
+            };
+            let key = (id, account_id.clone());
+            let prev_volume = self.competition_volumes.get(&key).unwrap_or(0);
+            self.competition_volumes.insert(&key, &(prev_volume + volume));
+            competition.total_volume += volume;
+            self.competitions.replace(id, &competition);
+        }
+    }
+
+    fn internal_get_ended_competition(&self, competition_id: u64) -> Competition {
+        let competition = self
+            .competitions
+            .get(competition_id)
+            .expect(ERR152_NO_COMPETITION);
+        assert!(
+            env::block_timestamp() >= competition.end,
+            "{}",
+            ERR154_COMPETITION_NOT_ENDED
+        );
+        competition
+    }
+
+    fn internal_get_competition_info(&self, id: u64) -> CompetitionInfo {
+        let competition = self.competitions.get(id).unwrap();
+        CompetitionInfo {
+            id,
+            pool_ids: competition.pool_ids,
+            volume_token: competition.volume_token,
+            start: competition.start.into(),
+            end: competition.end.into(),
+            reward_token: competition.reward_token,
+            reward_amount: U128(competition.reward_amount),
+            total_volume: U128(competition.total_volume),
+            claimed: U128(competition.claimed),
+        }
+    }
+}
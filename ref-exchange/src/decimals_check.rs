@@ -0,0 +1,103 @@
+//! Cross-check of stable pool decimals against `ft_metadata` of its tokens.
+//!
+//! Owner or guardians trigger the check for a stable pool. Liquidity can not be added to the pool
+//! while the check is pending or if any token reported decimals different from the pool's ones.
+//! Pools that were never checked are not affected.
+
+use near_contract_standards::fungible_token::metadata::FungibleTokenMetadata;
+use near_sdk::serde_json;
+
+use crate::utils::{ext_ft_metadata, ext_self, GAS_FOR_FT_METADATA, GAS_FOR_RESOLVE_CHECK_DECIMALS};
+use crate::*;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub enum DecimalsStatus {
+    /// Metadata of the pool tokens is requested, liquidity is blocked until it is checked.
+    Pending,
+    /// All tokens reported the same decimals as in the pool.
+    Verified,
+    /// Tokens that reported different decimals or failed to return metadata.
+    Mismatch { tokens: Vec<AccountId> },
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Fetches `ft_metadata` of all tokens of the stable pool and verifies decimals of the pool.
+    /// Only can be called by owner or guardians.
+    pub fn check_stable_pool_decimals(&mut self, pool_id: u64) -> Promise {
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        let pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+        if let Pool::SimplePool(_) = pool {
+            env::panic(b"ERR_NOT_STABLE_POOL");
+        }
+        self.pool_decimals_status
+            .insert(&pool_id, &DecimalsStatus::Pending);
+        let promise = pool
+            .tokens()
+            .iter()
+            .map(|token_id| ext_ft_metadata::ft_metadata(token_id, 0, GAS_FOR_FT_METADATA))
+            .reduce(|promise, next| promise.and(next))
+            .unwrap();
+        promise.then(ext_self::exchange_callback_check_decimals(
+            pool_id,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_CHECK_DECIMALS,
+        ))
+    }
+
+    #[private]
+    pub fn exchange_callback_check_decimals(&mut self, pool_id: u64) -> DecimalsStatus {
+        let pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+        let decimals = match &pool {
+            Pool::StableSwapPool(pool) => pool.token_decimals.clone(),
+            _ => env::panic(b"ERR_NOT_STABLE_POOL"),
+        };
+        let tokens = pool.tokens();
+        assert_eq!(
+            env::promise_results_count() as usize,
+            tokens.len(),
+            "ERR_WRONG_PROMISE_RESULTS"
+        );
+        let mismatched: Vec<AccountId> = (0..tokens.len())
+            .filter(|&i| {
+                let reported = match env::promise_result(i as u64) {
+                    PromiseResult::Successful(value) => {
+                        serde_json::from_slice::<FungibleTokenMetadata>(&value)
+                            .ok()
+                            .map(|metadata| metadata.decimals)
+                    }
+                    _ => None,
+                };
+                reported != Some(decimals[i])
+            })
+            .map(|i| tokens[i].clone())
+            .collect();
+        let status = if mismatched.is_empty() {
+            log!("Pool {} decimals verified", pool_id);
+            DecimalsStatus::Verified
+        } else {
+            log!("Pool {} decimals mismatch: {:?}", pool_id, mismatched);
+            DecimalsStatus::Mismatch { tokens: mismatched }
+        };
+        self.pool_decimals_status.insert(&pool_id, &status);
+        status
+    }
+
+    /// Returns status of decimals check of the pool, None if it was never checked.
+    pub fn get_pool_decimals_status(&self, pool_id: u64) -> Option<DecimalsStatus> {
+        self.pool_decimals_status.get(&pool_id)
+    }
+}
+
+impl Contract {
+    /// Panics if decimals check of the pool is pending or found a mismatch.
+    pub(crate) fn assert_pool_decimals_resolved(&self, pool_id: u64) {
+        match self.pool_decimals_status.get(&pool_id) {
+            None | Some(DecimalsStatus::Verified) => {}
+            _ => env::panic(ERR73_DECIMALS_UNVERIFIED.as_bytes()),
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Deposit receipts with memo.
+//!
+//! `ft_transfer_call` with `{"memo": "..."}` message deposits tokens like an empty message and
+//! passes the memo into the deposit log. Accounts can opt into keeping receipts of their recent
+//! deposits, paying for their max storage upfront, so services crediting users by memos can query them.
+
+use near_sdk::json_types::WrappedTimestamp;
+use near_sdk::StorageUsage;
+
+use crate::*;
+
+/// Max length of deposit memo in bytes.
+pub const MAX_DEPOSIT_MEMO_LENGTH: usize = 128;
+
+/// Number of recent deposit receipts kept per account.
+pub const MAX_DEPOSIT_RECEIPTS: usize = 10;
+
+/// Storage paid to enable deposit receipts: map key, vector length and receipts
+/// with max length token id (64 + 4), amount, timestamp and memo (128 + 1 + 4).
+pub const DEPOSIT_RECEIPTS_STORAGE: StorageUsage =
+    1 + 68 + 4 + MAX_DEPOSIT_RECEIPTS as StorageUsage * (68 + 16 + 8 + 133);
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct DepositReceipt {
+    pub token_id: AccountId,
+    /// Internal amount credited, i.e. shares for tokens in shares mode.
+    pub amount: U128,
+    pub timestamp: WrappedTimestamp,
+    pub memo: Option<String>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Enables receipts of caller's recent deposits.
+    /// Attached deposit must cover `DEPOSIT_RECEIPTS_STORAGE`, the rest is refunded.
+    #[payable]
+    pub fn enable_deposit_receipts(&mut self) {
+        self.assert_contract_running();
+        let account_id = env::predecessor_account_id();
+        self.internal_unwrap_account(&account_id);
+        let mut refund = env::attached_deposit();
+        if self.deposit_receipts.get(&account_id).is_none() {
+            let cost = deposit_receipts_cost();
+            assert!(refund >= cost, "{}", ERR211_INSUFFICIENT_RECEIPTS_DEPOSIT);
+            refund -= cost;
+            self.deposit_receipts.insert(&account_id, &vec![]);
+            log!("Account {} enabled deposit receipts", account_id);
+        }
+        if refund > 0 {
+            Promise::new(account_id).transfer(refund);
+        }
+    }
+
+    /// Disables receipts of caller's deposits, refunding their storage.
+    #[payable]
+    pub fn disable_deposit_receipts(&mut self) -> Promise {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        self.deposit_receipts
+            .remove(&account_id)
+            .expect(ERR212_DEPOSIT_RECEIPTS_NOT_ENABLED);
+        log!("Account {} disabled deposit receipts", account_id);
+        Promise::new(account_id).transfer(deposit_receipts_cost())
+    }
+
+    /// Returns recent deposits of the account, latest last. None if receipts are not enabled.
+    pub fn get_recent_deposits(&self, account_id: ValidAccountId) -> Option<Vec<DepositReceipt>> {
+        self.deposit_receipts.get(account_id.as_ref())
+    }
+}
+
+fn deposit_receipts_cost() -> Balance {
+    DEPOSIT_RECEIPTS_STORAGE as Balance * env::storage_byte_cost()
+}
+
+impl Contract {
+    /// Logs deposit and records its receipt if the account enabled them.
+    pub(crate) fn internal_record_deposit(
+        &mut self,
+        account_id: &AccountId,
+        token_id: &AccountId,
+        amount: Balance,
+        memo: Option<String>,
+    ) {
+        if let Some(memo) = &memo {
+            assert!(
+                memo.len() <= MAX_DEPOSIT_MEMO_LENGTH,
+                "{}",
+                ERR210_DEPOSIT_MEMO_TOO_LONG
+            );
+            log!(
+                "Deposit {} {} to {} with memo: {}",
+                amount,
+                token_id,
+                account_id,
+                memo
+            );
+        } else {
+            log!("Deposit {} {} to {}", amount, token_id, account_id);
+        }
+        if let Some(mut receipts) = self.deposit_receipts.get(account_id) {
+            if receipts.len() >= MAX_DEPOSIT_RECEIPTS {
+                receipts.remove(0);
+            }
+            receipts.push(DepositReceipt {
+                token_id: token_id.clone(),
+                amount: U128(amount),
+                timestamp: env::block_timestamp().into(),
+                memo,
+            });
+            self.deposit_receipts.insert(account_id, &receipts);
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! Sweeping of dust left in long inactive accounts.
+//!
+//! With a policy set by owner, an account that wasn't changed for `inactivity_period` and holds
+//! only dust, balances below min withdrawal of their tokens, can be flagged by owner or
+//! guardians, which is logged in advance. After `grace_period` from flagging its dust is moved
+//! to the insurance fund and its tokens are unregistered, storage balance stays with the account.
+//! Any change of the account after flagging cancels the sweep. Accounts without recorded
+//! activity count as active when the policy was enabled. LP shares and claims aren't affected.
+//! Activity record is included in `MEASURED_ACCOUNT_STORAGE`, the default minimal storage deposit.
+
+use near_sdk::json_types::{WrappedDuration, WrappedTimestamp};
+
+use crate::*;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct DustPolicy {
+    /// Time without changes after which an account holding only dust can be flagged.
+    pub inactivity_period: WrappedDuration,
+    /// Time between flagging and sweeping of an account.
+    pub grace_period: WrappedDuration,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug))]
+pub struct DustFlagInfo {
+    pub account_id: AccountId,
+    pub flagged_at: WrappedTimestamp,
+    pub sweepable_at: WrappedTimestamp,
+    /// True if the account changed after flagging, so it won't be swept.
+    pub active_since_flag: bool,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Sets dust policy, None disables flagging and sweeping. Only can be called by owner.
+    #[payable]
+    pub fn set_dust_policy(&mut self, policy: Option<DustPolicy>) {
+        assert_one_yocto();
+        self.assert_owner();
+        if self.dust_policy.is_none() && policy.is_some() {
+            self.dust_policy_enabled_at = env::block_timestamp();
+        }
+        match &policy {
+            Some(policy) => log!(
+                "Dust policy set to inactivity period {}, grace period {}",
+                policy.inactivity_period.0,
+                policy.grace_period.0
+            ),
+            None => log!("Dust policy removed"),
+        }
+        self.dust_policy = policy;
+    }
+
+    /// Flags inactive account holding only dust to be swept after the grace period.
+    /// Only can be called by owner or guardians.
+    pub fn flag_dust_account(&mut self, account_id: ValidAccountId) {
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        let policy = self.dust_policy.clone().expect(ERR350_NO_DUST_POLICY);
+        let account_id: AccountId = account_id.into();
+        assert!(
+            self.dust_flags.get(&account_id).is_none(),
+            "{}",
+            ERR353_ACCOUNT_ALREADY_FLAGGED
+        );
+        assert!(
+            env::block_timestamp()
+                >= self.internal_last_activity(&account_id) + policy.inactivity_period.0,
+            "{}",
+            ERR351_ACCOUNT_NOT_INACTIVE
+        );
+        self.assert_only_dust(&account_id);
+        self.dust_flags.insert(&account_id, &env::block_timestamp());
+        log!(
+            "Account {} flagged as inactive with dust, sweepable at {}",
+            account_id,
+            env::block_timestamp() + policy.grace_period.0
+        );
+    }
+
+    /// Moves dust of flagged account to the insurance fund once the grace period passed.
+    /// If the account changed after flagging, removes the flag instead and returns false.
+    /// Only can be called by owner or guardians.
+    pub fn sweep_dust_account(&mut self, account_id: ValidAccountId) -> bool {
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        let policy = self.dust_policy.clone().expect(ERR350_NO_DUST_POLICY);
+        let account_id: AccountId = account_id.into();
+        let flagged_at = self
+            .dust_flags
+            .remove(&account_id)
+            .expect(ERR354_ACCOUNT_NOT_FLAGGED);
+        if self.internal_last_activity(&account_id) > flagged_at {
+            log!("Account {} was active since flagged, sweep cancelled", account_id);
+            return false;
+        }
+        assert!(
+            env::block_timestamp() >= flagged_at + policy.grace_period.0,
+            "{}",
+            ERR355_DUST_GRACE_PERIOD
+        );
+        self.assert_only_dust(&account_id);
+        let mut account = self.internal_unwrap_account(&account_id);
+        for token_id in account.get_tokens() {
+            let amount = account.get_balance(&token_id).unwrap();
+            if amount > 0 {
+                account.withdraw(&token_id, amount);
+                let mut fund = self.insurance_tokens.get(&token_id).unwrap_or_default();
+                fund.balance += amount;
+                fund.total_inflow += amount;
+                self.insurance_tokens.insert(&token_id, &fund);
+                log!(
+                    "Insurance fund received {} of {} swept from {}",
+                    amount,
+                    token_id,
+                    account_id
+                );
+            }
+            account.unregister(&token_id);
+        }
+        // Storage only shrinks and sweep isn't an activity of the account, so it's saved directly.
+        self.accounts.insert(&account_id, &account.into());
+        log!("Account {} swept", account_id);
+        true
+    }
+
+    pub fn get_dust_policy(&self) -> Option<DustPolicy> {
+        self.dust_policy.clone()
+    }
+
+    /// Returns time of the last change of the account, None if it's not recorded.
+    pub fn get_account_activity(&self, account_id: ValidAccountId) -> Option<WrappedTimestamp> {
+        self.account_activity
+            .get(account_id.as_ref())
+            .map(|timestamp| timestamp.into())
+    }
+
+    pub fn get_dust_flags(&self, from_index: u64, limit: u64) -> Vec<DustFlagInfo> {
+        let grace_period = self
+            .dust_policy
+            .as_ref()
+            .map_or(0, |policy| policy.grace_period.0);
+        let keys = self.dust_flags.keys_as_vector();
+        (from_index..std::cmp::min(from_index + limit, keys.len()))
+            .map(|index| {
+                let account_id = keys.get(index).unwrap();
+                let flagged_at = self.dust_flags.get(&account_id).unwrap();
+                DustFlagInfo {
+                    active_since_flag: self.internal_last_activity(&account_id) > flagged_at,
+                    account_id,
+                    flagged_at: flagged_at.into(),
+                    sweepable_at: (flagged_at + grace_period).into(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl Contract {
+    /// Records change of the account for the dust policy.
+    pub(crate) fn internal_record_activity(&mut self, account_id: &AccountId) {
+        self.account_activity
+            .insert(account_id, &env::block_timestamp());
+    }
+
+    fn internal_last_activity(&self, account_id: &AccountId) -> Timestamp {
+        self.account_activity
+            .get(account_id)
+            .unwrap_or(self.dust_policy_enabled_at)
+    }
+
+    fn assert_only_dust(&self, account_id: &AccountId) {
+        assert_ne!(account_id, &self.owner_id, "{}", ERR352_MORE_THAN_DUST);
+        let account = self.internal_unwrap_account(account_id);
+        for token_id in account.get_tokens() {
+            let amount = account.get_balance(&token_id).unwrap();
+            assert!(
+                amount == 0 || amount < self.internal_min_withdrawal(&token_id),
+                "{}",
+                ERR352_MORE_THAN_DUST
+            );
+        }
+    }
+}
@@ -5,6 +5,8 @@ pub const ERR11_INSUFFICIENT_STORAGE: &str = "E11: insufficient $NEAR storage de
 pub const ERR12_TOKEN_NOT_WHITELISTED: &str = "E12: token not whitelisted";
 pub const ERR13_LP_NOT_REGISTERED: &str = "E13: LP not registered";
 pub const ERR14_LP_ALREADY_REGISTERED: &str = "E14: LP already registered";
+pub const ERR15_INVALID_STORAGE_CONFIG: &str = "E15: storage config below account storage";
+pub const ERR16_INVALID_STORAGE_PRICE_BUFFER: &str = "E16: storage price buffer too high";
 
 // Accounts.
 
@@ -49,12 +51,240 @@ pub const ERR68_SLIPPAGE: &str = "E68: slippage error";
 pub const ERR69_MIN_RESERVE: &str = "E69: pool reserved token balance less than MIN_RESERVE";
 pub const ERR70_SWAP_OUT_CALC_ERR: &str = "E70: encounter err when calc swap out";
 pub const ERR71_SWAP_DUP_TOKENS: &str = "E71: illegal swap with duplicated tokens";
+pub const ERR72_QUOTE_EXPIRED: &str = "E72: pool changed too much since quote";
+pub const ERR73_DECIMALS_UNVERIFIED: &str = "E73: pool decimals check pending or mismatched";
+pub const ERR74_EXTRACTION_LIMIT: &str = "E74: value extracted in block exceeds limit";
 
 // pool manage
 pub const ERR81_AMP_IN_LOCK: &str = "E81: amp is currently in lock";
 pub const ERR82_INSUFFICIENT_RAMP_TIME: &str = "E82: insufficient ramp time";
 pub const ERR83_INVALID_AMP_FACTOR: &str = "E83: invalid amp factor";
 pub const ERR84_AMP_LARGE_CHANGE: &str = "E84: amp factor change is too large";
+pub const ERR85_POOL_FEE_COMPOUNDING: &str = "E85: pool exchange fee is compounding";
+pub const ERR86_NO_EXCHANGE_FEE_SHARES: &str = "E86: no exchange fee shares to skim";
+pub const ERR87_POOL_NOT_EMPTY: &str = "E87: pool already has liquidity";
+pub const ERR88_TOO_MANY_INTERMEDIARIES: &str = "E88: too many route intermediaries";
+pub const ERR89_INVALID_REFERRAL_FEE_TIERS: &str = "E89: invalid referral fee tiers";
 
 // Permissions
 pub const ERR100_NOT_ALLOWED: &str = "E100: no permission to invoke this";
+
+// Permits
+pub const ERR110_INVALID_PERMIT_KEY: &str = "E110: invalid permit key";
+pub const ERR111_PERMIT_KEY_NOT_SET: &str = "E111: permit key not set";
+pub const ERR112_PERMIT_EXPIRED: &str = "E112: permit expired";
+pub const ERR113_PERMIT_WRONG_NONCE: &str = "E113: wrong permit nonce";
+pub const ERR114_PERMIT_BAD_SIGNATURE: &str = "E114: invalid permit signature";
+pub const ERR115_TOO_MANY_PERMITS: &str = "E115: too many permits in batch";
+pub const ERR116_PERMIT_WRONG_OPERATOR: &str = "E116: permit issued to another operator";
+
+// Token shares
+pub const ERR120_SHARES_MODE_ENABLED: &str = "E120: token already in shares mode";
+pub const ERR121_SHARES_AMOUNT_TOO_SMALL: &str = "E121: amount too small for token shares";
+pub const ERR122_NOT_SHARES_MODE: &str = "E122: token not in shares mode";
+pub const ERR123_TOKEN_HAS_DEPOSITS: &str = "E123: token has deposits or reserves";
+
+// Fee split
+pub const ERR130_INVALID_FEE_SPLIT: &str = "E130: invalid exchange fee split";
+pub const ERR131_TOO_MANY_FEE_RECIPIENTS: &str = "E131: too many exchange fee recipients";
+
+// Insurance fund
+pub const ERR140_INSUFFICIENT_INSURANCE_FUND: &str = "E140: insurance fund balance not enough";
+pub const ERR141_INSURANCE_TIMELOCK: &str = "E141: insurance disbursement is timelocked";
+pub const ERR142_INSURANCE_PERIOD_CAP: &str = "E142: insurance disbursement exceeds period cap";
+pub const ERR143_NO_PENDING_DISBURSEMENT: &str = "E143: no pending insurance disbursement";
+pub const ERR144_INVALID_INSURANCE_CONFIG: &str = "E144: invalid insurance config";
+
+// Trading competitions
+pub const ERR150_INVALID_COMPETITION: &str = "E150: invalid competition";
+pub const ERR151_TOO_MANY_COMPETITIONS: &str = "E151: too many open competitions";
+pub const ERR152_NO_COMPETITION: &str = "E152: competition not found";
+pub const ERR153_NO_COMPETITION_REWARD: &str = "E153: no competition reward to claim";
+pub const ERR154_COMPETITION_NOT_ENDED: &str = "E154: competition not ended";
+
+// Token quarantine
+pub const ERR160_NOT_ENOUGH_FAILED_WITHDRAWS: &str = "E160: not enough failed withdraws to quarantine token";
+pub const ERR161_TOKEN_UNRESPONSIVE: &str = "E161: token is unresponsive";
+pub const ERR162_NO_QUARANTINED_AMOUNT: &str = "E162: no quarantined amount of the token";
+
+// Account migration
+pub const ERR170_NO_MIGRATION_PEER: &str = "E170: migration peer not set";
+pub const ERR171_INVALID_ACCOUNT_SNAPSHOT: &str = "E171: invalid account snapshot";
+pub const ERR172_ACCOUNT_ALREADY_IMPORTED: &str = "E172: account already imported";
+
+// Withdraw delay
+pub const ERR180_WITHDRAW_DELAY_TOO_LONG: &str = "E180: withdraw delay too long";
+pub const ERR181_WITHDRAWAL_NOT_READY: &str = "E181: withdrawal delay not passed";
+pub const ERR182_TOO_MANY_PENDING_WITHDRAWALS: &str = "E182: too many pending withdrawals";
+pub const ERR183_NO_PENDING_WITHDRAWAL: &str = "E183: pending withdrawal not found";
+pub const ERR184_UNREGISTER_WITH_WITHDRAW_DELAY: &str = "E184: can't unregister token with withdraw delay";
+pub const ERR185_TRANSFER_WITH_WITHDRAW_DELAY: &str = "E185: can't transfer with withdraw delay";
+
+// Peg monitor
+pub const ERR190_INVALID_PEG_MONITOR_CONFIG: &str = "E190: invalid peg monitor config";
+
+// Pool bounds
+pub const ERR200_POOL_FEE_OUT_OF_BOUNDS: &str = "E200: pool fee out of bounds";
+pub const ERR201_AMP_OUT_OF_BOUNDS: &str = "E201: amp factor out of bounds";
+pub const ERR202_ADMIN_FEE_OUT_OF_BOUNDS: &str = "E202: admin fee out of bounds";
+pub const ERR203_INVALID_POOL_BOUNDS: &str = "E203: invalid pool bounds";
+
+// Deposit receipts
+pub const ERR210_DEPOSIT_MEMO_TOO_LONG: &str = "E210: deposit memo too long";
+pub const ERR211_INSUFFICIENT_RECEIPTS_DEPOSIT: &str = "E211: attached deposit not enough for deposit receipts";
+pub const ERR212_DEPOSIT_RECEIPTS_NOT_ENABLED: &str = "E212: deposit receipts not enabled";
+
+// Fee side
+pub const ERR220_FEE_SIDE_NOT_SUPPORTED: &str = "E220: fee side not supported by the pool";
+
+// Account index
+pub const ERR230_TOO_MANY_ACCOUNTS: &str = "E230: too many accounts in batch";
+
+// Referral budget
+pub const ERR240_INVALID_REFERRAL_BUDGET: &str = "E240: invalid referral budget";
+
+// Pool launch
+pub const ERR250_POOL_LAUNCH_DELAY_TOO_LONG: &str = "E250: pool launch delay too long";
+pub const ERR251_POOL_NOT_LAUNCHED: &str = "E251: swaps of the pool are not open yet";
+
+// Liquidity events
+pub const ERR260_INVALID_REFERENCE_POOL: &str = "E260: invalid reference pool";
+
+// Guardian limits
+pub const ERR270_GUARDIAN_LIMIT_EXCEEDED: &str = "E270: guardian limit exceeded";
+pub const ERR271_INVALID_GUARDIAN_LIMIT: &str = "E271: invalid guardian limit";
+
+// Share loans
+pub const ERR280_SHARE_LOANS_NOT_ALLOWED: &str = "E280: share loans not allowed for the pool";
+pub const ERR281_INVALID_SHARE_LOAN_CONFIG: &str = "E281: invalid share loan config";
+pub const ERR282_WRONG_LOAN_TOKEN: &str = "E282: wrong loan token";
+pub const ERR283_LOAN_TOO_LARGE: &str = "E283: loan exceeds max ltv";
+pub const ERR284_NO_SHARE_LOAN: &str = "E284: no share loan";
+pub const ERR285_LOAN_NOT_LIQUIDATABLE: &str = "E285: loan is not liquidatable";
+pub const ERR286_INSUFFICIENT_LENDING_RESERVE: &str = "E286: insufficient lending reserve";
+pub const ERR287_SHARES_LOCKED: &str = "E287: shares are locked by a loan";
+
+// Pagination
+pub const ERR290_INVALID_CURSOR: &str = "E290: invalid cursor";
+
+// Account merge
+pub const ERR300_NO_MERGE_REQUEST: &str = "E300: no merge request";
+pub const ERR301_MERGE_SHARE_LOAN: &str = "E301: account with share loan can't be merged";
+pub const ERR302_MERGE_PENDING_WITHDRAWALS: &str = "E302: account with pending withdrawals can't be merged";
+pub const ERR303_MERGE_INTO_SELF: &str = "E303: account can't be merged into itself";
+pub const ERR304_MERGE_WITHDRAW_DELAY: &str = "E304: account with withdraw delay can't be merged";
+
+// Price hints
+pub const ERR310_PRICE_HINT_NOT_ACCEPTED: &str = "E310: price hints of the token are not accepted";
+pub const ERR311_INVALID_PRICE_HINT: &str = "E311: invalid price hint";
+
+// Pool metadata
+pub const ERR320_INVALID_POOL_METADATA: &str = "E320: invalid pool metadata";
+
+// Min withdrawals
+pub const ERR330_BELOW_MIN_WITHDRAWAL: &str = "E330: amount is below min withdrawal of the token";
+
+// Failed withdrawals
+pub const ERR340_NO_FAILED_WITHDRAWAL: &str = "E340: no failed withdrawal of the token";
+pub const ERR341_NO_LOST_TOKENS: &str = "E341: no lost tokens of the token";
+
+// Dust policy
+pub const ERR350_NO_DUST_POLICY: &str = "E350: dust policy is not set";
+pub const ERR351_ACCOUNT_NOT_INACTIVE: &str = "E351: account is not inactive";
+pub const ERR352_MORE_THAN_DUST: &str = "E352: account holds more than dust";
+pub const ERR353_ACCOUNT_ALREADY_FLAGGED: &str = "E353: account is already flagged";
+pub const ERR354_ACCOUNT_NOT_FLAGGED: &str = "E354: account is not flagged";
+pub const ERR355_DUST_GRACE_PERIOD: &str = "E355: grace period of flagged account has not passed";
+
+// Action limits
+pub const ERR360_TOO_MANY_ACTIONS: &str = "E360: too many actions";
+pub const ERR361_ROUTE_TOO_LONG: &str = "E361: route has too many hops";
+pub const ERR362_TOO_MANY_POOL_TOKENS: &str = "E362: too many tokens in pool";
+pub const ERR363_INVALID_ACTION_LIMITS: &str = "E363: invalid action limits";
+
+// Routing
+pub const ERR370_INVALID_ROUTE: &str = "E370: invalid route";
+
+// Weighted pools
+pub const ERR380_INVALID_WEIGHTS: &str = "E380: invalid pool weights";
+pub const ERR381_MAX_IN_RATIO: &str = "E381: amount in exceeds max part of the pool reserve";
+pub const ERR382_EMPTY_RESERVE: &str = "E382: pool has empty reserve";
+pub const ERR383_MAX_OUT_RATIO: &str = "E383: amount out exceeds max part of the pool reserve";
+pub const ERR384_AMOUNT_IN_OVERFLOW: &str = "E384: amount in overflows";
+
+// Deadlines
+pub const ERR390_DEADLINE_PASSED: &str = "E390: deadline passed";
+
+// Pool pause
+pub const ERR391_POOL_PAUSED: &str = "E391: pool paused";
+
+// Referral registry
+pub const ERR392_REFERRER_ALREADY_REGISTERED: &str = "E392: referrer already registered";
+pub const ERR393_REFERRER_NOT_REGISTERED: &str = "E393: referrer not registered";
+pub const ERR394_INVALID_REFERRAL_TIER: &str = "E394: invalid referral tier";
+
+// wNEAR
+pub const ERR395_WNEAR_NOT_SET: &str = "E395: wNEAR contract is not set";
+
+// Batch withdrawals
+pub const ERR396_NOT_ENOUGH_GAS_FOR_WITHDRAWALS: &str = "E396: not enough gas for all withdrawals";
+
+// Single-sided liquidity
+pub const ERR397_SINGLE_LIQUIDITY_NOT_SUPPORTED: &str = "E397: single-sided liquidity is only supported by simple pools of two tokens";
+
+// Flash loans
+pub const ERR398_FLASH_LOANS_DISABLED: &str = "E398: flash loans are disabled";
+pub const ERR399_FLASH_LOAN_NOT_REPAID: &str = "E399: flash loan not repaid";
+pub const ERR400_INVALID_FLASH_LOAN_AMOUNT: &str = "E400: invalid flash loan amount";
+pub const ERR401_FLASH_LOANS_NOT_SUPPORTED: &str = "E401: flash loans are only supported by simple pools";
+pub const ERR402_NO_FLASH_LOAN: &str = "E402: no flash loan";
+pub const ERR403_POOL_FLASH_LOANED: &str = "E403: pool has flash loan in flight";
+
+// Price impact
+pub const ERR404_PRICE_IMPACT_TOO_HIGH: &str = "E404: price impact exceeds max";
+
+// Owner transfer
+pub const ERR405_NOT_PENDING_OWNER: &str = "E405: caller is not the proposed owner";
+
+// Timelock
+pub const ERR406_ACTION_TIMELOCKED: &str = "E406: action must be scheduled with timelock";
+pub const ERR407_NO_PENDING_ACTION: &str = "E407: no pending action";
+pub const ERR408_TIMELOCK_NOT_PASSED: &str = "E408: timelock has not passed";
+
+// Donations
+pub const ERR409_DONATION_TO_EMPTY_POOL: &str = "E409: can't donate to pool without liquidity";
+
+// Token holdings
+pub const ERR410_TOKEN_HOLDINGS_NOT_TRACKED: &str = "E410: token holdings not tracked";
+pub const ERR411_TOKEN_HOLDINGS_TRACKED: &str = "E411: token holdings already tracked";
+
+// Exact out quotes
+pub const ERR412_NOT_ENOUGH_LIQUIDITY: &str = "E412: not enough liquidity for amount out";
+
+// Share locks
+pub const ERR413_INVALID_SHARE_LOCK: &str = "E413: share lock amount and duration must be positive";
+pub const ERR414_SHARES_LOCKED: &str = "E414: shares are locked";
+pub const ERR415_TOO_MANY_SHARE_LOCKS: &str = "E415: too many share locks";
+pub const ERR416_MERGE_SHARE_LOCKS: &str = "E416: account with share locks can't be merged";
+
+// Token metadata
+pub const ERR417_INVALID_TOKEN_METADATA_BATCH: &str = "E417: invalid number of tokens to fetch metadata of";
+
+// Stable pool decimals
+pub const ERR418_AMOUNT_OVERFLOW: &str = "E418: amount overflows comparable amount of the pool";
+
+// Fee tiers
+pub const ERR419_FEE_NOT_IN_TIERS: &str = "E419: fee is not one of the allowed fee tiers";
+pub const ERR420_INVALID_FEE_TIERS: &str = "E420: invalid fee tiers";
+
+// Pool deduplication
+pub const ERR421_POOLS_NOT_INDEXED: &str = "E421: all pools must be indexed";
+pub const ERR422_DUPLICATE_POOL: &str = "E422: simple pool of the token pair with the fee exists";
+
+// Banned tokens
+pub const ERR423_TOKEN_BANNED: &str = "E423: token is banned";
+
+// Token holdings
+pub const ERR424_TRANSFERS_IN_FLIGHT: &str = "E424: transfers of the token in flight";
+pub const ERR425_TOKEN_HOLDINGS_NOT_INITIALIZED: &str =
+    "E425: token holdings not initialized by init_token_holdings";
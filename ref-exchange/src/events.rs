@@ -0,0 +1,95 @@
+//! Standard events of state changes following NEP-297.
+//!
+//! Swaps, liquidity changes, donations to pools, token transfers out of the exchange, pool
+//! creations and share locks are logged as
+//! `EVENT_JSON:{"standard":"jumbo-exchange","version":"1.0.0","event":<name>,"data":[..]}`
+//! with accounts, pools, tokens and amounts involved, so indexers don't need to parse free-form
+//! logs. Free-form logs are kept for existing consumers.
+
+use near_sdk::serde_json;
+
+use crate::liquidity_events::LiquidityEvent;
+use crate::share_locks::ShareLockEvent;
+use crate::*;
+
+pub const EVENT_STANDARD: &str = "jumbo-exchange";
+pub const EVENT_STANDARD_VERSION: &str = "1.0.0";
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug, PartialEq))]
+pub struct SwapEvent {
+    pub account_id: AccountId,
+    pub pool_id: u64,
+    pub token_in: AccountId,
+    pub amount_in: U128,
+    pub token_out: AccountId,
+    pub amount_out: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug, PartialEq))]
+pub struct WithdrawEvent {
+    pub account_id: AccountId,
+    pub token_id: AccountId,
+    /// Amount of the token transferred, failed transfers are logged separately.
+    pub amount: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug, PartialEq))]
+pub struct PoolCreatedEvent {
+    pub account_id: AccountId,
+    pub pool_id: u64,
+    pub pool_kind: String,
+    pub tokens: Vec<AccountId>,
+    pub total_fee: u32,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug, PartialEq))]
+pub struct DonateEvent {
+    pub account_id: AccountId,
+    pub pool_id: u64,
+    pub token_id: AccountId,
+    pub amount: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug, PartialEq))]
+pub enum Event {
+    Swap(Vec<SwapEvent>),
+    AddLiquidity(Vec<LiquidityEvent>),
+    RemoveLiquidity(Vec<LiquidityEvent>),
+    Withdraw(Vec<WithdrawEvent>),
+    PoolCreated(Vec<PoolCreatedEvent>),
+    Donate(Vec<DonateEvent>),
+    LockShares(Vec<ShareLockEvent>),
+    UnlockShares(Vec<ShareLockEvent>),
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug))]
+pub struct EventLog {
+    pub standard: String,
+    pub version: String,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+impl Event {
+    pub(crate) fn emit(self) {
+        let log = EventLog {
+            standard: EVENT_STANDARD.to_string(),
+            version: EVENT_STANDARD_VERSION.to_string(),
+            event: self,
+        };
+        log!("EVENT_JSON:{}", serde_json::to_string(&log).unwrap());
+    }
+}
@@ -0,0 +1,98 @@
+//! Cap on value a single account can extract from pools per block.
+//!
+//! Guardians can set a reference token and a max amount of it an account may gain
+//! from swaps within one block. Gains and losses of the reference token are netted per block,
+//! so only the flows that end up in the reference token are counted.
+//! It's a damping layer against exploits and MEV draining the pools faster than guardians react.
+
+use near_sdk::BlockHeight;
+
+use crate::*;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct ExtractionLimit {
+    /// Token the extracted value is measured in.
+    pub reference_token: AccountId,
+    /// Max net amount of reference token an account can receive from swaps per block.
+    pub max_per_block: U128,
+}
+
+/// Net amount of the reference token account received from swaps in the block.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct BlockExtraction {
+    pub block_height: BlockHeight,
+    pub extracted: i128,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Sets per block extraction limit, None disables it. Only can be called by owner or guardians.
+    pub fn set_extraction_limit(&mut self, limit: Option<ExtractionLimit>) {
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        match &limit {
+            Some(limit) => log!(
+                "Extraction limit set to {} of {} per block",
+                limit.max_per_block.0,
+                limit.reference_token
+            ),
+            None => log!("Extraction limit removed"),
+        }
+        self.extraction_limit = limit;
+    }
+
+    pub fn get_extraction_limit(&self) -> Option<ExtractionLimit> {
+        self.extraction_limit.clone()
+    }
+}
+
+impl Contract {
+    /// Returns account balance of the reference token if extraction limit is set.
+    pub(crate) fn internal_reference_balance(&self, account: &Account) -> Option<Balance> {
+        self.extraction_limit.as_ref().map(|limit| {
+            account
+                .get_balance(&limit.reference_token)
+                .unwrap_or(0)
+        })
+    }
+
+    /// Records change of the reference token balance of the account caused by swaps.
+    /// Panics if net amount extracted in current block exceeds the limit.
+    /// Record is reused every block, its storage is covered by the exchange.
+    pub(crate) fn internal_record_extraction(
+        &mut self,
+        account_id: &AccountId,
+        prev_balance: Option<Balance>,
+        balance: Option<Balance>,
+    ) {
+        let (limit, prev_balance, balance) = match (&self.extraction_limit, prev_balance, balance) {
+            (Some(limit), Some(prev_balance), Some(balance)) => (limit, prev_balance, balance),
+            _ => return,
+        };
+        if prev_balance == balance {
+            return;
+        }
+        let block_height = env::block_index();
+        let mut record = self
+            .block_extractions
+            .get(account_id)
+            .filter(|record| record.block_height == block_height)
+            .unwrap_or(BlockExtraction {
+                block_height,
+                extracted: 0,
+            });
+        record.extracted += balance as i128 - prev_balance as i128;
+        if record.extracted > 0 && record.extracted as u128 > limit.max_per_block.0 {
+            log!(
+                "Extraction limit exceeded by {}: {} of {} in block {}",
+                account_id,
+                record.extracted,
+                limit.reference_token,
+                block_height
+            );
+            env::panic(ERR74_EXTRACTION_LIMIT.as_bytes());
+        }
+        self.block_extractions.insert(account_id, &record);
+    }
+}
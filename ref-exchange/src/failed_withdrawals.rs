@@ -0,0 +1,103 @@
+//! Claims of failed withdrawals.
+//!
+//! A withdrawal transfer can fail because the account isn't registered on the token contract.
+//! Instead of returning the amount to deposits, where it would silently wait for another
+//! withdraw, the amount is kept as a claim of the account visible in
+//! `get_failed_withdrawals`. `claim_failed_withdrawal` retries the transfer, optionally
+//! registering the account on the token first with the attached deposit. A claim that fails
+//! again goes back to the claims. Claims are covered by the exchange, so only registered accounts
+//! get them and their number per account is bounded, further failures are returned to deposits.
+//! Failed withdrawals of accounts not registered on the exchange, e.g. receivers of instant swaps,
+//! are kept as their lost tokens, see `lostfound`.
+
+use std::collections::HashMap;
+
+use near_sdk::Promise;
+
+use crate::utils::{ext_ft_storage, GAS_FOR_STORAGE_DEPOSIT};
+use crate::*;
+
+/// Max number of tokens with failed withdrawals per account.
+pub const MAX_FAILED_WITHDRAWALS: usize = 10;
+
+#[near_bindgen]
+impl Contract {
+    /// Retries transfer of the whole failed withdrawal of the token to the caller.
+    /// With `register` the attached deposit is passed to `storage_deposit` of the token to
+    /// register the caller first, it should match the token's registration fee cause the token
+    /// refunds the rest to the exchange. Otherwise requires exactly 1 yoctoNEAR.
+    #[payable]
+    pub fn claim_failed_withdrawal(&mut self, token_id: ValidAccountId, register: bool) -> Promise {
+        self.assert_contract_running();
+        if register {
+            assert!(
+                env::attached_deposit() > 0,
+                "Requires attached deposit of at least 1 yoctoNEAR"
+            );
+        } else {
+            assert_one_yocto();
+        }
+        let account_id = env::predecessor_account_id();
+        let token_id: AccountId = token_id.into();
+        let mut claims = self.failed_withdrawals.get(&account_id).unwrap_or_default();
+        let amount = claims.remove(&token_id).expect(ERR340_NO_FAILED_WITHDRAWAL);
+        if claims.is_empty() {
+            self.failed_withdrawals.remove(&account_id);
+        } else {
+            self.failed_withdrawals.insert(&account_id, &claims);
+        }
+        log!(
+            "Account {} claimed failed withdrawal of {} {}",
+            account_id,
+            amount,
+            token_id
+        );
+        let register = if register {
+            Some(ext_ft_storage::storage_deposit(
+                Some(account_id.clone()),
+                Some(true),
+                &token_id,
+                env::attached_deposit(),
+                GAS_FOR_STORAGE_DEPOSIT,
+            ))
+        } else {
+            None
+        };
+        self.internal_send_tokens_after(register, &account_id, &token_id, amount)
+    }
+
+    /// Returns failed withdrawals of the account by token.
+    pub fn get_failed_withdrawals(&self, account_id: ValidAccountId) -> HashMap<AccountId, U128> {
+        self.failed_withdrawals
+            .get(account_id.as_ref())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(token_id, amount)| (token_id, U128(amount)))
+            .collect()
+    }
+}
+
+impl Contract {
+    /// Adds failed withdrawal of the token to claims of the account.
+    /// Returns false if the account has max number of claims of other tokens.
+    pub(crate) fn internal_add_failed_withdrawal(
+        &mut self,
+        account_id: &AccountId,
+        token_id: &AccountId,
+        amount: Balance,
+    ) -> bool {
+        let mut claims = self.failed_withdrawals.get(account_id).unwrap_or_default();
+        if !claims.contains_key(token_id) && claims.len() >= MAX_FAILED_WITHDRAWALS {
+            return false;
+        }
+        *claims.entry(token_id.clone()).or_insert(0) += amount;
+        self.failed_withdrawals.insert(account_id, &claims);
+        log!(
+            "Withdrawal of {} {} to {} failed, it can be claimed",
+            amount,
+            token_id,
+            account_id
+        );
+        true
+    }
+}
@@ -0,0 +1,58 @@
+//! Side of the swap a pool takes its fee on.
+//!
+//! Simple pools deduct the fee from the amount in by default, owner can switch a pool to take it
+//! from the amount out instead, so integrations know exactly which asset the fee is paid in.
+//! Stable pools always take the fee from the amount out, weighted pools from the amount in. The side is shown in `PoolInfo`.
+
+use crate::admin_fee::FeeSide;
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// Sets token the simple pool takes the fee in. Only can be called by owner, timelocked.
+    #[payable]
+    pub fn set_pool_fee_side(&mut self, pool_id: u64, fee_side: FeeSide) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.assert_not_timelocked();
+        self.internal_set_pool_fee_side(pool_id, fee_side);
+    }
+}
+
+impl Contract {
+    /// Sets token the simple pool takes the fee in, see `set_pool_fee_side`.
+    pub(crate) fn internal_set_pool_fee_side(&mut self, pool_id: u64, fee_side: FeeSide) {
+        let pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+        match pool {
+            Pool::StableSwapPool(_) => {
+                assert!(fee_side == FeeSide::Output, "{}", ERR220_FEE_SIDE_NOT_SUPPORTED);
+                return;
+            }
+            Pool::WeightedPool(_) => {
+                assert!(fee_side == FeeSide::Input, "{}", ERR220_FEE_SIDE_NOT_SUPPORTED);
+                return;
+            }
+            Pool::SimplePool(_) => (),
+        }
+        // Default side doesn't take storage, the rest is covered by the exchange.
+        match fee_side {
+            FeeSide::Input => {
+                self.pool_fee_sides.remove(&pool_id);
+                log!("Pool {} takes fee on input", pool_id);
+            }
+            FeeSide::Output => {
+                self.pool_fee_sides.insert(&pool_id, &fee_side);
+                log!("Pool {} takes fee on output", pool_id);
+            }
+        }
+    }
+
+    /// Returns side the pool takes the fee on.
+    pub(crate) fn internal_get_fee_side(&self, pool_id: u64, pool: &Pool) -> FeeSide {
+        match pool {
+            Pool::SimplePool(_) => self.pool_fee_sides.get(&pool_id).unwrap_or(FeeSide::Input),
+            Pool::StableSwapPool(_) => FeeSide::Output,
+            Pool::WeightedPool(_) => FeeSide::Input,
+        }
+    }
+}
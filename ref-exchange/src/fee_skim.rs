@@ -0,0 +1,103 @@
+//! Exchange fee shares are compounding in the pool by default.
+//! Owner can switch pools to "skim" mode, where anyone (e.g. a keeper) can periodically
+//! remove exchange fee liquidity to the fee recipients (see `fee_split`).
+
+use crate::*;
+
+/// Per pool exchange fee settings, absent for pool means compounding with nothing skimmed.
+#[derive(BorshSerialize, BorshDeserialize, Default)]
+pub struct FeeSkim {
+    /// If true, exchange fee shares can be skimmed to owner by anyone.
+    pub enabled: bool,
+    /// Total amounts of pool tokens skimmed so far.
+    pub skimmed_amounts: Vec<Balance>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug))]
+pub struct PoolFeeModeInfo {
+    /// True if exchange fee shares stay compounding in the pool.
+    pub compounding: bool,
+    /// Exchange fee shares accumulated in the pool and not yet skimmed.
+    pub exchange_shares: U128,
+    /// Exchange fee shares accrued in the pool, including removed ones.
+    pub accrued_shares: U128,
+    /// Total amounts of pool tokens skimmed to fee recipients so far.
+    pub skimmed_amounts: Vec<U128>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Switch given pool between compounding exchange fee shares (default) and skimming them.
+    /// Only can be called by owner.
+    #[payable]
+    pub fn set_pool_fee_compounding(&mut self, pool_id: u64, compounding: bool) {
+        assert_one_yocto();
+        self.assert_owner();
+        let pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+        let mut fee_skim = self.pool_fee_skims.get(&pool_id).unwrap_or_default();
+        if fee_skim.skimmed_amounts.is_empty() {
+            fee_skim.skimmed_amounts = vec![0; pool.tokens().len()];
+        }
+        fee_skim.enabled = !compounding;
+        self.pool_fee_skims.insert(&pool_id, &fee_skim);
+        log!(
+            "Pool {} exchange fee compounding set to {}",
+            pool_id,
+            compounding
+        );
+    }
+
+    /// Removes all exchange fee liquidity of the pool to fee recipients' inner accounts.
+    /// Can be called by anyone for pools that are not compounding.
+    #[payable]
+    pub fn skim_exchange_fee(&mut self, pool_id: u64) -> Vec<U128> {
+        assert_one_yocto();
+        self.assert_contract_running();
+        let mut fee_skim = self.pool_fee_skims.get(&pool_id).unwrap_or_default();
+        assert!(fee_skim.enabled, "{}", ERR85_POOL_FEE_COMPOUNDING);
+        let pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+        let shares = pool.share_balances(&env::current_account_id());
+        assert!(shares > 0, "{}", ERR86_NO_EXCHANGE_FEE_SHARES);
+        let amounts = self.internal_remove_exchange_fee_liquidity(
+            pool_id,
+            shares,
+            vec![0; pool.tokens().len()],
+        );
+        for (skimmed, amount) in fee_skim.skimmed_amounts.iter_mut().zip(amounts.iter()) {
+            *skimmed += amount;
+        }
+        self.pool_fee_skims.insert(&pool_id, &fee_skim);
+        log!(
+            "{} skimmed {} exchange fee shares of pool {}: {:?}",
+            env::predecessor_account_id(),
+            shares,
+            pool_id,
+            amounts
+        );
+        amounts.into_iter().map(|amount| amount.into()).collect()
+    }
+
+    /// Returns exchange fee mode of the pool with accumulated and skimmed amounts.
+    pub fn get_pool_fee_mode(&self, pool_id: u64) -> PoolFeeModeInfo {
+        let pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+        let fee_skim = self.pool_fee_skims.get(&pool_id).unwrap_or_default();
+        let exchange_shares = pool.share_balances(&env::current_account_id());
+        let removed_shares = self.exchange_fee_removed_shares.get(&pool_id).unwrap_or(0);
+        PoolFeeModeInfo {
+            compounding: !fee_skim.enabled,
+            exchange_shares: exchange_shares.into(),
+            accrued_shares: (exchange_shares + removed_shares).into(),
+            skimmed_amounts: if fee_skim.skimmed_amounts.is_empty() {
+                vec![U128(0); pool.tokens().len()]
+            } else {
+                fee_skim
+                    .skimmed_amounts
+                    .into_iter()
+                    .map(|amount| amount.into())
+                    .collect()
+            },
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! Distribution of the exchange fee between several recipients.
+//!
+//! Owner configures a split table (e.g. 50% treasury, 30% staking pool, 20% insurance fund).
+//! Whenever exchange fee liquidity is claimed or skimmed, removed tokens are deposited to
+//! the recipients' inner accounts according to the table. Empty table sends everything to the
+//! treasury, owner unless set.
+//! Slice of the insurance fund (see `insurance`) is taken before the split.
+
+use std::collections::HashMap;
+
+use crate::utils::{FEE_DIVISOR, U256};
+use crate::*;
+
+/// Max number of recipients in the split table, bounds gas of every fee claim.
+pub const MAX_FEE_RECIPIENTS: usize = 10;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct FeeRecipient {
+    pub account_id: AccountId,
+    /// Part of the exchange fee in bps of `FEE_DIVISOR`.
+    pub share: u32,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Replaces exchange fee split table. Shares must sum up to `FEE_DIVISOR`,
+    /// empty table sends all exchange fee to the treasury. Only can be called by owner.
+    #[payable]
+    pub fn set_exchange_fee_split(&mut self, recipients: Vec<FeeRecipient>) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.internal_set_exchange_fee_split(recipients);
+    }
+
+    /// Returns current exchange fee split table.
+    pub fn get_exchange_fee_split(&self) -> Vec<FeeRecipient> {
+        self.fee_split.clone()
+    }
+
+    /// Returns total amounts of exchange fee tokens given account received as a fee recipient.
+    pub fn get_fee_recipient_received(&self, account_id: ValidAccountId) -> HashMap<AccountId, U128> {
+        self.fee_recipient_received
+            .get(account_id.as_ref())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(token_id, amount)| (token_id, U128(amount)))
+            .collect()
+    }
+}
+
+impl Contract {
+    /// Validates and replaces exchange fee split table.
+    pub(crate) fn internal_set_exchange_fee_split(&mut self, recipients: Vec<FeeRecipient>) {
+        assert!(
+            recipients.len() <= MAX_FEE_RECIPIENTS,
+            "{}",
+            ERR131_TOO_MANY_FEE_RECIPIENTS
+        );
+        if !recipients.is_empty() {
+            let total: u32 = recipients.iter().map(|recipient| recipient.share).sum();
+            assert_eq!(total, FEE_DIVISOR, "{}", ERR130_INVALID_FEE_SPLIT);
+        }
+        for recipient in recipients.iter() {
+            assert!(
+                recipient.share > 0
+                    && recipients
+                        .iter()
+                        .filter(|other| other.account_id == recipient.account_id)
+                        .count()
+                        == 1,
+                "{}",
+                ERR130_INVALID_FEE_SPLIT
+            );
+            assert!(
+                self.accounts.contains_key(&recipient.account_id),
+                "{}",
+                ERR10_ACC_NOT_REGISTERED
+            );
+        }
+        let table: Vec<_> = recipients
+            .iter()
+            .map(|recipient| (&recipient.account_id, recipient.share))
+            .collect();
+        log!("Exchange fee split set to {:?}", table);
+        self.fee_split = recipients;
+    }
+
+    /// Deposits exchange fee tokens to recipients' inner accounts according to the split table,
+    /// after the insurance fund took its slice.
+    /// Rounding dust goes to the last recipient. Storage of recipients is covered by the exchange.
+    pub(crate) fn internal_distribute_exchange_fee(
+        &mut self,
+        tokens: &[AccountId],
+        amounts: &[Balance],
+    ) {
+        let amounts = self.internal_collect_insurance_fee(tokens, amounts);
+        let recipients = if self.fee_split.is_empty() {
+            vec![FeeRecipient {
+                account_id: self.get_treasury(),
+                share: FEE_DIVISOR,
+            }]
+        } else {
+            self.fee_split.clone()
+        };
+        let mut left = amounts.to_vec();
+        for (index, recipient) in recipients.iter().enumerate() {
+            let mut account = self.internal_unwrap_or_default_account(&recipient.account_id);
+            let mut received = self
+                .fee_recipient_received
+                .get(&recipient.account_id)
+                .unwrap_or_default();
+            for i in 0..tokens.len() {
+                let amount = if index + 1 == recipients.len() {
+                    left[i]
+                } else {
+                    (U256::from(amounts[i]) * U256::from(recipient.share)
+                        / U256::from(FEE_DIVISOR))
+                    .as_u128()
+                };
+                left[i] -= amount;
+                account.deposit(&tokens[i], amount);
+                *received.entry(tokens[i].clone()).or_insert(0) += amount;
+            }
+            self.accounts.insert(&recipient.account_id, &account.into());
+            self.account_index.insert(&recipient.account_id);
+            self.fee_recipient_received
+                .insert(&recipient.account_id, &received);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! Fee tiers of pools created by anyone.
+//!
+//! Owner curates a set of fees, e.g. 5, 30 and 100 bps, and new simple and weighted pools must
+//! use one of them, so pools can't be created with an arbitrary fee trapping users. Until owner
+//! sets tiers any fee within pool bounds is allowed. Stable pools are created by permitted
+//! accounts and are only limited by the bounds. Existing pools are not affected.
+
+use crate::utils::FEE_DIVISOR;
+use crate::*;
+
+/// Max number of fee tiers.
+pub const MAX_FEE_TIERS: usize = 10;
+
+#[near_bindgen]
+impl Contract {
+    /// Sets fee tiers of new simple and weighted pools in bps of `FEE_DIVISOR`, in ascending
+    /// order. Empty tiers allow any fee within pool bounds. Only can be called by owner.
+    #[payable]
+    pub fn set_pool_fee_tiers(&mut self, fee_tiers: Vec<u32>) {
+        assert_one_yocto();
+        self.assert_owner();
+        assert!(
+            fee_tiers.len() <= MAX_FEE_TIERS
+                && fee_tiers.windows(2).all(|pair| pair[0] < pair[1])
+                && fee_tiers.last().filter(|fee| **fee >= FEE_DIVISOR).is_none(),
+            "{}",
+            ERR420_INVALID_FEE_TIERS
+        );
+        log!("Pool fee tiers set to {:?}", fee_tiers);
+        self.pool_fee_tiers = fee_tiers;
+    }
+
+    /// Returns fees allowed for new simple and weighted pools, empty if any fee is allowed.
+    pub fn get_pool_fee_tiers(&self) -> Vec<u32> {
+        self.pool_fee_tiers.clone()
+    }
+}
+
+impl Contract {
+    /// Returns true if the fee is one of fee tiers or there are none.
+    pub(crate) fn is_fee_tier(&self, fee: u32) -> bool {
+        self.pool_fee_tiers.is_empty() || self.pool_fee_tiers.binary_search(&fee).is_ok()
+    }
+
+    pub(crate) fn assert_fee_tier(&self, fee: u32) {
+        assert!(self.is_fee_tier(fee), "{}", ERR419_FEE_NOT_IN_TIERS);
+    }
+}
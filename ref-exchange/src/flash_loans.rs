@@ -0,0 +1,268 @@
+//! Flash loans from reserves of simple pools.
+//!
+//! Cross-contract calls on NEAR are not atomic, so the loan never leaves the exchange: borrowed
+//! tokens are taken from the pool reserve into the borrower's inner account and the receiver's
+//! `on_flash_loan` hook is called. The receiver uses the balance through the exchange, e.g.
+//! swaps in other pools, and ensures the account holds the loan plus fee before the hook
+//! returns. The callback then takes the debt from the account back to the pool reserve, the fee
+//! accrues to the pool's LPs. The pool is locked for swaps and liquidity changes while the loan
+//! is in flight. If the account can't repay in full, the rest stays as debt and tokens can't
+//! leave the account until it's repaid with `repay_flash_loan`.
+
+// Generated `ext_flash_loan_receiver::on_flash_loan` takes the receiver, deposit and gas too.
+#![allow(clippy::too_many_arguments)]
+
+use near_sdk::{ext_contract, Gas};
+
+use crate::utils::{ext_self, FEE_DIVISOR, U256};
+use crate::*;
+
+/// Amount of gas for settling the loan after the hook.
+pub const GAS_FOR_RESOLVE_FLASH_LOAN: Gas = 10_000_000_000_000;
+
+/// Amount of gas kept by `flash_loan` for its own execution.
+pub const GAS_FOR_FLASH_LOAN_CALL: Gas = 20_000_000_000_000;
+
+#[ext_contract(ext_flash_loan_receiver)]
+pub trait FlashLoanReceiver {
+    /// Called after `amount` of `token_id` is deposited to the inner account of `sender_id`.
+    /// The account must hold `amount + fee` of the token once the call finishes.
+    fn on_flash_loan(
+        &mut self,
+        sender_id: AccountId,
+        token_id: AccountId,
+        amount: U128,
+        fee: U128,
+        msg: String,
+    );
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct FlashLoan {
+    pub pool_id: u64,
+    pub token_id: AccountId,
+    pub debt: Balance,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug, PartialEq))]
+pub struct FlashLoanInfo {
+    pub pool_id: u64,
+    pub token_id: AccountId,
+    pub debt: U128,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Sets fee of flash loans in bps of `FEE_DIVISOR`, None disables flash loans.
+    /// Only can be called by owner, timelocked.
+    #[payable]
+    pub fn set_flash_loan_fee(&mut self, fee: Option<u32>) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.assert_not_timelocked();
+        self.internal_set_flash_loan_fee(fee);
+    }
+
+    pub fn get_flash_loan_fee(&self) -> Option<u32> {
+        self.flash_loan_fee
+    }
+
+    /// Returns unpaid flash loan of the account.
+    pub fn get_flash_loan(&self, account_id: ValidAccountId) -> Option<FlashLoanInfo> {
+        self.flash_loans
+            .get(account_id.as_ref())
+            .map(|loan| FlashLoanInfo {
+                pool_id: loan.pool_id,
+                token_id: loan.token_id,
+                debt: U128(loan.debt),
+            })
+    }
+
+    /// Lends `amount` of `token_id` from the reserve of the simple pool to the caller's inner
+    /// account and calls `on_flash_loan` on the receiver. The token must be registered in the
+    /// account. Attached deposit covers loan storage while it's in flight.
+    #[payable]
+    pub fn flash_loan(
+        &mut self,
+        pool_id: u64,
+        token_id: ValidAccountId,
+        amount: U128,
+        receiver_id: ValidAccountId,
+        msg: String,
+    ) -> Promise {
+        self.assert_contract_running();
+        let fee = self.flash_loan_fee.expect(ERR398_FLASH_LOANS_DISABLED);
+        let prev_storage = env::storage_usage();
+        let sender_id = env::predecessor_account_id();
+        assert!(
+            !self.flash_loans.contains_key(&sender_id),
+            "{}",
+            ERR399_FLASH_LOAN_NOT_REPAID
+        );
+        self.assert_pool_launched(pool_id);
+        self.assert_pool_not_paused(pool_id);
+        self.assert_pool_not_flash_loaned(pool_id);
+        let token_id: AccountId = token_id.into();
+        let mut pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+        match &mut pool {
+            Pool::SimplePool(pool) => {
+                let index = pool
+                    .token_account_ids
+                    .iter()
+                    .position(|id| id == &token_id)
+                    .expect("ERR_MISSING_TOKEN");
+                assert!(
+                    amount.0 > 0 && amount.0 < pool.amounts[index],
+                    "{}",
+                    ERR400_INVALID_FLASH_LOAN_AMOUNT
+                );
+                pool.amounts[index] -= amount.0;
+            }
+            Pool::StableSwapPool(_) | Pool::WeightedPool(_) => {
+                env::panic(ERR401_FLASH_LOANS_NOT_SUPPORTED.as_bytes())
+            }
+        }
+        self.internal_save_pool(pool_id, &pool);
+        let mut account = self.internal_unwrap_account(&sender_id);
+        assert!(
+            account.get_balance(&token_id).is_some(),
+            "{}",
+            ERR21_TOKEN_NOT_REG
+        );
+        account.deposit(&token_id, amount.0);
+        self.internal_save_account(&sender_id, account);
+        let fee_amount = ((U256::from(amount.0) * U256::from(fee) + U256::from(FEE_DIVISOR - 1))
+            / U256::from(FEE_DIVISOR))
+        .as_u128();
+        self.flash_loans.insert(
+            &sender_id,
+            &FlashLoan {
+                pool_id,
+                token_id: token_id.clone(),
+                debt: amount.0 + fee_amount,
+            },
+        );
+        self.flash_loan_pools.insert(&pool_id);
+        self.internal_check_storage(prev_storage);
+        log!(
+            "{} flash borrowed {} of {} from pool {} with fee {}",
+            sender_id,
+            amount.0,
+            token_id,
+            pool_id,
+            fee_amount
+        );
+        ext_flash_loan_receiver::on_flash_loan(
+            sender_id.clone(),
+            token_id,
+            amount,
+            U128(fee_amount),
+            msg,
+            receiver_id.as_ref(),
+            0,
+            env::prepaid_gas()
+                - env::used_gas()
+                - GAS_FOR_RESOLVE_FLASH_LOAN
+                - GAS_FOR_FLASH_LOAN_CALL,
+        )
+        .then(ext_self::exchange_callback_flash_loan(
+            sender_id,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_FLASH_LOAN,
+        ))
+    }
+
+    /// Takes the debt from the account back to the pool whatever the hook result, and unlocks
+    /// the pool. Returns remaining debt.
+    #[private]
+    pub fn exchange_callback_flash_loan(&mut self, sender_id: AccountId) -> U128 {
+        let debt = self.internal_repay_flash_loan(&sender_id);
+        if let Some(loan) = self.flash_loans.get(&sender_id) {
+            self.flash_loan_pools.remove(&loan.pool_id);
+            log!("Flash loan of {} not repaid, debt {}", sender_id, debt);
+        }
+        U128(debt)
+    }
+
+    /// Repays unpaid flash loan from the inner account. Returns remaining debt.
+    #[payable]
+    pub fn repay_flash_loan(&mut self) -> U128 {
+        assert_one_yocto();
+        let sender_id = env::predecessor_account_id();
+        assert!(
+            self.flash_loans.contains_key(&sender_id),
+            "{}",
+            ERR402_NO_FLASH_LOAN
+        );
+        U128(self.internal_repay_flash_loan(&sender_id))
+    }
+}
+
+impl Contract {
+    /// Sets fee of flash loans, see `set_flash_loan_fee`.
+    pub(crate) fn internal_set_flash_loan_fee(&mut self, fee: Option<u32>) {
+        if let Some(fee) = fee {
+            assert!(fee <= FEE_DIVISOR, "ERR_ILLEGAL_FEE");
+        }
+        self.flash_loan_fee = fee;
+        match fee {
+            Some(fee) => log!("Flash loan fee set to {}", fee),
+            None => log!("Flash loans disabled"),
+        }
+    }
+
+    /// Moves up to the debt of the account's flash loan from its deposit to the pool reserve.
+    /// Removes the loan once it's repaid, returns remaining debt.
+    fn internal_repay_flash_loan(&mut self, account_id: &AccountId) -> Balance {
+        let mut loan = match self.flash_loans.get(account_id) {
+            Some(loan) => loan,
+            None => return 0,
+        };
+        let mut account = self.internal_unwrap_account(account_id);
+        let paid = std::cmp::min(account.get_balance(&loan.token_id).unwrap_or(0), loan.debt);
+        if paid > 0 {
+            account.withdraw(&loan.token_id, paid);
+            self.internal_save_account(account_id, account);
+            let mut pool = self.pools.get(loan.pool_id).expect("ERR_NO_POOL");
+            if let Pool::SimplePool(pool) = &mut pool {
+                let index = pool
+                    .token_account_ids
+                    .iter()
+                    .position(|id| id == &loan.token_id)
+                    .expect("ERR_MISSING_TOKEN");
+                pool.amounts[index] += paid;
+            }
+            self.internal_save_pool(loan.pool_id, &pool);
+        }
+        loan.debt -= paid;
+        if loan.debt == 0 {
+            self.flash_loans.remove(account_id);
+            self.flash_loan_pools.remove(&loan.pool_id);
+            log!("Flash loan of {} repaid", account_id);
+        } else {
+            self.flash_loans.insert(account_id, &loan);
+        }
+        loan.debt
+    }
+
+    /// Panics if the pool has a flash loan in flight.
+    pub(crate) fn assert_pool_not_flash_loaned(&self, pool_id: u64) {
+        assert!(
+            !self.flash_loan_pools.contains(&pool_id),
+            "{}",
+            ERR403_POOL_FLASH_LOANED
+        );
+    }
+
+    /// Panics if the account has unpaid flash loan, so borrowed tokens can't leave it.
+    pub(crate) fn assert_no_flash_loan(&self, account_id: &AccountId) {
+        assert!(
+            !self.flash_loans.contains_key(account_id),
+            "{}",
+            ERR399_FLASH_LOAN_NOT_REPAID
+        );
+    }
+}
@@ -0,0 +1,152 @@
+//! Execution of owner level operations by a governance DAO.
+//!
+//! Owner configures a DAO contract (e.g. Sputnik), which then can execute typed governance
+//! actions with a single function call proposal to `execute_governance_action`.
+//! The owner keeps its own permissions, so control can be moved to the DAO step by step.
+//! Actions timelocked for owner are rejected while the timelock delay is set, so the DAO can't
+//! bypass the queue of `schedule_action`.
+
+use near_sdk::serde_json;
+
+use crate::fee_split::FeeRecipient;
+use crate::*;
+
+/// Owner level operation executed by governance DAO.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum GovernanceAction {
+    /// Proposes new owner, ownership changes once it calls `accept_ownership`.
+    ProposeOwner {
+        owner_id: ValidAccountId,
+    },
+    /// Replaces governance DAO, None disables governance actions.
+    SetGovernanceDao {
+        dao_id: Option<ValidAccountId>,
+    },
+    ExtendGuardians {
+        guardians: Vec<ValidAccountId>,
+    },
+    RemoveGuardians {
+        guardians: Vec<ValidAccountId>,
+    },
+    ChangeState {
+        state: RunningState,
+    },
+    ExtendWhitelistedTokens {
+        tokens: Vec<ValidAccountId>,
+    },
+    RemoveWhitelistedTokens {
+        tokens: Vec<ValidAccountId>,
+    },
+    ModifyAdminFee {
+        exchange_fee: u32,
+        referral_fee: u32,
+    },
+    /// Treasury: replaces exchange fee split table.
+    SetExchangeFeeSplit {
+        recipients: Vec<FeeRecipient>,
+    },
+    /// Treasury: removes exchange fee liquidity of the pool to fee recipients.
+    RemoveExchangeFeeLiquidity {
+        pool_id: u64,
+        shares: U128,
+        min_amounts: Vec<U128>,
+    },
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Sets DAO allowed to execute governance actions, None disables them. Only can be called by owner.
+    #[payable]
+    pub fn set_governance_dao(&mut self, dao_id: Option<ValidAccountId>) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.internal_set_governance_dao(dao_id);
+    }
+
+    pub fn get_governance_dao(&self) -> Option<AccountId> {
+        self.governance_dao.clone()
+    }
+
+    /// Executes owner level operation. Only can be called by governance DAO.
+    #[payable]
+    pub fn execute_governance_action(&mut self, action: GovernanceAction) {
+        assert_one_yocto();
+        assert_eq!(
+            Some(env::predecessor_account_id()),
+            self.governance_dao,
+            "{}",
+            ERR100_NOT_ALLOWED
+        );
+        log!(
+            "Governance action by {}: {}",
+            env::predecessor_account_id(),
+            serde_json::to_string(&action).unwrap()
+        );
+        match action {
+            GovernanceAction::ProposeOwner { owner_id } => {
+                log!("Owner {} proposed", owner_id.as_ref());
+                self.pending_owner_id = Some(owner_id.into());
+            }
+            GovernanceAction::SetGovernanceDao { dao_id } => {
+                self.internal_set_governance_dao(dao_id);
+            }
+            GovernanceAction::ExtendGuardians { guardians } => {
+                for guardian in guardians {
+                    self.internal_set_guardian(guardian.as_ref(), true);
+                }
+            }
+            GovernanceAction::RemoveGuardians { guardians } => {
+                for guardian in guardians {
+                    self.internal_set_guardian(guardian.as_ref(), false);
+                }
+            }
+            GovernanceAction::ChangeState { state } => {
+                self.state = state;
+            }
+            GovernanceAction::ExtendWhitelistedTokens { tokens } => {
+                for token in tokens {
+                    self.whitelisted_tokens.insert(token.as_ref());
+                }
+            }
+            GovernanceAction::RemoveWhitelistedTokens { tokens } => {
+                for token in tokens {
+                    self.whitelisted_tokens.remove(token.as_ref());
+                }
+            }
+            GovernanceAction::ModifyAdminFee {
+                exchange_fee,
+                referral_fee,
+            } => {
+                self.assert_not_timelocked();
+                self.internal_modify_admin_fee(exchange_fee, referral_fee);
+            }
+            GovernanceAction::SetExchangeFeeSplit { recipients } => {
+                self.internal_set_exchange_fee_split(recipients);
+            }
+            GovernanceAction::RemoveExchangeFeeLiquidity {
+                pool_id,
+                shares,
+                min_amounts,
+            } => {
+                self.assert_not_timelocked();
+                self.assert_contract_running();
+                self.internal_remove_exchange_fee_liquidity(
+                    pool_id,
+                    shares.into(),
+                    min_amounts
+                        .into_iter()
+                        .map(|amount| amount.into())
+                        .collect(),
+                );
+            }
+        }
+    }
+}
+
+impl Contract {
+    fn internal_set_governance_dao(&mut self, dao_id: Option<ValidAccountId>) {
+        self.governance_dao = dao_id.map(|dao_id| dao_id.into());
+        log!("Governance DAO set to {:?}", self.governance_dao);
+    }
+}
@@ -0,0 +1,142 @@
+//! Per guardian caps on funds moved out of the exchange treasury.
+//!
+//! Owner can cap the amount of a token each guardian can move per period. Insurance
+//! disbursements executed by a guardian are counted against its cap, so a compromised guardian
+//! key can't drain the fund beyond it. Guardians without a cap for the token are only limited by
+//! the insurance period cap, and the owner is never limited. Records are covered by the exchange.
+
+use near_sdk::json_types::WrappedDuration;
+
+use crate::*;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct GuardianLimit {
+    /// Max amount of the token moved by the guardian per period.
+    pub max_per_period: U128,
+    pub period: WrappedDuration,
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct GuardianLimitRecord {
+    pub limit: GuardianLimit,
+    pub period_index: u64,
+    pub spent: Balance,
+}
+
+impl GuardianLimitRecord {
+    fn current_period(&self) -> u64 {
+        env::block_timestamp() / self.limit.period.0
+    }
+
+    /// Returns amount spent in the current period.
+    fn current_spent(&self) -> Balance {
+        if self.period_index == self.current_period() {
+            self.spent
+        } else {
+            0
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug))]
+pub struct GuardianLimitInfo {
+    pub max_per_period: U128,
+    pub period: WrappedDuration,
+    /// Amount the guardian can still move in the current period.
+    pub available: U128,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Sets cap of the token guardian can move per period, None removes the cap.
+    /// Only can be called by owner.
+    #[payable]
+    pub fn set_guardian_limit(
+        &mut self,
+        guardian_id: ValidAccountId,
+        token_id: ValidAccountId,
+        limit: Option<GuardianLimit>,
+    ) {
+        assert_one_yocto();
+        self.assert_owner();
+        let key = (guardian_id.to_string(), token_id.to_string());
+        match limit {
+            Some(limit) => {
+                assert!(limit.period.0 > 0, "{}", ERR271_INVALID_GUARDIAN_LIMIT);
+                log!(
+                    "Limit of {} for guardian {} set to {} per {}",
+                    key.1,
+                    key.0,
+                    limit.max_per_period.0,
+                    limit.period.0
+                );
+                let mut record = GuardianLimitRecord {
+                    limit,
+                    period_index: 0,
+                    spent: 0,
+                };
+                if let Some(prev) = self.guardian_limits.get(&key) {
+                    // Keep what was spent in the current period of the same length.
+                    if prev.limit.period.0 == record.limit.period.0 {
+                        record.period_index = prev.period_index;
+                        record.spent = prev.spent;
+                    }
+                }
+                self.guardian_limits.insert(&key, &record);
+            }
+            None => {
+                self.guardian_limits.remove(&key);
+                log!("Limit of {} for guardian {} removed", key.1, key.0);
+            }
+        }
+    }
+
+    pub fn get_guardian_limit(
+        &self,
+        guardian_id: ValidAccountId,
+        token_id: ValidAccountId,
+    ) -> Option<GuardianLimitInfo> {
+        self.guardian_limits
+            .get(&(guardian_id.into(), token_id.into()))
+            .map(|record| GuardianLimitInfo {
+                max_per_period: record.limit.max_per_period,
+                period: record.limit.period,
+                available: U128(
+                    record
+                        .limit
+                        .max_per_period
+                        .0
+                        .saturating_sub(record.current_spent()),
+                ),
+            })
+    }
+}
+
+impl Contract {
+    /// Counts amount of the token moved by the caller against its guardian limit.
+    /// Panics if the limit would be exceeded.
+    pub(crate) fn internal_spend_guardian_limit(&mut self, token_id: &AccountId, amount: Balance) {
+        let account_id = env::predecessor_account_id();
+        if account_id == self.owner_id {
+            return;
+        }
+        let key = (account_id, token_id.clone());
+        let mut record = match self.guardian_limits.get(&key) {
+            Some(record) => record,
+            None => return,
+        };
+        let spent = record.current_spent() + amount;
+        assert!(
+            spent <= record.limit.max_per_period.0,
+            "{}",
+            ERR270_GUARDIAN_LIMIT_EXCEEDED
+        );
+        record.period_index = record.current_period();
+        record.spent = spent;
+        self.guardian_limits.insert(&key, &record);
+    }
+}
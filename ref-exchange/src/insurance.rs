@@ -0,0 +1,326 @@
+//! Insurance fund accumulating a slice of exchange fees.
+//!
+//! Before the exchange fee is split between recipients, `fee_share` of it goes to the fund.
+//! Guardians can propose disbursements from the fund to compensate users after incidents.
+//! A disbursement can be executed only after `INSURANCE_TIMELOCK`, and the total disbursed
+//! per `INSURANCE_PERIOD` is capped by `period_cap` of the fund balance at the period start.
+//! Disbursements executed by a guardian also count against its guardian limit of the token.
+
+use near_sdk::json_types::WrappedTimestamp;
+use near_sdk::Timestamp;
+
+use crate::pagination::Page;
+use crate::utils::{FEE_DIVISOR, U256};
+use crate::*;
+
+/// Delay between proposal and execution of a disbursement, 1 day in nano sec.
+pub const INSURANCE_TIMELOCK: Timestamp = 24 * 3600 * 1_000_000_000;
+
+/// Length of the period disbursements are capped within, 7 days in nano sec.
+pub const INSURANCE_PERIOD: Timestamp = 7 * 24 * 3600 * 1_000_000_000;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Default)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct InsuranceConfig {
+    /// Part of the exchange fee that goes to the fund, in bps of `FEE_DIVISOR`.
+    pub fee_share: u32,
+    /// Max part of the fund balance of a token disbursed per period, in bps of `FEE_DIVISOR`.
+    pub period_cap: u32,
+}
+
+/// Fund accounting of a single token.
+#[derive(BorshSerialize, BorshDeserialize, Default)]
+pub struct InsuranceToken {
+    pub balance: Balance,
+    pub total_inflow: Balance,
+    pub total_disbursed: Balance,
+    /// Start of the current cap period.
+    pub period_start: Timestamp,
+    /// Max amount allowed to be disbursed in the current period.
+    pub period_limit: Balance,
+    /// Amount disbursed in the current period.
+    pub period_disbursed: Balance,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub enum DisbursementStatus {
+    Pending,
+    Executed,
+    Cancelled,
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct Disbursement {
+    pub proposer_id: AccountId,
+    pub receiver_id: AccountId,
+    pub token_id: AccountId,
+    pub amount: Balance,
+    pub memo: String,
+    /// Block timestamp after which the disbursement can be executed.
+    pub executable_at: Timestamp,
+    pub status: DisbursementStatus,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug))]
+pub struct InsuranceTokenInfo {
+    pub token_id: AccountId,
+    pub balance: U128,
+    pub total_inflow: U128,
+    pub total_disbursed: U128,
+    /// Amount still allowed to be disbursed in the current period.
+    pub period_available: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug))]
+pub struct DisbursementInfo {
+    pub id: u64,
+    pub proposer_id: AccountId,
+    pub receiver_id: AccountId,
+    pub token_id: AccountId,
+    pub amount: U128,
+    pub memo: String,
+    pub executable_at: WrappedTimestamp,
+    pub status: DisbursementStatus,
+}
+
+impl InsuranceToken {
+    /// Starts a new cap period if there was none yet or the current one is over.
+    fn refresh_period(&mut self, period_cap: u32) {
+        let now = env::block_timestamp();
+        if self.period_start == 0 || now >= self.period_start + INSURANCE_PERIOD {
+            self.period_start = now;
+            self.period_disbursed = 0;
+            self.period_limit = (U256::from(self.balance) * U256::from(period_cap)
+                / U256::from(FEE_DIVISOR))
+            .as_u128();
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Sets part of exchange fee going to the insurance fund and the disbursement cap per period.
+    /// Only can be called by owner.
+    #[payable]
+    pub fn set_insurance_config(&mut self, config: InsuranceConfig) {
+        assert_one_yocto();
+        self.assert_owner();
+        assert!(
+            config.fee_share <= FEE_DIVISOR && config.period_cap <= FEE_DIVISOR,
+            "{}",
+            ERR144_INVALID_INSURANCE_CONFIG
+        );
+        log!(
+            "Insurance fee share set to {}, period cap to {}",
+            config.fee_share,
+            config.period_cap
+        );
+        self.insurance_config = config;
+    }
+
+    /// Proposes to pay `amount` of `token_id` from the fund to the inner account of `receiver_id`.
+    /// Only can be called by owner or guardians. Returns id of the disbursement.
+    pub fn propose_insurance_disbursement(
+        &mut self,
+        receiver_id: ValidAccountId,
+        token_id: ValidAccountId,
+        amount: U128,
+        memo: String,
+    ) -> u64 {
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        assert!(
+            self.accounts.contains_key(receiver_id.as_ref()),
+            "{}",
+            ERR10_ACC_NOT_REGISTERED
+        );
+        let fund = self
+            .insurance_tokens
+            .get(token_id.as_ref())
+            .unwrap_or_default();
+        assert!(fund.balance >= amount.0, "{}", ERR140_INSUFFICIENT_INSURANCE_FUND);
+        let id = self.insurance_disbursements.len();
+        let disbursement = Disbursement {
+            proposer_id: env::predecessor_account_id(),
+            receiver_id: receiver_id.into(),
+            token_id: token_id.into(),
+            amount: amount.0,
+            memo,
+            executable_at: env::block_timestamp() + INSURANCE_TIMELOCK,
+            status: DisbursementStatus::Pending,
+        };
+        log!(
+            "Insurance disbursement {} proposed by {}: {} of {} to {}, memo: {}",
+            id,
+            disbursement.proposer_id,
+            disbursement.amount,
+            disbursement.token_id,
+            disbursement.receiver_id,
+            disbursement.memo
+        );
+        self.insurance_disbursements.push(&disbursement);
+        id
+    }
+
+    /// Executes pending disbursement after its timelock has passed and within the period cap
+    /// and the limit of executing guardian.
+    /// Only can be called by owner or guardians.
+    pub fn execute_insurance_disbursement(&mut self, id: u64) {
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        let mut disbursement = self.internal_get_pending_disbursement(id);
+        assert!(
+            env::block_timestamp() >= disbursement.executable_at,
+            "{}",
+            ERR141_INSURANCE_TIMELOCK
+        );
+        let mut fund = self
+            .insurance_tokens
+            .get(&disbursement.token_id)
+            .unwrap_or_default();
+        assert!(
+            fund.balance >= disbursement.amount,
+            "{}",
+            ERR140_INSUFFICIENT_INSURANCE_FUND
+        );
+        fund.refresh_period(self.insurance_config.period_cap);
+        assert!(
+            fund.period_disbursed + disbursement.amount <= fund.period_limit,
+            "{}",
+            ERR142_INSURANCE_PERIOD_CAP
+        );
+        self.internal_spend_guardian_limit(&disbursement.token_id, disbursement.amount);
+        fund.balance -= disbursement.amount;
+        fund.total_disbursed += disbursement.amount;
+        fund.period_disbursed += disbursement.amount;
+        self.insurance_tokens.insert(&disbursement.token_id, &fund);
+
+        let mut account = self.internal_unwrap_account(&disbursement.receiver_id);
+        account.deposit(&disbursement.token_id, disbursement.amount);
+        self.internal_save_account(&disbursement.receiver_id, account);
+        disbursement.status = DisbursementStatus::Executed;
+        self.insurance_disbursements.replace(id, &disbursement);
+        log!(
+            "Insurance disbursement {} executed by {}: {} of {} to {}",
+            id,
+            env::predecessor_account_id(),
+            disbursement.amount,
+            disbursement.token_id,
+            disbursement.receiver_id
+        );
+    }
+
+    /// Cancels pending disbursement. Only can be called by owner or guardians.
+    pub fn cancel_insurance_disbursement(&mut self, id: u64) {
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        let mut disbursement = self.internal_get_pending_disbursement(id);
+        disbursement.status = DisbursementStatus::Cancelled;
+        self.insurance_disbursements.replace(id, &disbursement);
+        log!(
+            "Insurance disbursement {} cancelled by {}",
+            id,
+            env::predecessor_account_id()
+        );
+    }
+
+    pub fn get_insurance_config(&self) -> InsuranceConfig {
+        self.insurance_config.clone()
+    }
+
+    /// Returns balances, inflows and disbursed totals of all tokens in the insurance fund.
+    pub fn get_insurance_fund(&self) -> Vec<InsuranceTokenInfo> {
+        self.insurance_tokens
+            .iter()
+            .map(|(token_id, mut fund)| {
+                fund.refresh_period(self.insurance_config.period_cap);
+                InsuranceTokenInfo {
+                    token_id,
+                    balance: U128(fund.balance),
+                    total_inflow: U128(fund.total_inflow),
+                    total_disbursed: U128(fund.total_disbursed),
+                    period_available: U128(fund.period_limit - fund.period_disbursed),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns `limit` disbursements starting from `from_index`.
+    pub fn get_insurance_disbursements(&self, from_index: u64, limit: u64) -> Vec<DisbursementInfo> {
+        (from_index..std::cmp::min(from_index + limit, self.insurance_disbursements.len()))
+            .map(|id| self.internal_get_disbursement_info(id))
+            .collect()
+    }
+
+    /// Returns page of `limit` disbursements after the cursor.
+    pub fn get_insurance_disbursements_page(
+        &self,
+        cursor: Option<String>,
+        limit: u64,
+    ) -> Page<DisbursementInfo> {
+        Page::from_index(cursor, limit, self.insurance_disbursements.len(), |id| {
+            self.internal_get_disbursement_info(id)
+        })
+    }
+}
+
+impl Contract {
+    /// Moves the fund's slice of exchange fee tokens to the fund, returns what is left.
+    pub(crate) fn internal_collect_insurance_fee(
+        &mut self,
+        tokens: &[AccountId],
+        amounts: &[Balance],
+    ) -> Vec<Balance> {
+        let fee_share = self.insurance_config.fee_share;
+        if fee_share == 0 {
+            return amounts.to_vec();
+        }
+        let mut left = amounts.to_vec();
+        for i in 0..tokens.len() {
+            let amount = (U256::from(amounts[i]) * U256::from(fee_share)
+                / U256::from(FEE_DIVISOR))
+            .as_u128();
+            if amount == 0 {
+                continue;
+            }
+            let mut fund = self.insurance_tokens.get(&tokens[i]).unwrap_or_default();
+            fund.balance += amount;
+            fund.total_inflow += amount;
+            self.insurance_tokens.insert(&tokens[i], &fund);
+            left[i] -= amount;
+            log!("Insurance fund received {} of {}", amount, tokens[i]);
+        }
+        left
+    }
+
+    fn internal_get_pending_disbursement(&self, id: u64) -> Disbursement {
+        let disbursement = self
+            .insurance_disbursements
+            .get(id)
+            .expect(ERR143_NO_PENDING_DISBURSEMENT);
+        assert!(
+            disbursement.status == DisbursementStatus::Pending,
+            "{}",
+            ERR143_NO_PENDING_DISBURSEMENT
+        );
+        disbursement
+    }
+
+    fn internal_get_disbursement_info(&self, id: u64) -> DisbursementInfo {
+        let disbursement = self.insurance_disbursements.get(id).unwrap();
+        DisbursementInfo {
+            id,
+            proposer_id: disbursement.proposer_id,
+            receiver_id: disbursement.receiver_id,
+            token_id: disbursement.token_id,
+            amount: U128(disbursement.amount),
+            memo: disbursement.memo,
+            executable_at: disbursement.executable_at.into(),
+            status: disbursement.status,
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! Interface of the exchange for calls from other contracts.
+//!
+//! Built with `no-contract` feature, the crate only contains this module, actions and error codes,
+//! so other contracts can depend on it without pulling in the exchange itself:
+//!
+//! ```ignore
+//! ext_jumbo::swap(actions, None, &exchange_id, 1, GAS_FOR_SWAP);
+//! ```
+//!
+//! Return types are only for reference, callbacks get them serialized as JSON.
+//! Swaps on tokens transferred with `ft_transfer_call` take `TokenReceiverMessage` serialized to JSON as `msg`.
+
+use near_sdk::ext_contract;
+use near_sdk::json_types::{ValidAccountId, U128};
+
+use crate::action::SwapAction;
+
+#[ext_contract(ext_jumbo)]
+pub trait Jumbo {
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<ValidAccountId>,
+        registration_only: Option<bool>,
+    ) -> near_contract_standards::storage_management::StorageBalance;
+
+    fn register_tokens(&mut self, token_ids: Vec<ValidAccountId>);
+
+    fn unregister_tokens(&mut self, token_ids: Vec<ValidAccountId>);
+
+    /// Pending for accounts with withdraw delay, see `get_pending_withdrawals`.
+    fn withdraw(&mut self, token_id: ValidAccountId, amount: U128, unregister: Option<bool>);
+
+    fn swap(&mut self, actions: Vec<SwapAction>, referral_id: Option<ValidAccountId>) -> U128;
+
+    fn add_liquidity(&mut self, pool_id: u64, amounts: Vec<U128>, min_amounts: Option<Vec<U128>>);
+
+    fn add_stable_liquidity(&mut self, pool_id: u64, amounts: Vec<U128>, min_shares: U128) -> U128;
+
+    fn remove_liquidity(
+        &mut self,
+        pool_id: u64,
+        shares: U128,
+        min_amounts: Vec<U128>,
+        withdraw: Option<bool>,
+    );
+
+    fn remove_liquidity_by_tokens(
+        &mut self,
+        pool_id: u64,
+        amounts: Vec<U128>,
+        max_burn_shares: U128,
+        withdraw: Option<bool>,
+    ) -> U128;
+
+    fn mft_transfer(
+        &mut self,
+        token_id: String,
+        receiver_id: ValidAccountId,
+        amount: U128,
+        memo: Option<String>,
+    );
+
+    fn mft_transfer_call(
+        &mut self,
+        token_id: String,
+        receiver_id: ValidAccountId,
+        amount: U128,
+        memo: Option<String>,
+        msg: String,
+    ) -> near_sdk::PromiseOrValue<U128>;
+
+    fn mft_balance_of(&self, token_id: String, account_id: ValidAccountId) -> U128;
+
+    fn mft_total_supply(&self, token_id: String) -> U128;
+
+    fn get_number_of_pools(&self) -> u64;
+
+    fn get_pool_shares(&self, pool_id: u64, account_id: ValidAccountId) -> U128;
+
+    fn get_pool_total_shares(&self, pool_id: u64) -> U128;
+
+    fn get_deposits(
+        &self,
+        account_id: ValidAccountId,
+    ) -> std::collections::HashMap<near_sdk::AccountId, U128>;
+
+    fn get_deposit(&self, account_id: ValidAccountId, token_id: ValidAccountId) -> U128;
+
+    fn get_return(
+        &self,
+        pool_id: u64,
+        token_in: ValidAccountId,
+        amount_in: U128,
+        token_out: ValidAccountId,
+    ) -> U128;
+
+    fn get_whitelisted_tokens(&self) -> Vec<near_sdk::AccountId>;
+}
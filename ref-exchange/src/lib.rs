@@ -1,56 +1,326 @@
+#[cfg(not(feature = "no-contract"))]
+use std::collections::HashMap;
+#[cfg(not(feature = "no-contract"))]
 use std::convert::TryInto;
+#[cfg(not(feature = "no-contract"))]
 use std::fmt;
 
+#[cfg(not(feature = "no-contract"))]
 use near_contract_standards::storage_management::{
     StorageBalance, StorageBalanceBounds, StorageManagement,
 };
+#[cfg(not(feature = "no-contract"))]
 use near_sdk::serde::{Deserialize, Serialize};
+#[cfg(not(feature = "no-contract"))]
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LookupMap, UnorderedSet, Vector};
-use near_sdk::json_types::{ValidAccountId, U128};
+#[cfg(not(feature = "no-contract"))]
+use near_sdk::collections::{LookupMap, LookupSet, UnorderedMap, UnorderedSet, Vector};
+#[cfg(not(feature = "no-contract"))]
+use near_sdk::json_types::{ValidAccountId, WrappedTimestamp, U128};
+#[cfg(not(feature = "no-contract"))]
 use near_sdk::{
-    assert_one_yocto, env, log, near_bindgen, AccountId, Balance, PanicOnDefault, Promise,
-    PromiseResult, StorageUsage, BorshStorageKey
+    assert_one_yocto, env, log, near_bindgen, AccountId, Balance, BlockHeight, Duration, PanicOnDefault, Promise,
+    PromiseResult, StorageUsage, BorshStorageKey, Timestamp
 };
 
-use crate::account_deposit::{VAccount, Account};
-pub use crate::action::SwapAction;
+#[cfg(not(feature = "no-contract"))]
+use crate::account_deposit::{log_near_amount_change, VAccount, Account};
+pub use crate::action::{RoutedSwapAction, SwapAction, TokenReceiverMessage};
+#[cfg(not(feature = "no-contract"))]
 use crate::action::{Action, ActionResult};
+#[cfg(not(feature = "no-contract"))]
+use crate::action_limits::ActionLimits;
+#[cfg(not(feature = "no-contract"))]
 use crate::errors::*;
-use crate::admin_fee::AdminFees;
+#[cfg(not(feature = "no-contract"))]
+use crate::competition::Competition;
+#[cfg(not(feature = "no-contract"))]
+use crate::decimals_check::DecimalsStatus;
+#[cfg(not(feature = "no-contract"))]
+use crate::deposit_receipts::DepositReceipt;
+#[cfg(not(feature = "no-contract"))]
+use crate::dust_policy::DustPolicy;
+#[cfg(not(feature = "no-contract"))]
+use crate::events::{DonateEvent, Event, PoolCreatedEvent, SwapEvent};
+#[cfg(not(feature = "no-contract"))]
+use crate::extraction_limit::{BlockExtraction, ExtractionLimit};
+#[cfg(not(feature = "no-contract"))]
+use crate::fee_skim::FeeSkim;
+#[cfg(not(feature = "no-contract"))]
+use crate::fee_split::FeeRecipient;
+#[cfg(not(feature = "no-contract"))]
+use crate::flash_loans::FlashLoan;
+#[cfg(not(feature = "no-contract"))]
+use crate::guardian_limits::GuardianLimitRecord;
+#[cfg(not(feature = "no-contract"))]
+use crate::insurance::{Disbursement, InsuranceConfig, InsuranceToken};
+#[cfg(not(feature = "no-contract"))]
+use crate::liquidity_events::LiquidityChange;
+#[cfg(not(feature = "no-contract"))]
+use crate::admin_fee::{AdminFees, PoolAdminFees, ReferralFeeTier};
+#[cfg(not(feature = "no-contract"))]
+use crate::apr::PoolCheckpoint;
+#[cfg(not(feature = "no-contract"))]
+use crate::peg_monitor::PegMonitorConfig;
+#[cfg(not(feature = "no-contract"))]
+use crate::owner::PendingAction;
+#[cfg(not(feature = "no-contract"))]
+use crate::permit::PermitKey;
+#[cfg(not(feature = "no-contract"))]
 use crate::pool::Pool;
+#[cfg(not(feature = "no-contract"))]
+use crate::pool_bounds::PoolBounds;
+#[cfg(not(feature = "no-contract"))]
+use crate::roles::Permission;
+#[cfg(not(feature = "no-contract"))]
+use crate::pool_metadata::PoolMetadata;
+#[cfg(not(feature = "no-contract"))]
+use crate::pool_stats::PoolStats;
+#[cfg(not(feature = "no-contract"))]
+use crate::price_hints::PriceHintRecord;
+#[cfg(not(feature = "no-contract"))]
+use crate::referral_budget::ReferralBudgetRecord;
+#[cfg(not(feature = "no-contract"))]
+use crate::share_loans::{ShareLoan, ShareLoanConfig};
+#[cfg(not(feature = "no-contract"))]
+use crate::share_locks::ShareLock;
+#[cfg(not(feature = "no-contract"))]
 use crate::simple_pool::SimplePool;
+#[cfg(not(feature = "no-contract"))]
 use crate::stable_swap::StableSwapPool;
-use crate::utils::check_token_duplicates;
+#[cfg(not(feature = "no-contract"))]
+use crate::weighted_pool::WeightedPool;
+#[cfg(not(feature = "no-contract"))]
+use crate::storage_impl::StorageConfig;
+#[cfg(not(feature = "no-contract"))]
+use crate::token_holdings::TokenHoldings;
+#[cfg(not(feature = "no-contract"))]
+use crate::token_metadata::CachedTokenMetadata;
+#[cfg(not(feature = "no-contract"))]
+use crate::token_shares::TokenShares;
+#[cfg(not(feature = "no-contract"))]
+use crate::twap::PoolTwap;
+#[cfg(not(feature = "no-contract"))]
+use crate::utils::{
+    assert_deadline, check_token_duplicates, FEE_DIVISOR, PRICE_IMPACT_PRECISION, U256,
+};
+#[cfg(not(feature = "no-contract"))]
+use crate::withdraw_delay::{PendingWithdrawal, WithdrawDelay};
+#[cfg(not(feature = "no-contract"))]
+pub use crate::admin_fee::FeeSide;
+pub use crate::interface::ext_jumbo;
+#[cfg(not(feature = "no-contract"))]
 pub use crate::views::{PoolInfo, ContractMetadata};
 
+pub mod action;
+pub mod errors;
+pub mod interface;
+
+#[cfg(not(feature = "no-contract"))]
 mod account_deposit;
-mod action;
-mod errors;
+#[cfg(not(feature = "no-contract"))]
+mod account_export;
+#[cfg(not(feature = "no-contract"))]
+mod account_index;
+#[cfg(not(feature = "no-contract"))]
+mod account_merge;
+#[cfg(not(feature = "no-contract"))]
+mod action_limits;
+#[cfg(not(feature = "no-contract"))]
 mod admin_fee;
+#[cfg(not(feature = "no-contract"))]
+mod apr;
+#[cfg(not(feature = "no-contract"))]
+mod banned_tokens;
+#[cfg(not(feature = "no-contract"))]
+mod competition;
+#[cfg(not(feature = "no-contract"))]
+mod decimals_check;
+#[cfg(not(feature = "no-contract"))]
+mod deposit_receipts;
+#[cfg(not(feature = "no-contract"))]
+mod dust_policy;
+#[cfg(not(feature = "no-contract"))]
+mod events;
+#[cfg(not(feature = "no-contract"))]
+mod extraction_limit;
+#[cfg(not(feature = "no-contract"))]
+mod failed_withdrawals;
+#[cfg(not(feature = "no-contract"))]
+mod fee_side;
+#[cfg(not(feature = "no-contract"))]
+mod fee_skim;
+#[cfg(not(feature = "no-contract"))]
+mod fee_split;
+#[cfg(not(feature = "no-contract"))]
+mod fee_tiers;
+#[cfg(not(feature = "no-contract"))]
+mod flash_loans;
+#[cfg(not(feature = "no-contract"))]
+mod governance;
+#[cfg(not(feature = "no-contract"))]
+mod guardian_limits;
+#[cfg(not(feature = "no-contract"))]
+mod insurance;
+#[cfg(not(feature = "no-contract"))]
 mod legacy;
+#[cfg(not(feature = "no-contract"))]
+mod liquidity_events;
+#[cfg(not(feature = "no-contract"))]
+mod lostfound;
+#[cfg(not(feature = "no-contract"))]
+mod min_withdrawals;
+#[cfg(not(feature = "no-contract"))]
 mod multi_fungible_token;
+#[cfg(not(feature = "no-contract"))]
 mod owner;
+#[cfg(not(feature = "no-contract"))]
+mod pagination;
+#[cfg(not(feature = "no-contract"))]
+mod peg_monitor;
+#[cfg(not(feature = "no-contract"))]
+mod permit;
+#[cfg(not(feature = "no-contract"))]
 mod pool;
+#[cfg(not(feature = "no-contract"))]
+mod pool_admin_fees;
+#[cfg(not(feature = "no-contract"))]
+mod pool_bounds;
+#[cfg(not(feature = "no-contract"))]
+mod pool_dedup;
+#[cfg(not(feature = "no-contract"))]
+mod pool_index;
+#[cfg(not(feature = "no-contract"))]
+mod pool_launch;
+#[cfg(not(feature = "no-contract"))]
+mod pool_metadata;
+#[cfg(not(feature = "no-contract"))]
+mod pool_pause;
+#[cfg(not(feature = "no-contract"))]
+mod pool_stats;
+#[cfg(not(feature = "no-contract"))]
+mod pool_validation;
+#[cfg(not(feature = "no-contract"))]
+mod price_hints;
+#[cfg(not(feature = "no-contract"))]
+mod quarantine;
+#[cfg(not(feature = "no-contract"))]
+mod quote;
+#[cfg(not(feature = "no-contract"))]
+mod referral;
+#[cfg(not(feature = "no-contract"))]
+mod referral_budget;
+#[cfg(not(feature = "no-contract"))]
+mod roles;
+#[cfg(all(test, not(feature = "no-contract")))]
+mod rounding_tests;
+#[cfg(not(feature = "no-contract"))]
+mod routing;
+#[cfg(not(feature = "no-contract"))]
+mod share_loans;
+#[cfg(not(feature = "no-contract"))]
+mod share_locks;
+#[cfg(not(feature = "no-contract"))]
 mod simple_pool;
+#[cfg(not(feature = "no-contract"))]
 mod stable_swap;
+#[cfg(not(feature = "no-contract"))]
 mod storage_impl;
+#[cfg(not(feature = "no-contract"))]
+mod token_holdings;
+#[cfg(not(feature = "no-contract"))]
+mod token_metadata;
+#[cfg(not(feature = "no-contract"))]
 mod token_receiver;
+#[cfg(not(feature = "no-contract"))]
+mod token_shares;
+#[cfg(not(feature = "no-contract"))]
+mod twap;
+#[cfg(not(feature = "no-contract"))]
 mod utils;
+#[cfg(not(feature = "no-contract"))]
 mod views;
+#[cfg(not(feature = "no-contract"))]
+mod weighted_pool;
+#[cfg(not(feature = "no-contract"))]
+mod withdraw_delay;
+#[cfg(not(feature = "no-contract"))]
+mod wnear;
 
+#[cfg(not(feature = "no-contract"))]
 near_sdk::setup_alloc!();
 
+#[cfg(not(feature = "no-contract"))]
 #[derive(BorshStorageKey, BorshSerialize)]
 pub(crate) enum StorageKey {
     Pools,
     Accounts,
     Shares { pool_id: u32 },
     Whitelist,
+    /// Prefix of the guardians set replaced by roles, kept so later keys don't shift.
+    #[allow(dead_code)]
     Guardian,
     AccountTokens {account_id: AccountId},
+    PermitKeys,
+    PoolFeeSkims,
+    TokenShares,
+    PoolNonces,
+    RouteIntermediaries,
+    FeeRecipientReceived,
+    InsuranceTokens,
+    InsuranceDisbursements,
+    ReferralFeeTiers,
+    PoolDecimalsStatus,
+    BlockExtractions,
+    Competitions,
+    CompetitionVolumes,
+    TokenWithdrawFailures,
+    UnresponsiveTokens,
+    PoolCheckpoints,
+    ImportedAccounts,
+    WithdrawDelays,
+    PendingWithdrawals,
+    PegAlerts,
+    DepositReceipts,
+    PoolFeeSides,
+    AccountIndex,
+    ReferralBudgets,
+    PoolSwapsOpenAt,
+    ReferencePools,
+    GuardianLimits,
+    ShareLoanConfigs,
+    ShareLoans,
+    LendingReserves,
+    AccountMerges,
+    PriceOracles,
+    PriceHintMaxAges,
+    PriceHints,
+    PoolCreators,
+    PoolMetadata,
+    MinWithdrawals,
+    FailedWithdrawals,
+    AccountActivity,
+    DustFlags,
+    PoolTwaps,
+    PausedPools,
+    ExchangeFeeRemovedShares,
+    Referrers,
+    FlashLoans,
+    FlashLoanPools,
+    TokenPools,
+    PoolAdminFees,
+    Roles,
+    PendingActions,
+    TokenHoldings,
+    PoolStats,
+    ShareLocks,
+    TokenMetadata,
+    BannedTokens,
+    QuarantinedAmounts,
+    LostTokens,
 }
 
+#[cfg(not(feature = "no-contract"))]
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Eq, PartialEq, Clone)]
 #[serde(crate = "near_sdk::serde")]
 #[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
@@ -58,6 +328,7 @@ pub enum RunningState {
     Running, Paused
 }
 
+#[cfg(not(feature = "no-contract"))]
 impl fmt::Display for RunningState {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -67,6 +338,7 @@ impl fmt::Display for RunningState {
     }
 }
 
+#[cfg(not(feature = "no-contract"))]
 #[near_bindgen]
 #[derive(BorshSerialize, BorshDeserialize, PanicOnDefault)]
 pub struct Contract {
@@ -82,12 +354,177 @@ pub struct Contract {
     accounts: LookupMap<AccountId, VAccount>,
     /// Set of whitelisted tokens by "owner".
     whitelisted_tokens: UnorderedSet<AccountId>,
-    /// Set of guardians.
-    guardians: UnorderedSet<AccountId>,
+    /// Permission flags of accounts with roles, with the guardian flag of guardians.
+    roles: UnorderedMap<AccountId, u32>,
     /// Running state
     state: RunningState,
+    /// Keys accounts registered to sign off-chain permits.
+    permit_keys: LookupMap<AccountId, PermitKey>,
+    /// Exchange fee skimming settings of pools, pools not listed are compounding.
+    pool_fee_skims: LookupMap<u64, FeeSkim>,
+    /// Tokens in shares mode, with tracked holdings of the exchange.
+    token_shares: UnorderedMap<AccountId, TokenShares>,
+    /// Number of mutations of each pool, used to pin quotes.
+    pool_nonces: LookupMap<u64, u64>,
+    /// Set of tokens route search is allowed to go through, managed by "owner".
+    route_intermediaries: UnorderedSet<AccountId>,
+    /// Recipients of the exchange fee with their shares, empty means all to owner.
+    fee_split: Vec<FeeRecipient>,
+    /// Total exchange fee tokens received by each fee recipient.
+    fee_recipient_received: LookupMap<AccountId, HashMap<AccountId, Balance>>,
+    /// Part of exchange fee going to the insurance fund and its disbursement cap.
+    insurance_config: InsuranceConfig,
+    /// Insurance fund accounting per token.
+    insurance_tokens: UnorderedMap<AccountId, InsuranceToken>,
+    /// All proposed disbursements from the insurance fund.
+    insurance_disbursements: Vector<Disbursement>,
+    /// Referral fee tiers by trade size, per input token.
+    referral_fee_tiers: UnorderedMap<AccountId, Vec<ReferralFeeTier>>,
+    /// Result of checking stable pool decimals against tokens' metadata.
+    pool_decimals_status: LookupMap<u64, DecimalsStatus>,
+    /// Max value an account can extract from pools per block, set by guardians.
+    extraction_limit: Option<ExtractionLimit>,
+    /// Value extracted by accounts in their last block of swaps.
+    block_extractions: LookupMap<AccountId, BlockExtraction>,
+    /// Storage deposit settings, managed by "owner".
+    storage_config: StorageConfig,
+    /// All trading competitions.
+    competitions: Vector<Competition>,
+    /// Ids of competitions that haven't ended yet.
+    open_competitions: Vec<u64>,
+    /// Volume of accounts in competitions.
+    competition_volumes: LookupMap<(u64, AccountId), Balance>,
+    /// Number of failed withdraws in a row per token.
+    token_withdraw_failures: LookupMap<AccountId, u32>,
+    /// Tokens quarantined by guardians, swaps through them are paused.
+    unresponsive_tokens: UnorderedSet<AccountId>,
+    /// DAO allowed to execute owner level governance actions.
+    governance_dao: Option<AccountId>,
+    /// Recent checkpoints of pools value per share, to compute realized APR.
+    pool_checkpoints: LookupMap<u64, Vec<PoolCheckpoint>>,
+    /// Exchange accounts are imported from on migration, set by "owner".
+    migration_peer: Option<AccountId>,
+    /// Accounts already imported from the migration peer.
+    imported_accounts: LookupSet<AccountId>,
+    /// Withdraw delays accounts opted into.
+    withdraw_delays: LookupMap<AccountId, WithdrawDelay>,
+    /// Pending withdrawals of accounts with withdraw delay.
+    pending_withdrawals: LookupMap<AccountId, Vec<PendingWithdrawal>>,
+    /// Peg alerts settings of stable pools, set by "owner".
+    peg_monitor_config: Option<PegMonitorConfig>,
+    /// Time of the last peg alert per pool.
+    peg_alerts: LookupMap<u64, Timestamp>,
+    /// Bounds of pool parameters, set by "owner".
+    pool_bounds: PoolBounds,
+    /// Recent deposits of accounts that enabled receipts.
+    deposit_receipts: LookupMap<AccountId, Vec<DepositReceipt>>,
+    /// Simple pools taking fee on output, set by "owner".
+    pool_fee_sides: LookupMap<u64, FeeSide>,
+    /// Ids of registered accounts, accounts registered before the index are added by "owner".
+    account_index: UnorderedSet<AccountId>,
+    /// Buffer over runtime storage byte cost in bps, set by "owner".
+    storage_price_buffer: u32,
+    /// Per epoch caps on referral fees by input token, set by "owner".
+    referral_budgets: LookupMap<AccountId, ReferralBudgetRecord>,
+    /// Blocks swaps are blocked for after first liquidity of a pool, set by "owner".
+    pool_launch_delay: BlockHeight,
+    /// Block swaps open at, for pools seeded within the launch delay.
+    pool_swaps_open_at: LookupMap<u64, BlockHeight>,
+    /// Token liquidity events are valued in, set by "owner".
+    reference_token: Option<AccountId>,
+    /// Simple pools pairing tokens with the reference token, set by "owner".
+    reference_pools: LookupMap<AccountId, u64>,
+    /// Caps of tokens guardians can move per period, by guardian and token, set by "owner".
+    guardian_limits: LookupMap<(AccountId, AccountId), GuardianLimitRecord>,
+    /// Stable pools whose shares can be loan collateral, set by "owner".
+    share_loan_configs: LookupMap<u64, ShareLoanConfig>,
+    /// Loans against shares, by borrower and pool.
+    share_loans: LookupMap<(AccountId, u64), ShareLoan>,
+    /// Tokens available to borrow against shares, funded by "owner".
+    lending_reserves: LookupMap<AccountId, Balance>,
+    /// Accounts requested to merge into another one, by source account.
+    account_merges: LookupMap<AccountId, AccountId>,
+    /// Accounts allowed to push price hints, set by "owner".
+    price_oracles: UnorderedSet<AccountId>,
+    /// Tokens accepting price hints with max age of their hints, set by "owner".
+    price_hint_max_ages: UnorderedMap<AccountId, Duration>,
+    /// Latest price hints in the reference token.
+    price_hints: LookupMap<AccountId, PriceHintRecord>,
+    /// Accounts that created pools, pools created before are absent.
+    pool_creators: LookupMap<u64, AccountId>,
+    /// Display metadata of pools, set by their creators.
+    pool_metadata: LookupMap<u64, PoolMetadata>,
+    /// Minimum withdrawal amounts of tokens, set by "owner".
+    min_withdrawals: UnorderedMap<AccountId, Balance>,
+    /// Claims of failed withdrawals by account and token.
+    failed_withdrawals: LookupMap<AccountId, HashMap<AccountId, Balance>>,
+    /// Sweeping of dust in inactive accounts, set by "owner".
+    dust_policy: Option<DustPolicy>,
+    /// When the dust policy was enabled, counts as activity of accounts without a record.
+    dust_policy_enabled_at: Timestamp,
+    /// Time of the last change of accounts.
+    account_activity: LookupMap<AccountId, Timestamp>,
+    /// Accounts flagged to have their dust swept, with time of flagging.
+    dust_flags: UnorderedMap<AccountId, Timestamp>,
+    /// Limits of actions per call and tokens per pool, set by "owner".
+    action_limits: ActionLimits,
+    /// Cumulative prices of pools, to compute time weighted average prices.
+    pool_twaps: LookupMap<u64, PoolTwap>,
+    /// Pools paused by guardians.
+    paused_pools: UnorderedSet<u64>,
+    /// Receiver of exchange fee withdrawn by "owner", owner itself if None.
+    treasury_id: Option<AccountId>,
+    /// Exchange fee shares removed from pools so far.
+    exchange_fee_removed_shares: LookupMap<u64, Balance>,
+    /// Registered referrers with their tiers.
+    referrers: LookupMap<AccountId, u32>,
+    /// Referral fees of referrer tiers, set by "owner".
+    referral_tier_fees: Vec<u32>,
+    /// wNEAR contract used for swaps of native NEAR, set by "owner".
+    wnear_account_id: Option<AccountId>,
+    /// Fee of flash loans, None if they are disabled, set by "owner".
+    flash_loan_fee: Option<u32>,
+    /// Unpaid flash loans by borrower.
+    flash_loans: LookupMap<AccountId, FlashLoan>,
+    /// Pools with flash loans in flight.
+    flash_loan_pools: LookupSet<u64>,
+    /// Sorted ids of pools of each token.
+    token_pools: LookupMap<AccountId, Vec<u64>>,
+    /// Number of first pools added to `token_pools`.
+    indexed_pools: u64,
+    /// Admin fees of pools not using the global ones, set by "owner".
+    pool_admin_fees: LookupMap<u64, PoolAdminFees>,
+    /// Owner proposed by "owner" until it accepts.
+    pending_owner_id: Option<AccountId>,
+    /// Delay of timelocked actions, zero if they can be called directly, set by "owner".
+    timelock_delay: Timestamp,
+    /// Timelocked actions scheduled by "owner", by id.
+    pending_actions: UnorderedMap<u64, PendingAction>,
+    /// Id of the next scheduled action.
+    next_pending_action_id: u64,
+    /// Tracked holdings of tokens not in shares mode, to skim tokens not owed to anyone.
+    token_holdings: LookupMap<AccountId, TokenHoldings>,
+    /// Whether holdings of all tokens are tracked, false for tokens held before migration.
+    track_all_holdings: bool,
+    /// Lifetime swap statistics of pools.
+    pool_stats: LookupMap<u64, PoolStats>,
+    /// Time locks of LP shares, by account and pool.
+    share_locks: LookupMap<(AccountId, u64), Vec<ShareLock>>,
+    /// Cached symbols and icons of tokens, for metadata of LP tokens.
+    token_metadata: LookupMap<AccountId, CachedTokenMetadata>,
+    /// Fees allowed for new simple and weighted pools, any fee within bounds if empty.
+    pool_fee_tiers: Vec<u32>,
+    /// Whether creation of duplicate simple pools of a token pair is rejected.
+    pool_dedup: bool,
+    /// Tokens banned by owner, they can't be deposited, registered or pooled.
+    banned_tokens: UnorderedSet<AccountId>,
+    /// Amounts of unresponsive tokens of removed liquidity, by account and token.
+    quarantined_amounts: LookupMap<AccountId, HashMap<AccountId, Balance>>,
+    /// Failed withdrawals that couldn't be returned to their receivers, by receiver and token.
+    lost_tokens: UnorderedMap<AccountId, HashMap<AccountId, Balance>>,
 }
 
+#[cfg(not(feature = "no-contract"))]
 #[near_bindgen]
 impl Contract {
     #[init]
@@ -99,24 +536,108 @@ impl Contract {
             pools: Vector::new(StorageKey::Pools),
             accounts: LookupMap::new(StorageKey::Accounts),
             whitelisted_tokens: UnorderedSet::new(StorageKey::Whitelist),
-            guardians: UnorderedSet::new(StorageKey::Guardian),
+            roles: UnorderedMap::new(StorageKey::Roles),
             state: RunningState::Running,
+            permit_keys: LookupMap::new(StorageKey::PermitKeys),
+            pool_fee_skims: LookupMap::new(StorageKey::PoolFeeSkims),
+            token_shares: UnorderedMap::new(StorageKey::TokenShares),
+            pool_nonces: LookupMap::new(StorageKey::PoolNonces),
+            route_intermediaries: UnorderedSet::new(StorageKey::RouteIntermediaries),
+            fee_split: vec![],
+            fee_recipient_received: LookupMap::new(StorageKey::FeeRecipientReceived),
+            insurance_config: InsuranceConfig::default(),
+            insurance_tokens: UnorderedMap::new(StorageKey::InsuranceTokens),
+            insurance_disbursements: Vector::new(StorageKey::InsuranceDisbursements),
+            referral_fee_tiers: UnorderedMap::new(StorageKey::ReferralFeeTiers),
+            pool_decimals_status: LookupMap::new(StorageKey::PoolDecimalsStatus),
+            extraction_limit: None,
+            block_extractions: LookupMap::new(StorageKey::BlockExtractions),
+            storage_config: StorageConfig::default(),
+            competitions: Vector::new(StorageKey::Competitions),
+            open_competitions: vec![],
+            competition_volumes: LookupMap::new(StorageKey::CompetitionVolumes),
+            token_withdraw_failures: LookupMap::new(StorageKey::TokenWithdrawFailures),
+            unresponsive_tokens: UnorderedSet::new(StorageKey::UnresponsiveTokens),
+            governance_dao: None,
+            pool_checkpoints: LookupMap::new(StorageKey::PoolCheckpoints),
+            migration_peer: None,
+            imported_accounts: LookupSet::new(StorageKey::ImportedAccounts),
+            withdraw_delays: LookupMap::new(StorageKey::WithdrawDelays),
+            pending_withdrawals: LookupMap::new(StorageKey::PendingWithdrawals),
+            peg_monitor_config: None,
+            peg_alerts: LookupMap::new(StorageKey::PegAlerts),
+            pool_bounds: PoolBounds::default(),
+            deposit_receipts: LookupMap::new(StorageKey::DepositReceipts),
+            pool_fee_sides: LookupMap::new(StorageKey::PoolFeeSides),
+            account_index: UnorderedSet::new(StorageKey::AccountIndex),
+            storage_price_buffer: 0,
+            referral_budgets: LookupMap::new(StorageKey::ReferralBudgets),
+            pool_launch_delay: 0,
+            pool_swaps_open_at: LookupMap::new(StorageKey::PoolSwapsOpenAt),
+            reference_token: None,
+            reference_pools: LookupMap::new(StorageKey::ReferencePools),
+            guardian_limits: LookupMap::new(StorageKey::GuardianLimits),
+            share_loan_configs: LookupMap::new(StorageKey::ShareLoanConfigs),
+            share_loans: LookupMap::new(StorageKey::ShareLoans),
+            lending_reserves: LookupMap::new(StorageKey::LendingReserves),
+            account_merges: LookupMap::new(StorageKey::AccountMerges),
+            price_oracles: UnorderedSet::new(StorageKey::PriceOracles),
+            price_hint_max_ages: UnorderedMap::new(StorageKey::PriceHintMaxAges),
+            price_hints: LookupMap::new(StorageKey::PriceHints),
+            pool_creators: LookupMap::new(StorageKey::PoolCreators),
+            pool_metadata: LookupMap::new(StorageKey::PoolMetadata),
+            min_withdrawals: UnorderedMap::new(StorageKey::MinWithdrawals),
+            failed_withdrawals: LookupMap::new(StorageKey::FailedWithdrawals),
+            dust_policy: None,
+            dust_policy_enabled_at: 0,
+            account_activity: LookupMap::new(StorageKey::AccountActivity),
+            dust_flags: UnorderedMap::new(StorageKey::DustFlags),
+            action_limits: ActionLimits::default(),
+            pool_twaps: LookupMap::new(StorageKey::PoolTwaps),
+            paused_pools: UnorderedSet::new(StorageKey::PausedPools),
+            treasury_id: None,
+            exchange_fee_removed_shares: LookupMap::new(StorageKey::ExchangeFeeRemovedShares),
+            referrers: LookupMap::new(StorageKey::Referrers),
+            referral_tier_fees: vec![],
+            wnear_account_id: None,
+            flash_loan_fee: None,
+            flash_loans: LookupMap::new(StorageKey::FlashLoans),
+            flash_loan_pools: LookupSet::new(StorageKey::FlashLoanPools),
+            token_pools: LookupMap::new(StorageKey::TokenPools),
+            indexed_pools: 0,
+            pool_admin_fees: LookupMap::new(StorageKey::PoolAdminFees),
+            pending_owner_id: None,
+            timelock_delay: 0,
+            pending_actions: UnorderedMap::new(StorageKey::PendingActions),
+            next_pending_action_id: 0,
+            token_holdings: LookupMap::new(StorageKey::TokenHoldings),
+            track_all_holdings: true,
+            pool_stats: LookupMap::new(StorageKey::PoolStats),
+            share_locks: LookupMap::new(StorageKey::ShareLocks),
+            token_metadata: LookupMap::new(StorageKey::TokenMetadata),
+            pool_fee_tiers: vec![],
+            pool_dedup: false,
+            banned_tokens: UnorderedSet::new(StorageKey::BannedTokens),
+            quarantined_amounts: LookupMap::new(StorageKey::QuarantinedAmounts),
+            lost_tokens: UnorderedMap::new(StorageKey::LostTokens),
         }
     }
 
     /// Adds new "Simple Pool" with given tokens and given fee.
+    /// Pool has 2 up to 8 tokens and swaps between any two of them.
     /// Attached NEAR should be enough to cover the added storage.
+    /// Fee is limited by pool bounds and must be one of fee tiers if owner set them.
+    /// With deduplication enabled, pool of two tokens must not duplicate their canonical pool.
     #[payable]
     pub fn add_simple_pool(&mut self, tokens: Vec<ValidAccountId>, fee: u32) -> u64 {
         self.assert_contract_running();
         check_token_duplicates(&tokens);
-        self.internal_add_pool(Pool::SimplePool(SimplePool::new(
-            self.pools.len() as u32,
-            tokens,
-            fee,
-            0,
-            0,
-        )))
+        self.action_limits.assert_pool_tokens(tokens.len());
+        self.assert_not_duplicate_pool(&tokens, fee);
+        let pool = SimplePool::new(self.pools.len() as u32, tokens, fee, 0, 0);
+        self.pool_bounds.assert_fee(fee);
+        self.assert_fee_tier(fee);
+        self.internal_add_pool(Pool::SimplePool(pool))
     }
 
     /// Adds new "Stable Pool" with given tokens, decimals, fee and amp.
@@ -125,6 +646,7 @@ impl Contract {
     /// decimals: each pool tokens decimal, needed to make them comparable.
     /// fee: total fee of the pool, admin fee is inclusive.
     /// amp_factor: algorithm parameter, decide how stable the pool will be.
+    /// Fee and amp_factor are limited by pool bounds.
     #[payable]
     pub fn add_stable_swap_pool(
         &mut self,
@@ -133,15 +655,43 @@ impl Contract {
         fee: u32,
         amp_factor: u64,
     ) -> u64 {
-        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        assert!(
+            self.is_owner_or_permitted(Permission::CreateStablePool),
+            "{}",
+            ERR100_NOT_ALLOWED
+        );
         check_token_duplicates(&tokens);
-        self.internal_add_pool(Pool::StableSwapPool(StableSwapPool::new(
+        self.action_limits.assert_pool_tokens(tokens.len());
+        let pool = StableSwapPool::new(
             self.pools.len() as u32,
             tokens,
             decimals,
             amp_factor as u128,
             fee,
-        )))
+        );
+        self.pool_bounds.assert_fee(fee);
+        self.pool_bounds.assert_amp(amp_factor);
+        self.internal_add_pool(Pool::StableSwapPool(pool))
+    }
+
+    /// Adds new "Weighted Pool" with given tokens, their weights and given fee.
+    /// weights: parts of 10000 each token takes of the pool value, e.g. [8000, 2000].
+    /// Attached NEAR should be enough to cover the added storage.
+    /// Fee is limited by pool bounds and must be one of fee tiers if owner set them.
+    #[payable]
+    pub fn add_weighted_pool(
+        &mut self,
+        tokens: Vec<ValidAccountId>,
+        weights: Vec<u32>,
+        fee: u32,
+    ) -> u64 {
+        self.assert_contract_running();
+        check_token_duplicates(&tokens);
+        self.action_limits.assert_pool_tokens(tokens.len());
+        let pool = WeightedPool::new(self.pools.len() as u32, tokens, weights, fee);
+        self.pool_bounds.assert_fee(fee);
+        self.assert_fee_tier(fee);
+        self.internal_add_pool(Pool::WeightedPool(pool))
     }
 
     /// [AUDIT_03_reject(NOPE action is allowed by design)]
@@ -149,13 +699,19 @@ impl Contract {
     /// Executes generic set of actions.
     /// If referrer provided, pays referral_fee to it.
     /// If no attached deposit, outgoing tokens used in swaps must be whitelisted.
+    /// If `auto_withdraw` is true, output of the last action is withdrawn to the sender's wallet,
+    /// it requires 1 yoctoNEAR attached like `withdraw`.
     #[payable]
     pub fn execute_actions(
         &mut self,
         actions: Vec<Action>,
         referral_id: Option<ValidAccountId>,
+        auto_withdraw: Option<bool>,
     ) -> ActionResult {
         self.assert_contract_running();
+        if auto_withdraw == Some(true) {
+            assert_one_yocto();
+        }
         let sender_id = env::predecessor_account_id();
         let mut account = self.internal_unwrap_account(&sender_id);
         // Validate that all tokens are whitelisted if no deposit (e.g. trade with access key).
@@ -173,9 +729,25 @@ impl Contract {
             }
         }
         let referral_id = referral_id.map(|r| r.into());
-        let result =
-            self.internal_execute_actions(&mut account, &referral_id, &actions, ActionResult::None);
-        self.internal_save_account(&sender_id, account);
+        let result = self.internal_execute_actions(
+            &sender_id,
+            &mut account,
+            &referral_id,
+            &actions,
+            ActionResult::None,
+        );
+        let withdraw_token = actions
+            .last()
+            .and_then(|action| action.output_token())
+            .filter(|_| auto_withdraw == Some(true));
+        if let (Some(token_id), ActionResult::Amount(amount)) = (withdraw_token, &result) {
+            self.assert_min_withdrawal(&token_id, amount.0);
+            account.withdraw(&token_id, amount.0);
+            self.internal_save_account(&sender_id, account);
+            self.internal_withdraw_tokens(&sender_id, &token_id, amount.0);
+        } else {
+            self.internal_save_account(&sender_id, account);
+        }
         result
     }
 
@@ -193,20 +765,24 @@ impl Contract {
                     .map(|swap_action| Action::Swap(swap_action))
                     .collect(),
                 referral_id,
+                None,
             )
             .to_amount(),
         )
     }
 
     /// Add liquidity from already deposited amounts to given pool.
+    /// Fails if executed after optional `deadline` block timestamp.
     #[payable]
     pub fn add_liquidity(
         &mut self,
         pool_id: u64,
         amounts: Vec<U128>,
         min_amounts: Option<Vec<U128>>,
+        deadline: Option<WrappedTimestamp>,
     ) {
         self.assert_contract_running();
+        assert_deadline(deadline);
         assert!(
             env::attached_deposit() > 0,
             "Requires attached deposit of at least 1 yoctoNEAR"
@@ -215,8 +791,12 @@ impl Contract {
         let sender_id = env::predecessor_account_id();
         let mut amounts: Vec<u128> = amounts.into_iter().map(|amount| amount.into()).collect();
         let mut pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+        self.assert_pool_responsive(&pool);
+        self.assert_pool_not_paused(pool_id);
+        self.assert_pool_not_flash_loaned(pool_id);
+        let first_liquidity = pool.share_total_balance() == 0;
         // Add amounts given to liquidity first. It will return the balanced amounts.
-        pool.add_liquidity(
+        let mint_shares = pool.add_liquidity(
             &sender_id,
             &mut amounts,
         );
@@ -233,8 +813,20 @@ impl Contract {
             deposits.withdraw(&tokens[i], amounts[i]);
         }
         self.internal_save_account(&sender_id, deposits);
-        self.pools.replace(pool_id, &pool);
         self.internal_check_storage(prev_storage);
+        // Pool's nonce and launch records may be created here, they are covered by the exchange.
+        self.internal_save_pool(pool_id, &pool);
+        if first_liquidity {
+            self.internal_launch_pool(pool_id);
+        }
+        self.internal_log_liquidity_event(
+            LiquidityChange::Add,
+            &sender_id,
+            pool_id,
+            &pool,
+            &amounts,
+            mint_shares,
+        );
     }
 
     /// For stable swap pool, user can add liquidity with token's combination as his will.
@@ -257,13 +849,18 @@ impl Contract {
         let prev_storage = env::storage_usage();
         let sender_id = env::predecessor_account_id();
         let amounts: Vec<u128> = amounts.into_iter().map(|amount| amount.into()).collect();
+        self.assert_pool_decimals_resolved(pool_id);
         let mut pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+        self.assert_pool_responsive(&pool);
+        self.assert_pool_not_paused(pool_id);
+        self.assert_pool_not_flash_loaned(pool_id);
+        let first_liquidity = pool.share_total_balance() == 0;
         // Add amounts given to liquidity first. It will return the balanced amounts.
         let mint_shares = pool.add_stable_liquidity(
             &sender_id,
             &amounts,
             min_shares.into(),
-            AdminFees::new(self.exchange_fee),
+            AdminFees::new(self.internal_get_pool_admin_fees(pool_id).exchange_fee),
         );
         let mut deposits = self.internal_unwrap_or_default_account(&sender_id);
         let tokens = pool.tokens();
@@ -272,17 +869,148 @@ impl Contract {
             deposits.withdraw(&tokens[i], amounts[i]);
         }
         self.internal_save_account(&sender_id, deposits);
-        self.pools.replace(pool_id, &pool);
         self.internal_check_storage(prev_storage);
+        // Pool's nonce and launch records may be created here, they are covered by the exchange.
+        self.internal_save_pool(pool_id, &pool);
+        if first_liquidity {
+            self.internal_launch_pool(pool_id);
+        }
+        self.internal_log_liquidity_event(
+            LiquidityChange::Add,
+            &sender_id,
+            pool_id,
+            &pool,
+            &amounts,
+            mint_shares,
+        );
+
+        mint_shares.into()
+    }
+
+    /// Adds liquidity to a simple pool of two tokens from `amount` of one of its tokens.
+    /// Half of the amount is swapped through the pool into the other token first, then balanced
+    /// liquidity is added from both halves. Leftovers of the tokens stay in the deposits.
+    /// min_shares: Slippage, if shares minted are less than it, panic with ERR68_SLIPPAGE.
+    #[payable]
+    pub fn add_liquidity_single(
+        &mut self,
+        pool_id: u64,
+        token_id: ValidAccountId,
+        amount: U128,
+        min_shares: U128,
+    ) -> U128 {
+        self.assert_contract_running();
+        assert!(
+            env::attached_deposit() > 0,
+            "Requires attached deposit of at least 1 yoctoNEAR"
+        );
+        let prev_storage = env::storage_usage();
+        let sender_id = env::predecessor_account_id();
+        let token_in: AccountId = token_id.into();
+        let token_out = match self.pools.get(pool_id).expect("ERR_NO_POOL") {
+            Pool::SimplePool(pool) if pool.token_account_ids.len() == 2 => {
+                match pool.token_account_ids.iter().position(|id| id == &token_in) {
+                    Some(index) => pool.token_account_ids[1 - index].clone(),
+                    None => env::panic(b"ERR_MISSING_TOKEN"),
+                }
+            }
+            _ => env::panic(ERR397_SINGLE_LIQUIDITY_NOT_SUPPORTED.as_bytes()),
+        };
+        let swap_amount = amount.0 / 2;
+        assert!(swap_amount > 0, "{}", ERR29_ILLEGAL_WITHDRAW_AMOUNT);
+        let mut deposits = self.internal_unwrap_account(&sender_id);
+        let amount_out = self.internal_execute_swap(
+            &sender_id,
+            &mut deposits,
+            &None,
+            pool_id,
+            &token_in,
+            swap_amount,
+            &token_out,
+            0,
+        );
+        let mut pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+        self.assert_pool_responsive(&pool);
+        let mut amounts = if pool.tokens()[0] == token_in {
+            vec![amount.0 - swap_amount, amount_out]
+        } else {
+            vec![amount_out, amount.0 - swap_amount]
+        };
+        let mint_shares = pool.add_liquidity(&sender_id, &mut amounts);
+        assert!(mint_shares >= min_shares.0, "{}", ERR68_SLIPPAGE);
+        let tokens = pool.tokens();
+        // Subtract balanced amounts from deposits, the other half of the amount is checked here.
+        for i in 0..tokens.len() {
+            deposits.withdraw(&tokens[i], amounts[i]);
+        }
+        self.internal_save_account(&sender_id, deposits);
+        self.internal_check_storage(prev_storage);
+        self.internal_save_pool(pool_id, &pool);
+        self.internal_log_liquidity_event(
+            LiquidityChange::Add,
+            &sender_id,
+            pool_id,
+            &pool,
+            &amounts,
+            mint_shares,
+        );
 
         mint_shares.into()
     }
 
+    /// Moves `amount` of the token from the caller's deposit into the pool reserves without
+    /// minting shares, e.g. to seed incentives or compensate LPs. The pool must have liquidity.
+    #[payable]
+    pub fn donate_to_pool(&mut self, pool_id: u64, token_id: ValidAccountId, amount: U128) {
+        assert_one_yocto();
+        self.assert_contract_running();
+        assert!(amount.0 > 0, "{}", ERR31_ZERO_AMOUNT);
+        self.assert_pool_not_paused(pool_id);
+        self.assert_pool_not_flash_loaned(pool_id);
+        let sender_id = env::predecessor_account_id();
+        let token_id: AccountId = token_id.into();
+        let mut pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+        self.internal_accumulate_prices(pool_id, &pool);
+        pool.donate(&token_id, amount.0);
+        let mut deposits = self.internal_unwrap_account(&sender_id);
+        deposits.withdraw(&token_id, amount.0);
+        self.internal_save_account(&sender_id, deposits);
+        self.internal_save_pool(pool_id, &pool);
+        self.internal_checkpoint_pool(pool_id, &pool);
+        log!(
+            "{} donated {} {} to pool {}",
+            sender_id,
+            amount.0,
+            token_id,
+            pool_id
+        );
+        Event::Donate(vec![DonateEvent {
+            account_id: sender_id,
+            pool_id,
+            token_id,
+            amount,
+        }])
+        .emit();
+    }
+
     /// Remove liquidity from the pool into general pool of liquidity.
+    /// If `withdraw` is true, tokens are transferred to the sender's wallet instead,
+    /// failed transfers are returned to the inner account.
+    /// Part of unresponsive tokens goes to lostfound, so LPs can exit quarantined pools.
+    /// Fails if executed after optional `deadline` block timestamp.
     #[payable]
-    pub fn remove_liquidity(&mut self, pool_id: u64, shares: U128, min_amounts: Vec<U128>) {
+    pub fn remove_liquidity(
+        &mut self,
+        pool_id: u64,
+        shares: U128,
+        min_amounts: Vec<U128>,
+        withdraw: Option<bool>,
+        deadline: Option<WrappedTimestamp>,
+    ) {
         assert_one_yocto();
         self.assert_contract_running();
+        assert_deadline(deadline);
+        self.assert_pool_not_flash_loaned(pool_id);
         let prev_storage = env::storage_usage();
         let sender_id = env::predecessor_account_id();
         let mut pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
@@ -294,29 +1022,57 @@ impl Contract {
                 .map(|amount| amount.into())
                 .collect(),
         );
-        self.pools.replace(pool_id, &pool);
+        self.assert_loan_collateral(&sender_id, pool_id, &pool);
+        self.assert_shares_unlocked(&sender_id, pool_id, &pool);
+        self.internal_save_pool(pool_id, &pool);
+        self.internal_log_liquidity_event(
+            LiquidityChange::Remove,
+            &sender_id,
+            pool_id,
+            &pool,
+            &amounts,
+            shares.into(),
+        );
         let tokens = pool.tokens();
+        let amounts = self.internal_quarantine_removed_liquidity(&sender_id, tokens, amounts);
+        let withdraw = withdraw.unwrap_or(false);
         let mut deposits = self.internal_unwrap_or_default_account(&sender_id);
-        for i in 0..tokens.len() {
-            deposits.deposit(&tokens[i], amounts[i]);
+        if !withdraw {
+            for i in 0..tokens.len() {
+                if !self.unresponsive_tokens.contains(&tokens[i]) {
+                    deposits.deposit(&tokens[i], amounts[i]);
+                }
+            }
         }
         // Freed up storage balance from LP tokens will be returned to near_balance.
         if prev_storage > env::storage_usage() {
+            let prev_near_amount = deposits.near_amount;
             deposits.near_amount +=
                 (prev_storage - env::storage_usage()) as Balance * env::storage_byte_cost();
+            log_near_amount_change(
+                &sender_id,
+                "lp_storage_refund",
+                prev_near_amount,
+                deposits.near_amount,
+            );
         }
         self.internal_save_account(&sender_id, deposits);
+        if withdraw {
+            self.internal_send_removed_liquidity(&sender_id, tokens, &amounts);
+        }
     }
 
     /// For stable swap pool, LP can use it to remove liquidity with given token amount and distribution.
     /// pool_id: the stable swap pool id. If simple pool is given, panic with Unimplement.
     /// amounts: Each tokens (in pool tokens sequence) amounts user want get, a 0 means user don't want to get that token back.
     /// max_burn_shares: This is slippage protection, if user request would burn shares more than it, panic with ERR68_SLIPPAGE
+    /// withdraw: if true, tokens are transferred to the sender's wallet instead of inner account.
     #[payable]
     pub fn remove_liquidity_by_tokens(
         &mut self, pool_id: u64, 
         amounts: Vec<U128>, 
-        max_burn_shares: U128
+        max_burn_shares: U128,
+        withdraw: Option<bool>,
     ) -> U128 {
         assert_one_yocto();
         self.assert_contract_running();
@@ -331,27 +1087,70 @@ impl Contract {
                 .map(|amount| amount.into())
                 .collect(),
             max_burn_shares.into(),
-            AdminFees::new(self.exchange_fee),
+            AdminFees::new(self.internal_get_pool_admin_fees(pool_id).exchange_fee),
         );
-        self.pools.replace(pool_id, &pool);
+        self.assert_loan_collateral(&sender_id, pool_id, &pool);
+        self.assert_shares_unlocked(&sender_id, pool_id, &pool);
+        self.internal_save_pool(pool_id, &pool);
         let tokens = pool.tokens();
+        let amounts: Vec<Balance> = amounts.into_iter().map(|amount| amount.into()).collect();
+        self.internal_log_liquidity_event(
+            LiquidityChange::Remove,
+            &sender_id,
+            pool_id,
+            &pool,
+            &amounts,
+            burn_shares,
+        );
+        let amounts = self.internal_quarantine_removed_liquidity(&sender_id, tokens, amounts);
+        let withdraw = withdraw.unwrap_or(false);
         let mut deposits = self.internal_unwrap_or_default_account(&sender_id);
-        for i in 0..tokens.len() {
-            deposits.deposit(&tokens[i], amounts[i].into());
+        if !withdraw {
+            for i in 0..tokens.len() {
+                if !self.unresponsive_tokens.contains(&tokens[i]) {
+                    deposits.deposit(&tokens[i], amounts[i]);
+                }
+            }
         }
         // Freed up storage balance from LP tokens will be returned to near_balance.
         if prev_storage > env::storage_usage() {
+            let prev_near_amount = deposits.near_amount;
             deposits.near_amount +=
                 (prev_storage - env::storage_usage()) as Balance * env::storage_byte_cost();
+            log_near_amount_change(
+                &sender_id,
+                "lp_storage_refund",
+                prev_near_amount,
+                deposits.near_amount,
+            );
         }
         self.internal_save_account(&sender_id, deposits);
+        if withdraw {
+            self.internal_send_removed_liquidity(&sender_id, tokens, &amounts);
+        }
 
         burn_shares.into()
     }
 }
 
+#[cfg(not(feature = "no-contract"))]
 /// Internal methods implementation.
 impl Contract {
+    /// Transfers tokens removed from a pool to the sender's wallet, each with its own callback.
+    /// Transfers are pending for accounts with withdraw delay.
+    fn internal_send_removed_liquidity(
+        &mut self,
+        sender_id: &AccountId,
+        tokens: &[AccountId],
+        amounts: &[Balance],
+    ) {
+        for (token_id, amount) in tokens.iter().zip(amounts.iter()) {
+            if *amount > 0 {
+                self.internal_withdraw_tokens(sender_id, token_id, *amount);
+            }
+        }
+    }
+
 
     fn assert_contract_running(&self) {
         match self.state {
@@ -365,7 +1164,7 @@ impl Contract {
         let storage_cost = env::storage_usage()
             .checked_sub(prev_storage)
             .unwrap_or_default() as Balance
-            * env::storage_byte_cost();
+            * self.internal_storage_byte_price();
 
         let refund = env::attached_deposit()
             .checked_sub(storage_cost)
@@ -384,34 +1183,58 @@ impl Contract {
     /// If there is not enough attached balance to cover storage, fails.
     /// If too much attached - refunds it back.
     fn internal_add_pool(&mut self, mut pool: Pool) -> u64 {
+        for token_id in pool.tokens() {
+            self.assert_token_not_banned(token_id);
+        }
         let prev_storage = env::storage_usage();
         let id = self.pools.len() as u64;
         // exchange share was registered at creation time
         pool.share_register(&env::current_account_id());
         self.pools.push(&pool);
         self.internal_check_storage(prev_storage);
+        // Creator record and token index are covered by the exchange, so pool creation cost
+        // stays the same.
+        self.pool_creators.insert(&id, &env::predecessor_account_id());
+        if self.indexed_pools == id {
+            self.internal_index_pool(id);
+        }
+        Event::PoolCreated(vec![PoolCreatedEvent {
+            account_id: env::predecessor_account_id(),
+            pool_id: id,
+            pool_kind: pool.kind(),
+            tokens: pool.tokens().to_vec(),
+            total_fee: pool.get_fee(),
+        }])
+        .emit();
         id
     }
 
     /// Execute sequence of actions on given account. Modifies passed account.
+    /// Value extracted by `account_id` is checked against the extraction limit.
     /// Returns result of the last action.
     fn internal_execute_actions(
         &mut self,
+        account_id: &AccountId,
         account: &mut Account,
         referral_id: &Option<AccountId>,
         actions: &[Action],
         prev_result: ActionResult,
     ) -> ActionResult {
+        self.action_limits.assert_actions(actions);
+        let prev_reference_balance = self.internal_reference_balance(account);
         let mut result = prev_result;
         for action in actions {
-            result = self.internal_execute_action(account, referral_id, action, result);
+            result = self.internal_execute_action(account_id, account, referral_id, action, result);
         }
+        let reference_balance = self.internal_reference_balance(account);
+        self.internal_record_extraction(account_id, prev_reference_balance, reference_balance);
         result
     }
 
     /// Executes single action on given account. Modifies passed account. Returns a result based on type of action.
     fn internal_execute_action(
         &mut self,
+        account_id: &AccountId,
         account: &mut Account,
         referral_id: &Option<AccountId>,
         action: &Action,
@@ -419,26 +1242,142 @@ impl Contract {
     ) -> ActionResult {
         match action {
             Action::Swap(swap_action) => {
+                assert_deadline(swap_action.deadline);
                 let amount_in = swap_action
                     .amount_in
                     .map(|value| value.0)
                     .unwrap_or_else(|| prev_result.to_amount());
-                account.withdraw(&swap_action.token_in, amount_in);
-                let amount_out = self.internal_pool_swap(
+                if let Some(quote_id) = &swap_action.quote_id {
+                    self.assert_quote(
+                        quote_id,
+                        swap_action.pool_id,
+                        &swap_action.token_in,
+                        amount_in,
+                        &swap_action.token_out,
+                    );
+                }
+                let spot_price_before = swap_action.max_price_impact_bps.map(|_| {
+                    self.internal_spot_price(
+                        swap_action.pool_id,
+                        &swap_action.token_in,
+                        &swap_action.token_out,
+                    )
+                });
+                let amount_out = self.internal_execute_swap(
+                    account_id,
+                    account,
+                    referral_id,
                     swap_action.pool_id,
                     &swap_action.token_in,
                     amount_in,
                     &swap_action.token_out,
                     swap_action.min_amount_out.0,
-                    referral_id,
                 );
-                account.deposit(&swap_action.token_out, amount_out);
+                if let (Some(max_price_impact), Some(spot_price_before)) =
+                    (swap_action.max_price_impact_bps, spot_price_before)
+                {
+                    let spot_price_after = self.internal_spot_price(
+                        swap_action.pool_id,
+                        &swap_action.token_in,
+                        &swap_action.token_out,
+                    );
+                    assert!(
+                        spot_price_after * U256::from(FEE_DIVISOR)
+                            >= spot_price_before
+                                * U256::from(FEE_DIVISOR.saturating_sub(max_price_impact)),
+                        "{}",
+                        ERR404_PRICE_IMPACT_TOO_HIGH
+                    );
+                }
                 // [AUDIT_02]
                 ActionResult::Amount(U128(amount_out))
             }
+            Action::RoutedSwap(routed_swap_action) => {
+                assert_deadline(routed_swap_action.deadline);
+                let pool_ids = &routed_swap_action.pool_ids;
+                let tokens = &routed_swap_action.tokens;
+                assert!(
+                    !pool_ids.is_empty() && tokens.len() == pool_ids.len() + 1,
+                    "{}",
+                    ERR370_INVALID_ROUTE
+                );
+                let mut amount = routed_swap_action
+                    .amount_in
+                    .map(|value| value.0)
+                    .unwrap_or_else(|| prev_result.to_amount());
+                for (hop, pool_id) in pool_ids.iter().enumerate() {
+                    // Only output of the whole route is checked against the minimum.
+                    let min_amount_out = if hop + 1 == pool_ids.len() {
+                        routed_swap_action.min_amount_out.0
+                    } else {
+                        0
+                    };
+                    amount = self.internal_execute_swap(
+                        account_id,
+                        account,
+                        referral_id,
+                        *pool_id,
+                        &tokens[hop],
+                        amount,
+                        &tokens[hop + 1],
+                        min_amount_out,
+                    );
+                }
+                ActionResult::Amount(U128(amount))
+            }
         }
     }
 
+    /// Returns spot price of `token_in` in `token_out` in the pool, 0 if the pool is empty.
+    fn internal_spot_price(&self, pool_id: u64, token_in: &AccountId, token_out: &AccountId) -> U256 {
+        self.pools
+            .get(pool_id)
+            .expect("ERR_NO_POOL")
+            .spot_price(token_in, token_out, PRICE_IMPACT_PRECISION)
+            .unwrap_or_default()
+    }
+
+    /// Swaps `amount_in` of `token_in` from the account into `token_out` via given pool,
+    /// depositing the output to the account. Returns amount of `token_out` received.
+    #[allow(clippy::too_many_arguments)]
+    fn internal_execute_swap(
+        &mut self,
+        account_id: &AccountId,
+        account: &mut Account,
+        referral_id: &Option<AccountId>,
+        pool_id: u64,
+        token_in: &AccountId,
+        amount_in: Balance,
+        token_out: &AccountId,
+        min_amount_out: Balance,
+    ) -> Balance {
+        self.assert_token_responsive(token_in);
+        self.assert_token_responsive(token_out);
+        account.withdraw(token_in, amount_in);
+        let amount_out = self.internal_pool_swap(
+            pool_id,
+            token_in,
+            amount_in,
+            token_out,
+            min_amount_out,
+            referral_id,
+        );
+        account.deposit(token_out, amount_out);
+        Event::Swap(vec![SwapEvent {
+            account_id: account_id.clone(),
+            pool_id,
+            token_in: token_in.clone(),
+            amount_in: U128(amount_in),
+            token_out: token_out.clone(),
+            amount_out: U128(amount_out),
+        }])
+        .emit();
+        self.internal_record_competition_volume(
+            account_id, pool_id, token_in, amount_in, token_out, amount_out,
+        );
+        amount_out
+    }
+
     /// Swaps given amount_in of token_in into token_out via given pool.
     /// Should be at least min_amount_out or swap will fail (prevents front running and other slippage issues).
     fn internal_pool_swap(
@@ -450,34 +1389,115 @@ impl Contract {
         min_amount_out: u128,
         referral_id: &Option<AccountId>,
     ) -> u128 {
+        self.assert_pool_launched(pool_id);
+        self.assert_pool_not_paused(pool_id);
+        self.assert_pool_not_flash_loaned(pool_id);
         let mut pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
-        let amount_out = pool.swap(
-            token_in,
+        let pool_admin_fees = self.internal_get_pool_admin_fees(pool_id);
+        let admin_fee = AdminFees {
+            exchange_fee: pool_admin_fees.exchange_fee,
+            exchange_id: env::current_account_id(),
+            referral_fee: self.internal_referral_fee(referral_id, pool_admin_fees.referral_fee),
+            referral_id: referral_id.clone(),
+            fee_side: self.internal_get_fee_side(pool_id, &pool),
+        }
+        .with_referral_tiers(
+            &self.referral_fee_tiers.get(token_in).unwrap_or_default(),
             amount_in,
-            token_out,
-            min_amount_out,
-            AdminFees {
-                exchange_fee: self.exchange_fee,
-                exchange_id: env::current_account_id(),
-                referral_fee: self.referral_fee,
-                referral_id: referral_id.clone(),
-            },
         );
-        self.pools.replace(pool_id, &pool);
+        let admin_fee =
+            self.internal_apply_referral_budget(admin_fee, token_in, amount_in, pool.get_fee());
+        if let Some(referral_id) = referral_id {
+            log!(
+                "Referral {} fee {} applied to swap of {} {}",
+                referral_id,
+                admin_fee.referral_fee,
+                amount_in,
+                token_in
+            );
+        }
+        self.internal_accumulate_prices(pool_id, &pool);
+        let amount_out = pool.swap(token_in, amount_in, token_out, min_amount_out, admin_fee);
+        self.internal_save_pool(pool_id, &pool);
+        self.internal_record_pool_swap(pool_id, &pool, token_in, amount_in, token_out, amount_out);
+        self.internal_checkpoint_pool(pool_id, &pool);
+        self.internal_monitor_peg(pool_id, &pool);
         amount_out
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, not(feature = "no-contract")))]
 mod tests {
     use std::convert::TryFrom;
 
+    use near_contract_standards::fungible_token::metadata::FungibleTokenMetadata;
     use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
-    use near_sdk::test_utils::{accounts, VMContextBuilder};
-    use near_sdk::{testing_env, Balance, MockedBlockchain};
+    use near_sdk::test_utils::{accounts, get_logs, VMContextBuilder};
+    use near_sdk::{testing_env, Balance, Gas, MockedBlockchain};
     use near_sdk_sim::to_yocto;
 
     use super::*;
+    use crate::account_export::AccountSnapshot;
+    use crate::action_limits::MAX_POOL_TOKENS_CEILING;
+    use crate::apr::POOL_CHECKPOINT_INTERVAL as DAY;
+    use crate::deposit_receipts::MAX_DEPOSIT_RECEIPTS;
+    use crate::events::{EventLog, WithdrawEvent, EVENT_STANDARD};
+    use crate::extraction_limit::ExtractionLimit;
+    use crate::flash_loans::FlashLoanInfo;
+    use crate::governance::GovernanceAction;
+    use crate::guardian_limits::GuardianLimit;
+    use crate::insurance::{DisbursementStatus, INSURANCE_PERIOD, INSURANCE_TIMELOCK};
+    use crate::liquidity_events::LiquidityEvent;
+    use crate::owner::{PendingActionInfo, TimelockedAction};
+    use crate::peg_monitor::PegMonitorConfig;
+    use crate::pool_bounds::PoolBounds;
+    use crate::pool_index::IndexedPoolInfo;
+    use crate::pool_metadata::MAX_POOL_NAME_LEN;
+    use crate::pool_validation::PoolParamViolation;
+    use crate::price_hints::PriceHint;
+    use crate::quarantine::MIN_FAILED_WITHDRAWS;
+    use crate::referral::ReferrerInfo;
+    use crate::referral_budget::ReferralBudget;
+    use crate::roles::{RoleInfo, PERMISSIONS};
+    use crate::share_loans::ShareLoanConfig;
+    use crate::twap::TWAP_PRICE_PRECISION;
+    use crate::utils::{MAX_ROUTE_INTERMEDIARIES, U256};
+
+    /// Returns method names of receipts created since the last `testing_env!`, with indices of
+    /// receipts they wait for.
+    fn created_receipts() -> Vec<(String, Vec<u64>)> {
+        #[derive(Deserialize)]
+        #[serde(crate = "near_sdk::serde")]
+        struct Receipt {
+            receipt_indices: Vec<u64>,
+            actions: Vec<Action>,
+        }
+        #[derive(Deserialize)]
+        #[serde(crate = "near_sdk::serde")]
+        enum Action {
+            FunctionCall { method_name: String },
+        }
+        env::BLOCKCHAIN_INTERFACE.with(|blockchain| {
+            let blockchain = blockchain.borrow();
+            let receipts = blockchain
+                .as_ref()
+                .unwrap()
+                .as_mocked_blockchain()
+                .unwrap()
+                .created_receipts();
+            receipts
+                .iter()
+                .map(|receipt| {
+                    let receipt: Receipt = near_sdk::serde_json::from_str(
+                        &near_sdk::serde_json::to_string(receipt).unwrap(),
+                    )
+                    .unwrap();
+                    let Action::FunctionCall { method_name } = &receipt.actions[0];
+                    (method_name.clone(), receipt.receipt_indices)
+                })
+                .collect()
+        })
+    }
 
     /// Creates contract and a pool with tokens with 0.3% of total fee.
     fn setup_contract() -> (VMContextBuilder, Contract) {
@@ -533,7 +1553,7 @@ mod tests {
         contract.extend_whitelisted_tokens(tokens.clone());
         testing_env!(context
             .predecessor_account_id(account_id.clone())
-            .attached_deposit(env::storage_byte_cost() * 300)
+            .attached_deposit(env::storage_byte_cost() * 150 * tokens.len() as u128)
             .build());
         let pool_id = contract.add_simple_pool(tokens, 25);
         testing_env!(context
@@ -550,6 +1570,7 @@ mod tests {
             pool_id,
             token_amounts.into_iter().map(|(_, x)| U128(x)).collect(),
             None,
+            None,
         );
         pool_id
     }
@@ -569,6 +1590,9 @@ mod tests {
                     amount_in: Some(U128(amount_in)),
                     token_out: token_out.into(),
                     min_amount_out: U128(1),
+                    quote_id: None,
+                    deadline: None,
+                    max_price_impact_bps: None,
                 }],
                 None,
             )
@@ -650,6 +1674,8 @@ mod tests {
             0,
             contract.get_pool_shares(0, accounts(3)),
             vec![1.into(), 2.into()],
+            None,
+            None,
         );
         // Exchange fees left in the pool as liquidity + 1m from transfer.
         assert_eq!(
@@ -684,10 +1710,10 @@ mod tests {
             .build());
         let id = contract.add_simple_pool(vec![accounts(1), accounts(2)], 25);
         testing_env!(context.attached_deposit(to_yocto("0.0007")).build());
-        contract.add_liquidity(id, vec![U128(to_yocto("50")), U128(to_yocto("10"))], None);
-        contract.add_liquidity(id, vec![U128(to_yocto("50")), U128(to_yocto("50"))], None);
+        contract.add_liquidity(id, vec![U128(to_yocto("50")), U128(to_yocto("10"))], None, None);
+        contract.add_liquidity(id, vec![U128(to_yocto("50")), U128(to_yocto("50"))], None, None);
         testing_env!(context.attached_deposit(1).build());
-        contract.remove_liquidity(id, U128(to_yocto("1")), vec![U128(1), U128(1)]);
+        contract.remove_liquidity(id, U128(to_yocto("1")), vec![U128(1), U128(1)], None, None);
 
         // Check that amounts add up to deposits.
         let amounts = contract.get_pool(id).amounts;
@@ -697,6 +1723,86 @@ mod tests {
         assert_eq!(amounts[1].0 + deposit2, to_yocto("100"));
     }
 
+    /// Removed liquidity goes directly to the wallet, failed transfer returns to deposits.
+    #[test]
+    fn test_mft_transfer_call_refund() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let token_id = format!(":{}", pool_id);
+        let shares = contract.get_pool_shares(pool_id, accounts(3)).0;
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(to_yocto("0.0067"))
+            .build());
+        contract.mft_register(token_id.clone(), accounts(4));
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.mft_transfer_call(token_id.clone(), accounts(4), U128(1000), None, "".to_string());
+        assert_eq!(contract.get_pool_shares(pool_id, accounts(4)).0, 1000);
+
+        // Receiver used only part of the shares, the rest is rolled back to the sender.
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"\"400\"".to_vec())]
+        );
+        let unused = contract.mft_resolve_transfer(
+            token_id,
+            accounts(3).into(),
+            &accounts(4).into(),
+            U128(1000),
+        );
+        assert_eq!(unused.0, 400);
+        assert_eq!(contract.get_pool_shares(pool_id, accounts(4)).0, 600);
+        assert_eq!(contract.get_pool_shares(pool_id, accounts(3)).0, shares - 600);
+    }
+
+    #[test]
+    fn test_remove_liquidity_withdraw() {
+        let (mut context, mut contract) = setup_contract();
+        let id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let deposits = contract.get_deposits(accounts(3));
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.remove_liquidity(id, U128(to_yocto("0.5")), vec![U128(1), U128(1)], Some(true), None);
+        assert_eq!(contract.get_deposits(accounts(3)), deposits);
+        let amounts = contract.get_pool(id).amounts;
+        let withdrawn = to_yocto("5") - amounts[0].0;
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.exchange_callback_post_withdraw(
+            accounts(1).into(),
+            accounts(3).into(),
+            U128(withdrawn),
+        );
+        assert_eq!(contract.get_deposits(accounts(3)), deposits);
+        assert_eq!(
+            contract.get_failed_withdrawals(accounts(3))[accounts(1).as_ref()].0,
+            withdrawn
+        );
+    }
+
     /// Should deny creating a pool with duplicate tokens.
     #[test]
     #[should_panic(expected = "ERR_TOKEN_DUPLICATES")]
@@ -712,7 +1818,7 @@ mod tests {
 
     /// Deny pool with a single token
     #[test]
-    #[should_panic(expected = "ERR_SHOULD_HAVE_2_TOKENS")]
+    #[should_panic(expected = "ERR_WRONG_TOKEN_COUNT")]
     fn test_deny_single_token_pool() {
         let (mut context, mut contract) = setup_contract();
         create_pool_with_liquidity(
@@ -723,21 +1829,57 @@ mod tests {
         );
     }
 
-    /// Deny pool with a single token
+    /// Deny pool with more than 8 tokens
     #[test]
-    #[should_panic(expected = "ERR_SHOULD_HAVE_2_TOKENS")]
+    #[should_panic(expected = "ERR_WRONG_TOKEN_COUNT")]
     fn test_deny_too_many_tokens_pool() {
         let (mut context, mut contract) = setup_contract();
         create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            (0..9)
+                .map(|i| {
+                    let token_id = ValidAccountId::try_from(format!("token{}.near", i)).unwrap();
+                    (token_id, to_yocto("10"))
+                })
+                .collect(),
+        );
+    }
+
+    /// Pool of three tokens swaps between any two of them.
+    #[test]
+    fn test_three_token_pool() {
+        let one_near = 10u128.pow(24);
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
             &mut context,
             &mut contract,
             accounts(3),
             vec![
                 (accounts(1), to_yocto("5")),
                 (accounts(2), to_yocto("10")),
-                (accounts(3), to_yocto("10")),
+                (accounts(4), to_yocto("20")),
             ],
         );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(4), one_near)]);
+        let expected_out = contract.get_return(pool_id, accounts(4), one_near.into(), accounts(1));
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        let amount_out = swap(&mut contract, pool_id, accounts(4), one_near, accounts(1));
+        assert_eq!(amount_out, expected_out.0);
+        assert_eq!(
+            contract.get_pool(pool_id).amounts,
+            vec![
+                U128(to_yocto("5") - amount_out),
+                U128(to_yocto("10")),
+                U128(to_yocto("21"))
+            ]
+        );
+        testing_env!(context.block_timestamp(DAY).build());
+        assert!(contract.get_pool_apr(pool_id, DAY.into()).is_none());
     }
 
     #[test]
@@ -811,6 +1953,9 @@ mod tests {
                 amount_in: Some(U128(1_000_000)),
                 token_out: accounts(2).into(),
                 min_amount_out: U128(1_000_000),
+                quote_id: None,
+                deadline: None,
+                max_price_impact_bps: None,
             }],
             None,
         );
@@ -887,6 +2032,9 @@ mod tests {
                     amount_in: Some(U128(1_000)),
                     token_out: accounts(2).into(),
                     min_amount_out: U128(1),
+                    quote_id: None,
+                    deadline: None,
+                    max_price_impact_bps: None,
                 },
                 SwapAction {
                     pool_id: 0,
@@ -894,6 +2042,9 @@ mod tests {
                     amount_in: None,
                     token_out: accounts(1).into(),
                     min_amount_out: U128(1),
+                    quote_id: None,
+                    deadline: None,
+                    max_price_impact_bps: None,
                 },
             ],
             None,
@@ -903,51 +2054,4896 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "E14: LP already registered")]
-    fn test_lpt_transfer() {
-        // account(0) -- swap contract
-        // account(1) -- token0 contract
-        // account(2) -- token1 contract
-        // account(3) -- user account
-        // account(4) -- another user account
+    fn test_storage_balance_logs() {
         let (mut context, mut contract) = setup_contract();
-        deposit_tokens(
-            &mut context,
-            &mut contract,
-            accounts(3),
-            vec![
-                (accounts(1), to_yocto("100")),
-                (accounts(2), to_yocto("100")),
-            ],
-        );
         testing_env!(context
-            .predecessor_account_id(accounts(3))
+            .predecessor_account_id(accounts(1))
             .attached_deposit(to_yocto("1"))
             .build());
-        let id = contract.add_simple_pool(vec![accounts(1), accounts(2)], 25);
-        testing_env!(context.attached_deposit(to_yocto("0.0007")).build());
-        contract.add_liquidity(id, vec![U128(to_yocto("50")), U128(to_yocto("10"))], None);
+        contract.storage_deposit(None, None);
         assert_eq!(
-            contract.mft_balance_of(":0".to_string(), accounts(3)).0,
-            to_yocto("1")
+            get_logs(),
+            vec![format!(
+                "Account {} storage balance changed by storage_deposit: 0 -> {}",
+                accounts(1),
+                to_yocto("1")
+            )]
         );
-        assert_eq!(contract.mft_total_supply(":0".to_string()).0, to_yocto("1"));
         testing_env!(context.attached_deposit(1).build());
-        contract.add_liquidity(id, vec![U128(to_yocto("50")), U128(to_yocto("50"))], None);
+        let available = contract.storage_balance_of(accounts(1)).unwrap().available.0;
+        contract.storage_withdraw(None);
         assert_eq!(
-            contract.mft_balance_of(":0".to_string(), accounts(3)).0,
-            to_yocto("2")
+            get_logs(),
+            vec![format!(
+                "Account {} storage balance changed by storage_withdraw: {} -> {}",
+                accounts(1),
+                to_yocto("1"),
+                to_yocto("1") - available
+            )]
         );
-        assert_eq!(contract.mft_total_supply(":0".to_string()).0, to_yocto("2"));
+    }
 
-        // register another user
-        testing_env!(context
-            .predecessor_account_id(accounts(4))
-            .attached_deposit(to_yocto("0.00071"))
-            .build());
-        contract.mft_register(":0".to_string(), accounts(4));
-        // make transfer to him
-        testing_env!(context
+    #[test]
+    fn test_token_shares_rebase() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.attached_deposit(1).build());
+        contract.enable_token_shares_mode(accounts(1));
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), 100)],
+        );
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(4),
+            vec![(accounts(1), 100)],
+        );
+        // token rebased from 200 to 300 held by the exchange.
+        testing_env!(
+            context
+                .predecessor_account_id(accounts(0))
+                .attached_deposit(0)
+                .build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"\"300\"".to_vec())]
+        );
+        contract.exchange_callback_reconcile_shares(accounts(1).into(), U128(200));
+        assert_eq!(
+            contract.get_token_shares(accounts(1)),
+            Some(token_shares::TokenSharesInfo {
+                total_shares: U128(200),
+                total_balance: U128(300),
+                in_flight: U128(0),
+            })
+        );
+        // new deposit gets shares at the new price.
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(5),
+            vec![(accounts(1), 150)],
+        );
+        assert_eq!(contract.get_deposit(accounts(5), accounts(1)).0, 100);
+        // withdrawing 100 shares burns them for 150 tokens.
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.withdraw(accounts(1), U128(100), None);
+        assert_eq!(
+            contract.get_token_shares(accounts(1)),
+            Some(token_shares::TokenSharesInfo {
+                total_shares: U128(200),
+                total_balance: U128(300),
+                in_flight: U128(150),
+            })
+        );
+        // the balance doesn't include the transfer yet, reconciling waits for its callback.
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"\"450\"".to_vec())]
+        );
+        contract.exchange_callback_reconcile_shares(accounts(1).into(), U128(300));
+        assert_eq!(contract.get_token_shares(accounts(1)).unwrap().total_balance, U128(300));
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.exchange_callback_post_withdraw(accounts(1).into(), accounts(3).into(), U128(150));
+        assert_eq!(contract.get_token_shares(accounts(1)).unwrap().in_flight, U128(0));
+        contract.reconcile_token_shares(accounts(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "E424: transfers of the token in flight")]
+    fn test_token_shares_reconcile_in_flight() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.attached_deposit(1).build());
+        contract.enable_token_shares_mode(accounts(1));
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 100)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.withdraw(accounts(1), U128(100), None);
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.reconcile_token_shares(accounts(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "E100: no permission to invoke this")]
+    fn test_token_shares_reconcile_not_allowed() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.attached_deposit(1).build());
+        contract.enable_token_shares_mode(accounts(1));
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(0)
+            .build());
+        contract.reconcile_token_shares(accounts(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "E123: token has deposits or reserves")]
+    fn test_token_shares_mode_with_deposits() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 100)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.enable_token_shares_mode(accounts(1));
+    }
+
+    /// Resolves skim of accounts(1) started with current holdings with reported balance.
+    fn skim_callback(context: &mut VMContextBuilder, contract: &mut Contract, balance: &str) -> U128 {
+        let internal = contract.get_token_holdings(accounts(1)).unwrap().internal;
+        balance_callback(context, balance);
+        contract.exchange_callback_skim(accounts(1).into(), internal)
+    }
+
+    fn balance_callback(context: &mut VMContextBuilder, balance: &str) {
+        testing_env!(
+            context
+                .predecessor_account_id(accounts(0))
+                .attached_deposit(0)
+                .build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(format!("\"{}\"", balance).into_bytes())]
+        );
+    }
+
+    #[test]
+    fn test_skim_token_holdings() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 100)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.withdraw(accounts(1), U128(30), None);
+        assert_eq!(
+            contract.get_token_holdings(accounts(1)),
+            Some(token_holdings::TokenHoldingsInfo {
+                internal: U128(70),
+                in_flight: U128(30),
+                skimmed: U128(0),
+            })
+        );
+        // 50 tokens were sent to the exchange directly, skim waits for the transfer.
+        assert_eq!(skim_callback(&mut context, &mut contract, "150"), U128(0));
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.exchange_callback_post_withdraw(accounts(1).into(), accounts(3).into(), U128(30));
+        assert_eq!(skim_callback(&mut context, &mut contract, "120"), U128(50));
+        testing_env!(context.attached_deposit(1).build());
+        contract.extend_whitelisted_tokens(vec![accounts(1)]);
+        assert_eq!(contract.claim_skimmed_tokens(accounts(1)), U128(50));
+        assert_eq!(contract.get_deposit(accounts(0), accounts(1)), U128(50));
+        assert_eq!(
+            contract.get_token_holdings(accounts(1)),
+            Some(token_holdings::TokenHoldingsInfo {
+                internal: U128(120),
+                in_flight: U128(0),
+                skimmed: U128(0),
+            })
+        );
+    }
+
+    /// Balance reported lower than tracked holdings takes back unclaimed skimmed tokens.
+    #[test]
+    fn test_skim_deficit() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 100)]);
+        assert_eq!(skim_callback(&mut context, &mut contract, "140"), U128(40));
+        assert_eq!(skim_callback(&mut context, &mut contract, "110"), U128(10));
+        assert_eq!(skim_callback(&mut context, &mut contract, "90"), U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "E424: transfers of the token in flight")]
+    fn test_skim_in_flight() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 100)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.withdraw(accounts(1), U128(30), None);
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.skim(accounts(1));
+    }
+
+    /// Holdings changed after the skim started are not skimmed.
+    #[test]
+    fn test_skim_holdings_changed() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 100)]);
+        balance_callback(&mut context, "150");
+        assert_eq!(contract.exchange_callback_skim(accounts(1).into(), U128(70)), U128(0));
+    }
+
+    #[test]
+    fn test_init_token_holdings() {
+        let (mut context, mut contract) = setup_contract();
+        contract.track_all_holdings = false;
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 100)]);
+        assert!(contract.get_token_holdings(accounts(1)).is_none());
+        balance_callback(&mut context, "130");
+        contract.exchange_callback_init_token_holdings(accounts(1).into());
+        // Tokens held before initialization are owed.
+        assert_eq!(contract.get_token_holdings(accounts(1)).unwrap().internal, U128(130));
+        assert_eq!(skim_callback(&mut context, &mut contract, "130"), U128(0));
+    }
+
+    #[test]
+    fn test_check_solvency() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 100)]);
+        let check = |context: &mut VMContextBuilder, contract: &Contract, balance: &str| {
+            testing_env!(
+                context.predecessor_account_id(accounts(0)).build(),
+                near_sdk::VMConfig::default(),
+                near_sdk::RuntimeFeesConfig::default(),
+                Default::default(),
+                vec![PromiseResult::Successful(format!("\"{}\"", balance).into_bytes())]
+            );
+            contract.exchange_callback_check_solvency(accounts(1).into())
+        };
+        let solvency = check(&mut context, &contract, "90");
+        assert_eq!((solvency.tracked, solvency.deficit), (U128(100), U128(10)));
+        assert_eq!(skim_callback(&mut context, &mut contract, "120"), U128(20));
+        assert_eq!(
+            check(&mut context, &contract, "125"),
+            token_holdings::SolvencyInfo {
+                balance: U128(125),
+                tracked: U128(120),
+                surplus: U128(5),
+                deficit: U128(0),
+                initialized_from_balance: false,
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E425: token holdings not initialized by init_token_holdings")]
+    fn test_check_solvency_not_initialized() {
+        let (mut context, mut contract) = setup_contract();
+        contract.track_all_holdings = false;
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 100)]);
+        contract.check_solvency(accounts(1));
+    }
+
+    fn swap_with_quote(contract: &mut Contract, pool_id: u64, quote_id: String) -> Balance {
+        contract
+            .swap(
+                vec![SwapAction {
+                    pool_id,
+                    token_in: accounts(1).into(),
+                    amount_in: Some(U128(1_000)),
+                    token_out: accounts(2).into(),
+                    min_amount_out: U128(1),
+                    quote_id: Some(quote_id),
+                    deadline: None,
+                    max_price_impact_bps: None,
+                }],
+                None,
+            )
+            .0
+    }
+
+    #[test]
+    fn test_swap_with_quote() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(4),
+            vec![(accounts(1), to_yocto("1"))],
+        );
+        let quote = contract.get_return_quote(pool_id, accounts(1), U128(1_000), accounts(2));
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(1)
+            .build());
+        // quote stays valid for a couple of pool mutations.
+        swap(&mut contract, pool_id, accounts(1), 1_000, accounts(2));
+        let amount_out = swap_with_quote(&mut contract, pool_id, quote.quote_id);
+        assert!(amount_out <= quote.amount_out.0);
+        assert_eq!(contract.get_pool_nonce(pool_id).0, quote.pool_nonce.0 + 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "E72: pool changed too much since quote")]
+    fn test_swap_with_expired_quote() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(4),
+            vec![(accounts(1), to_yocto("1"))],
+        );
+        let quote = contract.get_return_quote(pool_id, accounts(1), U128(1_000), accounts(2));
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(1)
+            .build());
+        for _ in 0..3 {
+            swap(&mut contract, pool_id, accounts(1), 1_000, accounts(2));
+        }
+        swap_with_quote(&mut contract, pool_id, quote.quote_id);
+    }
+
+    #[test]
+    fn test_exchange_info() {
+        let (mut context, mut contract) = setup_contract();
+        create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let info = contract.get_exchange_info();
+        assert_eq!(info.version, contract.version());
+        assert_eq!(info.exchange_fee, 1600);
+        assert!(info
+            .pool_types
+            .iter()
+            .any(|pool_type| pool_type.pool_kind == contract.get_pool(0).pool_kind));
+    }
+
+    #[test]
+    #[should_panic(expected = "E88: too many route intermediaries")]
+    fn test_route_intermediaries() {
+        let (_, mut contract) = setup_contract();
+        contract.extend_route_intermediaries(vec![accounts(1), accounts(2)]);
+        contract.remove_route_intermediaries(vec![accounts(1)]);
+        assert_eq!(contract.get_route_intermediaries(), vec![accounts(2).to_string()]);
+        let tokens = (0..MAX_ROUTE_INTERMEDIARIES)
+            .map(|i| ValidAccountId::try_from(format!("token{}", i)).unwrap())
+            .collect();
+        contract.extend_route_intermediaries(tokens);
+    }
+
+    #[test]
+    fn test_skim_exchange_fee() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        // owner receives skimmed tokens to the inner account.
+        deposit_tokens(&mut context, &mut contract, accounts(0), vec![]);
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(4),
+            vec![(accounts(1), to_yocto("1"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(1)
+            .build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        let info = contract.get_pool_fee_mode(pool_id);
+        assert!(info.compounding);
+        assert!(info.exchange_shares.0 > 0);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_pool_fee_compounding(pool_id, false);
+        // anyone can skim.
+        testing_env!(context
+            .predecessor_account_id(accounts(5))
+            .attached_deposit(1)
+            .build());
+        let amounts = contract.skim_exchange_fee(pool_id);
+        let info = contract.get_pool_fee_mode(pool_id);
+        assert!(!info.compounding);
+        assert_eq!(info.exchange_shares.0, 0);
+        assert_eq!(info.skimmed_amounts, amounts);
+        assert_eq!(
+            contract.get_deposits(accounts(0)).get(accounts(1).as_ref()),
+            Some(&amounts[0])
+        );
+    }
+
+    #[test]
+    fn test_withdraw_owner_fees() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(4),
+            vec![(accounts(1), to_yocto("1"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(1)
+            .build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        let exchange_shares = contract.get_pool_fee_mode(pool_id).exchange_shares.0;
+        assert!(exchange_shares > 0);
+
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        assert_eq!(contract.get_treasury(), accounts(0).to_string());
+        contract.set_treasury(Some(accounts(5)));
+        assert_eq!(contract.get_treasury(), accounts(5).to_string());
+        contract.set_insurance_config(InsuranceConfig {
+            fee_share: 1000,
+            period_cap: 0,
+        });
+        let amounts = contract.get_pool(pool_id).amounts;
+        contract.withdraw_owner_fees(pool_id);
+        let info = contract.get_pool_fee_mode(pool_id);
+        assert_eq!(info.exchange_shares.0, 0);
+        assert_eq!(info.accrued_shares.0, exchange_shares);
+        let pool_amounts = contract.get_pool(pool_id).amounts;
+        let removed = amounts[0].0 - pool_amounts[0].0;
+        assert!(removed > 0 && pool_amounts[1].0 < amounts[1].0);
+        // Insurance fund takes its slice, the rest goes to the treasury's inner account.
+        let insured = removed / 10;
+        let fund = contract.get_insurance_fund();
+        assert_eq!(fund[0].token_id, accounts(1).to_string());
+        assert_eq!(fund[0].balance.0, insured);
+        assert_eq!(contract.get_deposit(accounts(5), accounts(1)).0, removed - insured);
+        assert!(contract.get_deposits(accounts(0)).is_empty());
+    }
+
+    #[test]
+    fn test_exchange_fee_split() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(0), vec![]);
+        deposit_tokens(&mut context, &mut contract, accounts(5), vec![]);
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(4),
+            vec![(accounts(1), to_yocto("1"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(1)
+            .build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_exchange_fee_split(vec![
+            FeeRecipient {
+                account_id: accounts(5).into(),
+                share: 7000,
+            },
+            FeeRecipient {
+                account_id: accounts(0).into(),
+                share: 3000,
+            },
+        ]);
+        assert_eq!(contract.get_exchange_fee_split().len(), 2);
+        let shares = contract.get_pool_fee_mode(pool_id).exchange_shares;
+        contract.remove_exchange_fee_liquidity(pool_id, shares, vec![U128(0), U128(0)]);
+        let amount = contract.get_fee_recipient_received(accounts(5))[accounts(1).as_ref()].0;
+        let rest = contract.get_fee_recipient_received(accounts(0))[accounts(1).as_ref()].0;
+        assert!(amount > 0);
+        assert_eq!(amount, (amount + rest) * 7 / 10);
+        assert_eq!(
+            contract.get_deposits(accounts(5)).get(accounts(1).as_ref()),
+            Some(&U128(amount))
+        );
+        assert_eq!(
+            contract.get_deposits(accounts(0)).get(accounts(1).as_ref()),
+            Some(&U128(rest))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E130: invalid exchange fee split")]
+    fn test_exchange_fee_split_total() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(5), vec![]);
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_exchange_fee_split(vec![FeeRecipient {
+            account_id: accounts(5).into(),
+            share: 5000,
+        }]);
+    }
+
+    /// Creates a pool with exchange fee collected by a swap and claims it through the insurance fund.
+    fn setup_insurance_fund(context: &mut VMContextBuilder, contract: &mut Contract) -> Balance {
+        let pool_id = create_pool_with_liquidity(
+            context,
+            contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(context, contract, accounts(4), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(1)
+            .build());
+        swap(contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_insurance_config(InsuranceConfig {
+            fee_share: 5000,
+            period_cap: 5000,
+        });
+        let shares = contract.get_pool_fee_mode(pool_id).exchange_shares;
+        contract.remove_exchange_fee_liquidity(pool_id, shares, vec![U128(0), U128(0)]);
+        let fund = contract.get_insurance_fund();
+        let fund = fund
+            .iter()
+            .find(|fund| fund.token_id == accounts(1).to_string())
+            .unwrap();
+        let owner_amount = contract.get_deposits(accounts(0))[accounts(1).as_ref()].0;
+        assert_eq!(fund.balance.0, fund.total_inflow.0);
+        assert_eq!(fund.balance.0, (fund.balance.0 + owner_amount) / 2);
+        fund.balance.0
+    }
+
+    #[test]
+    fn test_insurance_disbursement() {
+        let (mut context, mut contract) = setup_contract();
+        let balance = setup_insurance_fund(&mut context, &mut contract);
+        let id = contract.propose_insurance_disbursement(
+            accounts(4),
+            accounts(1),
+            U128(balance / 4),
+            "incident".to_string(),
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .block_timestamp(INSURANCE_TIMELOCK)
+            .build());
+        contract.execute_insurance_disbursement(id);
+        assert_eq!(
+            contract.get_deposits(accounts(4))[accounts(1).as_ref()].0,
+            balance / 4
+        );
+        let fund = &contract.get_insurance_fund()[0];
+        assert_eq!(fund.balance.0, balance - balance / 4);
+        assert_eq!(fund.total_disbursed.0, balance / 4);
+        assert_eq!(
+            contract.get_insurance_disbursements(0, 10)[0].status,
+            DisbursementStatus::Executed
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E142: insurance disbursement exceeds period cap")]
+    fn test_insurance_disbursement_cap() {
+        let (mut context, mut contract) = setup_contract();
+        let balance = setup_insurance_fund(&mut context, &mut contract);
+        let id = contract.propose_insurance_disbursement(
+            accounts(4),
+            accounts(1),
+            U128(balance),
+            "incident".to_string(),
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .block_timestamp(INSURANCE_TIMELOCK)
+            .build());
+        contract.execute_insurance_disbursement(id);
+    }
+
+    #[test]
+    #[should_panic(expected = "E270: guardian limit exceeded")]
+    fn test_guardian_limit() {
+        let (mut context, mut contract) = setup_contract();
+        let balance = setup_insurance_fund(&mut context, &mut contract);
+        contract.extend_guardians(vec![accounts(5)]);
+        contract.set_guardian_limit(
+            accounts(5),
+            accounts(1),
+            Some(GuardianLimit {
+                max_per_period: U128(balance / 4),
+                period: INSURANCE_PERIOD.into(),
+            }),
+        );
+        let first = contract.propose_insurance_disbursement(
+            accounts(4),
+            accounts(1),
+            U128(balance / 8),
+            "incident".to_string(),
+        );
+        let second = contract.propose_insurance_disbursement(
+            accounts(4),
+            accounts(1),
+            U128(balance / 4),
+            "incident".to_string(),
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(5))
+            .block_timestamp(INSURANCE_TIMELOCK)
+            .build());
+        contract.execute_insurance_disbursement(first);
+        assert_eq!(
+            contract
+                .get_guardian_limit(accounts(5), accounts(1))
+                .unwrap()
+                .available
+                .0,
+            balance / 4 - balance / 8
+        );
+        contract.execute_insurance_disbursement(second);
+    }
+
+    #[test]
+    fn test_referral_fee_tiers() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_referral_fee_tiers(
+            accounts(1),
+            vec![
+                ReferralFeeTier {
+                    min_amount_in: U128(to_yocto("1")),
+                    referral_fee: 1000,
+                },
+                ReferralFeeTier {
+                    min_amount_in: U128(to_yocto("2")),
+                    referral_fee: 2000,
+                },
+            ],
+        );
+        assert_eq!(contract.get_referral_fee_tiers(accounts(1)).len(), 2);
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(4),
+            vec![(accounts(1), to_yocto("4"))],
+        );
+        for (amount_in, referral_fee) in vec![("0.5", 400), ("1", 1000), ("2.5", 2000)] {
+            testing_env!(context
+                .predecessor_account_id(accounts(4))
+                .attached_deposit(1)
+                .build());
+            contract.swap(
+                vec![SwapAction {
+                    pool_id,
+                    token_in: accounts(1).into(),
+                    amount_in: Some(U128(to_yocto(amount_in))),
+                    token_out: accounts(2).into(),
+                    min_amount_out: U128(1),
+                    quote_id: None,
+                    deadline: None,
+                    max_price_impact_bps: None,
+                }],
+                Some(accounts(3)),
+            );
+            assert!(get_logs()[0].starts_with(&format!(
+                "Referral {} fee {} applied",
+                accounts(3),
+                referral_fee
+            )));
+        }
+    }
+
+    #[test]
+    fn test_referral_registry() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(5))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.register_referrer();
+        assert_eq!(
+            contract.get_referrer(accounts(5)),
+            Some(ReferrerInfo {
+                tier: 0,
+                referral_fee: 400
+            })
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_referral_tier_fees(vec![500, 1000]);
+        contract.set_referrer_tier(accounts(5), 1);
+        assert_eq!(contract.get_referrer(accounts(5)).unwrap().referral_fee, 1000);
+        assert_eq!(contract.get_referrer(accounts(4)), None);
+
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(4),
+            vec![(accounts(1), to_yocto("2"))],
+        );
+        // Unregistered referrals keep the default fee.
+        for (referral_id, referral_fee) in vec![(accounts(5), 1000), (accounts(3), 400)] {
+            testing_env!(context
+                .predecessor_account_id(accounts(4))
+                .attached_deposit(1)
+                .build());
+            contract.swap(
+                vec![SwapAction {
+                    pool_id,
+                    token_in: accounts(1).into(),
+                    amount_in: Some(U128(to_yocto("1"))),
+                    token_out: accounts(2).into(),
+                    min_amount_out: U128(1),
+                    quote_id: None,
+                    deadline: None,
+                    max_price_impact_bps: None,
+                }],
+                Some(referral_id.clone()),
+            );
+            assert!(get_logs()[0].starts_with(&format!(
+                "Referral {} fee {} applied",
+                referral_id, referral_fee
+            )));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "E394: invalid referral tier")]
+    fn test_referrer_invalid_tier() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(5))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.register_referrer();
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_referral_tier_fees(vec![500]);
+        contract.set_referrer_tier(accounts(5), 1);
+    }
+
+    #[test]
+    fn test_referral_budget() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        // referral fee of 1 token swap is 1 * 0.25% * 4%.
+        contract.set_referral_budget(
+            accounts(1),
+            Some(ReferralBudget {
+                max_per_epoch: U128(to_yocto("0.00015")),
+                epoch_duration: DAY.into(),
+            }),
+        );
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(4),
+            vec![(accounts(1), to_yocto("3"))],
+        );
+        for (timestamp, referral_fee) in vec![(0, 400), (0, 0), (DAY, 400)] {
+            testing_env!(context
+                .predecessor_account_id(accounts(4))
+                .block_timestamp(timestamp)
+                .attached_deposit(1)
+                .build());
+            contract.swap(
+                vec![SwapAction {
+                    pool_id,
+                    token_in: accounts(1).into(),
+                    amount_in: Some(U128(to_yocto("1"))),
+                    token_out: accounts(2).into(),
+                    min_amount_out: U128(1),
+                    quote_id: None,
+                    deadline: None,
+                    max_price_impact_bps: None,
+                }],
+                Some(accounts(3)),
+            );
+            assert!(get_logs().iter().any(|log| log.starts_with(&format!(
+                "Referral {} fee {} applied",
+                accounts(3),
+                referral_fee
+            ))));
+        }
+        let budget = contract.get_referral_budget(accounts(1)).unwrap();
+        assert_eq!(budget.epoch, 1);
+        assert_eq!(budget.spent.0, to_yocto("0.0001"));
+    }
+
+    #[test]
+    fn test_check_stable_pool_decimals() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(env::storage_byte_cost() * 500)
+            .build());
+        let pool_id =
+            contract.add_stable_swap_pool(vec![accounts(1), accounts(2)], vec![18, 6], 25, 100);
+        contract.check_stable_pool_decimals(pool_id);
+        assert_eq!(
+            contract.get_pool(pool_id).decimals_status,
+            Some(DecimalsStatus::Pending)
+        );
+        let metadata = |decimals: u8| {
+            near_sdk::serde_json::to_vec(&FungibleTokenMetadata {
+                spec: "ft-1.0.0".to_string(),
+                name: "Token".to_string(),
+                symbol: "TKN".to_string(),
+                icon: None,
+                reference: None,
+                reference_hash: None,
+                decimals,
+            })
+            .unwrap()
+        };
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful(metadata(18)),
+                PromiseResult::Successful(metadata(18)),
+            ]
+        );
+        contract.exchange_callback_check_decimals(pool_id);
+        assert_eq!(
+            contract.get_pool_decimals_status(pool_id),
+            Some(DecimalsStatus::Mismatch {
+                tokens: vec![accounts(2).into()]
+            })
+        );
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful(metadata(18)),
+                PromiseResult::Successful(metadata(6)),
+            ]
+        );
+        contract.exchange_callback_check_decimals(pool_id);
+        assert_eq!(
+            contract.get_pool_decimals_status(pool_id),
+            Some(DecimalsStatus::Verified)
+        );
+    }
+
+    #[test]
+    fn test_mft_metadata_from_token_metadata() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let token_id = format!(":{}", pool_id);
+        assert_eq!(contract.mft_metadata(token_id.clone()).symbol, format!("REF-POOL-{}", pool_id));
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.cache_token_metadata(vec![accounts(1), accounts(2)]);
+        let metadata = |symbol: &str, icon: Option<String>| {
+            near_sdk::serde_json::to_vec(&FungibleTokenMetadata {
+                spec: "ft-1.0.0".to_string(),
+                name: "Token".to_string(),
+                symbol: symbol.to_string(),
+                icon,
+                reference: None,
+                reference_hash: None,
+                decimals: 24,
+            })
+            .unwrap()
+        };
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful(metadata("AAA", Some("x".repeat(5000)))),
+                PromiseResult::Failed,
+            ]
+        );
+        assert_eq!(
+            contract.exchange_callback_cache_token_metadata(vec![
+                accounts(1).into(),
+                accounts(2).into()
+            ]),
+            vec![accounts(1).to_string()]
+        );
+        assert_eq!(
+            contract.get_token_metadata(accounts(1)),
+            Some(token_metadata::CachedTokenMetadata {
+                symbol: "AAA".to_string(),
+                icon: None,
+            })
+        );
+        // Symbol is derived only once all pool tokens are cached.
+        assert_eq!(contract.mft_metadata(token_id.clone()).symbol, format!("REF-POOL-{}", pool_id));
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(metadata("BBB", Some("icon".to_string())))]
+        );
+        contract.exchange_callback_cache_token_metadata(vec![accounts(2).into()]);
+        let metadata = contract.mft_metadata(token_id);
+        assert_eq!(metadata.symbol, "AAA-BBB-LP");
+        assert_eq!(metadata.name, format!("ref-pool-{}", pool_id));
+        assert_eq!(metadata.icon, Some("icon".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "E73: pool decimals check pending or mismatched")]
+    fn test_stable_liquidity_blocked_by_decimals_check() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(env::storage_byte_cost() * 500)
+            .build());
+        let pool_id =
+            contract.add_stable_swap_pool(vec![accounts(1), accounts(2)], vec![18, 18], 25, 100);
+        contract.check_stable_pool_decimals(pool_id);
+        contract.add_stable_liquidity(pool_id, vec![U128(1), U128(1)], U128(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "E74: value extracted in block exceeds limit")]
+    fn test_extraction_limit() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(4),
+            vec![(accounts(1), to_yocto("2")), (accounts(2), to_yocto("1"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.set_extraction_limit(Some(ExtractionLimit {
+            reference_token: accounts(2).into(),
+            max_per_block: U128(to_yocto("1")),
+        }));
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(1)
+            .build());
+        // swaps out of reference token are netted with swaps into it.
+        swap(&mut contract, pool_id, accounts(1), to_yocto("0.4"), accounts(2));
+        swap(&mut contract, pool_id, accounts(2), to_yocto("0.5"), accounts(1));
+        swap(&mut contract, pool_id, accounts(1), to_yocto("0.4"), accounts(2));
+        // next block starts from zero.
+        testing_env!(context.block_index(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("0.2"), accounts(2));
+        swap(&mut contract, pool_id, accounts(1), to_yocto("0.6"), accounts(2));
+    }
+
+    /// Recalibrate `MEASURED_ACCOUNT_STORAGE` if this fails after account layout changes.
+    #[test]
+    fn test_storage_measured_account_bytes() {
+        let (mut context, mut contract) = setup_contract();
+        let account_id = ValidAccountId::try_from("a".repeat(64)).unwrap();
+        testing_env!(context
+            .predecessor_account_id(account_id.clone())
+            .attached_deposit(to_yocto("1"))
+            .build());
+        let prev_storage = env::storage_usage();
+        contract.storage_deposit(None, Some(true));
+        assert_eq!(
+            env::storage_usage() - prev_storage,
+            crate::account_deposit::MEASURED_ACCOUNT_STORAGE
+        );
+        assert_eq!(
+            contract.storage_balance_bounds().min.0,
+            crate::account_deposit::MEASURED_ACCOUNT_STORAGE as Balance * env::storage_byte_cost()
+        );
+    }
+
+    #[test]
+    fn test_storage_config() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(1),
+            vec![(accounts(2), 10), (accounts(3), 0)],
+        );
+        let breakdown = contract.get_storage_usage_breakdown(accounts(1)).unwrap();
+        assert_eq!(breakdown.tokens, 2);
+        assert_eq!(breakdown.legacy_tokens, 0);
+        assert_eq!(
+            breakdown.total_bytes,
+            breakdown.account_bytes + breakdown.token_bytes
+        );
+        assert_eq!(
+            breakdown.usage.0,
+            breakdown.total_bytes as Balance * breakdown.storage_byte_cost.0
+        );
+        assert_eq!(
+            breakdown.usage,
+            contract.get_user_storage_state(accounts(1)).unwrap().usage
+        );
+        assert!(contract.get_storage_usage_breakdown(accounts(4)).is_none());
+
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_storage_config(StorageConfig { account_bytes: 500 });
+        assert_eq!(contract.get_storage_config().account_bytes, 500);
+        assert_eq!(
+            contract.storage_balance_bounds().min.0,
+            500 * env::storage_byte_cost()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E15: storage config below account storage")]
+    fn test_storage_config_below_account_storage() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_storage_config(StorageConfig { account_bytes: 10 });
+    }
+
+    #[test]
+    #[should_panic(expected = "E11: insufficient $NEAR storage deposit")]
+    fn test_storage_price_buffer() {
+        use crate::account_deposit::{INIT_ACCOUNT_STORAGE, TOKEN_STORAGE};
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_storage_config(StorageConfig {
+            account_bytes: INIT_ACCOUNT_STORAGE + TOKEN_STORAGE,
+        });
+        let min_deposit = contract.storage_balance_bounds().min.0;
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(min_deposit)
+            .build());
+        contract.storage_deposit(None, Some(true));
+        testing_env!(context.attached_deposit(1).build());
+        contract.register_tokens(vec![accounts(1)]);
+        assert_eq!(contract.storage_balance_of(accounts(3)).unwrap().available.0, 0);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.modify_storage_price_buffer(5000);
+        assert_eq!(contract.storage_balance_bounds().min.0, min_deposit * 3 / 2);
+        // under-covered account keeps working while it doesn't take more storage.
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build());
+        contract.ft_on_transfer(accounts(3), U128(10), "".to_string());
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, 10);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.register_tokens(vec![accounts(2)]);
+    }
+
+    #[test]
+    fn test_competition() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(0), vec![(accounts(5), 1000)]);
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(2), to_yocto("2")), (accounts(5), 0)],
+        );
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(4),
+            vec![(accounts(1), to_yocto("1")), (accounts(5), 0)],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        let id = contract.create_competition(
+            vec![pool_id],
+            accounts(2),
+            10.into(),
+            100.into(),
+            accounts(5),
+            U128(1000),
+        );
+        assert_eq!(contract.get_deposit(accounts(0), accounts(5)).0, 0);
+
+        // swaps before the window are not counted.
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("0.1"), accounts(2));
+        assert_eq!(contract.get_competition_volume(id, accounts(4)).0, 0);
+
+        testing_env!(context.block_timestamp(50).build());
+        let amount_out = swap(&mut contract, pool_id, accounts(1), to_yocto("0.3"), accounts(2));
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        swap(&mut contract, pool_id, accounts(2), amount_out * 3, accounts(1));
+        assert_eq!(contract.get_competition_volume(id, accounts(4)).0, amount_out);
+        assert_eq!(contract.get_competition_volume(id, accounts(3)).0, amount_out * 3);
+        assert_eq!(contract.get_competitions(0, 10)[0].total_volume.0, amount_out * 4);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .block_timestamp(100)
+            .build());
+        assert_eq!(contract.claim_competition_reward(id).0, 250);
+        assert_eq!(contract.get_deposit(accounts(4), accounts(5)).0, 250);
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        assert_eq!(contract.claim_competition_reward(id).0, 750);
+        assert_eq!(contract.get_competitions(0, 10)[0].claimed.0, 1000);
+    }
+
+    #[test]
+    #[should_panic(expected = "E154: competition not ended")]
+    fn test_competition_claim_before_end() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(0), vec![(accounts(5), 1000)]);
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(2), to_yocto("2")), (accounts(5), 0)],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        let id = contract.create_competition(
+            vec![pool_id],
+            accounts(2),
+            0.into(),
+            100.into(),
+            accounts(5),
+            U128(1000),
+        );
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        swap(&mut contract, pool_id, accounts(2), to_yocto("0.1"), accounts(1));
+        contract.claim_competition_reward(id);
+    }
+
+    /// Fails withdraw of `token_id` by `account_id` with amount of 1.
+    fn fail_withdraw(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        account_id: ValidAccountId,
+        token_id: ValidAccountId,
+    ) {
+        testing_env!(context
+            .predecessor_account_id(account_id.clone())
+            .attached_deposit(1)
+            .build());
+        contract.withdraw(token_id.clone(), U128(1), None);
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.exchange_callback_post_withdraw(token_id.into(), account_id.into(), U128(1));
+    }
+
+    /// Creates pool of accounts(1) and accounts(2) and quarantines accounts(1).
+    fn setup_quarantined_pool(context: &mut VMContextBuilder, contract: &mut Contract) -> u64 {
+        let pool_id = create_pool_with_liquidity(
+            context,
+            contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(
+            context,
+            contract,
+            accounts(3),
+            vec![(accounts(1), MIN_FAILED_WITHDRAWS as Balance), (accounts(2), to_yocto("1"))],
+        );
+        for _ in 0..MIN_FAILED_WITHDRAWS {
+            fail_withdraw(context, contract, accounts(3), accounts(1));
+        }
+        assert_eq!(contract.get_token_withdraw_failures(accounts(1)), MIN_FAILED_WITHDRAWS);
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.mark_token_unresponsive(accounts(1));
+        pool_id
+    }
+
+    #[test]
+    fn test_token_quarantine() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = setup_quarantined_pool(&mut context, &mut contract);
+        assert_eq!(contract.get_unresponsive_tokens(), vec![accounts(1).to_string()]);
+
+        // LP exits with the working token, unresponsive one is quarantined.
+        let deposit1 = contract.get_deposit(accounts(3), accounts(1)).0;
+        let deposit2 = contract.get_deposit(accounts(3), accounts(2)).0;
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.remove_liquidity(pool_id, U128(to_yocto("0.5")), vec![U128(1), U128(1)], None, None);
+        let amounts = contract.get_pool(pool_id).amounts;
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, deposit1);
+        assert_eq!(
+            contract.get_deposit(accounts(3), accounts(2)).0,
+            deposit2 + to_yocto("10") - amounts[1].0
+        );
+        let quarantined = to_yocto("5") - amounts[0].0;
+        assert_eq!(
+            contract.get_quarantined_amounts(accounts(3)),
+            vec![(accounts(1).to_string(), U128(quarantined))].into_iter().collect()
+        );
+        assert_eq!(contract.get_deposit(accounts(0), accounts(1)).0, 0);
+
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.mark_token_responsive(accounts(1));
+        assert!(contract.get_unresponsive_tokens().is_empty());
+        assert_eq!(contract.get_token_withdraw_failures(accounts(1)), 0);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        assert_eq!(contract.claim_quarantined_tokens(accounts(1)).0, quarantined);
+        assert!(contract.get_quarantined_amounts(accounts(3)).is_empty());
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, deposit1 + quarantined);
+        swap(&mut contract, pool_id, accounts(2), to_yocto("0.1"), accounts(1));
+    }
+
+    /// Quarantined part of a pool token removed from whitelist stays with the LP.
+    #[test]
+    fn test_token_quarantine_not_whitelisted() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = setup_quarantined_pool(&mut context, &mut contract);
+        contract.remove_whitelisted_tokens(vec![accounts(1)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.remove_liquidity(pool_id, U128(to_yocto("0.5")), vec![U128(1), U128(1)], None, None);
+        let quarantined = to_yocto("5") - contract.get_pool(pool_id).amounts[0].0;
+        assert_eq!(
+            contract.get_quarantined_amounts(accounts(3)),
+            vec![(accounts(1).to_string(), U128(quarantined))].into_iter().collect()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E161: token is unresponsive")]
+    fn test_claim_quarantined_unresponsive() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = setup_quarantined_pool(&mut context, &mut contract);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.remove_liquidity(pool_id, U128(to_yocto("0.5")), vec![U128(1), U128(1)], None, None);
+        contract.claim_quarantined_tokens(accounts(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "E161: token is unresponsive")]
+    fn test_token_quarantine_swap() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = setup_quarantined_pool(&mut context, &mut contract);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        swap(&mut contract, pool_id, accounts(2), to_yocto("0.1"), accounts(1));
+    }
+
+    /// Successful withdraw resets failures, so the token can't be quarantined.
+    #[test]
+    #[should_panic(expected = "E160: not enough failed withdraws to quarantine token")]
+    fn test_token_quarantine_failures_reset() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 10)]);
+        for _ in 0..MIN_FAILED_WITHDRAWS - 1 {
+            fail_withdraw(&mut context, &mut contract, accounts(3), accounts(1));
+        }
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.exchange_callback_post_withdraw(accounts(1).into(), accounts(3).into(), U128(1));
+        fail_withdraw(&mut context, &mut contract, accounts(3), accounts(1));
+        assert_eq!(contract.get_token_withdraw_failures(accounts(1)), 1);
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.mark_token_unresponsive(accounts(1));
+    }
+
+    #[test]
+    fn test_governance_action() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_governance_dao(Some(accounts(5)));
+        assert_eq!(contract.get_governance_dao(), Some(accounts(5).into()));
+
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+        contract.execute_governance_action(GovernanceAction::ModifyAdminFee {
+            exchange_fee: 1000,
+            referral_fee: 500,
+        });
+        contract.execute_governance_action(GovernanceAction::ExtendWhitelistedTokens {
+            tokens: vec![accounts(1), accounts(2)],
+        });
+        contract.execute_governance_action(GovernanceAction::ChangeState {
+            state: RunningState::Paused,
+        });
+        contract.execute_governance_action(GovernanceAction::ProposeOwner {
+            owner_id: accounts(4),
+        });
+        let metadata = contract.metadata();
+        assert_eq!(metadata.exchange_fee, 1000);
+        assert_eq!(metadata.referral_fee, 500);
+        assert_eq!(metadata.state, RunningState::Paused);
+        assert_eq!(metadata.owner, accounts(0).to_string());
+        assert_eq!(contract.get_pending_owner(), Some(accounts(4).into()));
+        assert_eq!(
+            contract.get_whitelisted_tokens(),
+            vec![accounts(1).to_string(), accounts(2).to_string()]
+        );
+
+        contract.execute_governance_action(GovernanceAction::SetGovernanceDao { dao_id: None });
+        assert_eq!(contract.get_governance_dao(), None);
+
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.accept_ownership();
+        assert_eq!(contract.get_owner(), accounts(4).to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "E406: action must be scheduled with timelock")]
+    fn test_governance_action_timelocked() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_governance_dao(Some(accounts(5)));
+        let action_id = contract.schedule_action(TimelockedAction::SetTimelockDelay {
+            delay: DAY.into(),
+        });
+        contract.execute_action(action_id);
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+        contract.execute_governance_action(GovernanceAction::ModifyAdminFee {
+            exchange_fee: 1000,
+            referral_fee: 500,
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "E100: no permission to invoke this")]
+    fn test_governance_action_not_dao() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_governance_dao(Some(accounts(5)));
+        contract.execute_governance_action(GovernanceAction::ChangeState {
+            state: RunningState::Paused,
+        });
+    }
+
+    #[test]
+    fn test_pool_stats() {
+        let one_near = 10u128.pow(24);
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), one_near), (accounts(2), one_near)],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        let out_a = swap(&mut contract, pool_id, accounts(1), one_near, accounts(2));
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_pool_fee_side(pool_id, FeeSide::Output);
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        let out_b = swap(&mut contract, pool_id, accounts(2), one_near, accounts(1));
+        assert_eq!(
+            contract.get_pool_stats(pool_id),
+            pool_stats::PoolStatsInfo {
+                token_account_ids: vec![accounts(1).into(), accounts(2).into()],
+                swap_count: 2,
+                input_volumes: vec![U128(one_near), U128(one_near)],
+                output_volumes: vec![U128(out_b), U128(out_a)],
+                fees: vec![U128(out_b * 25 / 9975 + one_near * 25 / 10000), U128(0)],
+            }
+        );
+    }
+
+    #[test]
+    fn test_pool_apr() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        assert!(contract.get_pool_apr(pool_id, DAY.into()).is_none());
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("2")), (accounts(2), to_yocto("2"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        // first swap takes the first checkpoint.
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        assert!(contract.get_pool_apr(pool_id, DAY.into()).is_none());
+
+        testing_env!(context.block_timestamp(DAY).build());
+        swap(&mut contract, pool_id, accounts(2), to_yocto("1"), accounts(1));
+        let apr = contract.get_pool_apr(pool_id, DAY.into()).unwrap();
+        assert_eq!(apr.from_timestamp.0, 0);
+        assert_eq!(apr.to_timestamp.0, DAY);
+        assert!(apr.apr > 0);
+        assert_eq!(apr.fees, vec![U128(0), U128(to_yocto("0.0025"))]);
+
+        // next swap after the interval took a checkpoint, window of a day starts there.
+        testing_env!(context.block_timestamp(2 * DAY).build());
+        let apr = contract.get_pool_apr(pool_id, DAY.into()).unwrap();
+        assert_eq!(apr.from_timestamp.0, DAY);
+        assert_eq!(apr.fees, vec![U128(0), U128(0)]);
+        assert_eq!(apr.apr, 0);
+        let apr = contract.get_pool_apr(pool_id, (2 * DAY).into()).unwrap();
+        assert_eq!(apr.from_timestamp.0, 0);
+
+        let history = contract.get_pool_share_price_history(pool_id, (2 * DAY).into());
+        let timestamps: Vec<u64> = history.iter().map(|point| point.timestamp.0).collect();
+        assert_eq!(timestamps, vec![0, DAY, 2 * DAY]);
+        assert!(history[1].share_value.0 > history[0].share_value.0);
+        assert_eq!(history[2].share_value, history[1].share_value);
+        assert_eq!(contract.get_pool_share_price_history(pool_id, DAY.into()).len(), 2);
+    }
+
+    #[test]
+    fn test_pool_twap() {
+        const HOUR: u64 = 3600;
+        let spot_price = |contract: &Contract| {
+            let amounts = contract.get_pool(0).amounts;
+            U256::from(amounts[0].0) * U256::from(TWAP_PRICE_PRECISION) / U256::from(amounts[1].0)
+        };
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("2")), (accounts(2), to_yocto("2"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        assert!(contract.get_twap(pool_id, accounts(1), 0).is_none());
+        // first swap takes the first observation.
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        assert!(contract.get_twap(pool_id, accounts(1), 0).is_none());
+        let first_price = spot_price(&contract);
+
+        testing_env!(context.block_timestamp(HOUR * 1_000_000_000).build());
+        let twap = contract.get_twap(pool_id, accounts(2), HOUR).unwrap();
+        assert_eq!(twap.from_timestamp.0, 0);
+        assert_eq!(twap.to_timestamp.0, HOUR * 1_000_000_000);
+        assert_eq!(
+            twap.prices,
+            vec![first_price.to_string(), TWAP_PRICE_PRECISION.to_string()]
+        );
+        let precision = U256::from(TWAP_PRICE_PRECISION);
+        assert_eq!(
+            contract.get_twap(pool_id, accounts(1), HOUR).unwrap().prices[1],
+            (precision * precision / first_price).to_string()
+        );
+
+        swap(&mut contract, pool_id, accounts(2), to_yocto("2"), accounts(1));
+        let second_price = spot_price(&contract);
+        testing_env!(context.block_timestamp(2 * HOUR * 1_000_000_000).build());
+        assert_eq!(
+            contract.get_twap(pool_id, accounts(2), 2 * HOUR).unwrap().prices[0],
+            ((first_price + second_price) / 2).to_string()
+        );
+        // swap after the interval took an observation, window of an hour starts there.
+        let twap = contract.get_twap(pool_id, accounts(2), HOUR).unwrap();
+        assert_eq!(twap.from_timestamp.0, HOUR * 1_000_000_000);
+        assert_eq!(twap.prices[0], second_price.to_string());
+        assert!(contract.get_twap(pool_id, accounts(2), 3 * HOUR).is_none());
+    }
+
+    #[test]
+    fn test_stable_pool_twap() {
+        let (mut context, mut contract) = setup_contract();
+        let tokens: Vec<_> = (0..3).map(stress_token).collect();
+        let pool_id = create_stable_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            tokens.clone(),
+            to_yocto("100"),
+        );
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(tokens[0].clone(), to_yocto("1"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        swap(&mut contract, pool_id, tokens[0].clone(), to_yocto("1"), tokens[1].clone());
+        testing_env!(context.block_timestamp(3600 * 1_000_000_000).build());
+        let twap = contract.get_twap(pool_id, tokens[2].clone(), 3600).unwrap();
+        let precision = U256::from(TWAP_PRICE_PRECISION);
+        let prices: Vec<U256> = twap
+            .prices
+            .iter()
+            .map(|price| U256::from_dec_str(price).unwrap())
+            .collect();
+        assert_eq!(prices[2], precision);
+        // first token got cheaper and the second one dearer, all stay close to parity.
+        assert!(prices[0] > precision && prices[0] < precision * 101 / 100);
+        assert!(prices[1] < precision && prices[1] > precision * 99 / 100);
+    }
+
+    /// Swaps 1 of accounts(1) for accounts(2) in a pool of 5 and 10 of them with price impact
+    /// bound, the swap moves spot price by about 30%.
+    fn swap_with_max_price_impact(max_price_impact_bps: u32) -> Balance {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(1)
+            .build());
+        contract
+            .swap(
+                vec![SwapAction {
+                    pool_id,
+                    token_in: accounts(1).into(),
+                    amount_in: Some(U128(to_yocto("1"))),
+                    token_out: accounts(2).into(),
+                    min_amount_out: U128(1),
+                    quote_id: None,
+                    deadline: None,
+                    max_price_impact_bps: Some(max_price_impact_bps),
+                }],
+                None,
+            )
+            .0
+    }
+
+    #[test]
+    fn test_swap_max_price_impact() {
+        assert!(swap_with_max_price_impact(3200) > 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "E404: price impact exceeds max")]
+    fn test_swap_price_impact_too_high() {
+        swap_with_max_price_impact(3000);
+    }
+
+    #[test]
+    #[should_panic(expected = "E390: deadline passed")]
+    fn test_swap_deadline_passed() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("1"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .block_timestamp(1_000)
+            .attached_deposit(1)
+            .build());
+        contract.swap(
+            vec![SwapAction {
+                pool_id,
+                token_in: accounts(1).into(),
+                amount_in: Some(U128(to_yocto("1"))),
+                token_out: accounts(2).into(),
+                min_amount_out: U128(1),
+                quote_id: None,
+                deadline: Some(999.into()),
+                max_price_impact_bps: None,
+            }],
+            None,
+        );
+    }
+
+    #[test]
+    fn test_liquidity_deadline() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("1")), (accounts(2), to_yocto("2"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .block_timestamp(1_000)
+            .attached_deposit(to_yocto("0.0007"))
+            .build());
+        contract.add_liquidity(
+            pool_id,
+            vec![U128(to_yocto("1")), U128(to_yocto("2"))],
+            None,
+            Some(1_000.into()),
+        );
+        testing_env!(context.attached_deposit(1).build());
+        let shares = contract.get_pool_shares(pool_id, accounts(3));
+        contract.remove_liquidity(
+            pool_id,
+            shares,
+            vec![U128(1), U128(1)],
+            None,
+            Some(2_000.into()),
+        );
+        assert_eq!(contract.get_pool_shares(pool_id, accounts(3)).0, 0);
+    }
+
+    #[test]
+    fn test_add_liquidity_single() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        let shares = contract.add_liquidity_single(pool_id, accounts(1), U128(to_yocto("1")), U128(1));
+        assert!(shares.0 > 0);
+        assert_eq!(contract.get_pool_shares(pool_id, accounts(4)), shares);
+        // The input token is used up, leftover of the swapped half stays deposited.
+        assert_eq!(contract.get_deposit(accounts(4), accounts(1)).0, 0);
+        assert!(contract.get_deposit(accounts(4), accounts(2)).0 > 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "E68: slippage error")]
+    fn test_add_liquidity_single_slippage() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.add_liquidity_single(pool_id, accounts(1), U128(to_yocto("1")), U128(to_yocto("100")));
+    }
+
+    #[test]
+    fn test_donate_to_pool() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let shares = contract.get_pool_shares(pool_id, accounts(3));
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(1)
+            .build());
+        contract.donate_to_pool(pool_id, accounts(1), U128(to_yocto("1")));
+        assert_eq!(
+            last_event(),
+            Event::Donate(vec![DonateEvent {
+                account_id: accounts(4).into(),
+                pool_id,
+                token_id: accounts(1).into(),
+                amount: U128(to_yocto("1")),
+            }])
+        );
+        assert_eq!(contract.get_deposit(accounts(4), accounts(1)).0, 0);
+        assert_eq!(
+            contract.get_pool(pool_id).amounts,
+            vec![U128(to_yocto("6")), U128(to_yocto("10"))]
+        );
+        assert_eq!(contract.get_pool_shares(pool_id, accounts(3)), shares);
+    }
+
+    #[test]
+    #[should_panic(expected = "E409: can't donate to pool without liquidity")]
+    fn test_donate_to_empty_pool() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(env::storage_byte_cost() * 300)
+            .build());
+        let pool_id = contract.add_simple_pool(vec![accounts(1), accounts(2)], 25);
+        testing_env!(context.attached_deposit(1).build());
+        contract.donate_to_pool(pool_id, accounts(1), U128(to_yocto("1")));
+    }
+
+    /// Creates pool of accounts(1) and accounts(2), enables flash loans with 0.1% fee and
+    /// flash borrows 1 of accounts(1) by accounts(4) holding `balance` of it.
+    fn setup_flash_loan(balance: Balance) -> (VMContextBuilder, Contract, u64) {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![]);
+        testing_env!(context.attached_deposit(1).build());
+        contract.register_tokens(vec![accounts(1)]);
+        if balance > 0 {
+            deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), balance)]);
+        }
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_flash_loan_fee(Some(10));
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.flash_loan(pool_id, accounts(1), U128(to_yocto("1")), accounts(4), "".to_string());
+        (context, contract, pool_id)
+    }
+
+    #[test]
+    fn test_flash_loan() {
+        let (mut context, mut contract, pool_id) = setup_flash_loan(to_yocto("0.01"));
+        assert_eq!(contract.get_deposit(accounts(4), accounts(1)).0, to_yocto("1.01"));
+        assert_eq!(contract.get_pool(pool_id).amounts[0].0, to_yocto("4"));
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(0)
+            .build());
+        assert_eq!(contract.exchange_callback_flash_loan(accounts(4).into()).0, 0);
+        // Fee of the loan accrues to the pool reserve.
+        assert_eq!(contract.get_deposit(accounts(4), accounts(1)).0, to_yocto("0.009"));
+        assert_eq!(contract.get_pool(pool_id).amounts[0].0, to_yocto("5.001"));
+        assert!(contract.get_flash_loan(accounts(4)).is_none());
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(1)
+            .build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("0.001"), accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "E403: pool has flash loan in flight")]
+    fn test_flash_loan_locks_pool() {
+        let (mut context, mut contract, pool_id) = setup_flash_loan(to_yocto("0.01"));
+        testing_env!(context.attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("0.001"), accounts(2));
+    }
+
+    #[test]
+    fn test_flash_loan_not_repaid() {
+        let (mut context, mut contract, pool_id) = setup_flash_loan(0);
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(0)
+            .build());
+        let debt = contract.exchange_callback_flash_loan(accounts(4).into());
+        assert_eq!(debt.0, to_yocto("0.001"));
+        assert_eq!(
+            contract.get_flash_loan(accounts(4)),
+            Some(FlashLoanInfo {
+                pool_id,
+                token_id: accounts(1).into(),
+                debt,
+            })
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(1)
+            .build());
+        assert_eq!(contract.repay_flash_loan().0, 0);
+        assert_eq!(contract.get_deposit(accounts(4), accounts(1)).0, to_yocto("0.999"));
+        contract.withdraw(accounts(1), U128(to_yocto("0.999")), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "E399: flash loan not repaid")]
+    fn test_flash_loan_debt_locks_withdrawals() {
+        let (mut context, mut contract, _) = setup_flash_loan(0);
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(0)
+            .build());
+        contract.exchange_callback_flash_loan(accounts(4).into());
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(2), to_yocto("1"))]);
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(1)
+            .build());
+        contract.withdraw(accounts(2), U128(to_yocto("1")), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "E399: flash loan not repaid")]
+    fn test_flash_loan_locks_permits() {
+        let (mut context, mut contract, _) = setup_flash_loan(0);
+        deposit_tokens(&mut context, &mut contract, accounts(5), vec![(accounts(1), 1)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.set_permit_key(permit_public_key(&[7u8; 32]));
+        let permit = permit::Permit {
+            owner_id: accounts(4).into(),
+            receiver_id: accounts(5).into(),
+            token_id: accounts(1).into(),
+            amount: U128(to_yocto("1")),
+            nonce: 0,
+            deadline: 1_000.into(),
+        };
+        let signature = sign_permit(&[7u8; 32], &permit);
+        testing_env!(context.attached_deposit(1).build());
+        contract.execute_permit(permit, signature);
+    }
+
+    #[test]
+    #[should_panic(expected = "E399: flash loan not repaid")]
+    fn test_flash_loan_locks_account_merge() {
+        let (mut context, mut contract, _) = setup_flash_loan(0);
+        testing_env!(context.attached_deposit(1).build());
+        contract.merge_account_into(accounts(3));
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.confirm_account_merge(accounts(4));
+    }
+
+    #[test]
+    fn test_pool_pause() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.pause_pool(pool_id);
+        assert_eq!(contract.get_paused_pools(), vec![pool_id]);
+
+        // LPs can exit paused pool.
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.remove_liquidity(
+            pool_id,
+            U128(to_yocto("0.5")),
+            vec![U128(1), U128(1)],
+            None,
+            None,
+        );
+
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.resume_pool(pool_id);
+        assert!(contract.get_paused_pools().is_empty());
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("1"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        assert!(swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2)) > 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "E391: pool paused")]
+    fn test_pool_pause_swap() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("1"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.pause_pool(pool_id);
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "E100: no permission to invoke this")]
+    fn test_pool_pause_not_allowed() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.pause_pool(pool_id);
+    }
+
+    #[test]
+    fn test_swap_near() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![]);
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_wnear_account_id(Some(accounts(1)));
+        let actions = vec![SwapAction {
+            pool_id,
+            token_in: accounts(1).into(),
+            amount_in: None,
+            token_out: accounts(2).into(),
+            min_amount_out: U128(1),
+            quote_id: None,
+            deadline: None,
+            max_price_impact_bps: None,
+        }];
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(to_yocto("1"))
+            .build());
+        contract.swap_near(actions, None);
+
+        // Wrapped NEAR is deposited before the swap runs.
+        let wrapped_actions = || {
+            vec![SwapAction {
+                pool_id,
+                token_in: accounts(1).into(),
+                amount_in: Some(U128(to_yocto("1"))),
+                token_out: accounts(2).into(),
+                min_amount_out: U128(1),
+                quote_id: None,
+                deadline: None,
+                max_price_impact_bps: None,
+            }]
+        };
+        testing_env!(
+            context
+                .predecessor_account_id(accounts(0))
+                .attached_deposit(0)
+                .build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.exchange_callback_near_deposit(
+            accounts(4).into(),
+            U128(to_yocto("1")),
+            wrapped_actions(),
+            None,
+        );
+        assert_eq!(
+            contract.get_deposit(accounts(4), accounts(1)).0,
+            to_yocto("1")
+        );
+        let expected =
+            contract.get_return(pool_id, accounts(1), U128(to_yocto("1")), accounts(2));
+        let amount_out =
+            contract.exchange_callback_swap_near(accounts(4).into(), wrapped_actions(), None);
+        assert_eq!(amount_out, expected);
+        assert_eq!(contract.get_deposit(accounts(4), accounts(1)).0, 0);
+        assert_eq!(contract.get_deposit(accounts(4), accounts(2)), amount_out);
+    }
+
+    #[test]
+    #[should_panic(expected = "E181: withdrawal delay not passed")]
+    fn test_withdraw_near_delayed() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), 100)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_wnear_account_id(Some(accounts(1)));
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.set_withdraw_delay(100.into(), None);
+        contract.withdraw_near(U128(100));
+        assert_eq!(contract.get_deposit(accounts(4), accounts(1)).0, 0);
+        let withdrawals = contract.get_pending_withdrawals(accounts(4));
+        assert_eq!(withdrawals[0].amount.0, 100);
+        assert_eq!(withdrawals[0].token_id, accounts(1).to_string());
+        testing_env!(context.block_timestamp(99).build());
+        contract.execute_withdrawal(withdrawals[0].id);
+    }
+
+    #[test]
+    fn test_withdraw_near_failed() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), 100)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_wnear_account_id(Some(accounts(1)));
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.withdraw_near(U128(100));
+        assert_eq!(contract.get_deposit(accounts(4), accounts(1)).0, 0);
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.exchange_callback_near_withdraw(accounts(4).into(), U128(100));
+        assert_eq!(
+            contract.get_failed_withdrawals(accounts(4))[accounts(1).as_ref()].0,
+            100
+        );
+    }
+
+    #[test]
+    fn test_weighted_pool() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.attached_deposit(1).build());
+        contract.extend_whitelisted_tokens(vec![accounts(1), accounts(2)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(env::storage_byte_cost() * 400)
+            .build());
+        let pool_id =
+            contract.add_weighted_pool(vec![accounts(1), accounts(2)], vec![8000, 2000], 25);
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("81")), (accounts(2), to_yocto("5"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("0.0007"))
+            .build());
+        contract.add_liquidity(
+            pool_id,
+            vec![U128(to_yocto("80")), U128(to_yocto("5"))],
+            None,
+            None,
+        );
+        let pool = contract.get_pool(pool_id);
+        assert_eq!(pool.pool_kind, "WEIGHTED_POOL");
+        assert_eq!(pool.weights, Some(vec![8000, 2000]));
+        assert_eq!(pool.fee_side, FeeSide::Input);
+
+        // 80/20 pool prices the first token at a quarter of the second one.
+        let expected_out =
+            contract.get_return(pool_id, accounts(1), U128(to_yocto("1")), accounts(2));
+        assert!(expected_out.0 < to_yocto("0.25") && expected_out.0 > to_yocto("0.24"));
+        testing_env!(context.attached_deposit(1).build());
+        let amount_out = swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        assert_eq!(amount_out, expected_out.0);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(2)).0, amount_out);
+        assert_eq!(
+            contract.get_pool(pool_id).amounts,
+            vec![U128(to_yocto("81")), U128(to_yocto("5") - amount_out)]
+        );
+        assert!(contract.get_pool_fee_mode(pool_id).exchange_shares.0 > 0);
+    }
+
+    /// Checks the quoted amount in is the smallest one swapping for the amount out.
+    fn assert_input_for_output(contract: &Contract, pool_id: u64, amount_out: Balance) {
+        let amount_in = contract
+            .get_input_for_output(pool_id, accounts(1), accounts(2), U128(amount_out))
+            .0;
+        let get_return = |amount_in| {
+            contract
+                .get_return(pool_id, accounts(1), U128(amount_in), accounts(2))
+                .0
+        };
+        assert!(get_return(amount_in) >= amount_out);
+        assert!(amount_in == 1 || get_return(amount_in - 1) < amount_out);
+    }
+
+    #[test]
+    fn test_get_input_for_output() {
+        let (mut context, mut contract) = setup_contract();
+        let simple_pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(env::storage_byte_cost() * 800)
+            .build());
+        let weighted_pool_id =
+            contract.add_weighted_pool(vec![accounts(1), accounts(2)], vec![8000, 2000], 25);
+        let stable_pool_id =
+            contract.add_stable_swap_pool(vec![accounts(1), accounts(2)], vec![18, 18], 25, 100);
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("130")), (accounts(2), to_yocto("55"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.add_liquidity(
+            weighted_pool_id,
+            vec![U128(to_yocto("80")), U128(to_yocto("5"))],
+            None,
+            None,
+        );
+        contract.add_stable_liquidity(
+            stable_pool_id,
+            vec![U128(to_yocto("50")), U128(to_yocto("50"))],
+            U128(1),
+        );
+        // Up to the max out ratio of the weighted pool reserve of 5.
+        for amount_out in vec![1, 1_000, to_yocto("0.1"), to_yocto("1.5")] {
+            assert_input_for_output(&contract, simple_pool_id, amount_out);
+            assert_input_for_output(&contract, weighted_pool_id, amount_out);
+            assert_input_for_output(&contract, stable_pool_id, amount_out);
+        }
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_pool_fee_side(simple_pool_id, FeeSide::Output);
+        assert_input_for_output(&contract, simple_pool_id, to_yocto("2"));
+    }
+
+    #[test]
+    #[should_panic(expected = "E383: amount out exceeds max part of the pool reserve")]
+    fn test_weighted_pool_max_out_ratio() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(env::storage_byte_cost() * 500)
+            .build());
+        let pool_id =
+            contract.add_weighted_pool(vec![accounts(1), accounts(2)], vec![8000, 2000], 25);
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("80")), (accounts(2), to_yocto("5"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.add_liquidity(
+            pool_id,
+            vec![U128(to_yocto("80")), U128(to_yocto("5"))],
+            None,
+            None,
+        );
+        contract.get_input_for_output(pool_id, accounts(1), accounts(2), U128(to_yocto("1.6")));
+    }
+
+    #[test]
+    fn test_get_return_by_route() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_a = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let pool_b = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(2), to_yocto("10")), (accounts(4), to_yocto("20"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        let route = || vec![
+            SwapAction {
+                pool_id: pool_a,
+                token_in: accounts(1).into(),
+                amount_in: Some(U128(to_yocto("1"))),
+                token_out: accounts(2).into(),
+                min_amount_out: U128(0),
+                quote_id: None,
+                deadline: None,
+                max_price_impact_bps: None,
+            },
+            SwapAction {
+                pool_id: pool_b,
+                token_in: accounts(2).into(),
+                amount_in: None,
+                token_out: accounts(4).into(),
+                min_amount_out: U128(1),
+                quote_id: None,
+                deadline: None,
+                max_price_impact_bps: None,
+            },
+        ];
+        let expected_out = contract.get_return_by_route(route());
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        assert_eq!(contract.swap(route(), None), expected_out);
+    }
+
+    #[test]
+    fn test_account_portfolio() {
+        let (mut context, mut contract) = setup_contract();
+        create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("2")), (accounts(2), to_yocto("4"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.remove_liquidity(
+            pool_id,
+            U128(crate::utils::INIT_SHARES_SUPPLY / 2),
+            vec![U128(1), U128(1)],
+            None,
+            None,
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 100)]);
+        let portfolio = contract.get_account_portfolio(accounts(3));
+        assert_eq!(
+            portfolio.deposits,
+            vec![
+                (accounts(1).to_string(), U128(to_yocto("1") + 100)),
+                (accounts(2).to_string(), U128(to_yocto("2")))
+            ]
+                .into_iter()
+                .collect()
+        );
+        assert_eq!(
+            portfolio.positions,
+            vec![
+                views::LiquidityPosition {
+                    pool_id: 0,
+                    shares: U128(crate::utils::INIT_SHARES_SUPPLY),
+                    token_account_ids: vec![accounts(1).to_string(), accounts(2).to_string()],
+                    amounts: vec![U128(to_yocto("5")), U128(to_yocto("10"))],
+                },
+                views::LiquidityPosition {
+                    pool_id,
+                    shares: U128(crate::utils::INIT_SHARES_SUPPLY / 2),
+                    token_account_ids: vec![accounts(1).to_string(), accounts(2).to_string()],
+                    amounts: vec![U128(to_yocto("1")), U128(to_yocto("2"))],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_export_import_account() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 100)]);
+        let export = contract.export_account(accounts(3));
+        let snapshot = AccountSnapshot::try_from_slice(&export.snapshot.0).unwrap();
+        assert_eq!(snapshot.account_id, accounts(3).to_string());
+        assert_eq!(snapshot.shares.len(), 1);
+        assert_eq!(snapshot.shares[0].pool_id, pool_id);
+        assert_eq!(
+            snapshot.shares[0].amounts,
+            vec![
+                (accounts(1).to_string(), to_yocto("5")),
+                (accounts(2).to_string(), to_yocto("10"))
+            ]
+        );
+
+        // exchange imports from itself, so imported balances are added on top of existing ones.
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_migration_peer(Some(env::current_account_id().try_into().unwrap()));
+        testing_env!(context.attached_deposit(to_yocto("0.1")).build());
+        contract.import_account(export.snapshot.clone(), export.proof.clone());
+        assert_eq!(
+            contract.get_deposit(accounts(3), accounts(1)).0,
+            to_yocto("5") + 200
+        );
+        assert_eq!(contract.get_deposit(accounts(3), accounts(2)).0, to_yocto("10"));
+    }
+
+    #[test]
+    #[should_panic(expected = "E172: account already imported")]
+    fn test_import_account_twice() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 100)]);
+        let export = contract.export_account(accounts(3));
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_migration_peer(Some(env::current_account_id().try_into().unwrap()));
+        contract.import_account(export.snapshot.clone(), export.proof.clone());
+        contract.import_account(export.snapshot, export.proof);
+    }
+
+    #[test]
+    #[should_panic(expected = "E171: invalid account snapshot")]
+    fn test_import_account_wrong_proof() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 100)]);
+        let export = contract.export_account(accounts(3));
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_migration_peer(Some(env::current_account_id().try_into().unwrap()));
+        contract.import_account(export.snapshot, vec![0; 32].into());
+    }
+
+    #[test]
+    fn test_withdraw_delay() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 1000)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.set_withdraw_delay(100.into(), Some(accounts(4)));
+        contract.withdraw(accounts(1), U128(300), None);
+        contract.withdraw(accounts(1), U128(200), None);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, 500);
+        let withdrawals = contract.get_pending_withdrawals(accounts(3));
+        assert_eq!(withdrawals.len(), 2);
+        assert_eq!(withdrawals[0].amount.0, 300);
+        assert_eq!(withdrawals[0].executable_at.0, 100);
+
+        // guardian cancels, the amount is back in deposits.
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.cancel_withdrawal(accounts(3), withdrawals[1].id);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, 700);
+
+        // disabling the delay waits for the current delay.
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.set_withdraw_delay(0.into(), None);
+        assert_eq!(contract.get_withdraw_delay(accounts(3)).unwrap().delay.0, 100);
+        testing_env!(context.block_timestamp(100).build());
+        contract.execute_withdrawal(withdrawals[0].id);
+        assert!(contract.get_pending_withdrawals(accounts(3)).is_empty());
+        assert_eq!(contract.get_withdraw_delay(accounts(3)).unwrap().delay.0, 0);
+        contract.withdraw(accounts(1), U128(100), None);
+        assert!(contract.get_pending_withdrawals(accounts(3)).is_empty());
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, 600);
+    }
+
+    #[test]
+    #[should_panic(expected = "E185: can't transfer with withdraw delay")]
+    fn test_withdraw_delay_transfer() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 1000)]);
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), 0)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.set_withdraw_delay(100.into(), None);
+        contract.mft_transfer(accounts(1).to_string(), accounts(4), U128(300), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "E181: withdrawal delay not passed")]
+    fn test_withdraw_delay_execute_early() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 1000)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.set_withdraw_delay(100.into(), None);
+        contract.withdraw(accounts(1), U128(300), None);
+        testing_env!(context.block_timestamp(99).build());
+        contract.execute_withdrawal(0);
+    }
+
+    #[test]
+    fn test_peg_monitor() {
+        let (mut context, mut contract) = setup_contract();
+        let mut pool = StableSwapPool::new(0, vec![accounts(1), accounts(2)], vec![18, 18], 100, 0);
+        pool.add_liquidity(
+            accounts(3).as_ref(),
+            &mut vec![to_yocto("10"), to_yocto("1")],
+            1,
+            &AdminFees::zero(),
+        );
+        let pool = Pool::StableSwapPool(pool);
+        contract.pools.push(&pool);
+        assert!(contract.get_peg_deviations(0)[1] > 100);
+
+        // disabled by default.
+        testing_env!(context.build());
+        contract.internal_monitor_peg(0, &pool);
+        assert!(get_logs().is_empty());
+
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_peg_monitor_config(Some(PegMonitorConfig {
+            threshold: 100,
+            interval: 1000.into(),
+        }));
+        testing_env!(context.build());
+        contract.internal_monitor_peg(0, &pool);
+        assert_eq!(get_logs().len(), 1);
+        assert!(get_logs()[0].starts_with("Peg alert in pool 0: price of charlie"));
+
+        // rate-limited within the interval.
+        testing_env!(context.block_timestamp(999).build());
+        contract.internal_monitor_peg(0, &pool);
+        assert!(get_logs().is_empty());
+        testing_env!(context.block_timestamp(1000).build());
+        contract.internal_monitor_peg(0, &pool);
+        assert_eq!(get_logs().len(), 1);
+    }
+
+    #[test]
+    fn test_pool_bounds() {
+        let (mut context, mut contract) = setup_contract();
+        assert_eq!(contract.get_pool_bounds(), PoolBounds::default());
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_pool_bounds(PoolBounds {
+            max_fee: 2500,
+            min_amp: 10,
+            max_amp: 1000,
+            max_admin_fee: 3000,
+        });
+        assert_eq!(contract.get_pool_bounds().max_fee, 2500);
+        testing_env!(context
+            .attached_deposit(env::storage_byte_cost() * 500)
+            .build());
+        contract.add_simple_pool(vec![accounts(1), accounts(2)], 2500);
+        contract.add_stable_swap_pool(vec![accounts(1), accounts(2)], vec![18, 18], 25, 1000);
+        contract.modify_admin_fee(2000, 1000);
+    }
+
+    #[test]
+    #[should_panic(expected = "E200: pool fee out of bounds")]
+    fn test_pool_bounds_fee() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(env::storage_byte_cost() * 300)
+            .build());
+        contract.add_simple_pool(vec![accounts(1), accounts(2)], 2500);
+    }
+
+    #[test]
+    fn test_pool_fee_tiers() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_pool_fee_tiers(vec![5, 30, 100]);
+        assert_eq!(contract.get_pool_fee_tiers(), vec![5, 30, 100]);
+        assert_eq!(
+            contract.validate_pool_params(
+                "SIMPLE_POOL".to_string(),
+                vec![accounts(1), accounts(2)],
+                25,
+                None,
+                None,
+                None
+            ),
+            vec![
+                PoolParamViolation::TokenNotWhitelisted { token_id: accounts(1).into() },
+                PoolParamViolation::TokenNotWhitelisted { token_id: accounts(2).into() },
+                PoolParamViolation::FeeNotInTiers { fee_tiers: vec![5, 30, 100] },
+            ]
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(env::storage_byte_cost() * 500)
+            .build());
+        contract.add_simple_pool(vec![accounts(1), accounts(2)], 30);
+        // Stable pools are only limited by pool bounds.
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.add_stable_swap_pool(vec![accounts(1), accounts(2)], vec![18, 18], 25, 1000);
+    }
+
+    #[test]
+    #[should_panic(expected = "E419: fee is not one of the allowed fee tiers")]
+    fn test_pool_fee_tiers_reject() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_pool_fee_tiers(vec![5, 30, 100]);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(env::storage_byte_cost() * 300)
+            .build());
+        contract.add_simple_pool(vec![accounts(1), accounts(2)], 25);
+    }
+
+    #[test]
+    fn test_pool_dedup() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(env::storage_byte_cost() * 1000)
+            .build());
+        let pool_id = contract.add_simple_pool(vec![accounts(1), accounts(2)], 25);
+        contract.add_simple_pool(vec![accounts(1), accounts(2), accounts(4)], 25);
+        assert_eq!(contract.get_canonical_pool(accounts(2), accounts(1), 25), Some(pool_id));
+        assert_eq!(contract.get_canonical_pool(accounts(1), accounts(2), 30), None);
+        // Duplicates are allowed until owner enables deduplication.
+        contract.add_simple_pool(vec![accounts(2), accounts(1)], 25);
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_pool_dedup(true);
+        assert_eq!(
+            contract.validate_pool_params(
+                "SIMPLE_POOL".to_string(),
+                vec![accounts(2), accounts(1)],
+                25,
+                None,
+                None,
+                None
+            ),
+            vec![
+                PoolParamViolation::TokenNotWhitelisted { token_id: accounts(2).into() },
+                PoolParamViolation::TokenNotWhitelisted { token_id: accounts(1).into() },
+                PoolParamViolation::DuplicatePool { pool_id },
+            ]
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(env::storage_byte_cost() * 500)
+            .build());
+        contract.add_simple_pool(vec![accounts(1), accounts(2)], 30);
+        contract.add_weighted_pool(vec![accounts(1), accounts(2)], vec![5000, 5000], 25);
+    }
+
+    #[test]
+    #[should_panic(expected = "E422: simple pool of the token pair with the fee exists")]
+    fn test_pool_dedup_reject() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_pool_dedup(true);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(env::storage_byte_cost() * 500)
+            .build());
+        contract.add_simple_pool(vec![accounts(1), accounts(2)], 25);
+        contract.add_simple_pool(vec![accounts(2), accounts(1)], 25);
+    }
+
+    #[test]
+    fn test_banned_tokens() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 100)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.extend_whitelisted_tokens(vec![accounts(1), accounts(2)]);
+        contract.ban_tokens(vec![accounts(1)]);
+        assert_eq!(contract.get_banned_tokens(), vec![accounts(1).to_string()]);
+        assert_eq!(contract.get_whitelisted_tokens(), vec![accounts(2).to_string()]);
+        assert_eq!(
+            contract.validate_pool_params(
+                "SIMPLE_POOL".to_string(),
+                vec![accounts(1), accounts(2)],
+                25,
+                None,
+                None,
+                None
+            ),
+            vec![PoolParamViolation::TokenBanned { token_id: accounts(1).into() }]
+        );
+        // Deposited tokens can still be withdrawn.
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.withdraw(accounts(1), U128(100), None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.unban_tokens(vec![accounts(1)]);
+        assert!(!contract.is_token_banned(accounts(1)));
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 100)]);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "E423: token is banned")]
+    fn test_banned_token_deposit() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.attached_deposit(1).build());
+        contract.ban_tokens(vec![accounts(1)]);
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(2), 100)]);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.ft_on_transfer(accounts(3), U128(100), "".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "E423: token is banned")]
+    fn test_banned_token_pool() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.attached_deposit(1).build());
+        contract.ban_tokens(vec![accounts(2)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(env::storage_byte_cost() * 500)
+            .build());
+        contract.add_simple_pool(vec![accounts(1), accounts(2)], 25);
+    }
+
+    #[test]
+    #[should_panic(expected = "E202: admin fee out of bounds")]
+    fn test_pool_bounds_admin_fee() {
+        let (_, mut contract) = setup_contract();
+        contract.modify_admin_fee(4000, 2000);
+    }
+
+    #[test]
+    fn test_deposit_receipts() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 100)]);
+        assert!(contract.get_recent_deposits(accounts(3)).is_none());
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("1"))
+            .build());
+        contract.enable_deposit_receipts();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build());
+        contract.ft_on_transfer(accounts(3), U128(50), "{\"memo\": \"user-42\"}".to_string());
+        assert_eq!(
+            get_logs(),
+            vec![format!("Deposit 50 {} to {} with memo: user-42", accounts(1), accounts(3))]
+        );
+        for _ in 0..MAX_DEPOSIT_RECEIPTS {
+            contract.ft_on_transfer(accounts(3), U128(1), "".to_string());
+        }
+        let receipts = contract.get_recent_deposits(accounts(3)).unwrap();
+        assert_eq!(receipts.len(), MAX_DEPOSIT_RECEIPTS);
+        assert!(receipts.iter().all(|receipt| receipt.memo.is_none()));
+        contract.ft_on_transfer(accounts(3), U128(2), "{\"memo\": \"user-43\"}".to_string());
+        let receipts = contract.get_recent_deposits(accounts(3)).unwrap();
+        assert_eq!(receipts.last().unwrap().amount.0, 2);
+        assert_eq!(receipts.last().unwrap().memo, Some("user-43".to_string()));
+        assert_eq!(
+            contract.get_deposit(accounts(3), accounts(1)).0,
+            152 + MAX_DEPOSIT_RECEIPTS as u128
+        );
+
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.disable_deposit_receipts();
+        assert!(contract.get_recent_deposits(accounts(3)).is_none());
+    }
+
+    #[test]
+    fn test_deposit_to_receiver() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 100)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build());
+        let msg = format!("{{\"receiver_id\": \"{}\", \"memo\": \"desk-1\"}}", accounts(3));
+        contract.ft_on_transfer(accounts(4), U128(50), msg);
+        assert_eq!(
+            get_logs(),
+            vec![
+                format!("{} deposited 50 {} to {}", accounts(4), accounts(1), accounts(3)),
+                format!("Deposit 50 {} to {} with memo: desk-1", accounts(1), accounts(3)),
+            ]
+        );
+        let msg = format!("{{\"receiver_id\": \"{}\"}}", accounts(3));
+        contract.ft_on_transfer(accounts(4), U128(25), msg);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, 175);
+        assert!(contract.storage_balance_of(accounts(4)).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "E10: account not registered")]
+    fn test_deposit_to_unregistered_receiver() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build());
+        let msg = format!("{{\"receiver_id\": \"{}\"}}", accounts(3));
+        contract.ft_on_transfer(accounts(4), U128(50), msg);
+    }
+
+    #[test]
+    fn test_pool_fee_on_output() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        assert_eq!(contract.get_pool(pool_id).fee_side, FeeSide::Input);
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_pool_fee_side(pool_id, FeeSide::Output);
+        assert_eq!(contract.get_pool(pool_id).fee_side, FeeSide::Output);
+
+        let expected_out = contract.get_return(pool_id, accounts(1), to_yocto("1").into(), accounts(2));
+        // 10 * 1 / (5 + 1) minus 0.25% fee.
+        assert_eq!(expected_out.0, to_yocto("1.6625"));
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        let amount_out = swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        assert_eq!(amount_out, expected_out.0);
+        assert_eq!(
+            contract.get_pool(pool_id).amounts,
+            vec![U128(to_yocto("6")), U128(to_yocto("10") - amount_out)]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E220: fee side not supported by the pool")]
+    fn test_stable_pool_fee_on_input() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .attached_deposit(env::storage_byte_cost() * 500)
+            .build());
+        let pool_id =
+            contract.add_stable_swap_pool(vec![accounts(1), accounts(2)], vec![18, 18], 25, 1000);
+        assert_eq!(contract.get_pool(pool_id).fee_side, FeeSide::Output);
+        testing_env!(context.attached_deposit(1).build());
+        contract.set_pool_fee_side(pool_id, FeeSide::Input);
+    }
+
+    #[test]
+    fn test_amp_ramp_schedule() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .attached_deposit(env::storage_byte_cost() * 500)
+            .build());
+        let pool_id =
+            contract.add_stable_swap_pool(vec![accounts(1), accounts(2)], vec![18, 18], 25, 100);
+        let start = 2 * DAY;
+        testing_env!(context.block_timestamp(start).attached_deposit(1).build());
+        contract.stable_swap_ramp_amp(pool_id, 200, (start + 2 * DAY).into());
+        testing_env!(context.block_timestamp(start + DAY).build());
+        assert_eq!(
+            contract.get_amp_ramp(pool_id),
+            views::AmpRampInfo {
+                amp: 150,
+                init_amp_factor: 100,
+                init_amp_time: start.into(),
+                target_amp_factor: 200,
+                stop_amp_time: (start + 2 * DAY).into(),
+            }
+        );
+        contract.stable_swap_stop_ramp_amp(pool_id);
+        testing_env!(context.block_timestamp(start + 3 * DAY).build());
+        assert_eq!(contract.get_amp_ramp(pool_id).amp, 150);
+    }
+
+    /// Creates stable pool with liquidity of accounts(3) and lending reserve of accounts(1),
+    /// then borrows 20 of accounts(1) against half of the shares.
+    fn setup_share_loan(context: &mut VMContextBuilder, contract: &mut Contract) -> (u64, Balance) {
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(env::storage_byte_cost() * 500)
+            .build());
+        contract.extend_whitelisted_tokens(vec![accounts(1), accounts(2)]);
+        let pool_id =
+            contract.add_stable_swap_pool(vec![accounts(1), accounts(2)], vec![18, 18], 25, 100);
+        deposit_tokens(
+            context,
+            contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("50")), (accounts(2), to_yocto("50"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        let shares = contract
+            .add_stable_liquidity(pool_id, vec![U128(to_yocto("50")), U128(to_yocto("50"))], U128(1))
+            .0;
+        deposit_tokens(context, contract, accounts(0), vec![(accounts(1), to_yocto("20"))]);
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.fund_lending_reserve(accounts(1), U128(to_yocto("20")));
+        contract.set_share_loan_config(
+            pool_id,
+            ShareLoanConfig {
+                max_ltv: 5000,
+                liquidation_ltv: 8000,
+                liquidation_bonus: 500,
+            },
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.borrow_against_shares(pool_id, U128(shares / 2), accounts(1), U128(to_yocto("20")));
+        (pool_id, shares)
+    }
+
+    #[test]
+    fn test_share_loan() {
+        let (mut context, mut contract) = setup_contract();
+        let (pool_id, shares) = setup_share_loan(&mut context, &mut contract);
+        let loan = contract.get_share_loan(accounts(3), pool_id).unwrap();
+        assert_eq!(loan.shares.0, shares / 2);
+        assert_eq!(loan.ltv, 4000);
+        assert_eq!(contract.get_lending_reserve(accounts(1)).0, 0);
+        assert_eq!(
+            contract.get_deposit(accounts(3), accounts(1)).0,
+            to_yocto("20")
+        );
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        assert_eq!(contract.repay_share_loan(pool_id, U128(to_yocto("5"))).0, to_yocto("15"));
+        assert_eq!(contract.get_lending_reserve(accounts(1)).0, to_yocto("5"));
+
+        // Tightened limits make the loan of 15 against 50 liquidatable.
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_share_loan_config(
+            pool_id,
+            ShareLoanConfig {
+                max_ltv: 1000,
+                liquidation_ltv: 2000,
+                liquidation_bonus: 500,
+            },
+        );
+        // Anyone registered can liquidate for the bonus of 5% of the debt.
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(to_yocto("1"))
+            .build());
+        contract.storage_deposit(None, None);
+        testing_env!(context.attached_deposit(1).build());
+        contract.register_tokens(vec![accounts(1)]);
+        contract.liquidate_share_loan(accounts(3), pool_id);
+        assert!(contract.get_share_loan(accounts(3), pool_id).is_none());
+        assert_eq!(contract.get_pool_shares(pool_id, accounts(3)).0, shares - shares / 2);
+        assert_eq!(contract.get_lending_reserve(accounts(1)).0, to_yocto("20"));
+        assert_eq!(contract.get_deposit(accounts(4), accounts(1)).0, to_yocto("0.75"));
+        assert_eq!(
+            contract.get_deposit(accounts(3), accounts(1)).0,
+            to_yocto("15") + to_yocto("9.25")
+        );
+        assert_eq!(
+            contract.get_deposit(accounts(3), accounts(2)).0,
+            to_yocto("25")
+        );
+    }
+
+    #[test]
+    fn test_share_loan_conservative_value() {
+        let (mut context, mut contract) = setup_contract();
+        let (pool_id, shares) = setup_share_loan(&mut context, &mut contract);
+        // Dumping accounts(1) into the pool leaves only the balanced part as collateral.
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("10"), accounts(2));
+        let pool = contract.get_pool(pool_id);
+        let (amounts, supply) = (pool.amounts, pool.shares_total_supply.0);
+        assert!(amounts[1].0 < amounts[0].0);
+        let loan = contract.get_share_loan(accounts(3), pool_id).unwrap();
+        assert_eq!(
+            loan.value.0,
+            (U256::from(2 * amounts[1].0) * U256::from(shares / 2) / U256::from(supply)).as_u128()
+        );
+
+        // accounts(2) hinted at half the price of accounts(1) values the whole pool at it.
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.extend_price_oracles(vec![accounts(5)]);
+        contract.set_price_hint_max_age(accounts(1), Some(100.into()));
+        contract.set_price_hint_max_age(accounts(2), Some(100.into()));
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+        contract.push_price_hints(vec![
+            (
+                accounts(1),
+                PriceHint {
+                    multiplier: U128(2),
+                    decimals: 0,
+                },
+            ),
+            (
+                accounts(2),
+                PriceHint {
+                    multiplier: U128(1),
+                    decimals: 0,
+                },
+            ),
+        ]);
+        let loan = contract.get_share_loan(accounts(3), pool_id).unwrap();
+        assert_eq!(
+            loan.value.0,
+            (U256::from((amounts[0].0 + amounts[1].0) / 2) * U256::from(shares / 2)
+                / U256::from(supply))
+            .as_u128()
+        );
+    }
+
+    #[test]
+    fn test_share_loan_storage() {
+        let (mut context, mut contract) = setup_contract();
+        let (pool_id, shares) = setup_share_loan(&mut context, &mut contract);
+        let available =
+            |contract: &Contract| contract.storage_balance_of(accounts(3)).unwrap().available.0;
+        let with_loan = available(&contract);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.repay_share_loan(pool_id, U128(to_yocto("20")));
+        assert!(available(&contract) > with_loan);
+        // Borrowing again takes loan storage from the storage balance.
+        contract.borrow_against_shares(pool_id, U128(shares / 2), accounts(1), U128(to_yocto("20")));
+        assert_eq!(available(&contract), with_loan);
+    }
+
+    #[test]
+    #[should_panic(expected = "Requires attached deposit of exactly 1 yoctoNEAR")]
+    fn test_share_loan_requires_one_yocto() {
+        let (mut context, mut contract) = setup_contract();
+        let (pool_id, shares) = setup_share_loan(&mut context, &mut contract);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.borrow_against_shares(pool_id, U128(shares / 4), accounts(1), U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "E287: shares are locked by a loan")]
+    fn test_share_loan_locks_shares() {
+        let (mut context, mut contract) = setup_contract();
+        let (pool_id, shares) = setup_share_loan(&mut context, &mut contract);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.remove_liquidity(pool_id, U128(shares / 2 + 1), vec![U128(0), U128(0)], None, None);
+    }
+
+    #[test]
+    fn test_share_locks() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let shares = contract.get_pool_shares(pool_id, accounts(3)).0;
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.mft_register(format!(":{}", pool_id), accounts(4));
+        contract.lock_shares(pool_id, U128(shares / 2), DAY.into());
+        assert_eq!(
+            last_event(),
+            Event::LockShares(vec![share_locks::ShareLockEvent {
+                account_id: accounts(3).into(),
+                pool_id,
+                shares: U128(shares / 2),
+                unlock_at: Some(DAY.into()),
+            }])
+        );
+        contract.lock_shares(pool_id, U128(shares / 4), (2 * DAY).into());
+        assert_eq!(
+            contract.get_locked_shares(accounts(3), pool_id).0,
+            shares / 2 + shares / 4
+        );
+
+        // Shares above the locked ones can be removed.
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        let unlocked = shares - shares / 2 - shares / 4;
+        contract.remove_liquidity(pool_id, U128(unlocked), vec![U128(0), U128(0)], None, None);
+        assert_eq!(contract.unlock_shares(pool_id).0, 0);
+
+        // First tranche vests after a day.
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .block_timestamp(DAY)
+            .attached_deposit(1)
+            .build());
+        assert_eq!(contract.get_locked_shares(accounts(3), pool_id).0, shares / 4);
+        contract.mft_transfer(format!(":{}", pool_id), accounts(4), U128(shares / 2), None);
+        assert_eq!(contract.get_share_locks(accounts(3), pool_id).len(), 2);
+        assert_eq!(contract.unlock_shares(pool_id).0, shares / 2);
+        assert_eq!(
+            last_event(),
+            Event::UnlockShares(vec![share_locks::ShareLockEvent {
+                account_id: accounts(3).into(),
+                pool_id,
+                shares: U128(shares / 2),
+                unlock_at: None,
+            }])
+        );
+        assert_eq!(
+            contract.get_share_locks(accounts(3), pool_id),
+            vec![share_locks::ShareLockInfo {
+                shares: U128(shares / 4),
+                unlock_at: (2 * DAY).into(),
+            }]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E414: shares are locked")]
+    fn test_share_locks_lock_shares() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let shares = contract.get_pool_shares(pool_id, accounts(3)).0;
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.mft_register(format!(":{}", pool_id), accounts(4));
+        contract.lock_shares(pool_id, U128(shares / 2), DAY.into());
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .block_timestamp(DAY - 1)
+            .attached_deposit(1)
+            .build());
+        contract.mft_transfer(format!(":{}", pool_id), accounts(4), U128(shares / 2 + 1), None);
+    }
+
+    #[test]
+    fn test_pagination() {
+        let (mut context, mut contract) = setup_contract();
+        let tokens = vec![accounts(2), accounts(1), accounts(5)];
+        contract.extend_whitelisted_tokens(tokens.clone());
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            tokens.into_iter().map(|token_id| (token_id, 10)).collect(),
+        );
+        let page = contract.get_deposits_page(accounts(3), None, 2);
+        assert_eq!(
+            page.items,
+            vec![(accounts(1).to_string(), U128(10)), (accounts(2).to_string(), U128(10))]
+        );
+        assert!(page.has_more);
+        // New deposit before the cursor doesn't shift the next page.
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 5)]);
+        let page = contract.get_deposits_page(accounts(3), page.next_cursor, 2);
+        assert_eq!(page.items, vec![(accounts(5).to_string(), U128(10))]);
+        assert_eq!(page.next_cursor, None);
+        assert!(!page.has_more);
+
+        let page = contract.get_account_ids_page(None, 1);
+        assert_eq!(page.items, vec![accounts(3).to_string()]);
+        assert!(!page.has_more);
+        assert!(contract.get_pools_page(None, 10).items.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "E290: invalid cursor")]
+    fn test_pagination_invalid_cursor() {
+        let (_, contract) = setup_contract();
+        contract.get_pools_page(Some("cursor".to_string()), 10);
+    }
+
+    #[test]
+    fn test_account_merge() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 7)]);
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(2), 3)]);
+        let shares = contract.get_pool_shares(pool_id, accounts(3)).0;
+        let near_amount = contract.storage_balance_of(accounts(3)).unwrap().total.0
+            + contract.storage_balance_of(accounts(4)).unwrap().total.0;
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.merge_account_into(accounts(4));
+        assert_eq!(contract.get_account_merge(accounts(3)), Some(accounts(4).to_string()));
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.confirm_account_merge(accounts(3));
+
+        assert!(contract.storage_balance_of(accounts(3)).is_none());
+        assert!(contract.get_account_merge(accounts(3)).is_none());
+        assert_eq!(contract.get_pool_shares(pool_id, accounts(3)).0, 0);
+        assert_eq!(contract.get_pool_shares(pool_id, accounts(4)).0, shares);
+        assert_eq!(contract.get_deposit(accounts(4), accounts(1)).0, 7);
+        assert_eq!(contract.get_deposit(accounts(4), accounts(2)).0, 3);
+        assert_eq!(contract.storage_balance_of(accounts(4)).unwrap().total.0, near_amount);
+        assert_eq!(contract.get_account_ids(0, 10), vec![accounts(4).to_string()]);
+    }
+
+    #[test]
+    fn test_account_merge_claims() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = setup_quarantined_pool(&mut context, &mut contract);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.remove_liquidity(pool_id, U128(to_yocto("0.5")), vec![U128(1), U128(1)], None, None);
+        let failed = contract.get_failed_withdrawals(accounts(3));
+        let quarantined = contract.get_quarantined_amounts(accounts(3));
+        assert!(!failed.is_empty() && !quarantined.is_empty());
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![]);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.merge_account_into(accounts(4));
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.confirm_account_merge(accounts(3));
+        assert!(contract.get_failed_withdrawals(accounts(3)).is_empty());
+        assert!(contract.get_quarantined_amounts(accounts(3)).is_empty());
+        assert_eq!(contract.get_failed_withdrawals(accounts(4)), failed);
+        assert_eq!(contract.get_quarantined_amounts(accounts(4)), quarantined);
+    }
+
+    #[test]
+    #[should_panic(expected = "E304: account with withdraw delay can't be merged")]
+    fn test_account_merge_withdraw_delay() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 7)]);
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![]);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.set_withdraw_delay(100.into(), None);
+        contract.merge_account_into(accounts(4));
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.confirm_account_merge(accounts(3));
+    }
+
+    #[test]
+    #[should_panic(expected = "E300: no merge request")]
+    fn test_account_merge_not_requested() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![]);
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![]);
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(1)
+            .build());
+        contract.confirm_account_merge(accounts(3));
+    }
+
+    #[test]
+    fn test_pool_metadata() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        assert_eq!(contract.get_pool_creator(pool_id), Some(accounts(3).to_string()));
+        let metadata = PoolMetadata {
+            name: "Pool".to_string(),
+            description: None,
+            project_url: Some("https://example.com".to_string()),
+            logo: None,
+        };
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.set_pool_metadata(pool_id, metadata.clone());
+        assert_eq!(contract.get_pool(pool_id).metadata, Some(metadata));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.extend_guardians(vec![accounts(5)]);
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+        contract.remove_pool_metadata(pool_id);
+        assert_eq!(contract.get_pool(pool_id).metadata, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "E320: invalid pool metadata")]
+    fn test_pool_metadata_too_long() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .attached_deposit(env::storage_byte_cost() * 300)
+            .build());
+        let pool_id = contract.add_simple_pool(vec![accounts(1), accounts(2)], 25);
+        testing_env!(context.attached_deposit(to_yocto("0.01")).build());
+        contract.set_pool_metadata(
+            pool_id,
+            PoolMetadata {
+                name: "P".repeat(MAX_POOL_NAME_LEN + 1),
+                description: None,
+                project_url: None,
+                logo: None,
+            },
+        );
+    }
+
+    #[test]
+    fn test_min_withdrawal() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 1000)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_min_withdrawal(accounts(1), Some(U128(100)));
+        assert_eq!(contract.get_min_withdrawals().get(&accounts(1).to_string()), Some(&U128(100)));
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.withdraw(accounts(1), U128(100), None);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, 900);
+
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.set_min_withdrawal(accounts(1), None);
+        assert!(contract.get_min_withdrawals().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "E330: amount is below min withdrawal of the token")]
+    fn test_withdraw_below_min_withdrawal() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 1000)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_min_withdrawal(accounts(1), Some(U128(100)));
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.withdraw(accounts(1), U128(99), None);
+    }
+
+    #[test]
+    fn test_withdraw_all() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), 1000), (accounts(2), 50), (accounts(4), 200)],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_min_withdrawal(accounts(2), Some(U128(100)));
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.withdraw_all(Some(vec![accounts(4)]));
+        assert_eq!(contract.get_deposit(accounts(3), accounts(4)).0, 0);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, 1000);
+
+        contract.withdraw_all(None);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, 0);
+        // Balance below min withdrawal stays, withdrawn tokens stay registered.
+        assert_eq!(contract.get_deposit(accounts(3), accounts(2)).0, 50);
+        assert_eq!(contract.get_deposits(accounts(3)).len(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "E396: not enough gas for all withdrawals")]
+    fn test_withdraw_all_not_enough_gas() {
+        let (mut context, mut contract) = setup_contract();
+        let tokens: Vec<_> = (0..8).map(|i| (stress_token(i), 100)).collect();
+        deposit_tokens(&mut context, &mut contract, accounts(3), tokens);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.withdraw_all(None);
+    }
+
+    #[test]
+    fn test_failed_withdrawal_claim() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 100)]);
+        fail_withdraw(&mut context, &mut contract, accounts(3), accounts(1));
+        fail_withdraw(&mut context, &mut contract, accounts(3), accounts(1));
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, 98);
+        assert_eq!(
+            contract.get_failed_withdrawals(accounts(3))[accounts(1).as_ref()].0,
+            2
+        );
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("0.00125"))
+            .build());
+        contract.claim_failed_withdrawal(accounts(1), true);
+        assert!(contract.get_failed_withdrawals(accounts(3)).is_empty());
+        // Callback resolves the transfer, which waits for the registration.
+        assert_eq!(
+            created_receipts(),
+            vec![
+                ("storage_deposit".to_string(), vec![]),
+                ("ft_transfer".to_string(), vec![0]),
+                ("exchange_callback_post_withdraw".to_string(), vec![1]),
+            ]
+        );
+
+        // failed claim goes back to claims.
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.exchange_callback_post_withdraw(accounts(1).into(), accounts(3).into(), U128(2));
+        assert_eq!(
+            contract.get_failed_withdrawals(accounts(3))[accounts(1).as_ref()].0,
+            2
+        );
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, 98);
+    }
+
+    /// Failed transfer of a token not whitelisted to an account not registered on the exchange
+    /// is kept as its lost tokens, the account claims them once registered.
+    #[test]
+    fn test_failed_withdrawal_unregistered_account() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 100)]);
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.exchange_callback_post_withdraw(accounts(1).into(), accounts(4).into(), U128(10));
+        assert!(contract.get_failed_withdrawals(accounts(4)).is_empty());
+        assert_eq!(contract.get_deposit(accounts(0), accounts(1)).0, 0);
+        assert_eq!(contract.get_lost_tokens(accounts(4))[accounts(1).as_ref()].0, 10);
+        let page = contract.get_lost_tokens_page(None, 10);
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].0, accounts(4).to_string());
+
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(to_yocto("1"))
+            .build());
+        contract.storage_deposit(None, None);
+        testing_env!(context.attached_deposit(1).build());
+        assert_eq!(contract.claim_lost_tokens(accounts(1)).0, 10);
+        assert!(contract.get_lost_tokens(accounts(4)).is_empty());
+        assert_eq!(contract.get_deposit(accounts(4), accounts(1)).0, 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "E341: no lost tokens of the token")]
+    fn test_claim_without_lost_tokens() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(1)
+            .build());
+        contract.claim_lost_tokens(accounts(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "E340: no failed withdrawal of the token")]
+    fn test_claim_without_failed_withdrawal() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 100)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.claim_failed_withdrawal(accounts(1), false);
+    }
+
+    /// Sets min withdrawal of accounts(1) to 100 and dust policy with inactivity of 1000 and
+    /// grace of 100, accounts(3) holds 50 of accounts(1).
+    fn setup_dust_policy(context: &mut VMContextBuilder, contract: &mut Contract) {
+        deposit_tokens(context, contract, accounts(3), vec![(accounts(1), 50)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_min_withdrawal(accounts(1), Some(U128(100)));
+        contract.set_dust_policy(Some(DustPolicy {
+            inactivity_period: 1000.into(),
+            grace_period: 100.into(),
+        }));
+        testing_env!(context.attached_deposit(0).block_timestamp(1000).build());
+        contract.flag_dust_account(accounts(3));
+    }
+
+    #[test]
+    fn test_dust_policy() {
+        let (mut context, mut contract) = setup_contract();
+        setup_dust_policy(&mut context, &mut contract);
+        let flags = contract.get_dust_flags(0, 10);
+        assert_eq!(flags.len(), 1);
+        assert_eq!(flags[0].account_id, accounts(3).to_string());
+        assert_eq!(flags[0].sweepable_at.0, 1100);
+        assert!(!flags[0].active_since_flag);
+
+        testing_env!(context.block_timestamp(1100).build());
+        assert!(contract.sweep_dust_account(accounts(3)));
+        assert!(contract.get_deposits(accounts(3)).is_empty());
+        assert!(contract.storage_balance_of(accounts(3)).is_some());
+        assert_eq!(contract.get_insurance_fund()[0].balance.0, 50);
+        assert!(contract.get_dust_flags(0, 10).is_empty());
+    }
+
+    #[test]
+    fn test_dust_sweep_cancelled_by_activity() {
+        let (mut context, mut contract) = setup_contract();
+        setup_dust_policy(&mut context, &mut contract);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .block_timestamp(1050)
+            .build());
+        contract.register_tokens(vec![accounts(2)]);
+        assert!(contract.get_dust_flags(0, 10)[0].active_since_flag);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(0)
+            .block_timestamp(1100)
+            .build());
+        assert!(!contract.sweep_dust_account(accounts(3)));
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, 50);
+        assert!(contract.get_dust_flags(0, 10).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "E351: account is not inactive")]
+    fn test_dust_flag_active_account() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 50)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .block_timestamp(500)
+            .build());
+        contract.set_dust_policy(Some(DustPolicy {
+            inactivity_period: 1000.into(),
+            grace_period: 100.into(),
+        }));
+        contract.flag_dust_account(accounts(3));
+    }
+
+    /// Swaps accounts(1) to accounts(2) and back in the pool `hops` times as one route.
+    fn swap_route(contract: &mut Contract, pool_id: u64, hops: usize) {
+        let tokens = [accounts(1), accounts(2)];
+        let actions = (0..hops)
+            .map(|i| SwapAction {
+                pool_id,
+                token_in: tokens[i % 2].clone().into(),
+                amount_in: if i == 0 { Some(U128(to_yocto("0.1"))) } else { None },
+                token_out: tokens[(i + 1) % 2].clone().into(),
+                min_amount_out: U128(1),
+                quote_id: None,
+                deadline: None,
+                max_price_impact_bps: None,
+            })
+            .collect();
+        contract.swap(actions, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "E361: route has too many hops")]
+    fn test_action_limits() {
+        let (mut context, mut contract) = setup_contract();
+        assert_eq!(contract.metadata().action_limits, ActionLimits::default());
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_action_limits(ActionLimits {
+            max_actions: 4,
+            max_route_hops: 2,
+            max_pool_tokens: 5,
+        });
+        assert_eq!(contract.metadata().action_limits.max_route_hops, 2);
+        assert_eq!(
+            contract.get_exchange_info().pool_types[1].max_tokens,
+            Some(5)
+        );
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        swap_route(&mut contract, pool_id, 2);
+        swap_route(&mut contract, pool_id, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "E362: too many tokens in pool")]
+    fn test_action_limits_pool_tokens() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(env::storage_byte_cost() * 2000)
+            .build());
+        let tokens: Vec<_> = (0..MAX_POOL_TOKENS_CEILING as usize + 1)
+            .map(stress_token)
+            .collect();
+        contract.add_stable_swap_pool(tokens.clone(), vec![18; tokens.len()], 25, 100);
+    }
+
+    #[test]
+    fn test_routed_swap() {
+        let (mut context, mut contract) = setup_contract();
+        create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("10")), (accounts(2), to_yocto("10"))],
+        );
+        create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(2), to_yocto("10")), (accounts(5), to_yocto("10"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.extend_route_intermediaries(vec![accounts(2)]);
+        assert!(contract
+            .get_best_route(accounts(1), accounts(5), U128(to_yocto("1")), 1)
+            .is_none());
+        let route = contract
+            .get_best_route(accounts(1), accounts(5), U128(to_yocto("1")), 2)
+            .unwrap();
+        assert_eq!(route.pool_ids, vec![0, 1]);
+        assert_eq!(
+            route.tokens,
+            vec![
+                accounts(1).to_string(),
+                accounts(2).to_string(),
+                accounts(5).to_string()
+            ]
+        );
+
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(4),
+            vec![(accounts(1), to_yocto("1"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.storage_deposit(Some(accounts(4)), None);
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(1)
+            .build());
+        contract.register_tokens(vec![accounts(5)]);
+        let amount_out = contract.execute_actions(
+            vec![Action::RoutedSwap(RoutedSwapAction {
+                pool_ids: route.pool_ids.clone(),
+                tokens: route.tokens.clone(),
+                amount_in: Some(U128(to_yocto("1"))),
+                min_amount_out: route.amount_out,
+                deadline: None,
+            })],
+            None,
+            None,
+        );
+        assert_eq!(amount_out.to_amount(), route.amount_out.0);
+        assert_eq!(contract.get_deposit(accounts(4), accounts(1)).0, 0);
+        assert_eq!(contract.get_deposit(accounts(4), accounts(2)).0, 0);
+        assert_eq!(
+            contract.get_deposit(accounts(4), accounts(5)),
+            route.amount_out
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E370: invalid route")]
+    fn test_routed_swap_invalid_route() {
+        let (mut context, mut contract) = setup_contract();
+        create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("10")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.execute_actions(
+            vec![Action::RoutedSwap(RoutedSwapAction {
+                pool_ids: vec![0],
+                tokens: vec![accounts(1).into()],
+                amount_in: Some(U128(1)),
+                min_amount_out: U128(0),
+                deadline: None,
+            })],
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn test_execute_actions_auto_withdraw() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(4),
+            vec![(accounts(1), to_yocto("1"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(1)
+            .build());
+        let amount_out = contract
+            .execute_actions(
+                vec![Action::Swap(SwapAction {
+                    pool_id,
+                    token_in: accounts(1).into(),
+                    amount_in: Some(U128(to_yocto("1"))),
+                    token_out: accounts(2).into(),
+                    min_amount_out: U128(1),
+                    quote_id: None,
+                    deadline: None,
+                    max_price_impact_bps: None,
+                })],
+                None,
+                Some(true),
+            )
+            .to_amount();
+        assert_eq!(contract.get_deposit(accounts(4), accounts(2)).0, 0);
+        assert_eq!(
+            last_event(),
+            Event::Withdraw(vec![WithdrawEvent {
+                account_id: accounts(4).into(),
+                token_id: accounts(2).into(),
+                amount: U128(amount_out),
+            }])
+        );
+    }
+
+    /// Gas limit of a single function call on NEAR.
+    const MAX_CALL_GAS: Gas = 300_000_000_000_000;
+    /// Ceilings of worst-case operations, measured on the mocked blockchain with headroom.
+    /// Mocked blockchain only counts host function costs, so they sit well below the real usage
+    /// and the call limit, a change crossing them should be checked on the sandbox.
+    const MAX_GAS_STABLE_ROUTE: Gas = 7_000_000_000_000;
+    const MAX_GAS_ADD_STABLE_LIQUIDITY: Gas = 5_000_000_000_000;
+
+    fn stress_token(index: usize) -> ValidAccountId {
+        ValidAccountId::try_from(format!("token{}.near", index)).unwrap()
+    }
+
+    /// Creates stable pool of given tokens with liquidity of accounts(3).
+    fn create_stable_pool_with_liquidity(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        tokens: Vec<ValidAccountId>,
+        amount: Balance,
+    ) -> u64 {
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(env::storage_byte_cost() * 1000)
+            .build());
+        contract.extend_whitelisted_tokens(tokens.clone());
+        let pool_id =
+            contract.add_stable_swap_pool(tokens.clone(), vec![18; tokens.len()], 25, 100);
+        deposit_tokens(
+            context,
+            contract,
+            accounts(3),
+            tokens.iter().map(|token_id| (token_id.clone(), amount)).collect(),
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.add_stable_liquidity(pool_id, vec![U128(amount); tokens.len()], U128(1));
+        pool_id
+    }
+
+    #[test]
+    fn test_gas_stable_route() {
+        let (mut context, mut contract) = setup_contract();
+        let tokens: Vec<_> = (0..4).map(stress_token).collect();
+        let pool_ids: Vec<_> = (0..3)
+            .map(|i| {
+                create_stable_pool_with_liquidity(
+                    &mut context,
+                    &mut contract,
+                    vec![tokens[i].clone(), tokens[i + 1].clone()],
+                    to_yocto("100"),
+                )
+            })
+            .collect();
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(tokens[0].clone(), to_yocto("1"))]);
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(1)
+            .build());
+        let actions = (0..3)
+            .map(|i| SwapAction {
+                pool_id: pool_ids[i],
+                token_in: tokens[i].clone().into(),
+                amount_in: if i == 0 { Some(U128(to_yocto("1"))) } else { None },
+                token_out: tokens[i + 1].clone().into(),
+                min_amount_out: U128(1),
+                quote_id: None,
+                deadline: None,
+                max_price_impact_bps: None,
+            })
+            .collect();
+        let prev_gas = env::used_gas();
+        contract.swap(actions, None);
+        let gas = env::used_gas() - prev_gas;
+        assert!(gas <= MAX_GAS_STABLE_ROUTE, "3-hop stable route used {} gas", gas);
+        assert!(MAX_GAS_STABLE_ROUTE < MAX_CALL_GAS);
+    }
+
+    #[test]
+    fn test_gas_add_stable_liquidity() {
+        let (mut context, mut contract) = setup_contract();
+        let tokens: Vec<_> = (0..5).map(stress_token).collect();
+        let pool_id = create_stable_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            tokens.clone(),
+            to_yocto("100"),
+        );
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(4),
+            tokens.iter().map(|token_id| (token_id.clone(), to_yocto("10"))).collect(),
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        let prev_gas = env::used_gas();
+        contract.add_stable_liquidity(pool_id, vec![U128(to_yocto("10")); 5], U128(1));
+        let gas = env::used_gas() - prev_gas;
+        assert!(
+            gas <= MAX_GAS_ADD_STABLE_LIQUIDITY,
+            "add_stable_liquidity of 5 tokens used {} gas",
+            gas
+        );
+        assert!(MAX_GAS_ADD_STABLE_LIQUIDITY < MAX_CALL_GAS);
+    }
+
+    #[test]
+    fn test_simulate_actions() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), to_yocto("1"))]);
+        let actions = || {
+            vec![
+                Action::Swap(SwapAction {
+                    pool_id,
+                    token_in: accounts(1).into(),
+                    amount_in: Some(U128(to_yocto("1"))),
+                    token_out: accounts(2).into(),
+                    min_amount_out: U128(1),
+                    quote_id: None,
+                    deadline: None,
+                    max_price_impact_bps: None,
+                }),
+                Action::Swap(SwapAction {
+                    pool_id,
+                    token_in: accounts(2).into(),
+                    amount_in: None,
+                    token_out: accounts(1).into(),
+                    min_amount_out: U128(1),
+                    quote_id: None,
+                    deadline: None,
+                    max_price_impact_bps: None,
+                }),
+            ]
+        };
+        let first_out = contract.get_return(pool_id, accounts(1), U128(to_yocto("1")), accounts(2));
+        let pool_before = contract.get_pool(pool_id);
+        let results = contract.simulate_actions(actions(), Some(accounts(4)));
+        assert_eq!(contract.get_pool(pool_id), pool_before);
+        assert_eq!(results[0].amount_out, first_out);
+        assert_eq!(results[0].swaps[0].fee.0, to_yocto("1") * 25 / 10000);
+        assert_eq!(results[1].amount_in, first_out);
+        // Second swap sees reserves changed by the first one.
+        assert!(results[1].amount_out.0 < to_yocto("1"));
+        assert!(results[1].price_impact > 0);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(1)
+            .build());
+        let result = contract.execute_actions(actions(), None, None);
+        assert_eq!(result.to_amount(), results[1].amount_out.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "E22: not enough tokens in deposit")]
+    fn test_simulate_actions_not_enough_deposit() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let action = Action::Swap(SwapAction {
+            pool_id,
+            token_in: accounts(1).into(),
+            amount_in: Some(U128(to_yocto("1"))),
+            token_out: accounts(2).into(),
+            min_amount_out: U128(1),
+            quote_id: None,
+            deadline: None,
+            max_price_impact_bps: None,
+        });
+        contract.simulate_actions(vec![action], Some(accounts(4)));
+    }
+
+    #[test]
+    fn test_pools_by_token() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.attached_deposit(1).build());
+        contract.extend_whitelisted_tokens(vec![accounts(1), accounts(2), accounts(3)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(env::storage_byte_cost() * 300)
+            .build());
+        for tokens in vec![
+            vec![accounts(1), accounts(2)],
+            vec![accounts(2), accounts(3)],
+            vec![accounts(2), accounts(1)],
+        ] {
+            contract.add_simple_pool(tokens, 25);
+        }
+        let pool_ids = |pools: Vec<IndexedPoolInfo>| -> Vec<u64> {
+            pools.into_iter().map(|pool| pool.pool_id).collect()
+        };
+        assert_eq!(pool_ids(contract.get_pools_by_token(accounts(2), 0, 10)), vec![0, 1, 2]);
+        assert_eq!(pool_ids(contract.get_pools_by_token(accounts(2), 1, 1)), vec![1]);
+        assert_eq!(pool_ids(contract.get_pools_by_token_pair(accounts(1), accounts(2))), vec![0, 2]);
+        assert!(contract.get_pools_by_token_pair(accounts(1), accounts(3)).is_empty());
+        assert_eq!(
+            contract.get_pools_by_token(accounts(3), 0, 10)[0].pool,
+            contract.get_pool(1)
+        );
+    }
+
+    #[test]
+    fn test_index_token_pools() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        // Pools created before the index existed.
+        contract.token_pools.remove(accounts(1).as_ref());
+        contract.token_pools.remove(accounts(2).as_ref());
+        contract.indexed_pools = 0;
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(env::storage_byte_cost() * 300)
+            .build());
+        let new_pool_id = contract.add_simple_pool(vec![accounts(1), accounts(2)], 25);
+        assert!(contract.get_pools_by_token(accounts(1), 0, 10).is_empty());
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        assert_eq!(contract.index_token_pools(1), 1);
+        assert_eq!(contract.index_token_pools(10), 2);
+        assert_eq!(contract.get_number_of_indexed_pools(), 2);
+        let pools = contract.get_pools_by_token_pair(accounts(2), accounts(1));
+        assert_eq!(
+            pools.into_iter().map(|pool| pool.pool_id).collect::<Vec<_>>(),
+            vec![pool_id, new_pool_id]
+        );
+    }
+
+    #[test]
+    fn test_pool_admin_fees() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        let admin_fees = PoolAdminFees {
+            exchange_fee: 0,
+            referral_fee: 200,
+        };
+        contract.set_pool_admin_fees(pool_id, Some(admin_fees.clone()));
+        assert_eq!(contract.get_pool_admin_fees(pool_id), admin_fees);
+        let fees = contract.get_fees_by_pool_ids(vec![pool_id]);
+        assert_eq!((fees[0].exchange_fee, fees[0].referral_fee), (0, 200));
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(4),
+            vec![(accounts(1), to_yocto("1"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(1)
+            .build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        // no exchange shares minted with zero exchange fee.
+        let exchange_id = ValidAccountId::try_from(env::current_account_id()).unwrap();
+        assert_eq!(contract.get_pool_shares(pool_id, exchange_id).0, 0);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_pool_admin_fees(pool_id, None);
+        assert_eq!(
+            contract.get_pool_admin_fees(pool_id),
+            PoolAdminFees {
+                exchange_fee: 1600,
+                referral_fee: 400,
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E202: admin fee out of bounds")]
+    fn test_pool_admin_fees_out_of_bounds() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_referral_tier_fees(vec![2000]);
+        // fits the bounds by itself, but not with the referrer tier fee.
+        contract.set_pool_admin_fees(
+            pool_id,
+            Some(PoolAdminFees {
+                exchange_fee: 4000,
+                referral_fee: 500,
+            }),
+        );
+    }
+
+    #[test]
+    fn test_roles() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.attached_deposit(1).build());
+        contract.grant_permissions(accounts(5), vec![Permission::Whitelist, Permission::Pause]);
+        contract.revoke_permissions(accounts(5), vec![Permission::Pause]);
+        contract.extend_guardians(vec![accounts(4)]);
+        assert_eq!(contract.get_permissions(accounts(5)), vec![Permission::Whitelist]);
+        assert_eq!(contract.get_guardians(), vec![accounts(4).to_string()]);
+        assert_eq!(
+            contract.get_roles(0, 10),
+            vec![
+                RoleInfo {
+                    account_id: accounts(5).into(),
+                    permissions: vec![Permission::Whitelist],
+                    guardian: false,
+                },
+                RoleInfo {
+                    account_id: accounts(4).into(),
+                    permissions: PERMISSIONS.to_vec(),
+                    guardian: true,
+                },
+            ]
+        );
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+        contract.extend_whitelisted_tokens(vec![accounts(1)]);
+        assert_eq!(contract.get_whitelisted_tokens(), vec![accounts(1).to_string()]);
+
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.revoke_permissions(accounts(5), vec![Permission::Whitelist]);
+        assert!(contract.get_permissions(accounts(5)).is_empty());
+        assert_eq!(contract.get_roles(0, 10).len(), 1);
+    }
+
+    #[test]
+    fn test_guardian_flag_apart_from_permissions() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.attached_deposit(1).build());
+        // All permissions don't make a guardian.
+        contract.grant_permissions(accounts(5), PERMISSIONS.to_vec());
+        assert!(contract.get_guardians().is_empty());
+        contract.extend_guardians(vec![accounts(5)]);
+        contract.revoke_permissions(accounts(5), PERMISSIONS.to_vec());
+        assert_eq!(contract.get_guardians(), vec![accounts(5).to_string()]);
+        assert_eq!(contract.get_permissions(accounts(5)), PERMISSIONS.to_vec());
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+        assert!(contract.is_owner_or_guardians());
+
+        // Removed guardian keeps separately granted permissions.
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.grant_permissions(accounts(5), vec![Permission::Whitelist]);
+        contract.remove_guardians(vec![accounts(5)]);
+        assert!(contract.get_guardians().is_empty());
+        assert_eq!(contract.get_permissions(accounts(5)), vec![Permission::Whitelist]);
+        contract.remove_guardians(vec![accounts(4)]);
+        assert_eq!(contract.get_roles(0, 10).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_ALLOWED")]
+    fn test_roles_not_permitted() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.attached_deposit(1).build());
+        contract.grant_permissions(accounts(5), vec![Permission::Whitelist]);
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+        contract.change_state(RunningState::Paused);
+    }
+
+    #[test]
+    fn test_propose_owner() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.attached_deposit(1).build());
+        contract.propose_owner(accounts(5));
+        contract.propose_owner(accounts(4));
+        assert_eq!(contract.get_owner(), accounts(0).to_string());
+        assert_eq!(contract.get_pending_owner(), Some(accounts(4).into()));
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.accept_ownership();
+        assert_eq!(contract.get_owner(), accounts(4).to_string());
+        assert_eq!(contract.get_pending_owner(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "E405: caller is not the proposed owner")]
+    fn test_accept_ownership_not_proposed() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.attached_deposit(1).build());
+        contract.propose_owner(accounts(4));
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+        contract.accept_ownership();
+    }
+
+    #[test]
+    fn test_timelocked_actions() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.attached_deposit(1).build());
+        // without delay scheduled action is executable at once.
+        let action_id = contract.schedule_action(TimelockedAction::SetTimelockDelay {
+            delay: DAY.into(),
+        });
+        contract.execute_action(action_id);
+        assert_eq!(contract.get_timelock_delay().0, DAY);
+
+        let action = TimelockedAction::ModifyAdminFee {
+            exchange_fee: 1000,
+            referral_fee: 500,
+        };
+        let action_id = contract.schedule_action(action.clone());
+        assert_eq!(
+            contract.get_pending_actions(0, 10),
+            vec![PendingActionInfo {
+                action_id,
+                action,
+                executable_at: DAY.into(),
+            }]
+        );
+        testing_env!(context.block_timestamp(DAY).build());
+        contract.execute_action(action_id);
+        let metadata = contract.metadata();
+        assert_eq!((metadata.exchange_fee, metadata.referral_fee), (1000, 500));
+        assert!(contract.get_pending_actions(0, 10).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "E408: timelock has not passed")]
+    fn test_timelocked_action_too_early() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.attached_deposit(1).build());
+        let action_id = contract.schedule_action(TimelockedAction::SetTimelockDelay {
+            delay: DAY.into(),
+        });
+        contract.execute_action(action_id);
+        let action_id = contract.schedule_action(TimelockedAction::SetTimelockDelay {
+            delay: 0.into(),
+        });
+        testing_env!(context.block_timestamp(DAY - 1).build());
+        contract.execute_action(action_id);
+    }
+
+    #[test]
+    fn test_timelocked_fee_setters() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.attached_deposit(1).build());
+        let action_id = contract.schedule_action(TimelockedAction::SetTimelockDelay {
+            delay: DAY.into(),
+        });
+        contract.execute_action(action_id);
+        let action_ids: Vec<u64> = vec![
+            TimelockedAction::SetReferralTierFees { fees: vec![10, 20] },
+            TimelockedAction::SetFlashLoanFee { fee: Some(9) },
+        ]
+        .into_iter()
+        .map(|action| contract.schedule_action(action))
+        .collect();
+        testing_env!(context.block_timestamp(DAY).build());
+        for action_id in action_ids {
+            contract.execute_action(action_id);
+        }
+        assert_eq!(contract.get_referral_tier_fees(), vec![10, 20]);
+        assert_eq!(contract.get_flash_loan_fee(), Some(9));
+    }
+
+    #[test]
+    #[should_panic(expected = "E406: action must be scheduled with timelock")]
+    fn test_referral_tier_fees_timelocked() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.attached_deposit(1).build());
+        let action_id = contract.schedule_action(TimelockedAction::SetTimelockDelay {
+            delay: DAY.into(),
+        });
+        contract.execute_action(action_id);
+        contract.set_referral_tier_fees(vec![10]);
+    }
+
+    #[test]
+    #[should_panic(expected = "E406: action must be scheduled with timelock")]
+    fn test_timelocked_action_direct_call() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.attached_deposit(1).build());
+        let action_id = contract.schedule_action(TimelockedAction::SetTimelockDelay {
+            delay: DAY.into(),
+        });
+        contract.execute_action(action_id);
+        contract.modify_admin_fee(1000, 500);
+    }
+
+    #[test]
+    fn test_account_index() {
+        let (mut context, mut contract) = setup_contract();
+        for account_id in vec![accounts(3), accounts(4)] {
+            testing_env!(context
+                .predecessor_account_id(account_id)
+                .attached_deposit(to_yocto("1"))
+                .build());
+            contract.storage_deposit(None, None);
+        }
+        assert_eq!(contract.get_number_of_accounts(), 2);
+        testing_env!(context.attached_deposit(1).build());
+        contract.storage_unregister(None);
+        assert_eq!(contract.get_account_ids(0, 10), vec![accounts(3).to_string()]);
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        assert_eq!(contract.index_accounts(vec![accounts(3), accounts(4)]), 0);
+        assert_eq!(contract.get_number_of_accounts(), 1);
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 100)]);
+        let states = contract.get_accounts(0, 10);
+        assert_eq!(states.len(), 1);
+        assert_eq!(Some(&states[0]), contract.get_account_storage_state(accounts(3)).as_ref());
+        assert_eq!(states[0].near_amount.0, to_yocto("1"));
+        assert_eq!(states[0].deposits.get(&accounts(1).to_string()), Some(&U128(100)));
+        assert!(contract.get_account_storage_state(accounts(4)).is_none());
+    }
+
+    #[test]
+    fn test_validate_pool_params() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.attached_deposit(1).build());
+        contract.extend_whitelisted_tokens(vec![accounts(1), accounts(2)]);
+        assert!(contract
+            .validate_pool_params(
+                "SIMPLE_POOL".to_string(),
+                vec![accounts(1), accounts(2)],
+                25,
+                None,
+                None,
+                None
+            )
+            .is_empty());
+        assert_eq!(
+            contract.validate_pool_params(
+                "SIMPLE_POOL".to_string(),
+                vec![accounts(1), accounts(1), accounts(3)],
+                600,
+                None,
+                None,
+                None
+            ),
+            vec![
+                PoolParamViolation::DuplicateToken { token_id: accounts(1).into() },
+                PoolParamViolation::TokenNotWhitelisted { token_id: accounts(3).into() },
+                PoolParamViolation::FeeOutOfBounds { max_fee: 500 },
+            ]
+        );
+        assert_eq!(
+            contract.validate_pool_params(
+                "SIMPLE_POOL".to_string(),
+                vec![accounts(1)],
+                25,
+                None,
+                None,
+                None
+            ),
+            vec![PoolParamViolation::TokenCountOutOfRange { min: 2, max: Some(8) }]
+        );
+        assert_eq!(
+            contract.validate_pool_params(
+                "STABLE_SWAP".to_string(),
+                vec![accounts(1), accounts(2)],
+                25,
+                Some(vec![24, 25]),
+                None,
+                None
+            ),
+            vec![
+                PoolParamViolation::InvalidDecimals { min: 1, max: 24 },
+                PoolParamViolation::AmpOutOfBounds { min_amp: 1, max_amp: 1_000_000 },
+            ]
+        );
+        assert_eq!(
+            contract.validate_pool_params(
+                "WEIGHTED_POOL".to_string(),
+                vec![accounts(1), accounts(2)],
+                25,
+                None,
+                None,
+                Some(vec![8000, 1000])
+            ),
+            vec![PoolParamViolation::InvalidWeights { min_weight: 100, weight_divisor: 10_000 }]
+        );
+        assert_eq!(
+            contract.validate_pool_params("WEIGHTED".to_string(), vec![], 0, None, None, None),
+            vec![PoolParamViolation::UnknownPoolKind]
+        );
+    }
+
+    #[test]
+    fn test_mft_balance_of_batch() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 100)]);
+        let shares = contract.mft_balance_of(format!(":{}", pool_id), accounts(3));
+        assert_eq!(
+            contract.mft_balance_of_batch(vec![
+                (format!(":{}", pool_id), accounts(3)),
+                (accounts(1).to_string(), accounts(3)),
+                (format!(":{}", pool_id), accounts(4)),
+                (accounts(2).to_string(), accounts(4)),
+            ]),
+            vec![shares, U128(100), U128(0), U128(0)]
+        );
+    }
+
+    fn setup_launching_pool(context: &mut VMContextBuilder, contract: &mut Contract) -> u64 {
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_pool_launch_delay(10);
+        let pool_id = create_pool_with_liquidity(
+            context,
+            contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(context, contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        pool_id
+    }
+
+    #[test]
+    fn test_pool_launch_delay() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = setup_launching_pool(&mut context, &mut contract);
+        assert_eq!(contract.get_pool(pool_id).launch_delay, Some(10));
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .block_index(10)
+            .attached_deposit(1)
+            .build());
+        assert_eq!(contract.get_pool(pool_id).launch_delay, None);
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "E251: swaps of the pool are not open yet")]
+    fn test_pool_launch_delay_swap() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = setup_launching_pool(&mut context, &mut contract);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .block_index(9)
+            .attached_deposit(1)
+            .build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+    }
+
+    #[test]
+    fn test_get_fees_by_pool_ids() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        let tiers = vec![ReferralFeeTier {
+            min_amount_in: U128(to_yocto("1")),
+            referral_fee: 1000,
+        }];
+        contract.set_referral_fee_tiers(accounts(2), tiers.clone());
+        contract.set_pool_fee_side(pool_id, FeeSide::Output);
+        assert_eq!(
+            contract.get_fees_by_pool_ids(vec![pool_id]),
+            vec![views::PoolFees {
+                total_fee: 25,
+                exchange_fee: 1600,
+                referral_fee: 400,
+                referral_fee_tiers: vec![(accounts(2).into(), tiers)],
+                fee_side: FeeSide::Output,
+                compounding: true,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_health_check() {
+        let (mut context, mut contract) = setup_contract();
+        let health = contract.health_check();
+        assert_eq!(health.state, RunningState::Running);
+        assert_eq!(health.quarantined_tokens, 0);
+        assert_eq!(health.storage_usage.0, env::storage_usage());
+        assert!(health.storage_headroom.0 > 0);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .block_index(7)
+            .build());
+        contract.change_state(RunningState::Paused);
+        let health = contract.health_check();
+        assert_eq!(health.state, RunningState::Paused);
+        assert_eq!(health.block_height.0, 7);
+    }
+
+    /// Returns the last standard event logged.
+    fn last_event() -> Event {
+        let log = get_logs()
+            .into_iter()
+            .rev()
+            .find(|log| log.starts_with("EVENT_JSON:"))
+            .unwrap();
+        let log: EventLog = near_sdk::serde_json::from_str(&log["EVENT_JSON:".len()..]).unwrap();
+        assert_eq!(log.standard, EVENT_STANDARD);
+        log.event
+    }
+
+    fn last_liquidity_event() -> LiquidityEvent {
+        match last_event() {
+            Event::AddLiquidity(mut events) | Event::RemoveLiquidity(mut events) => {
+                events.pop().unwrap()
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn test_standard_events() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(env::storage_byte_cost() * 300)
+            .build());
+        let pool_id = contract.add_simple_pool(vec![accounts(1), accounts(2)], 25);
+        assert!(matches!(
+            last_event(),
+            Event::PoolCreated(events) if events[0].pool_id == pool_id && events[0].total_fee == 25
+        ));
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(1)
+            .build());
+        let amount_out = swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        assert_eq!(
+            last_event(),
+            Event::Swap(vec![SwapEvent {
+                account_id: accounts(4).into(),
+                pool_id,
+                token_in: accounts(1).into(),
+                amount_in: U128(to_yocto("1")),
+                token_out: accounts(2).into(),
+                amount_out: U128(amount_out),
+            }])
+        );
+        contract.withdraw(accounts(2), U128(amount_out), None);
+        assert_eq!(
+            last_event(),
+            Event::Withdraw(vec![WithdrawEvent {
+                account_id: accounts(4).into(),
+                token_id: accounts(2).into(),
+                amount: U128(amount_out),
+            }])
+        );
+    }
+
+    #[test]
+    fn test_liquidity_events() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        assert!(matches!(last_event(), Event::AddLiquidity(_)));
+        let event = last_liquidity_event();
+        assert_eq!(event.amounts, vec![U128(to_yocto("5")), U128(to_yocto("10"))]);
+        assert_eq!(event.total_shares, event.shares);
+        assert_eq!(event.value, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_reference_token(Some(accounts(2)));
+        contract.set_reference_pool(accounts(1), Some(pool_id));
+        assert_eq!(contract.get_reference_pool(accounts(1)), Some(pool_id));
+
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("1")), (accounts(2), to_yocto("2"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("0.0007"))
+            .build());
+        contract.add_liquidity(pool_id, vec![U128(to_yocto("1")), U128(to_yocto("2"))], None, None);
+        let event = last_liquidity_event();
+        assert_eq!(event.account_id, accounts(3).to_string());
+        // 1 token at spot price of 2 plus 2 reference tokens.
+        assert_eq!(event.value, Some(U128(to_yocto("4"))));
+
+        let shares = contract.get_pool_shares(pool_id, accounts(3)).0;
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build());
+        contract.remove_liquidity(pool_id, U128(shares), vec![U128(1), U128(1)], None, None);
+        assert!(matches!(last_event(), Event::RemoveLiquidity(_)));
+        let event = last_liquidity_event();
+        assert_eq!(event.shares.0, shares);
+        assert_eq!(event.total_shares.0, 0);
+        assert_eq!(event.value, None);
+    }
+
+    #[test]
+    fn test_price_hints() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_reference_token(Some(accounts(2)));
+        contract.extend_price_oracles(vec![accounts(5)]);
+        contract.set_price_hint_max_age(accounts(1), Some(100.into()));
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+        // 1 token is worth 3 reference tokens.
+        contract.push_price_hints(vec![(
+            accounts(1),
+            PriceHint {
+                multiplier: U128(30),
+                decimals: 1,
+            },
+        )]);
+        assert_eq!(
+            contract.internal_value_in_reference(
+                &[accounts(1).into(), accounts(2).into()],
+                &[to_yocto("1"), to_yocto("2")]
+            ),
+            Some(to_yocto("5"))
+        );
+
+        testing_env!(context.block_timestamp(101).build());
+        let hints = contract.get_price_hints();
+        assert_eq!(hints[0].age.0, 101);
+        assert!(hints[0].stale);
+        // Stale hint falls back to the reference pool, if there is one.
+        assert_eq!(
+            contract.internal_value_in_reference(&[accounts(1).into()], &[to_yocto("1")]),
+            None
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_reference_pool(accounts(1), Some(pool_id));
+        assert_eq!(
+            contract.internal_value_in_reference(&[accounts(1).into()], &[to_yocto("1")]),
+            Some(to_yocto("2"))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E260: invalid reference pool")]
+    fn test_reference_pool_without_reference_token() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(1)
+            .build());
+        contract.set_reference_pool(accounts(1), Some(pool_id));
+    }
+
+    #[test]
+    #[should_panic(expected = "E85: pool exchange fee is compounding")]
+    fn test_skim_compounding_pool() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(5))
+            .attached_deposit(1)
+            .build());
+        contract.skim_exchange_fee(pool_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "E14: LP already registered")]
+    fn test_lpt_transfer() {
+        // account(0) -- swap contract
+        // account(1) -- token0 contract
+        // account(2) -- token1 contract
+        // account(3) -- user account
+        // account(4) -- another user account
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![
+                (accounts(1), to_yocto("100")),
+                (accounts(2), to_yocto("100")),
+            ],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("1"))
+            .build());
+        let id = contract.add_simple_pool(vec![accounts(1), accounts(2)], 25);
+        testing_env!(context.attached_deposit(to_yocto("0.0007")).build());
+        contract.add_liquidity(id, vec![U128(to_yocto("50")), U128(to_yocto("10"))], None, None);
+        assert_eq!(
+            contract.mft_balance_of(":0".to_string(), accounts(3)).0,
+            to_yocto("1")
+        );
+        assert_eq!(contract.mft_total_supply(":0".to_string()).0, to_yocto("1"));
+        testing_env!(context.attached_deposit(1).build());
+        contract.add_liquidity(id, vec![U128(to_yocto("50")), U128(to_yocto("50"))], None, None);
+        assert_eq!(
+            contract.mft_balance_of(":0".to_string(), accounts(3)).0,
+            to_yocto("2")
+        );
+        assert_eq!(contract.mft_total_supply(":0".to_string()).0, to_yocto("2"));
+
+        // register another user
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(to_yocto("0.00071"))
+            .build());
+        contract.mft_register(":0".to_string(), accounts(4));
+        // make transfer to him
+        testing_env!(context
             .predecessor_account_id(accounts(3))
             .attached_deposit(1)
             .build());
@@ -966,7 +6962,7 @@ mod tests {
             .predecessor_account_id(accounts(3))
             .attached_deposit(1)
             .build());
-        contract.remove_liquidity(id, U128(to_yocto("0.6")), vec![U128(1), U128(1)]);
+        contract.remove_liquidity(id, U128(to_yocto("0.6")), vec![U128(1), U128(1)], None, None);
         assert_eq!(
             contract.mft_balance_of(":0".to_string(), accounts(3)).0,
             to_yocto("0.4")
@@ -987,7 +6983,7 @@ mod tests {
             .predecessor_account_id(accounts(4))
             .attached_deposit(1)
             .build());
-        contract.remove_liquidity(id, U128(to_yocto("1")), vec![U128(1), U128(1)]);
+        contract.remove_liquidity(id, U128(to_yocto("1")), vec![U128(1), U128(1)], None, None);
         assert_eq!(
             contract.mft_balance_of(":0".to_string(), accounts(4)).0,
             to_yocto("0")
@@ -1030,13 +7026,13 @@ mod tests {
             .build());
         let id = contract.add_simple_pool(vec![accounts(1), accounts(2)], 25);
         testing_env!(context.attached_deposit(to_yocto("0.0007")).build());
-        contract.add_liquidity(id, vec![U128(to_yocto("50")), U128(to_yocto("10"))], None);
+        contract.add_liquidity(id, vec![U128(to_yocto("50")), U128(to_yocto("10"))], None, None);
         assert_eq!(
             contract.mft_balance_of(":0".to_string(), accounts(3)).0,
             to_yocto("1")
         );
         testing_env!(context.attached_deposit(1).build());
-        contract.add_liquidity(id, vec![U128(to_yocto("50")), U128(to_yocto("50"))], None);
+        contract.add_liquidity(id, vec![U128(to_yocto("50")), U128(to_yocto("50"))], None, None);
         assert_eq!(
             contract.mft_balance_of(":0".to_string(), accounts(3)).0,
             to_yocto("2")
@@ -1049,4 +7045,184 @@ mod tests {
             .build());
         contract.mft_transfer(":0".to_string(), accounts(3), U128(to_yocto("1")), None);
     }
+
+    fn sign_hash(secret: &[u8; 32], hash: &[u8]) -> near_sdk::json_types::Base64VecU8 {
+        use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signer};
+        let secret = SecretKey::from_bytes(secret).unwrap();
+        let public = PublicKey::from(&secret);
+        let keypair = Keypair { secret, public };
+        keypair.sign(hash).to_bytes().to_vec().into()
+    }
+
+    fn sign_permit(secret: &[u8; 32], permit: &permit::Permit) -> near_sdk::json_types::Base64VecU8 {
+        sign_hash(secret, &permit.hash())
+    }
+
+    fn permit_public_key(secret: &[u8; 32]) -> near_sdk::json_types::Base58PublicKey {
+        use ed25519_dalek::{PublicKey, SecretKey};
+        let public = PublicKey::from(&SecretKey::from_bytes(secret).unwrap());
+        let mut data = vec![0u8];
+        data.extend_from_slice(public.as_bytes());
+        near_sdk::json_types::Base58PublicKey(data)
+    }
+
+    fn setup_permit(context: &mut VMContextBuilder, contract: &mut Contract) -> permit::Permit {
+        deposit_tokens(
+            context,
+            contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("100"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(to_yocto("1"))
+            .build());
+        contract.storage_deposit(None, None);
+        testing_env!(context.attached_deposit(1).build());
+        contract.register_tokens(vec![accounts(1)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.set_permit_key(permit_public_key(&[7u8; 32]));
+        permit::Permit {
+            owner_id: accounts(3).into(),
+            receiver_id: accounts(4).into(),
+            token_id: accounts(1).into(),
+            amount: U128(to_yocto("10")),
+            nonce: 0,
+            deadline: 1_000.into(),
+        }
+    }
+
+    #[test]
+    fn test_permit_transfer() {
+        let (mut context, mut contract) = setup_contract();
+        let permit = setup_permit(&mut context, &mut contract);
+        let signature = sign_permit(&[7u8; 32], &permit);
+        // anyone can submit the permit.
+        testing_env!(context
+            .predecessor_account_id(accounts(5))
+            .attached_deposit(1)
+            .build());
+        contract.execute_permit(permit, signature);
+        assert_eq!(
+            contract.get_deposit(accounts(3), accounts(1)).0,
+            to_yocto("90")
+        );
+        assert_eq!(
+            contract.get_deposit(accounts(4), accounts(1)).0,
+            to_yocto("10")
+        );
+        assert_eq!(contract.get_permit_key(accounts(3)).unwrap().next_nonce, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "E113: wrong permit nonce")]
+    fn test_permit_replay() {
+        let (mut context, mut contract) = setup_contract();
+        let permit = setup_permit(&mut context, &mut contract);
+        let signature = sign_permit(&[7u8; 32], &permit);
+        testing_env!(context
+            .predecessor_account_id(accounts(5))
+            .attached_deposit(1)
+            .build());
+        contract.execute_permit(permit.clone(), signature.clone());
+        contract.execute_permit(permit, signature);
+    }
+
+    #[test]
+    #[should_panic(expected = "E114: invalid permit signature")]
+    fn test_permit_wrong_signer() {
+        let (mut context, mut contract) = setup_contract();
+        let permit = setup_permit(&mut context, &mut contract);
+        let signature = sign_permit(&[8u8; 32], &permit);
+        testing_env!(context
+            .predecessor_account_id(accounts(5))
+            .attached_deposit(1)
+            .build());
+        contract.execute_permit(permit, signature);
+    }
+
+    #[test]
+    #[should_panic(expected = "E112: permit expired")]
+    fn test_permit_expired() {
+        let (mut context, mut contract) = setup_contract();
+        let permit = setup_permit(&mut context, &mut contract);
+        let signature = sign_permit(&[7u8; 32], &permit);
+        testing_env!(context
+            .predecessor_account_id(accounts(5))
+            .block_timestamp(1_001)
+            .attached_deposit(1)
+            .build());
+        contract.execute_permit(permit, signature);
+    }
+
+    fn signed_storage_permit(
+        secret: &[u8; 32],
+        owner_id: ValidAccountId,
+        operator_id: ValidAccountId,
+    ) -> permit::SignedStoragePermit {
+        let permit = permit::StoragePermit {
+            owner_id: owner_id.into(),
+            operator_id: operator_id.into(),
+            nonce: 0,
+            deadline: 1_000.into(),
+        };
+        permit::SignedStoragePermit {
+            signature: sign_hash(secret, &permit.hash()),
+            permit,
+        }
+    }
+
+    #[test]
+    fn test_storage_withdraw_batch() {
+        let (mut context, mut contract) = setup_contract();
+        for (account_id, secret) in vec![(accounts(3), [7u8; 32]), (accounts(4), [8u8; 32])] {
+            testing_env!(context
+                .predecessor_account_id(account_id.clone())
+                .attached_deposit(to_yocto("1"))
+                .build());
+            contract.storage_deposit(None, None);
+            testing_env!(context.attached_deposit(to_yocto("0.01")).build());
+            contract.set_permit_key(permit_public_key(&secret));
+        }
+        let available = contract.storage_balance_of(accounts(3)).unwrap().available.0;
+        assert!(available > 0);
+        testing_env!(context
+            .predecessor_account_id(accounts(5))
+            .attached_deposit(1)
+            .build());
+        let amounts = contract.storage_withdraw_batch(vec![
+            signed_storage_permit(&[7u8; 32], accounts(3), accounts(5)),
+            signed_storage_permit(&[8u8; 32], accounts(4), accounts(5)),
+        ]);
+        assert_eq!(amounts, vec![U128(available), U128(available)]);
+        for account_id in vec![accounts(3), accounts(4)] {
+            assert_eq!(contract.storage_balance_of(account_id.clone()).unwrap().available.0, 0);
+            assert_eq!(contract.get_permit_key(account_id).unwrap().next_nonce, 1);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "E116: permit issued to another operator")]
+    fn test_storage_withdraw_batch_wrong_operator() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("1"))
+            .build());
+        contract.storage_deposit(None, None);
+        testing_env!(context.attached_deposit(to_yocto("0.01")).build());
+        contract.set_permit_key(permit_public_key(&[7u8; 32]));
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(1)
+            .build());
+        contract.storage_withdraw_batch(vec![signed_storage_permit(
+            &[7u8; 32],
+            accounts(3),
+            accounts(5),
+        )]);
+    }
 }
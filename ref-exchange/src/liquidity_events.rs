@@ -0,0 +1,166 @@
+//! Events of liquidity changes with their value in a reference token.
+//!
+//! Every add and remove of liquidity logs a standard event with token amounts, shares minted or
+//! burnt and resulting total shares of the pool. Owner can set a reference token and, for other
+//! tokens, a simple pool pairing them with it. If all tokens of the event have a fresh price
+//! hint or such a route, the event carries value of the amounts at hinted prices or spot prices
+//! of the route pools, so TVL change feeds don't need to join prices off-chain. The value is
+//! approximate, spot prices don't account for slippage.
+
+use crate::events::Event;
+use crate::utils::U256;
+use crate::*;
+
+pub enum LiquidityChange {
+    Add,
+    Remove,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug, PartialEq))]
+pub struct LiquidityEvent {
+    pub account_id: AccountId,
+    pub pool_id: u64,
+    pub tokens: Vec<AccountId>,
+    /// Amounts added to or removed from the pool, in pool tokens sequence.
+    pub amounts: Vec<U128>,
+    /// Shares minted or burnt.
+    pub shares: U128,
+    /// Total shares of the pool after the change.
+    pub total_shares: U128,
+    /// Value of the amounts in the reference token, None if some token has no price.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<U128>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Sets token liquidity events are valued in, None disables valuation.
+    /// Only can be called by owner.
+    #[payable]
+    pub fn set_reference_token(&mut self, token_id: Option<ValidAccountId>) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.reference_token = token_id.map(|token_id| token_id.into());
+        match &self.reference_token {
+            Some(token_id) => log!("Reference token set to {}", token_id),
+            None => log!("Reference token removed"),
+        }
+    }
+
+    /// Sets simple pool pairing given token with the reference token, None removes the route.
+    /// Storage of routes is covered by the exchange. Only can be called by owner.
+    #[payable]
+    pub fn set_reference_pool(&mut self, token_id: ValidAccountId, pool_id: Option<u64>) {
+        assert_one_yocto();
+        self.assert_owner();
+        match pool_id {
+            Some(pool_id) => {
+                let reference_token = self
+                    .reference_token
+                    .clone()
+                    .expect(ERR260_INVALID_REFERENCE_POOL);
+                let pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+                let valid = match &pool {
+                    Pool::SimplePool(pool) => {
+                        pool.token_account_ids.contains(token_id.as_ref())
+                            && pool.token_account_ids.contains(&reference_token)
+                            && token_id.as_ref() != &reference_token
+                    }
+                    Pool::StableSwapPool(_) | Pool::WeightedPool(_) => false,
+                };
+                assert!(valid, "{}", ERR260_INVALID_REFERENCE_POOL);
+                self.reference_pools.insert(token_id.as_ref(), &pool_id);
+                log!("Reference pool of {} set to {}", token_id.as_ref(), pool_id);
+            }
+            None => {
+                self.reference_pools.remove(token_id.as_ref());
+                log!("Reference pool of {} removed", token_id.as_ref());
+            }
+        }
+    }
+
+    pub fn get_reference_token(&self) -> Option<AccountId> {
+        self.reference_token.clone()
+    }
+
+    pub fn get_reference_pool(&self, token_id: ValidAccountId) -> Option<u64> {
+        self.reference_pools.get(token_id.as_ref())
+    }
+}
+
+impl Contract {
+    /// Returns value of given amounts in the reference token at hinted prices or spot prices of
+    /// route pools.
+    pub(crate) fn internal_value_in_reference(
+        &self,
+        tokens: &[AccountId],
+        amounts: &[Balance],
+    ) -> Option<Balance> {
+        let reference_token = self.reference_token.as_ref()?;
+        let mut value = U256::zero();
+        for (token_id, amount) in tokens.iter().zip(amounts.iter()) {
+            if *amount == 0 {
+                continue;
+            }
+            if token_id == reference_token {
+                value += U256::from(*amount);
+                continue;
+            }
+            if let Some(hint_value) = self.internal_value_by_price_hint(token_id, *amount) {
+                value += U256::from(hint_value);
+                continue;
+            }
+            let pool = self.pools.get(self.reference_pools.get(token_id)?)?;
+            // Pool tokens may have changed since the route was set, they are checked again.
+            let pool = match pool {
+                Pool::SimplePool(pool) => pool,
+                Pool::StableSwapPool(_) | Pool::WeightedPool(_) => return None,
+            };
+            let token_index = pool.token_account_ids.iter().position(|id| id == token_id)?;
+            let reference_index = pool
+                .token_account_ids
+                .iter()
+                .position(|id| id == reference_token)?;
+            if pool.amounts[token_index] == 0 {
+                return None;
+            }
+            value += U256::from(*amount) * U256::from(pool.amounts[reference_index])
+                / U256::from(pool.amounts[token_index]);
+        }
+        if value > U256::from(Balance::MAX) {
+            return None;
+        }
+        Some(value.as_u128())
+    }
+
+    /// Logs liquidity event of the pool, must be called after the pool is saved.
+    pub(crate) fn internal_log_liquidity_event(
+        &self,
+        change: LiquidityChange,
+        account_id: &AccountId,
+        pool_id: u64,
+        pool: &Pool,
+        amounts: &[Balance],
+        shares: Balance,
+    ) {
+        let tokens = pool.tokens();
+        let event = LiquidityEvent {
+            account_id: account_id.clone(),
+            pool_id,
+            tokens: tokens.to_vec(),
+            amounts: amounts.iter().map(|amount| U128(*amount)).collect(),
+            shares: U128(shares),
+            total_shares: U128(pool.share_total_balance()),
+            value: self
+                .internal_value_in_reference(tokens, amounts)
+                .map(U128),
+        };
+        match change {
+            LiquidityChange::Add => Event::AddLiquidity(vec![event]),
+            LiquidityChange::Remove => Event::RemoveLiquidity(vec![event]),
+        }
+        .emit();
+    }
+}
@@ -0,0 +1,90 @@
+//! Lost and found of failed withdrawals.
+//!
+//! A failed withdrawal that can't be kept as a claim of the account, because the receiver isn't
+//! registered on the exchange, e.g. receiver of an instant swap, or has no storage for it, is
+//! kept as lost tokens of the receiver, separate from any deposits. Putting it anywhere else
+//! could panic in the withdraw callback and lose the tokens. The receiver registers on the
+//! exchange and claims lost tokens to its deposits with `claim_lost_tokens`. Support staff look
+//! lost tokens up by account with `get_lost_tokens` or go through all of them with
+//! `get_lost_tokens_page`. Lost tokens are covered by the exchange, there is one amount per
+//! receiver and token.
+
+use std::collections::HashMap;
+
+use crate::pagination::Page;
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// Deposits lost tokens of the token to the caller's account, which must be registered and
+    /// have storage for the token. Returns claimed amount.
+    #[payable]
+    pub fn claim_lost_tokens(&mut self, token_id: ValidAccountId) -> U128 {
+        assert_one_yocto();
+        self.assert_contract_running();
+        let account_id = env::predecessor_account_id();
+        let token_id: AccountId = token_id.into();
+        let mut lost = self.lost_tokens.get(&account_id).unwrap_or_default();
+        let amount = lost.remove(&token_id).expect(ERR341_NO_LOST_TOKENS);
+        if lost.is_empty() {
+            self.lost_tokens.remove(&account_id);
+        } else {
+            self.lost_tokens.insert(&account_id, &lost);
+        }
+        let mut account = self.internal_unwrap_account(&account_id);
+        account.deposit(&token_id, amount);
+        self.internal_save_account(&account_id, account);
+        log!("Account {} claimed lost {} {}", account_id, amount, token_id);
+        U128(amount)
+    }
+
+    /// Returns lost tokens of the account by token.
+    pub fn get_lost_tokens(&self, account_id: ValidAccountId) -> HashMap<AccountId, U128> {
+        self.lost_tokens
+            .get(account_id.as_ref())
+            .map(internal_lost_tokens_view)
+            .unwrap_or_default()
+    }
+
+    /// Returns page of `limit` accounts with lost tokens after the cursor.
+    pub fn get_lost_tokens_page(
+        &self,
+        cursor: Option<String>,
+        limit: u64,
+    ) -> Page<(AccountId, HashMap<AccountId, U128>)> {
+        let keys = self.lost_tokens.keys_as_vector();
+        let values = self.lost_tokens.values_as_vector();
+        Page::from_index(cursor, limit, keys.len(), |index| {
+            (
+                keys.get(index).unwrap(),
+                internal_lost_tokens_view(values.get(index).unwrap()),
+            )
+        })
+    }
+}
+
+fn internal_lost_tokens_view(lost: HashMap<AccountId, Balance>) -> HashMap<AccountId, U128> {
+    lost.into_iter()
+        .map(|(token_id, amount)| (token_id, U128(amount)))
+        .collect()
+}
+
+impl Contract {
+    /// Adds amount of the token to lost tokens of the account, never panics.
+    pub(crate) fn internal_add_lost_tokens(
+        &mut self,
+        account_id: &AccountId,
+        token_id: &AccountId,
+        amount: Balance,
+    ) {
+        let mut lost = self.lost_tokens.get(account_id).unwrap_or_default();
+        *lost.entry(token_id.clone()).or_insert(0) += amount;
+        self.lost_tokens.insert(account_id, &lost);
+        log!(
+            "{} {} kept as lost tokens of {}, see claim_lost_tokens",
+            amount,
+            token_id,
+            account_id
+        );
+    }
+}
@@ -0,0 +1,53 @@
+//! Minimum withdrawal amounts of tokens.
+//!
+//! Owner can set per token the smallest amount `withdraw` accepts, so a withdrawal can't cost
+//! the exchange more in receipts and callbacks than the value it transfers. Amounts below the
+//! threshold stay in deposits, they can still be swapped or added to pools.
+
+use std::collections::HashMap;
+
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// Sets minimum withdrawal amount of the token, None removes it.
+    /// Only can be called by owner.
+    #[payable]
+    pub fn set_min_withdrawal(&mut self, token_id: ValidAccountId, amount: Option<U128>) {
+        assert_one_yocto();
+        self.assert_owner();
+        match amount {
+            Some(amount) => {
+                self.min_withdrawals.insert(token_id.as_ref(), &amount.0);
+                log!("Min withdrawal of {} set to {}", token_id.as_ref(), amount.0);
+            }
+            None => {
+                self.min_withdrawals.remove(token_id.as_ref());
+                log!("Min withdrawal of {} removed", token_id.as_ref());
+            }
+        }
+    }
+
+    /// Returns minimum withdrawal amounts of tokens that have one.
+    pub fn get_min_withdrawals(&self) -> HashMap<AccountId, U128> {
+        self.min_withdrawals
+            .iter()
+            .map(|(token_id, amount)| (token_id, U128(amount)))
+            .collect()
+    }
+}
+
+impl Contract {
+    /// Returns smallest amount of the token that can be withdrawn, 0 if there is no minimum.
+    pub(crate) fn internal_min_withdrawal(&self, token_id: &AccountId) -> Balance {
+        self.min_withdrawals.get(token_id).unwrap_or(0)
+    }
+
+    pub(crate) fn assert_min_withdrawal(&self, token_id: &AccountId, amount: Balance) {
+        assert!(
+            amount >= self.internal_min_withdrawal(token_id),
+            "{}",
+            ERR330_BELOW_MIN_WITHDRAWAL
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use near_contract_standards::fungible_token::metadata::FungibleTokenMetadata;
 use near_sdk::json_types::{ValidAccountId, U128};
 use near_sdk::{ext_contract, near_bindgen, Balance, PromiseOrValue};
@@ -58,7 +60,7 @@ fn parse_token_id(token_id: String) -> TokenOrPool {
 
 #[near_bindgen]
 impl Contract {
-    fn internal_mft_transfer(
+    pub(crate) fn internal_mft_transfer(
         &mut self,
         token_id: String,
         sender_id: &AccountId,
@@ -68,10 +70,14 @@ impl Contract {
     ) {
         // [AUDIT_07]
         assert_ne!(sender_id, receiver_id, "{}", ERR33_TRANSFER_TO_SELF);
+        self.assert_no_flash_loan(sender_id);
+        self.assert_transfer_not_delayed(sender_id);
         match parse_token_id(token_id) {
             TokenOrPool::Pool(pool_id) => {
                 let mut pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
                 pool.share_transfer(sender_id, receiver_id, amount);
+                self.assert_loan_collateral(sender_id, pool_id, &pool);
+                self.assert_shares_unlocked(sender_id, pool_id, &pool);
                 self.pools.replace(pool_id, &pool);
                 log!(
                     "Transfer shares {} pool: {} from {} to {}",
@@ -119,6 +125,26 @@ impl Contract {
             .into()
     }
 
+    /// Returns balances of given `(token_id, account_id)` pairs, in the same order.
+    /// Token ids are in the same format as in `mft_balance_of`, each pool is read once.
+    pub fn mft_balance_of_batch(&self, requests: Vec<(String, ValidAccountId)>) -> Vec<U128> {
+        let mut pools = HashMap::new();
+        requests
+            .into_iter()
+            .map(|(token_id, account_id)| match parse_token_id(token_id) {
+                TokenOrPool::Pool(pool_id) => {
+                    let pool = pools
+                        .entry(pool_id)
+                        .or_insert_with(|| self.pools.get(pool_id).expect("ERR_NO_POOL"));
+                    U128(pool.share_balances(account_id.as_ref()))
+                }
+                TokenOrPool::Token(token_id) => {
+                    U128(self.internal_get_deposit(account_id.as_ref(), &token_id))
+                }
+            })
+            .collect()
+    }
+
     /// Returns the total supply of the given token, if the token is one of the pools.
     /// If token references external token - fails with unimplemented.
     pub fn mft_total_supply(&self, token_id: String) -> U128 {
@@ -233,7 +259,10 @@ impl Contract {
         };
         if unused_amount > 0 {
             let receiver_balance = self.internal_mft_balance(token_id.clone(), &receiver_id);
-            if receiver_balance > 0 {
+            if self.flash_loans.contains_key(receiver_id) || self.is_withdraw_delayed(receiver_id) {
+                // Refund could move out borrowed or delayed tokens, it stays with the receiver.
+                log!("Refund of {} skipped, {} can't transfer", token_id, receiver_id);
+            } else if receiver_balance > 0 {
                 let refund_amount = std::cmp::min(receiver_balance, unused_amount);
                 
                 let refund_to = if self.accounts.get(&sender_id).is_some() {
@@ -249,17 +278,42 @@ impl Contract {
         U128(unused_amount)
     }
 
+    /// Returns metadata of LP token of the pool. Symbol is derived from cached symbols of the
+    /// pool tokens and icon is the pool logo or the first cached icon of its tokens.
     pub fn mft_metadata(&self, token_id: String) -> FungibleTokenMetadata {
         match parse_token_id(token_id) {
             TokenOrPool::Pool(pool_id) => {
                 let pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
                 let decimals = pool.get_share_decimal();
+                let token_metadata: Vec<_> = pool
+                    .tokens()
+                    .iter()
+                    .map(|token_id| self.token_metadata.get(token_id))
+                    .collect();
+                let symbol = token_metadata
+                    .iter()
+                    .map(|metadata| metadata.as_ref().map(|metadata| metadata.symbol.clone()))
+                    .collect::<Option<Vec<_>>>()
+                    .map(|symbols| format!("{}-LP", symbols.join("-")))
+                    .unwrap_or_else(|| format!("REF-POOL-{}", pool_id));
+                let pool_metadata = self.pool_metadata.get(&pool_id);
+                let icon = pool_metadata
+                    .as_ref()
+                    .and_then(|metadata| metadata.logo.clone())
+                    .or_else(|| {
+                        token_metadata
+                            .into_iter()
+                            .flatten()
+                            .find_map(|metadata| metadata.icon)
+                    });
                 FungibleTokenMetadata {
                     // [AUDIT_08]
                     spec: "mft-1.0.0".to_string(),
-                    name: format!("ref-pool-{}", pool_id),
-                    symbol: format!("REF-POOL-{}", pool_id),
-                    icon: None,
+                    name: pool_metadata
+                        .map(|metadata| metadata.name)
+                        .unwrap_or_else(|| format!("ref-pool-{}", pool_id)),
+                    symbol,
+                    icon,
                     reference: None,
                     reference_hash: None,
                     decimals,
@@ -1,16 +1,105 @@
 //! Implement all the relevant logic for owner of this contract.
 
-use near_sdk::json_types::WrappedTimestamp;
+use near_sdk::json_types::{WrappedTimestamp, U64};
 
 use crate::*;
-use crate::utils::FEE_DIVISOR;
+use crate::legacy::ContractV2;
+use crate::roles::GUARDIAN_FLAG;
+use crate::utils::{FEE_DIVISOR, MAX_REFERRAL_FEE_TIERS, MAX_ROUTE_INTERMEDIARIES};
+
+/// Sensitive owner operation executed through the timelock queue.
+/// While the delay is non-zero the operations can't be called directly.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub enum TimelockedAction {
+    ModifyAdminFee {
+        exchange_fee: u32,
+        referral_fee: u32,
+    },
+    SetPoolAdminFees {
+        pool_id: u64,
+        admin_fees: Option<PoolAdminFees>,
+    },
+    StableSwapRampAmp {
+        pool_id: u64,
+        future_amp_factor: u64,
+        future_amp_time: WrappedTimestamp,
+    },
+    RemoveExchangeFeeLiquidity {
+        pool_id: u64,
+        shares: U128,
+        min_amounts: Vec<U128>,
+    },
+    WithdrawOwnerFees {
+        pool_id: u64,
+    },
+    /// Changes delay of actions scheduled afterwards.
+    SetTimelockDelay {
+        delay: WrappedTimestamp,
+    },
+    SetReferralFeeTiers {
+        token_id: ValidAccountId,
+        tiers: Vec<ReferralFeeTier>,
+    },
+    SetReferralTierFees {
+        fees: Vec<u32>,
+    },
+    SetFlashLoanFee {
+        fee: Option<u32>,
+    },
+    SetPoolFeeSide {
+        pool_id: u64,
+        fee_side: FeeSide,
+    },
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct PendingAction {
+    pub action: TimelockedAction,
+    pub executable_at: Timestamp,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug, PartialEq))]
+pub struct PendingActionInfo {
+    pub action_id: u64,
+    pub action: TimelockedAction,
+    pub executable_at: U64,
+}
 
 #[near_bindgen]
 impl Contract {
-    /// Change owner. Only can be called by owner.
-    pub fn set_owner(&mut self, owner_id: ValidAccountId) {
+    /// Proposes new owner, ownership changes once it calls `accept_ownership`.
+    /// Replaces previous proposal. Only can be called by owner.
+    #[payable]
+    pub fn propose_owner(&mut self, new_owner: ValidAccountId) {
+        assert_one_yocto();
         self.assert_owner();
-        self.owner_id = owner_id.as_ref().clone();
+        log!("Owner {} proposed", new_owner.as_ref());
+        self.pending_owner_id = Some(new_owner.into());
+    }
+
+    /// Accepts ownership proposed to the caller.
+    #[payable]
+    pub fn accept_ownership(&mut self) {
+        assert_one_yocto();
+        let sender_id = env::predecessor_account_id();
+        assert_eq!(
+            self.pending_owner_id.as_ref(),
+            Some(&sender_id),
+            "{}",
+            ERR405_NOT_PENDING_OWNER
+        );
+        log!("Owner changed from {} to {}", self.owner_id, sender_id);
+        self.owner_id = sender_id;
+        self.pending_owner_id = None;
+    }
+
+    /// Returns owner proposed by `propose_owner` and not accepted yet.
+    pub fn get_pending_owner(&self) -> Option<AccountId> {
+        self.pending_owner_id.clone()
     }
 
     /// Get the owner of this account.
@@ -18,28 +107,29 @@ impl Contract {
         self.owner_id.clone()
     }
 
-    /// Extend guardians. Only can be called by owner.
+    /// Extend guardians, giving them all permissions. Only can be called by owner.
     #[payable]
     pub fn extend_guardians(&mut self, guardians: Vec<ValidAccountId>) {
         self.assert_owner();
         for guardian in guardians {
-            self.guardians.insert(guardian.as_ref());
+            self.internal_set_guardian(guardian.as_ref(), true);
         }
     }
 
-    /// Remove guardians. Only can be called by owner.
+    /// Remove guardians, they keep only permissions granted separately.
+    /// Only can be called by owner.
     pub fn remove_guardians(&mut self, guardians: Vec<ValidAccountId>) {
         self.assert_owner();
         for guardian in guardians {
-            self.guardians.remove(guardian.as_ref());
+            self.internal_set_guardian(guardian.as_ref(), false);
         }
     }
 
-    /// Change state of contract, Only can be called by owner or guardians.
+    /// Change state of contract, Only can be called by owner or accounts permitted to pause.
     #[payable]
     pub fn change_state(&mut self, state: RunningState) {
         assert_one_yocto();
-        assert!(self.is_owner_or_guardians(), "ERR_NOT_ALLOWED");
+        assert!(self.is_owner_or_permitted(Permission::Pause), "ERR_NOT_ALLOWED");
 
         if self.state != state {
             if state == RunningState::Running {
@@ -57,55 +147,109 @@ impl Contract {
         }
     }
 
-    /// Extend whitelisted tokens with new tokens. Only can be called by owner.
+    /// Extend whitelisted tokens with new tokens.
+    /// Only can be called by owner or accounts permitted to manage the whitelist.
     #[payable]
     pub fn extend_whitelisted_tokens(&mut self, tokens: Vec<ValidAccountId>) {
-        assert!(self.is_owner_or_guardians(), "ERR_NOT_ALLOWED");
+        assert!(self.is_owner_or_permitted(Permission::Whitelist), "ERR_NOT_ALLOWED");
         for token in tokens {
             self.whitelisted_tokens.insert(token.as_ref());
         }
     }
 
-    /// Remove whitelisted token. Only can be called by owner.
+    /// Remove whitelisted token.
+    /// Only can be called by owner or accounts permitted to manage the whitelist.
     pub fn remove_whitelisted_tokens(&mut self, tokens: Vec<ValidAccountId>) {
-        assert!(self.is_owner_or_guardians(), "ERR_NOT_ALLOWED");
+        assert!(self.is_owner_or_permitted(Permission::Whitelist), "ERR_NOT_ALLOWED");
         for token in tokens {
             self.whitelisted_tokens.remove(token.as_ref());
         }
     }
 
+    /// Extend tokens that route search can use as intermediary hops. Only can be called by owner.
+    #[payable]
+    pub fn extend_route_intermediaries(&mut self, tokens: Vec<ValidAccountId>) {
+        self.assert_owner();
+        for token in tokens {
+            self.route_intermediaries.insert(token.as_ref());
+        }
+        assert!(
+            self.route_intermediaries.len() <= MAX_ROUTE_INTERMEDIARIES,
+            "{}",
+            ERR88_TOO_MANY_INTERMEDIARIES
+        );
+    }
+
+    /// Remove route intermediary tokens. Only can be called by owner.
+    pub fn remove_route_intermediaries(&mut self, tokens: Vec<ValidAccountId>) {
+        self.assert_owner();
+        for token in tokens {
+            self.route_intermediaries.remove(token.as_ref());
+        }
+    }
+
+    /// Sets exchange and referral fee, their sum is limited by pool bounds. Only can be called by owner.
     pub fn modify_admin_fee(&mut self, exchange_fee: u32, referral_fee: u32) {
         self.assert_owner();
-        assert!(exchange_fee + referral_fee <= FEE_DIVISOR, "ERR_ILLEGAL_FEE");
-        self.exchange_fee = exchange_fee;
-        self.referral_fee = referral_fee;
+        self.assert_not_timelocked();
+        self.internal_modify_admin_fee(exchange_fee, referral_fee);
+    }
+
+    /// Sets referral fee tiers for swaps of given input token, sorted by `min_amount_in`.
+    /// Swaps smaller than the first tier use default referral fee, empty `tiers` remove tiering.
+    /// Only can be called by owner, timelocked.
+    #[payable]
+    pub fn set_referral_fee_tiers(&mut self, token_id: ValidAccountId, tiers: Vec<ReferralFeeTier>) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.assert_not_timelocked();
+        self.internal_set_referral_fee_tiers(token_id, tiers);
     }
 
-    /// Remove exchange fee liquidity to owner's inner account.
+    /// Remove exchange fee liquidity to fee recipients' (by default owner's) inner accounts.
     /// without any storage and fee.
     #[payable]
     pub fn remove_exchange_fee_liquidity(&mut self, pool_id: u64, shares: U128, min_amounts: Vec<U128>) {
         assert_one_yocto();
         self.assert_owner();
+        self.assert_not_timelocked();
         self.assert_contract_running();
-        let ex_id = env::current_account_id();
-        let owner_id = self.owner_id.clone();
-        let mut pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
-        let amounts = pool.remove_liquidity(
-            &ex_id,
+        self.internal_remove_exchange_fee_liquidity(
+            pool_id,
             shares.into(),
             min_amounts
                 .into_iter()
                 .map(|amount| amount.into())
                 .collect(),
         );
-        self.pools.replace(pool_id, &pool);
-        let tokens = pool.tokens();
-        let mut deposits = self.internal_unwrap_or_default_account(&owner_id);
-        for i in 0..tokens.len() {
-            deposits.deposit(&tokens[i], amounts[i]);
-        }
-        self.internal_save_account(&owner_id, deposits);
+    }
+
+    /// Sets account receiving exchange fee while the fee split table is empty, None resets it to
+    /// owner. Only can be called by owner.
+    #[payable]
+    pub fn set_treasury(&mut self, treasury_id: Option<ValidAccountId>) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.treasury_id = treasury_id.map(|treasury_id| treasury_id.into());
+        log!("Treasury set to {}", self.get_treasury());
+    }
+
+    /// Returns account receiving exchange fee while the fee split table is empty.
+    pub fn get_treasury(&self) -> AccountId {
+        self.treasury_id
+            .clone()
+            .unwrap_or_else(|| self.owner_id.clone())
+    }
+
+    /// Removes all exchange fee liquidity of the pool, its tokens go through the insurance slice
+    /// and the fee split like other fee claims, by default to the treasury's inner account.
+    /// Only can be called by owner.
+    #[payable]
+    pub fn withdraw_owner_fees(&mut self, pool_id: u64) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.assert_not_timelocked();
+        self.internal_withdraw_owner_fees(pool_id);
     }
 
     /// to eventually change a stable pool's amp factor
@@ -118,7 +262,153 @@ impl Contract {
         future_amp_factor: u64,
         future_amp_time: WrappedTimestamp,
     ) {
-        assert!(self.is_owner_or_guardians(), "ERR_NOT_ALLOWED");
+        assert!(self.is_owner_or_permitted(Permission::RampAmp), "ERR_NOT_ALLOWED");
+        self.assert_not_timelocked();
+        self.internal_stable_swap_ramp_amp(pool_id, future_amp_factor, future_amp_time);
+    }
+
+    /// Schedules the action, it can be executed by owner after the timelock delay.
+    /// Returns id of the action. Only can be called by owner.
+    #[payable]
+    pub fn schedule_action(&mut self, action: TimelockedAction) -> u64 {
+        assert_one_yocto();
+        self.assert_owner();
+        let action_id = self.next_pending_action_id;
+        let executable_at = env::block_timestamp() + self.timelock_delay;
+        log!(
+            "Action {} scheduled at {}: {}",
+            action_id,
+            executable_at,
+            near_sdk::serde_json::to_string(&action).unwrap()
+        );
+        self.pending_actions.insert(
+            &action_id,
+            &PendingAction {
+                action,
+                executable_at,
+            },
+        );
+        self.next_pending_action_id += 1;
+        action_id
+    }
+
+    /// Executes the scheduled action once its delay has passed. Only can be called by owner.
+    #[payable]
+    pub fn execute_action(&mut self, action_id: u64) {
+        assert_one_yocto();
+        self.assert_owner();
+        let pending = self
+            .pending_actions
+            .remove(&action_id)
+            .expect(ERR407_NO_PENDING_ACTION);
+        assert!(
+            env::block_timestamp() >= pending.executable_at,
+            "{}",
+            ERR408_TIMELOCK_NOT_PASSED
+        );
+        log!("Action {} executed", action_id);
+        match pending.action {
+            TimelockedAction::ModifyAdminFee {
+                exchange_fee,
+                referral_fee,
+            } => self.internal_modify_admin_fee(exchange_fee, referral_fee),
+            TimelockedAction::SetPoolAdminFees {
+                pool_id,
+                admin_fees,
+            } => self.internal_set_pool_admin_fees(pool_id, admin_fees),
+            TimelockedAction::StableSwapRampAmp {
+                pool_id,
+                future_amp_factor,
+                future_amp_time,
+            } => self.internal_stable_swap_ramp_amp(pool_id, future_amp_factor, future_amp_time),
+            TimelockedAction::RemoveExchangeFeeLiquidity {
+                pool_id,
+                shares,
+                min_amounts,
+            } => {
+                self.assert_contract_running();
+                self.internal_remove_exchange_fee_liquidity(
+                    pool_id,
+                    shares.into(),
+                    min_amounts
+                        .into_iter()
+                        .map(|amount| amount.into())
+                        .collect(),
+                );
+            }
+            TimelockedAction::WithdrawOwnerFees { pool_id } => {
+                self.internal_withdraw_owner_fees(pool_id)
+            }
+            TimelockedAction::SetTimelockDelay { delay } => {
+                self.timelock_delay = delay.0;
+                log!("Timelock delay set to {}", delay.0);
+            }
+            TimelockedAction::SetReferralFeeTiers { token_id, tiers } => {
+                self.internal_set_referral_fee_tiers(token_id, tiers)
+            }
+            TimelockedAction::SetReferralTierFees { fees } => {
+                self.internal_set_referral_tier_fees(fees)
+            }
+            TimelockedAction::SetFlashLoanFee { fee } => self.internal_set_flash_loan_fee(fee),
+            TimelockedAction::SetPoolFeeSide { pool_id, fee_side } => {
+                self.internal_set_pool_fee_side(pool_id, fee_side)
+            }
+        }
+    }
+
+    /// Removes the scheduled action. Only can be called by owner.
+    #[payable]
+    pub fn cancel_action(&mut self, action_id: u64) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.pending_actions
+            .remove(&action_id)
+            .expect(ERR407_NO_PENDING_ACTION);
+        log!("Action {} cancelled", action_id);
+    }
+
+    /// Returns delay of scheduled actions, zero if timelocked actions can be called directly.
+    pub fn get_timelock_delay(&self) -> U64 {
+        U64(self.timelock_delay)
+    }
+
+    /// Returns `limit` scheduled actions from `from_index`.
+    pub fn get_pending_actions(&self, from_index: u64, limit: u64) -> Vec<PendingActionInfo> {
+        let keys = self.pending_actions.keys_as_vector();
+        let values = self.pending_actions.values_as_vector();
+        (from_index..std::cmp::min(from_index + limit, keys.len()))
+            .map(|index| {
+                let pending = values.get(index).unwrap();
+                PendingActionInfo {
+                    action_id: keys.get(index).unwrap(),
+                    action: pending.action,
+                    executable_at: U64(pending.executable_at),
+                }
+            })
+            .collect()
+    }
+
+    pub(crate) fn internal_withdraw_owner_fees(&mut self, pool_id: u64) {
+        self.assert_contract_running();
+        let pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+        let shares = pool.share_balances(&env::current_account_id());
+        assert!(shares > 0, "{}", ERR86_NO_EXCHANGE_FEE_SHARES);
+        let min_amounts = vec![0; pool.tokens().len()];
+        let amounts = self.internal_remove_exchange_fee_liquidity(pool_id, shares, min_amounts);
+        log!(
+            "Withdrawn {} exchange fee shares of pool {}: {:?}",
+            shares,
+            pool_id,
+            amounts
+        );
+    }
+
+    pub(crate) fn internal_stable_swap_ramp_amp(
+        &mut self,
+        pool_id: u64,
+        future_amp_factor: u64,
+        future_amp_time: WrappedTimestamp,
+    ) {
         let mut pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
         match &mut pool {
             Pool::StableSwapPool(pool) => {
@@ -126,17 +416,96 @@ impl Contract {
             }
             _ => env::panic(b"ERR_NOT_STABLE_POOL"),
         }
-        self.pools.replace(pool_id, &pool);
+        self.pool_bounds.assert_amp(future_amp_factor);
+        self.internal_save_pool(pool_id, &pool);
+    }
+
+    /// Fix decimals of a stable pool that was created with wrong ones.
+    /// Only can be called by owner and only before the pool received any liquidity.
+    pub fn stable_swap_set_decimals(&mut self, pool_id: u64, decimals: Vec<u8>) {
+        self.assert_owner();
+        let mut pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+        match &mut pool {
+            Pool::StableSwapPool(pool) => pool.set_token_decimals(decimals),
+            _ => env::panic(b"ERR_NOT_STABLE_POOL"),
+        }
+        self.internal_save_pool(pool_id, &pool);
     }
 
     pub fn stable_swap_stop_ramp_amp(&mut self, pool_id: u64) {
-        assert!(self.is_owner_or_guardians(), "ERR_NOT_ALLOWED");
+        assert!(self.is_owner_or_permitted(Permission::RampAmp), "ERR_NOT_ALLOWED");
         let mut pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
         match &mut pool {
             Pool::StableSwapPool(pool) => pool.stop_ramp_amplification(),
             _ => env::panic(b"ERR_NOT_STABLE_POOL"),
         }
-        self.pools.replace(pool_id, &pool);
+        self.internal_save_pool(pool_id, &pool);
+    }
+
+    pub(crate) fn internal_modify_admin_fee(&mut self, exchange_fee: u32, referral_fee: u32) {
+        assert!(exchange_fee + referral_fee <= FEE_DIVISOR, "ERR_ILLEGAL_FEE");
+        self.pool_bounds.assert_admin_fee(exchange_fee + referral_fee);
+        self.exchange_fee = exchange_fee;
+        self.referral_fee = referral_fee;
+    }
+
+    /// Removes given exchange fee shares of the pool and deposits tokens to fee recipients.
+    pub(crate) fn internal_remove_exchange_fee_liquidity(
+        &mut self,
+        pool_id: u64,
+        shares: Balance,
+        min_amounts: Vec<Balance>,
+    ) -> Vec<Balance> {
+        let ex_id = env::current_account_id();
+        let mut pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+        let amounts = pool.remove_liquidity(&ex_id, shares, min_amounts);
+        self.internal_save_pool(pool_id, &pool);
+        self.internal_record_exchange_fee_removed(pool_id, shares);
+        self.internal_distribute_exchange_fee(pool.tokens(), &amounts);
+        amounts
+    }
+
+    /// Adds to exchange fee shares removed from the pool, their storage is covered by the exchange.
+    fn internal_record_exchange_fee_removed(&mut self, pool_id: u64, shares: Balance) {
+        let removed = self.exchange_fee_removed_shares.get(&pool_id).unwrap_or(0);
+        self.exchange_fee_removed_shares
+            .insert(&pool_id, &(removed + shares));
+    }
+
+    /// Sets referral fee tiers of the token, see `set_referral_fee_tiers`.
+    pub(crate) fn internal_set_referral_fee_tiers(
+        &mut self,
+        token_id: ValidAccountId,
+        tiers: Vec<ReferralFeeTier>,
+    ) {
+        assert!(
+            tiers.len() <= MAX_REFERRAL_FEE_TIERS,
+            "{}",
+            ERR89_INVALID_REFERRAL_FEE_TIERS
+        );
+        for (i, tier) in tiers.iter().enumerate() {
+            assert!(
+                self.exchange_fee + tier.referral_fee <= FEE_DIVISOR,
+                "ERR_ILLEGAL_FEE"
+            );
+            self.pool_bounds
+                .assert_admin_fee(self.exchange_fee + tier.referral_fee);
+            assert!(
+                i == 0 || tiers[i - 1].min_amount_in.0 < tier.min_amount_in.0,
+                "{}",
+                ERR89_INVALID_REFERRAL_FEE_TIERS
+            );
+        }
+        if tiers.is_empty() {
+            self.referral_fee_tiers.remove(token_id.as_ref());
+        } else {
+            self.referral_fee_tiers.insert(token_id.as_ref(), &tiers);
+        }
+    }
+
+    /// Panics if timelocked actions must be scheduled.
+    pub(crate) fn assert_not_timelocked(&self) {
+        assert_eq!(self.timelock_delay, 0, "{}", ERR406_ACTION_TIMELOCKED);
     }
 
     pub(crate) fn assert_owner(&self) {
@@ -148,8 +517,8 @@ impl Contract {
     }
 
     pub(crate) fn is_owner_or_guardians(&self) -> bool {
-        env::predecessor_account_id() == self.owner_id 
-            || self.guardians.contains(&env::predecessor_account_id())
+        env::predecessor_account_id() == self.owner_id
+            || self.internal_is_guardian(&env::predecessor_account_id())
     }
 
     /// Migration function from v2 to v2.
@@ -158,10 +527,105 @@ impl Contract {
     // [AUDIT_09]
     #[private]
     pub fn migrate() -> Self {
-        let mut prev: Contract = env::state_read().expect("ERR_NOT_INITIALIZED");
-        prev.exchange_fee = 1600;
-        prev.referral_fee = 400;
-        prev
+        let prev: ContractV2 = env::state_read().expect("ERR_NOT_INITIALIZED");
+        let mut roles = UnorderedMap::new(StorageKey::Roles);
+        let mut guardians = prev.guardians;
+        for guardian in guardians.iter() {
+            roles.insert(&guardian, &GUARDIAN_FLAG);
+        }
+        guardians.clear();
+        Contract {
+            owner_id: prev.owner_id,
+            exchange_fee: 1600,
+            referral_fee: 400,
+            pools: prev.pools,
+            accounts: prev.accounts,
+            whitelisted_tokens: prev.whitelisted_tokens,
+            roles,
+            state: prev.state,
+            permit_keys: LookupMap::new(StorageKey::PermitKeys),
+            pool_fee_skims: LookupMap::new(StorageKey::PoolFeeSkims),
+            token_shares: UnorderedMap::new(StorageKey::TokenShares),
+            pool_nonces: LookupMap::new(StorageKey::PoolNonces),
+            route_intermediaries: UnorderedSet::new(StorageKey::RouteIntermediaries),
+            fee_split: vec![],
+            fee_recipient_received: LookupMap::new(StorageKey::FeeRecipientReceived),
+            insurance_config: InsuranceConfig::default(),
+            insurance_tokens: UnorderedMap::new(StorageKey::InsuranceTokens),
+            insurance_disbursements: Vector::new(StorageKey::InsuranceDisbursements),
+            referral_fee_tiers: UnorderedMap::new(StorageKey::ReferralFeeTiers),
+            pool_decimals_status: LookupMap::new(StorageKey::PoolDecimalsStatus),
+            extraction_limit: None,
+            block_extractions: LookupMap::new(StorageKey::BlockExtractions),
+            storage_config: StorageConfig::default(),
+            competitions: Vector::new(StorageKey::Competitions),
+            open_competitions: vec![],
+            competition_volumes: LookupMap::new(StorageKey::CompetitionVolumes),
+            token_withdraw_failures: LookupMap::new(StorageKey::TokenWithdrawFailures),
+            unresponsive_tokens: UnorderedSet::new(StorageKey::UnresponsiveTokens),
+            governance_dao: None,
+            pool_checkpoints: LookupMap::new(StorageKey::PoolCheckpoints),
+            migration_peer: None,
+            imported_accounts: LookupSet::new(StorageKey::ImportedAccounts),
+            withdraw_delays: LookupMap::new(StorageKey::WithdrawDelays),
+            pending_withdrawals: LookupMap::new(StorageKey::PendingWithdrawals),
+            peg_monitor_config: None,
+            peg_alerts: LookupMap::new(StorageKey::PegAlerts),
+            pool_bounds: PoolBounds::default(),
+            deposit_receipts: LookupMap::new(StorageKey::DepositReceipts),
+            pool_fee_sides: LookupMap::new(StorageKey::PoolFeeSides),
+            account_index: UnorderedSet::new(StorageKey::AccountIndex),
+            storage_price_buffer: 0,
+            referral_budgets: LookupMap::new(StorageKey::ReferralBudgets),
+            pool_launch_delay: 0,
+            pool_swaps_open_at: LookupMap::new(StorageKey::PoolSwapsOpenAt),
+            reference_token: None,
+            reference_pools: LookupMap::new(StorageKey::ReferencePools),
+            guardian_limits: LookupMap::new(StorageKey::GuardianLimits),
+            share_loan_configs: LookupMap::new(StorageKey::ShareLoanConfigs),
+            share_loans: LookupMap::new(StorageKey::ShareLoans),
+            lending_reserves: LookupMap::new(StorageKey::LendingReserves),
+            account_merges: LookupMap::new(StorageKey::AccountMerges),
+            price_oracles: UnorderedSet::new(StorageKey::PriceOracles),
+            price_hint_max_ages: UnorderedMap::new(StorageKey::PriceHintMaxAges),
+            price_hints: LookupMap::new(StorageKey::PriceHints),
+            pool_creators: LookupMap::new(StorageKey::PoolCreators),
+            pool_metadata: LookupMap::new(StorageKey::PoolMetadata),
+            min_withdrawals: UnorderedMap::new(StorageKey::MinWithdrawals),
+            failed_withdrawals: LookupMap::new(StorageKey::FailedWithdrawals),
+            dust_policy: None,
+            dust_policy_enabled_at: 0,
+            account_activity: LookupMap::new(StorageKey::AccountActivity),
+            dust_flags: UnorderedMap::new(StorageKey::DustFlags),
+            action_limits: ActionLimits::default(),
+            pool_twaps: LookupMap::new(StorageKey::PoolTwaps),
+            paused_pools: UnorderedSet::new(StorageKey::PausedPools),
+            treasury_id: None,
+            exchange_fee_removed_shares: LookupMap::new(StorageKey::ExchangeFeeRemovedShares),
+            referrers: LookupMap::new(StorageKey::Referrers),
+            referral_tier_fees: vec![],
+            wnear_account_id: None,
+            flash_loan_fee: None,
+            flash_loans: LookupMap::new(StorageKey::FlashLoans),
+            flash_loan_pools: LookupSet::new(StorageKey::FlashLoanPools),
+            token_pools: LookupMap::new(StorageKey::TokenPools),
+            indexed_pools: 0,
+            pool_admin_fees: LookupMap::new(StorageKey::PoolAdminFees),
+            pending_owner_id: None,
+            timelock_delay: 0,
+            pending_actions: UnorderedMap::new(StorageKey::PendingActions),
+            next_pending_action_id: 0,
+            token_holdings: LookupMap::new(StorageKey::TokenHoldings),
+            track_all_holdings: false,
+            pool_stats: LookupMap::new(StorageKey::PoolStats),
+            share_locks: LookupMap::new(StorageKey::ShareLocks),
+            token_metadata: LookupMap::new(StorageKey::TokenMetadata),
+            pool_fee_tiers: vec![],
+            pool_dedup: false,
+            banned_tokens: UnorderedSet::new(StorageKey::BannedTokens),
+            quarantined_amounts: LookupMap::new(StorageKey::QuarantinedAmounts),
+            lost_tokens: UnorderedMap::new(StorageKey::LostTokens),
+        }
     }
 }
 
@@ -0,0 +1,73 @@
+//! Cursor based pagination of enumeration views.
+//!
+//! `*_page` views take an opaque cursor returned in `next_cursor` of the previous page, None
+//! starts from the beginning. Pools, disbursements and competitions are only ever appended, so
+//! their pages don't shift between calls. Deposits are paged in order of token ids and continue
+//! after the last returned token, whatever changed in between. Account ids are kept in an
+//! unordered set, removing an account may make another one move before the cursor.
+
+use near_sdk::bs58;
+
+use crate::*;
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub(crate) enum Cursor {
+    /// Index of the next item.
+    Index(u64),
+    /// Key of the last returned item.
+    After(AccountId),
+}
+
+impl Cursor {
+    pub(crate) fn encode(&self) -> String {
+        bs58::encode(self.try_to_vec().unwrap()).into_string()
+    }
+
+    pub(crate) fn decode(cursor: &str) -> Self {
+        bs58::decode(cursor)
+            .into_vec()
+            .ok()
+            .and_then(|data| Cursor::try_from_slice(&data).ok())
+            .expect(ERR290_INVALID_CURSOR)
+    }
+
+    /// Returns index to start from for collections paged by index.
+    pub(crate) fn start_index(cursor: Option<String>) -> u64 {
+        match cursor.map(|cursor| Cursor::decode(&cursor)) {
+            None => 0,
+            Some(Cursor::Index(index)) => index,
+            Some(Cursor::After(_)) => env::panic(ERR290_INVALID_CURSOR.as_bytes()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug))]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Cursor of the next page, None if this is the last one.
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+}
+
+impl<T> Page<T> {
+    /// Collects `limit` items of a collection of `len` items indexed from 0 after the cursor.
+    pub(crate) fn from_index<F>(cursor: Option<String>, limit: u64, len: u64, get: F) -> Self
+    where
+        F: Fn(u64) -> T,
+    {
+        let start = std::cmp::min(Cursor::start_index(cursor), len);
+        let end = std::cmp::min(start.saturating_add(limit), len);
+        let has_more = end < len;
+        Page {
+            items: (start..end).map(get).collect(),
+            next_cursor: if has_more {
+                Some(Cursor::Index(end).encode())
+            } else {
+                None
+            },
+            has_more,
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! Depeg alerts of stable pools.
+//!
+//! After a swap in a stable pool, if the marginal price of any token against the first one deviates
+//! from parity more than the owner-set threshold, the exchange logs a peg alert for each such token.
+//! Alerts are rate-limited to one per pool per interval.
+
+use near_sdk::json_types::WrappedDuration;
+
+use crate::utils::FEE_DIVISOR;
+use crate::*;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct PegMonitorConfig {
+    /// Deviation from parity triggering alert, in bps of `FEE_DIVISOR`.
+    pub threshold: u32,
+    /// Min time between alerts of a pool.
+    pub interval: WrappedDuration,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Sets peg alerts settings, None disables them. Only can be called by owner.
+    #[payable]
+    pub fn set_peg_monitor_config(&mut self, config: Option<PegMonitorConfig>) {
+        assert_one_yocto();
+        self.assert_owner();
+        if let Some(config) = &config {
+            assert!(
+                config.threshold > 0 && config.threshold < FEE_DIVISOR,
+                "{}",
+                ERR190_INVALID_PEG_MONITOR_CONFIG
+            );
+        }
+        log!("Peg monitor config set to {:?}", config.as_ref().map(|config| config.threshold));
+        self.peg_monitor_config = config;
+    }
+
+    pub fn get_peg_monitor_config(&self) -> Option<PegMonitorConfig> {
+        self.peg_monitor_config.clone()
+    }
+
+    /// Returns deviation from parity of each token's price against the first token of stable pool,
+    /// in bps of `FEE_DIVISOR`. Empty for other pools.
+    pub fn get_peg_deviations(&self, pool_id: u64) -> Vec<u32> {
+        match self.pools.get(pool_id).expect("ERR_NO_POOL") {
+            Pool::SimplePool(_) | Pool::WeightedPool(_) => vec![],
+            Pool::StableSwapPool(pool) => pool.get_peg_deviations(),
+        }
+    }
+}
+
+impl Contract {
+    /// Logs peg alert if stable pool tokens deviate beyond the threshold and the pool
+    /// wasn't alerted within the interval. Alert timestamps are covered by the exchange.
+    pub(crate) fn internal_monitor_peg(&mut self, pool_id: u64, pool: &Pool) {
+        let (threshold, interval, pool) = match (&self.peg_monitor_config, pool) {
+            (Some(config), Pool::StableSwapPool(pool)) => (config.threshold, config.interval.0, pool),
+            _ => return,
+        };
+        let now = env::block_timestamp();
+        if let Some(last_alert) = self.peg_alerts.get(&pool_id) {
+            if now < last_alert + interval {
+                return;
+            }
+        }
+        let mut alerted = false;
+        for (index, deviation) in pool.get_peg_deviations().into_iter().enumerate() {
+            if deviation > threshold {
+                log!(
+                    "Peg alert in pool {}: price of {} deviates {} bps from parity with {}",
+                    pool_id,
+                    pool.token_account_ids[index],
+                    deviation,
+                    pool.token_account_ids[0]
+                );
+                alerted = true;
+            }
+        }
+        if alerted {
+            self.peg_alerts.insert(&pool_id, &now);
+        }
+    }
+}
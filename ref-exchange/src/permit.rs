@@ -0,0 +1,258 @@
+//! Off-chain signed permits for internal transfers.
+//!
+//! An account registers an ed25519 key once. Afterwards anyone (e.g. a relayer) can submit
+//! a `Permit` signed by that key, moving part of the account's internal balance
+//! (token deposit or pool shares) to a receiver exactly once.
+//! The same key signs `StoragePermit`s, letting an operator sweep available storage balances
+//! of many accounts back to them in one `storage_withdraw_batch` call.
+
+use std::convert::TryFrom;
+
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+use near_sdk::json_types::{Base58PublicKey, Base64VecU8, WrappedTimestamp};
+
+use crate::*;
+
+/// Key registered by an account to sign permits, together with the nonce expected next.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct PermitKey {
+    /// Raw ed25519 public key, empty if permits are revoked.
+    pub public_key: Vec<u8>,
+    /// Nonce the next permit must carry. Never decreases, even when key is replaced.
+    pub next_nonce: u64,
+}
+
+/// One-time authorization to move `amount` of `token_id` from `owner_id` to `receiver_id`.
+#[derive(Serialize, Deserialize, BorshSerialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Clone))]
+pub struct Permit {
+    pub owner_id: AccountId,
+    pub receiver_id: AccountId,
+    /// Same format as in `mft_transfer`: token account id or ":<pool_id>" for LP shares.
+    pub token_id: String,
+    pub amount: U128,
+    pub nonce: u64,
+    /// Permit can not be executed after this block timestamp (in nano sec).
+    pub deadline: WrappedTimestamp,
+}
+
+/// Max number of accounts in one `storage_withdraw_batch`.
+pub const MAX_STORAGE_WITHDRAW_BATCH: usize = 50;
+
+/// One-time authorization for `operator_id` to withdraw available storage balance of `owner_id`.
+/// Uses the same key and nonces as `Permit`.
+#[derive(Serialize, Deserialize, BorshSerialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Clone))]
+pub struct StoragePermit {
+    pub owner_id: AccountId,
+    pub operator_id: AccountId,
+    pub nonce: u64,
+    /// Permit can not be executed after this block timestamp (in nano sec).
+    pub deadline: WrappedTimestamp,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, Clone))]
+pub struct SignedStoragePermit {
+    pub permit: StoragePermit,
+    pub signature: Base64VecU8,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug))]
+pub struct PermitKeyInfo {
+    pub public_key: Option<Base58PublicKey>,
+    pub next_nonce: u64,
+}
+
+impl Permit {
+    /// Message that owner signs: sha256 of borsh serialized (exchange account id, permit).
+    pub fn hash(&self) -> Vec<u8> {
+        let data = (env::current_account_id(), self)
+            .try_to_vec()
+            .expect("ERR_SERIALIZE_PERMIT");
+        env::sha256(&data)
+    }
+}
+
+impl StoragePermit {
+    /// Message that owner signs: sha256 of borsh serialized (exchange account id, "storage_withdraw", permit).
+    pub fn hash(&self) -> Vec<u8> {
+        let data = (env::current_account_id(), "storage_withdraw", self)
+            .try_to_vec()
+            .expect("ERR_SERIALIZE_PERMIT");
+        env::sha256(&data)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Sets (or replaces) the key predecessor uses to sign permits.
+    /// Attached NEAR should cover the storage of a newly registered key, the rest is refunded.
+    #[payable]
+    pub fn set_permit_key(&mut self, public_key: Base58PublicKey) {
+        self.assert_contract_running();
+        let prev_storage = env::storage_usage();
+        let account_id = env::predecessor_account_id();
+        assert!(self.accounts.contains_key(&account_id), "{}", ERR10_ACC_NOT_REGISTERED);
+        let key_data: Vec<u8> = public_key.into();
+        // Base58PublicKey prefixes the curve type, 0 stands for ed25519.
+        assert!(
+            key_data.len() == 33 && key_data[0] == 0,
+            "{}",
+            ERR110_INVALID_PERMIT_KEY
+        );
+        let raw_key = key_data[1..].to_vec();
+        PublicKey::from_bytes(&raw_key).expect(ERR110_INVALID_PERMIT_KEY);
+        let next_nonce = self
+            .permit_keys
+            .get(&account_id)
+            .map(|key| key.next_nonce)
+            .unwrap_or(0);
+        self.permit_keys.insert(
+            &account_id,
+            &PermitKey {
+                public_key: raw_key,
+                next_nonce,
+            },
+        );
+        self.internal_check_storage(prev_storage);
+    }
+
+    /// Revokes permit key of the predecessor, all not yet executed permits become invalid.
+    #[payable]
+    pub fn revoke_permit_key(&mut self) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut key = self
+            .permit_keys
+            .get(&account_id)
+            .expect(ERR111_PERMIT_KEY_NOT_SET);
+        key.public_key = vec![];
+        self.permit_keys.insert(&account_id, &key);
+    }
+
+    /// Executes a permit signed by its owner. Can be submitted by anyone.
+    /// Moves tokens or LP shares the same way as `mft_transfer` from the owner.
+    #[payable]
+    pub fn execute_permit(&mut self, permit: Permit, signature: Base64VecU8) {
+        assert_one_yocto();
+        self.assert_contract_running();
+        self.internal_use_permit(
+            &permit.owner_id,
+            permit.nonce,
+            permit.deadline.0,
+            &permit.hash(),
+            &signature,
+        );
+        log!(
+            "Permit {} of {} executed by {}",
+            permit.nonce,
+            permit.owner_id,
+            env::predecessor_account_id()
+        );
+        self.internal_mft_transfer(
+            permit.token_id,
+            &permit.owner_id,
+            &permit.receiver_id,
+            permit.amount.0,
+            None,
+        );
+    }
+
+    /// Withdraws all available storage balance of each account to the account itself.
+    /// Each account authorizes the caller with a `StoragePermit`. Returns withdrawn amounts,
+    /// zero for accounts with nothing available.
+    #[payable]
+    pub fn storage_withdraw_batch(&mut self, permits: Vec<SignedStoragePermit>) -> Vec<U128> {
+        assert_one_yocto();
+        self.assert_contract_running();
+        assert!(
+            permits.len() <= MAX_STORAGE_WITHDRAW_BATCH,
+            "{}",
+            ERR115_TOO_MANY_PERMITS
+        );
+        let operator_id = env::predecessor_account_id();
+        permits
+            .into_iter()
+            .map(|SignedStoragePermit { permit, signature }| {
+                assert_eq!(permit.operator_id, operator_id, "{}", ERR116_PERMIT_WRONG_OPERATOR);
+                self.internal_use_permit(
+                    &permit.owner_id,
+                    permit.nonce,
+                    permit.deadline.0,
+                    &permit.hash(),
+                    &signature,
+                );
+                let available = self
+                    .internal_unwrap_account(&permit.owner_id)
+                    .storage_available(self.internal_storage_byte_price());
+                if available > 0 {
+                    let amount = self.internal_storage_withdraw(&permit.owner_id, available);
+                    Promise::new(permit.owner_id).transfer(amount);
+                }
+                U128(available)
+            })
+            .collect()
+    }
+
+    /// Returns permit key and the nonce expected in the next permit of given account.
+    pub fn get_permit_key(&self, account_id: ValidAccountId) -> Option<PermitKeyInfo> {
+        self.permit_keys.get(account_id.as_ref()).map(|key| {
+            PermitKeyInfo {
+                public_key: if key.public_key.is_empty() {
+                    None
+                } else {
+                    let mut data = vec![0u8];
+                    data.extend(key.public_key);
+                    Some(Base58PublicKey(data))
+                },
+                next_nonce: key.next_nonce,
+            }
+        })
+    }
+
+    /// Returns the bytes permit owner is expected to sign.
+    pub fn get_permit_hash(&self, permit: Permit) -> Base64VecU8 {
+        permit.hash().into()
+    }
+
+    /// Returns the bytes storage permit owner is expected to sign.
+    pub fn get_storage_permit_hash(&self, permit: StoragePermit) -> Base64VecU8 {
+        permit.hash().into()
+    }
+}
+
+impl Contract {
+    /// Checks permit of the owner against its key and consumes the nonce.
+    fn internal_use_permit(
+        &mut self,
+        owner_id: &AccountId,
+        nonce: u64,
+        deadline: u64,
+        hash: &[u8],
+        signature: &Base64VecU8,
+    ) {
+        assert!(env::block_timestamp() <= deadline, "{}", ERR112_PERMIT_EXPIRED);
+        let mut key = self
+            .permit_keys
+            .get(owner_id)
+            .expect(ERR111_PERMIT_KEY_NOT_SET);
+        assert!(!key.public_key.is_empty(), "{}", ERR111_PERMIT_KEY_NOT_SET);
+        assert_eq!(nonce, key.next_nonce, "{}", ERR113_PERMIT_WRONG_NONCE);
+        let public_key = PublicKey::from_bytes(&key.public_key).expect(ERR110_INVALID_PERMIT_KEY);
+        let signature =
+            Signature::try_from(signature.0.as_slice()).expect(ERR114_PERMIT_BAD_SIGNATURE);
+        assert!(
+            public_key.verify(hash, &signature).is_ok(),
+            "{}",
+            ERR114_PERMIT_BAD_SIGNATURE
+        );
+        key.next_nonce += 1;
+        self.permit_keys.insert(owner_id, &key);
+    }
+}
@@ -4,14 +4,17 @@ use near_sdk::{AccountId, Balance};
 use crate::admin_fee::AdminFees;
 use crate::simple_pool::SimplePool;
 use crate::stable_swap::StableSwapPool;
-use crate::utils::SwapVolume;
+use crate::weighted_pool::WeightedPool;
+use crate::utils::{SwapVolume, U256};
 
 /// Generic Pool, providing wrapper around different implementations of swap pools.
 /// Allows to add new types of pools just by adding extra item in the enum without needing to migrate the storage.
 #[derive(BorshSerialize, BorshDeserialize)]
+#[allow(clippy::enum_variant_names)]
 pub enum Pool {
     SimplePool(SimplePool),
     StableSwapPool(StableSwapPool),
+    WeightedPool(WeightedPool),
 }
 
 impl Pool {
@@ -20,6 +23,7 @@ impl Pool {
         match self {
             Pool::SimplePool(_) => "SIMPLE_POOL".to_string(),
             Pool::StableSwapPool(_) => "STABLE_SWAP".to_string(),
+            Pool::WeightedPool(_) => "WEIGHTED_POOL".to_string(),
         }
     }
 
@@ -28,6 +32,7 @@ impl Pool {
         match self {
             Pool::SimplePool(pool) => pool.tokens(),
             Pool::StableSwapPool(pool) => pool.tokens(),
+            Pool::WeightedPool(pool) => pool.tokens(),
         }
     }
 
@@ -41,6 +46,7 @@ impl Pool {
         match self {
             Pool::SimplePool(pool) => pool.add_liquidity(sender_id, amounts),
             Pool::StableSwapPool(_) => unimplemented!(),
+            Pool::WeightedPool(pool) => pool.add_liquidity(sender_id, amounts),
         }
     }
 
@@ -54,6 +60,7 @@ impl Pool {
         match self {
             Pool::SimplePool(_) => unimplemented!(),
             Pool::StableSwapPool(pool) => pool.add_liquidity(sender_id, amounts, min_shares, &admin_fee),
+            Pool::WeightedPool(_) => unimplemented!(),
         }
     }
 
@@ -69,6 +76,7 @@ impl Pool {
             Pool::StableSwapPool(pool) => {
                 pool.remove_liquidity_by_shares(sender_id, shares, min_amounts)
             }
+            Pool::WeightedPool(pool) => pool.remove_liquidity(sender_id, shares, min_amounts),
         }
     }
 
@@ -85,6 +93,7 @@ impl Pool {
             Pool::StableSwapPool(pool) => {
                 pool.remove_liquidity_by_tokens(sender_id, amounts, max_burn_shares, &admin_fee)
             }
+            Pool::WeightedPool(_) => unimplemented!(),
         }
     }
 
@@ -97,8 +106,31 @@ impl Pool {
         fees: &AdminFees,
     ) -> Balance {
         match self {
-            Pool::SimplePool(pool) => pool.get_return(token_in, amount_in, token_out),
+            Pool::SimplePool(pool) => {
+                pool.get_return(token_in, amount_in, token_out, fees.fee_side)
+            }
             Pool::StableSwapPool(pool) => pool.get_return(token_in, amount_in, token_out, fees),
+            Pool::WeightedPool(pool) => pool.get_return(token_in, amount_in, token_out),
+        }
+    }
+
+    /// Returns how many tokens one needs to swap into the pool to receive given amount of
+    /// token_out.
+    pub fn get_input_for_output(
+        &self,
+        token_in: &AccountId,
+        amount_out: Balance,
+        token_out: &AccountId,
+        fees: &AdminFees,
+    ) -> Balance {
+        match self {
+            Pool::SimplePool(pool) => {
+                pool.get_input_for_output(token_in, amount_out, token_out, fees.fee_side)
+            }
+            Pool::StableSwapPool(pool) => {
+                pool.get_input_for_output(token_in, amount_out, token_out, fees)
+            }
+            Pool::WeightedPool(pool) => pool.get_input_for_output(token_in, amount_out, token_out),
         }
     }
 
@@ -107,14 +139,48 @@ impl Pool {
         match self {
             Pool::SimplePool(_) => 24,
             Pool::StableSwapPool(_) => 18,
+            Pool::WeightedPool(_) => 24,
+        }
+    }
+
+    /// Returns fee-less spot price of each token in the first one, in their smallest units
+    /// multiplied by `precision`. None if the pool is empty.
+    pub fn spot_prices(&self, precision: u128) -> Option<Vec<U256>> {
+        match self {
+            Pool::SimplePool(pool) => pool.spot_prices(precision),
+            Pool::StableSwapPool(pool) => pool.spot_prices(precision),
+            Pool::WeightedPool(pool) => pool.spot_prices(precision),
         }
     }
 
+    /// Returns fee-less spot price of `token_in` in `token_out` multiplied by `precision`.
+    /// None if the pool is empty.
+    pub fn spot_price(
+        &self,
+        token_in: &AccountId,
+        token_out: &AccountId,
+        precision: u128,
+    ) -> Option<U256> {
+        let prices = self.spot_prices(precision)?;
+        let index = |token_id: &AccountId| {
+            self.tokens()
+                .iter()
+                .position(|id| id == token_id)
+                .expect("ERR_MISSING_TOKEN")
+        };
+        let price_out = prices[index(token_out)];
+        if price_out.is_zero() {
+            return None;
+        }
+        Some(prices[index(token_in)] * U256::from(precision) / price_out)
+    }
+
     /// Returns given pool's total fee.
     pub fn get_fee(&self) -> u32 {
         match self {
             Pool::SimplePool(pool) => pool.get_fee(),
             Pool::StableSwapPool(pool) => pool.get_fee(),
+            Pool::WeightedPool(pool) => pool.get_fee(),
         }
     }
 
@@ -123,6 +189,7 @@ impl Pool {
         match self {
             Pool::SimplePool(pool) => pool.get_volumes(),
             Pool::StableSwapPool(pool) => pool.get_volumes(),
+            Pool::WeightedPool(pool) => pool.get_volumes(),
         }
     }
 
@@ -131,6 +198,7 @@ impl Pool {
         match self {
             Pool::SimplePool(_) => unimplemented!(),
             Pool::StableSwapPool(pool) => pool.get_share_price(),
+            Pool::WeightedPool(_) => unimplemented!(),
         }
     }
 
@@ -150,6 +218,18 @@ impl Pool {
             Pool::StableSwapPool(pool) => {
                 pool.swap(token_in, amount_in, token_out, min_amount_out, &admin_fee)
             }
+            Pool::WeightedPool(pool) => {
+                pool.swap(token_in, amount_in, token_out, min_amount_out, &admin_fee)
+            }
+        }
+    }
+
+    /// Adds amount of the token to pool reserves without minting shares.
+    pub fn donate(&mut self, token_id: &AccountId, amount: Balance) {
+        match self {
+            Pool::SimplePool(pool) => pool.donate(token_id, amount),
+            Pool::StableSwapPool(pool) => pool.donate(token_id, amount),
+            Pool::WeightedPool(pool) => pool.donate(token_id, amount),
         }
     }
 
@@ -157,6 +237,7 @@ impl Pool {
         match self {
             Pool::SimplePool(pool) => pool.share_total_balance(),
             Pool::StableSwapPool(pool) => pool.share_total_balance(),
+            Pool::WeightedPool(pool) => pool.share_total_balance(),
         }
     }
 
@@ -164,6 +245,7 @@ impl Pool {
         match self {
             Pool::SimplePool(pool) => pool.share_balance_of(account_id),
             Pool::StableSwapPool(pool) => pool.share_balance_of(account_id),
+            Pool::WeightedPool(pool) => pool.share_balance_of(account_id),
         }
     }
 
@@ -171,6 +253,15 @@ impl Pool {
         match self {
             Pool::SimplePool(pool) => pool.share_transfer(sender_id, receiver_id, amount),
             Pool::StableSwapPool(pool) => pool.share_transfer(sender_id, receiver_id, amount),
+            Pool::WeightedPool(pool) => pool.share_transfer(sender_id, receiver_id, amount),
+        }
+    }
+
+    pub fn share_is_registered(&self, account_id: &AccountId) -> bool {
+        match self {
+            Pool::SimplePool(pool) => pool.share_is_registered(account_id),
+            Pool::StableSwapPool(pool) => pool.share_is_registered(account_id),
+            Pool::WeightedPool(pool) => pool.share_is_registered(account_id),
         }
     }
 
@@ -178,6 +269,7 @@ impl Pool {
         match self {
             Pool::SimplePool(pool) => pool.share_register(account_id),
             Pool::StableSwapPool(pool) => pool.share_register(account_id),
+            Pool::WeightedPool(pool) => pool.share_register(account_id),
         }
     }
 
@@ -189,6 +281,7 @@ impl Pool {
         match self {
             Pool::SimplePool(_) => unimplemented!(),
             Pool::StableSwapPool(pool) => pool.predict_add_stable_liquidity(amounts, fees),
+            Pool::WeightedPool(_) => unimplemented!(),
         }
     }
 
@@ -197,8 +290,9 @@ impl Pool {
         shares: Balance,
     ) -> Vec<Balance> {
         match self {
-            Pool::SimplePool(_) => unimplemented!(),
+            Pool::SimplePool(pool) => pool.predict_remove_liquidity(shares),
             Pool::StableSwapPool(pool) => pool.predict_remove_liquidity(shares),
+            Pool::WeightedPool(pool) => pool.predict_remove_liquidity(shares),
         }
     }
 
@@ -210,6 +304,7 @@ impl Pool {
         match self {
             Pool::SimplePool(_) => unimplemented!(),
             Pool::StableSwapPool(pool) => pool.predict_remove_liquidity_by_tokens(amounts, fees),
+            Pool::WeightedPool(_) => unimplemented!(),
         }
     }
 }
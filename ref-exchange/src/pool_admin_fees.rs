@@ -0,0 +1,74 @@
+//! Admin fees of single pools.
+//!
+//! By default all pools split their fee with the global `exchange_fee` and `referral_fee`. Owner
+//! can give a pool its own split, e.g. a lower protocol take rate for stable pools, within the
+//! same caps as the global one. Referrer tiers and referral fee tiers of pool tokens set at the
+//! time are checked against the pool's exchange fee too. Pool admin fees are covered by the
+//! exchange.
+
+use crate::utils::FEE_DIVISOR;
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// Sets admin fees of the pool, None returns it to the global ones.
+    /// Only can be called by owner.
+    #[payable]
+    pub fn set_pool_admin_fees(&mut self, pool_id: u64, admin_fees: Option<PoolAdminFees>) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.assert_not_timelocked();
+        self.internal_set_pool_admin_fees(pool_id, admin_fees);
+    }
+
+    /// Returns admin fees applied to swaps in the pool.
+    pub fn get_pool_admin_fees(&self, pool_id: u64) -> PoolAdminFees {
+        assert!(pool_id < self.pools.len(), "ERR_NO_POOL");
+        self.internal_get_pool_admin_fees(pool_id)
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_set_pool_admin_fees(
+        &mut self,
+        pool_id: u64,
+        admin_fees: Option<PoolAdminFees>,
+    ) {
+        let pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+        match admin_fees {
+            Some(admin_fees) => {
+                let max_referral_fee = pool
+                    .tokens()
+                    .iter()
+                    .flat_map(|token_id| self.referral_fee_tiers.get(token_id).unwrap_or_default())
+                    .map(|tier| tier.referral_fee)
+                    .chain(self.referral_tier_fees.iter().cloned())
+                    .fold(admin_fees.referral_fee, std::cmp::max);
+                let max_admin_fee = admin_fees.exchange_fee + max_referral_fee;
+                assert!(max_admin_fee <= FEE_DIVISOR, "ERR_ILLEGAL_FEE");
+                self.pool_bounds.assert_admin_fee(max_admin_fee);
+                log!(
+                    "Pool {} admin fees set to exchange {} referral {}",
+                    pool_id,
+                    admin_fees.exchange_fee,
+                    admin_fees.referral_fee
+                );
+                self.pool_admin_fees.insert(&pool_id, &admin_fees);
+            }
+            None => {
+                self.pool_admin_fees.remove(&pool_id);
+                log!("Pool {} uses global admin fees", pool_id);
+            }
+        }
+    }
+
+    /// Returns admin fees of the pool, or the global ones.
+    pub(crate) fn internal_get_pool_admin_fees(&self, pool_id: u64) -> PoolAdminFees {
+        self.pool_admin_fees
+            .get(&pool_id)
+            .unwrap_or(PoolAdminFees {
+                exchange_fee: self.exchange_fee,
+                referral_fee: self.referral_fee,
+            })
+    }
+}
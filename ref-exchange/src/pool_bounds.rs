@@ -0,0 +1,92 @@
+//! Global bounds of pool parameters.
+//!
+//! Every pool constructor and parameter change is checked against these bounds, so a mistyped
+//! fee or amp can't create a working but predatory pool. Existing pools are not affected by
+//! changes of the bounds.
+
+use crate::stable_swap::math::{MAX_AMP, MIN_AMP};
+use crate::utils::FEE_DIVISOR;
+use crate::*;
+
+/// Default max total fee of a pool, 5% in bps.
+pub const DEFAULT_MAX_POOL_FEE: u32 = 500;
+
+/// Default max share of pool fee going to exchange and referral, 50% in bps.
+pub const DEFAULT_MAX_ADMIN_FEE: u32 = 5_000;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct PoolBounds {
+    /// Max total fee of a pool, in bps of `FEE_DIVISOR`.
+    pub max_fee: u32,
+    /// Amplification coefficient bounds of stable pools.
+    pub min_amp: u64,
+    pub max_amp: u64,
+    /// Max exchange plus referral fee, in bps of pool fee.
+    pub max_admin_fee: u32,
+}
+
+impl Default for PoolBounds {
+    fn default() -> Self {
+        Self {
+            max_fee: DEFAULT_MAX_POOL_FEE,
+            min_amp: MIN_AMP as u64,
+            max_amp: MAX_AMP as u64,
+            max_admin_fee: DEFAULT_MAX_ADMIN_FEE,
+        }
+    }
+}
+
+impl PoolBounds {
+    pub fn assert_fee(&self, fee: u32) {
+        assert!(fee <= self.max_fee, "{}", ERR200_POOL_FEE_OUT_OF_BOUNDS);
+    }
+
+    pub fn assert_amp(&self, amp: u64) {
+        assert!(
+            amp >= self.min_amp && amp <= self.max_amp,
+            "{}",
+            ERR201_AMP_OUT_OF_BOUNDS
+        );
+    }
+
+    pub fn assert_admin_fee(&self, admin_fee: u32) {
+        assert!(
+            admin_fee <= self.max_admin_fee,
+            "{}",
+            ERR202_ADMIN_FEE_OUT_OF_BOUNDS
+        );
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Sets bounds of pool parameters. Only can be called by owner.
+    #[payable]
+    pub fn set_pool_bounds(&mut self, bounds: PoolBounds) {
+        assert_one_yocto();
+        self.assert_owner();
+        assert!(
+            bounds.max_fee < FEE_DIVISOR
+                && bounds.min_amp >= MIN_AMP as u64
+                && bounds.min_amp <= bounds.max_amp
+                && bounds.max_amp <= MAX_AMP as u64
+                && bounds.max_admin_fee <= FEE_DIVISOR,
+            "{}",
+            ERR203_INVALID_POOL_BOUNDS
+        );
+        log!(
+            "Pool bounds set: max fee {}, amp {}..{}, max admin fee {}",
+            bounds.max_fee,
+            bounds.min_amp,
+            bounds.max_amp,
+            bounds.max_admin_fee
+        );
+        self.pool_bounds = bounds;
+    }
+
+    pub fn get_pool_bounds(&self) -> PoolBounds {
+        self.pool_bounds.clone()
+    }
+}
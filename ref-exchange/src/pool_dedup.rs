@@ -0,0 +1,72 @@
+//! Deduplication of simple pools of a token pair.
+//!
+//! Canonical pool of a token pair and fee is the oldest simple pool of just these two tokens
+//! with the fee, found through the token index. Once owner enables deduplication, creating
+//! another such pool fails, so liquidity of the pair isn't fragmented across pools routers have to
+//! choose from. It can be enabled only after all pools are indexed. Pools of more tokens and
+//! existing duplicates are not affected.
+
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// Enables or disables rejection of duplicate simple pools. Only can be called by owner.
+    #[payable]
+    pub fn set_pool_dedup(&mut self, enabled: bool) {
+        assert_one_yocto();
+        self.assert_owner();
+        assert!(
+            !enabled || self.indexed_pools == self.pools.len(),
+            "{}",
+            ERR421_POOLS_NOT_INDEXED
+        );
+        self.pool_dedup = enabled;
+        log!("Pool deduplication set to {}", enabled);
+    }
+
+    pub fn is_pool_dedup_enabled(&self) -> bool {
+        self.pool_dedup
+    }
+
+    /// Returns the oldest indexed simple pool of just the two tokens with given fee.
+    pub fn get_canonical_pool(
+        &self,
+        token_a: ValidAccountId,
+        token_b: ValidAccountId,
+        fee: u32,
+    ) -> Option<u64> {
+        self.internal_get_canonical_pool(token_a.as_ref(), token_b.as_ref(), fee)
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_get_canonical_pool(
+        &self,
+        token_a: &AccountId,
+        token_b: &AccountId,
+        fee: u32,
+    ) -> Option<u64> {
+        let pools_b = self.token_pools.get(token_b).unwrap_or_default();
+        self.token_pools
+            .get(token_a)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|pool_id| pools_b.binary_search(pool_id).is_ok())
+            .find(|pool_id| match self.pools.get(*pool_id).unwrap() {
+                Pool::SimplePool(pool) => pool.token_account_ids.len() == 2 && pool.total_fee == fee,
+                Pool::StableSwapPool(_) | Pool::WeightedPool(_) => false,
+            })
+    }
+
+    /// Panics if deduplication is enabled and the tokens already have a canonical pool.
+    pub(crate) fn assert_not_duplicate_pool(&self, tokens: &[ValidAccountId], fee: u32) {
+        if self.pool_dedup && tokens.len() == 2 {
+            assert!(
+                self.internal_get_canonical_pool(tokens[0].as_ref(), tokens[1].as_ref(), fee)
+                    .is_none(),
+                "{}",
+                ERR422_DUPLICATE_POOL
+            );
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! Index of pools by their tokens.
+//!
+//! Every new pool is added to sorted lists of pool ids of its tokens, so clients can find pools
+//! of a token or a token pair without paging through all pools. Pools created before the index
+//! existed are added in order by owner with `index_token_pools`, pools created meanwhile wait
+//! for it to keep the lists sorted. Index is covered by the exchange.
+
+use crate::*;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct IndexedPoolInfo {
+    pub pool_id: u64,
+    #[serde(flatten)]
+    pub pool: PoolInfo,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Adds up to `limit` next pools not indexed yet to the token index.
+    /// Returns number of indexed pools. Only can be called by owner.
+    #[payable]
+    pub fn index_token_pools(&mut self, limit: u64) -> u64 {
+        assert_one_yocto();
+        self.assert_owner();
+        let end = std::cmp::min(self.indexed_pools + limit, self.pools.len());
+        for pool_id in self.indexed_pools..end {
+            self.internal_index_pool(pool_id);
+        }
+        log!("Indexed pools up to {}", self.indexed_pools);
+        self.indexed_pools
+    }
+
+    /// Returns number of pools in the token index.
+    pub fn get_number_of_indexed_pools(&self) -> u64 {
+        self.indexed_pools
+    }
+
+    /// Returns `limit` pools of the token from `from_index` of its pools, in order of pool ids.
+    pub fn get_pools_by_token(
+        &self,
+        token_id: ValidAccountId,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<IndexedPoolInfo> {
+        self.token_pools
+            .get(token_id.as_ref())
+            .unwrap_or_default()
+            .into_iter()
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .map(|pool_id| self.internal_indexed_pool_info(pool_id))
+            .collect()
+    }
+
+    /// Returns pools having both tokens, in order of pool ids.
+    pub fn get_pools_by_token_pair(
+        &self,
+        token_a: ValidAccountId,
+        token_b: ValidAccountId,
+    ) -> Vec<IndexedPoolInfo> {
+        let pools_a = self.token_pools.get(token_a.as_ref()).unwrap_or_default();
+        let pools_b = self.token_pools.get(token_b.as_ref()).unwrap_or_default();
+        let (shorter, longer) = if pools_a.len() <= pools_b.len() {
+            (pools_a, pools_b)
+        } else {
+            (pools_b, pools_a)
+        };
+        shorter
+            .into_iter()
+            .filter(|pool_id| longer.binary_search(pool_id).is_ok())
+            .map(|pool_id| self.internal_indexed_pool_info(pool_id))
+            .collect()
+    }
+}
+
+impl Contract {
+    /// Adds the pool to the lists of its tokens, must be the next pool not indexed yet.
+    pub(crate) fn internal_index_pool(&mut self, pool_id: u64) {
+        assert_eq!(pool_id, self.indexed_pools);
+        let pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+        for token_id in pool.tokens() {
+            let mut pool_ids = self.token_pools.get(token_id).unwrap_or_default();
+            pool_ids.push(pool_id);
+            self.token_pools.insert(token_id, &pool_ids);
+        }
+        self.indexed_pools += 1;
+    }
+
+    fn internal_indexed_pool_info(&self, pool_id: u64) -> IndexedPoolInfo {
+        IndexedPoolInfo {
+            pool_id,
+            pool: self.get_pool(pool_id),
+        }
+    }
+}
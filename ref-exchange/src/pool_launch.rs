@@ -0,0 +1,60 @@
+//! Anti-sniping delay of newly seeded pools.
+//!
+//! With a launch delay set, swaps of a pool are blocked for that many blocks after its first
+//! liquidity is added, while adding liquidity stays open. Deployers can finish seeding the pool
+//! before bots can trade against it. The delay is pinned when the pool is seeded, changes of
+//! the config don't affect pools already launching.
+
+use crate::*;
+
+/// Max launch delay owner can set, about a day of blocks.
+pub const MAX_POOL_LAUNCH_DELAY: BlockHeight = 86_400;
+
+#[near_bindgen]
+impl Contract {
+    /// Sets number of blocks swaps are blocked for after first liquidity of a pool, 0 disables it.
+    /// Only can be called by owner.
+    #[payable]
+    pub fn set_pool_launch_delay(&mut self, blocks: u64) {
+        assert_one_yocto();
+        self.assert_owner();
+        assert!(
+            blocks <= MAX_POOL_LAUNCH_DELAY,
+            "{}",
+            ERR250_POOL_LAUNCH_DELAY_TOO_LONG
+        );
+        log!("Pool launch delay set to {} blocks", blocks);
+        self.pool_launch_delay = blocks;
+    }
+
+    pub fn get_pool_launch_delay(&self) -> u64 {
+        self.pool_launch_delay
+    }
+}
+
+impl Contract {
+    /// Starts launch delay of the pool that just received its first liquidity.
+    pub(crate) fn internal_launch_pool(&mut self, pool_id: u64) {
+        if self.pool_launch_delay > 0 && !self.pool_swaps_open_at.contains_key(&pool_id) {
+            let open_at = env::block_index() + self.pool_launch_delay;
+            self.pool_swaps_open_at.insert(&pool_id, &open_at);
+            log!("Swaps of pool {} open at block {}", pool_id, open_at);
+        }
+    }
+
+    /// Returns number of blocks until swaps of the pool open, None if they are open.
+    pub(crate) fn internal_remaining_launch_delay(&self, pool_id: u64) -> Option<u64> {
+        self.pool_swaps_open_at
+            .get(&pool_id)
+            .filter(|open_at| *open_at > env::block_index())
+            .map(|open_at| open_at - env::block_index())
+    }
+
+    pub(crate) fn assert_pool_launched(&self, pool_id: u64) {
+        assert!(
+            self.internal_remaining_launch_delay(pool_id).is_none(),
+            "{}",
+            ERR251_POOL_NOT_LAUNCHED
+        );
+    }
+}
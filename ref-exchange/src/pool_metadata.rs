@@ -0,0 +1,90 @@
+//! Display metadata of pools set by their creators.
+//!
+//! Creator of a pool can attach a display name, description, project URL and logo reference to
+//! it, each bounded in size, paying for the storage with attached deposit. The metadata is shown
+//! in `PoolInfo`. Guardians can take down metadata of any pool. Storage fee is not refunded when
+//! metadata is replaced or removed. Pools created before creators were recorded are managed by
+//! owner.
+
+use crate::*;
+
+pub const MAX_POOL_NAME_LEN: usize = 64;
+pub const MAX_POOL_DESCRIPTION_LEN: usize = 512;
+pub const MAX_POOL_LINK_LEN: usize = 256;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct PoolMetadata {
+    pub name: String,
+    pub description: Option<String>,
+    pub project_url: Option<String>,
+    /// URL or content address of the logo.
+    pub logo: Option<String>,
+}
+
+impl PoolMetadata {
+    fn is_valid(&self) -> bool {
+        let link_valid = |link: &Option<String>| {
+            link.as_ref()
+                .filter(|link| link.len() > MAX_POOL_LINK_LEN)
+                .is_none()
+        };
+        !self.name.is_empty()
+            && self.name.len() <= MAX_POOL_NAME_LEN
+            && self
+                .description
+                .as_ref()
+                .filter(|description| description.len() > MAX_POOL_DESCRIPTION_LEN)
+                .is_none()
+            && link_valid(&self.project_url)
+            && link_valid(&self.logo)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Sets metadata of the pool, attached deposit covers its storage.
+    /// Only can be called by creator of the pool or owner.
+    #[payable]
+    pub fn set_pool_metadata(&mut self, pool_id: u64, metadata: PoolMetadata) {
+        self.assert_contract_running();
+        let prev_storage = env::storage_usage();
+        assert!(pool_id < self.pools.len(), "ERR_NO_POOL");
+        let sender_id = env::predecessor_account_id();
+        assert!(
+            sender_id == self.owner_id || self.pool_creators.get(&pool_id) == Some(sender_id),
+            "{}",
+            ERR100_NOT_ALLOWED
+        );
+        assert!(metadata.is_valid(), "{}", ERR320_INVALID_POOL_METADATA);
+        log!("Metadata of pool {} set to {}", pool_id, metadata.name);
+        self.pool_metadata.insert(&pool_id, &metadata);
+        self.internal_check_storage(prev_storage);
+    }
+
+    /// Removes metadata of the pool.
+    /// Only can be called by creator of the pool, owner or guardians.
+    #[payable]
+    pub fn remove_pool_metadata(&mut self, pool_id: u64) {
+        assert_one_yocto();
+        assert!(
+            self.is_owner_or_guardians()
+                || self.pool_creators.get(&pool_id) == Some(env::predecessor_account_id()),
+            "{}",
+            ERR100_NOT_ALLOWED
+        );
+        if self.pool_metadata.remove(&pool_id).is_some() {
+            log!(
+                "Metadata of pool {} removed by {}",
+                pool_id,
+                env::predecessor_account_id()
+            );
+        }
+    }
+
+    /// Returns account that created the pool, None if it was created before creators were recorded.
+    pub fn get_pool_creator(&self, pool_id: u64) -> Option<AccountId> {
+        self.pool_creators.get(&pool_id)
+    }
+}
@@ -0,0 +1,55 @@
+//! Pausing of single pools.
+//!
+//! Guardians can pause a pool, e.g. one with a compromised token, instead of pausing the whole
+//! exchange. Swaps through a paused pool and adding liquidity to it fail, while LPs can still
+//! remove their liquidity. Paused pools are covered by the exchange.
+
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// Pauses swaps and adding liquidity in the pool.
+    /// Only can be called by owner or accounts permitted to pause.
+    #[payable]
+    pub fn pause_pool(&mut self, pool_id: u64) {
+        assert_one_yocto();
+        assert!(self.is_owner_or_permitted(Permission::Pause), "{}", ERR100_NOT_ALLOWED);
+        assert!(pool_id < self.pools.len(), "ERR_NO_POOL");
+        if self.paused_pools.insert(&pool_id) {
+            log!(
+                "Pool {} paused by {}",
+                pool_id,
+                env::predecessor_account_id()
+            );
+        }
+    }
+
+    /// Resumes the paused pool. Only can be called by owner or accounts permitted to pause.
+    #[payable]
+    pub fn resume_pool(&mut self, pool_id: u64) {
+        assert_one_yocto();
+        assert!(self.is_owner_or_permitted(Permission::Pause), "{}", ERR100_NOT_ALLOWED);
+        if self.paused_pools.remove(&pool_id) {
+            log!(
+                "Pool {} resumed by {}",
+                pool_id,
+                env::predecessor_account_id()
+            );
+        }
+    }
+
+    pub fn get_paused_pools(&self) -> Vec<u64> {
+        self.paused_pools.to_vec()
+    }
+}
+
+impl Contract {
+    /// Panics if the pool is paused.
+    pub(crate) fn assert_pool_not_paused(&self, pool_id: u64) {
+        assert!(
+            !self.paused_pools.contains(&pool_id),
+            "{}",
+            ERR391_POOL_PAUSED
+        );
+    }
+}
@@ -0,0 +1,102 @@
+//! Lifetime swap statistics of pools.
+//!
+//! Every swap adds its amounts in and out and the total pool fee charged to the counters of the
+//! pool, so volume and fees can be verified on chain instead of trusting an indexer. Fee is
+//! counted in the token it's taken in, the input token or the output one depending on the fee
+//! side of the pool. Pools created before the stats were added count from the first swap after
+//! the upgrade. Stats are covered by the exchange.
+
+use crate::utils::{FEE_DIVISOR, U256};
+use crate::*;
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct PoolStats {
+    pub swap_count: u64,
+    /// Amounts of the pool tokens swapped into the pool.
+    pub input_volumes: Vec<Balance>,
+    /// Amounts of the pool tokens swapped out of the pool.
+    pub output_volumes: Vec<Balance>,
+    /// Total fees charged in the pool tokens, including exchange and referral fees.
+    pub fees: Vec<Balance>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug, PartialEq))]
+pub struct PoolStatsInfo {
+    pub token_account_ids: Vec<AccountId>,
+    pub swap_count: u64,
+    pub input_volumes: Vec<U128>,
+    pub output_volumes: Vec<U128>,
+    pub fees: Vec<U128>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Returns lifetime swap statistics of the pool.
+    pub fn get_pool_stats(&self, pool_id: u64) -> PoolStatsInfo {
+        let pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+        let num_tokens = pool.tokens().len();
+        let stats = self
+            .pool_stats
+            .get(&pool_id)
+            .unwrap_or_else(|| PoolStats::new(num_tokens));
+        let wrap = |amounts: Vec<Balance>| amounts.into_iter().map(U128).collect();
+        PoolStatsInfo {
+            token_account_ids: pool.tokens().to_vec(),
+            swap_count: stats.swap_count,
+            input_volumes: wrap(stats.input_volumes),
+            output_volumes: wrap(stats.output_volumes),
+            fees: wrap(stats.fees),
+        }
+    }
+}
+
+impl PoolStats {
+    fn new(num_tokens: usize) -> Self {
+        Self {
+            swap_count: 0,
+            input_volumes: vec![0; num_tokens],
+            output_volumes: vec![0; num_tokens],
+            fees: vec![0; num_tokens],
+        }
+    }
+}
+
+impl Contract {
+    /// Adds the swap to the stats of the pool.
+    pub(crate) fn internal_record_pool_swap(
+        &mut self,
+        pool_id: u64,
+        pool: &Pool,
+        token_in: &AccountId,
+        amount_in: Balance,
+        token_out: &AccountId,
+        amount_out: Balance,
+    ) {
+        let tokens = pool.tokens();
+        let index = |token_id: &AccountId| tokens.iter().position(|id| id == token_id).unwrap();
+        let (in_idx, out_idx) = (index(token_in), index(token_out));
+        let mut stats = self
+            .pool_stats
+            .get(&pool_id)
+            .unwrap_or_else(|| PoolStats::new(tokens.len()));
+        let total_fee = U256::from(pool.get_fee());
+        // Amount out is what's left of the output after the fee.
+        let (fee_idx, fee) = match self.internal_get_fee_side(pool_id, pool) {
+            FeeSide::Input => (
+                in_idx,
+                U256::from(amount_in) * total_fee / U256::from(FEE_DIVISOR),
+            ),
+            FeeSide::Output => (
+                out_idx,
+                U256::from(amount_out) * total_fee / (U256::from(FEE_DIVISOR) - total_fee),
+            ),
+        };
+        stats.swap_count += 1;
+        stats.input_volumes[in_idx] += amount_in;
+        stats.output_volumes[out_idx] += amount_out;
+        stats.fees[fee_idx] += fee.as_u128();
+        self.pool_stats.insert(&pool_id, &stats);
+    }
+}
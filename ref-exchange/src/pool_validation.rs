@@ -0,0 +1,153 @@
+//! Dry-run validation of pool creation parameters.
+//!
+//! `validate_pool_params` runs the checks of `add_simple_pool`, `add_stable_swap_pool` and
+//! `add_weighted_pool` against given parameters and returns every violation found, so UIs can pre-validate
+//! a pool instead of decoding the panic of a failed transaction.
+
+use std::collections::HashSet;
+
+use crate::simple_pool::{MAX_SIMPLE_POOL_TOKENS, MIN_SIMPLE_POOL_TOKENS};
+use crate::stable_swap::{MAX_DECIMAL, MIN_DECIMAL};
+use crate::utils::FEE_DIVISOR;
+use crate::weighted_pool::{
+    MAX_WEIGHTED_POOL_TOKENS, MIN_WEIGHT, MIN_WEIGHTED_POOL_TOKENS, WEIGHT_DIVISOR,
+};
+use crate::*;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub enum PoolParamViolation {
+    /// Kind is not one of `pool_kind`s listed in `get_exchange_info`.
+    UnknownPoolKind,
+    TokenCountOutOfRange { min: u32, max: Option<u32> },
+    DuplicateToken { token_id: AccountId },
+    /// Token is banned by owner, pools of it can't be created.
+    TokenBanned { token_id: AccountId },
+    /// Pool can be created, but only accounts that registered the token can deposit it.
+    TokenNotWhitelisted { token_id: AccountId },
+    FeeOutOfBounds { max_fee: u32 },
+    /// Fee of a simple or weighted pool is not one of the allowed fee tiers.
+    FeeNotInTiers { fee_tiers: Vec<u32> },
+    /// Deduplication is enabled and the token pair already has a simple pool with the fee.
+    DuplicatePool { pool_id: u64 },
+    /// Decimals are missing, don't match the tokens or some are out of supported range.
+    InvalidDecimals { min: u8, max: u8 },
+    AmpOutOfBounds { min_amp: u64, max_amp: u64 },
+    /// Weights are missing, don't match the tokens, some are below min or they don't sum up to
+    /// the divisor.
+    InvalidWeights { min_weight: u32, weight_divisor: u32 },
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Returns violations of pool creation rules by given parameters, empty if pool can be created.
+    /// `decimals` and `amp_factor` are only checked for stable pools, `weights` for weighted pools.
+    pub fn validate_pool_params(
+        &self,
+        pool_kind: String,
+        tokens: Vec<ValidAccountId>,
+        fee: u32,
+        decimals: Option<Vec<u8>>,
+        amp_factor: Option<u64>,
+        weights: Option<Vec<u32>>,
+    ) -> Vec<PoolParamViolation> {
+        let (min_tokens, max_tokens) = match pool_kind.as_str() {
+            "SIMPLE_POOL" => (
+                MIN_SIMPLE_POOL_TOKENS as u32,
+                Some(std::cmp::min(
+                    MAX_SIMPLE_POOL_TOKENS as u32,
+                    self.action_limits.max_pool_tokens,
+                )),
+            ),
+            "STABLE_SWAP" => (2, Some(self.action_limits.max_pool_tokens)),
+            "WEIGHTED_POOL" => (
+                MIN_WEIGHTED_POOL_TOKENS as u32,
+                Some(std::cmp::min(
+                    MAX_WEIGHTED_POOL_TOKENS as u32,
+                    self.action_limits.max_pool_tokens,
+                )),
+            ),
+            _ => return vec![PoolParamViolation::UnknownPoolKind],
+        };
+        let mut violations = vec![];
+        let num_tokens = tokens.len() as u32;
+        if num_tokens < min_tokens || max_tokens.filter(|max| num_tokens > *max).is_some() {
+            violations.push(PoolParamViolation::TokenCountOutOfRange {
+                min: min_tokens,
+                max: max_tokens,
+            });
+        }
+        let mut seen = HashSet::new();
+        for token_id in tokens.iter().map(|token_id| token_id.as_ref()) {
+            if !seen.insert(token_id) {
+                violations.push(PoolParamViolation::DuplicateToken {
+                    token_id: token_id.clone(),
+                });
+            } else if self.banned_tokens.contains(token_id) {
+                violations.push(PoolParamViolation::TokenBanned {
+                    token_id: token_id.clone(),
+                });
+            } else if !self.whitelisted_tokens.contains(token_id) {
+                violations.push(PoolParamViolation::TokenNotWhitelisted {
+                    token_id: token_id.clone(),
+                });
+            }
+        }
+        let max_fee = std::cmp::min(self.pool_bounds.max_fee, FEE_DIVISOR - 1);
+        if fee > max_fee {
+            violations.push(PoolParamViolation::FeeOutOfBounds { max_fee });
+        }
+        if pool_kind != "STABLE_SWAP" && !self.is_fee_tier(fee) {
+            violations.push(PoolParamViolation::FeeNotInTiers {
+                fee_tiers: self.pool_fee_tiers.clone(),
+            });
+        }
+        if pool_kind == "SIMPLE_POOL" && self.pool_dedup && tokens.len() == 2 {
+            if let Some(pool_id) =
+                self.internal_get_canonical_pool(tokens[0].as_ref(), tokens[1].as_ref(), fee)
+            {
+                violations.push(PoolParamViolation::DuplicatePool { pool_id });
+            }
+        }
+        if pool_kind == "STABLE_SWAP" {
+            let valid_decimals = match decimals {
+                Some(decimals) => {
+                    decimals.len() == tokens.len()
+                        && decimals
+                            .iter()
+                            .all(|decimal| *decimal >= MIN_DECIMAL && *decimal <= MAX_DECIMAL)
+                }
+                None => false,
+            };
+            if !valid_decimals {
+                violations.push(PoolParamViolation::InvalidDecimals {
+                    min: MIN_DECIMAL,
+                    max: MAX_DECIMAL,
+                });
+            }
+            let (min_amp, max_amp) = (self.pool_bounds.min_amp, self.pool_bounds.max_amp);
+            if !matches!(amp_factor, Some(amp) if amp >= min_amp && amp <= max_amp) {
+                violations.push(PoolParamViolation::AmpOutOfBounds { min_amp, max_amp });
+            }
+        }
+        if pool_kind == "WEIGHTED_POOL" {
+            let valid_weights = match weights {
+                Some(weights) => {
+                    weights.len() == tokens.len()
+                        && weights.iter().all(|weight| *weight >= MIN_WEIGHT)
+                        && weights.iter().map(|weight| *weight as u64).sum::<u64>()
+                            == WEIGHT_DIVISOR as u64
+                }
+                None => false,
+            };
+            if !valid_weights {
+                violations.push(PoolParamViolation::InvalidWeights {
+                    min_weight: MIN_WEIGHT,
+                    weight_divisor: WEIGHT_DIVISOR,
+                });
+            }
+        }
+        violations
+    }
+}
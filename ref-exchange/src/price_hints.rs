@@ -0,0 +1,162 @@
+//! Reference prices pushed by registered oracles.
+//!
+//! Owner registers oracle accounts and selects tokens that accept price hints, each with its
+//! max age. An oracle pushes the price of a selected token in the reference token, as the amount
+//! of reference token per unit of the token, both in their smallest units, equal to
+//! `multiplier / 10^decimals`. A hint older than the max age of its token is stale and ignored.
+//! Valuation of liquidity events prefers a fresh hint over the spot price of the reference pool,
+//! so tokens with only a thin pool can still be valued. Hint records are covered by the exchange.
+
+use near_sdk::json_types::{WrappedDuration, WrappedTimestamp};
+
+use crate::utils::U256;
+use crate::*;
+
+/// Max decimals of a price hint multiplier.
+pub const MAX_PRICE_HINT_DECIMALS: u8 = 48;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct PriceHint {
+    pub multiplier: U128,
+    pub decimals: u8,
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct PriceHintRecord {
+    pub hint: PriceHint,
+    pub oracle_id: AccountId,
+    pub timestamp: Timestamp,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug))]
+pub struct PriceHintInfo {
+    pub token_id: AccountId,
+    pub hint: PriceHint,
+    pub oracle_id: AccountId,
+    pub timestamp: WrappedTimestamp,
+    /// Time since the hint was pushed.
+    pub age: WrappedDuration,
+    pub max_age: WrappedDuration,
+    pub stale: bool,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Registers accounts allowed to push price hints. Only can be called by owner.
+    #[payable]
+    pub fn extend_price_oracles(&mut self, oracle_ids: Vec<ValidAccountId>) {
+        assert_one_yocto();
+        self.assert_owner();
+        for oracle_id in oracle_ids {
+            self.price_oracles.insert(oracle_id.as_ref());
+        }
+    }
+
+    /// Only can be called by owner.
+    #[payable]
+    pub fn remove_price_oracles(&mut self, oracle_ids: Vec<ValidAccountId>) {
+        assert_one_yocto();
+        self.assert_owner();
+        for oracle_id in oracle_ids {
+            self.price_oracles.remove(oracle_id.as_ref());
+        }
+    }
+
+    /// Selects token to accept price hints with given max age, None stops accepting them and
+    /// removes the current hint. Only can be called by owner.
+    #[payable]
+    pub fn set_price_hint_max_age(
+        &mut self,
+        token_id: ValidAccountId,
+        max_age: Option<WrappedDuration>,
+    ) {
+        assert_one_yocto();
+        self.assert_owner();
+        match max_age {
+            Some(max_age) => {
+                self.price_hint_max_ages.insert(token_id.as_ref(), &max_age.0);
+                log!("Price hints of {} accepted for {}", token_id.as_ref(), max_age.0);
+            }
+            None => {
+                self.price_hint_max_ages.remove(token_id.as_ref());
+                self.price_hints.remove(token_id.as_ref());
+                log!("Price hints of {} not accepted", token_id.as_ref());
+            }
+        }
+    }
+
+    /// Pushes prices of selected tokens in the reference token. Only can be called by oracles.
+    pub fn push_price_hints(&mut self, hints: Vec<(ValidAccountId, PriceHint)>) {
+        let oracle_id = env::predecessor_account_id();
+        assert!(self.price_oracles.contains(&oracle_id), "{}", ERR100_NOT_ALLOWED);
+        for (token_id, hint) in hints {
+            assert!(
+                self.price_hint_max_ages.get(token_id.as_ref()).is_some(),
+                "{}",
+                ERR310_PRICE_HINT_NOT_ACCEPTED
+            );
+            assert!(
+                hint.decimals <= MAX_PRICE_HINT_DECIMALS,
+                "{}",
+                ERR311_INVALID_PRICE_HINT
+            );
+            self.price_hints.insert(
+                token_id.as_ref(),
+                &PriceHintRecord {
+                    hint,
+                    oracle_id: oracle_id.clone(),
+                    timestamp: env::block_timestamp(),
+                },
+            );
+        }
+    }
+
+    pub fn get_price_oracles(&self) -> Vec<AccountId> {
+        self.price_oracles.to_vec()
+    }
+
+    /// Returns hints of selected tokens with their age.
+    pub fn get_price_hints(&self) -> Vec<PriceHintInfo> {
+        self.price_hint_max_ages
+            .iter()
+            .filter_map(|(token_id, max_age)| {
+                let record = self.price_hints.get(&token_id)?;
+                let age = env::block_timestamp().saturating_sub(record.timestamp);
+                Some(PriceHintInfo {
+                    token_id,
+                    hint: record.hint,
+                    oracle_id: record.oracle_id,
+                    timestamp: record.timestamp.into(),
+                    age: age.into(),
+                    max_age: max_age.into(),
+                    stale: age > max_age,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Contract {
+    /// Returns value of the amount of the token in the reference token by its fresh price hint.
+    pub(crate) fn internal_value_by_price_hint(
+        &self,
+        token_id: &AccountId,
+        amount: Balance,
+    ) -> Option<Balance> {
+        let max_age = self.price_hint_max_ages.get(token_id)?;
+        let record = self.price_hints.get(token_id)?;
+        if env::block_timestamp().saturating_sub(record.timestamp) > max_age {
+            return None;
+        }
+        let value = U256::from(amount).checked_mul(U256::from(record.hint.multiplier.0))?
+            / U256::exp10(record.hint.decimals as usize);
+        if value > U256::from(Balance::MAX) {
+            return None;
+        }
+        Some(value.as_u128())
+    }
+}
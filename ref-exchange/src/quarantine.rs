@@ -0,0 +1,159 @@
+//! Quarantine of pools whose token contract is deleted or broken.
+//!
+//! Failed withdraws are counted per token, a successful one resets the counter.
+//! After `MIN_FAILED_WITHDRAWS` failures in a row guardians can mark the token as unresponsive.
+//! Swaps in and out of unresponsive token and adding liquidity to its pools are paused, while LPs
+//! can still exit: the unresponsive token part of removed liquidity is kept as quarantined amount
+//! of the LP, the working tokens are received as usual. Once the token is marked responsive again
+//! the LP claims quarantined amount to its deposits. Quarantined amounts are covered by the
+//! exchange, there is one per LP and token.
+
+use std::collections::HashMap;
+
+use crate::*;
+
+/// Failed withdraws in a row required before a token can be marked as unresponsive.
+pub const MIN_FAILED_WITHDRAWS: u32 = 3;
+
+#[near_bindgen]
+impl Contract {
+    /// Marks token as unresponsive, pausing swaps through it. Only can be called by owner or guardians.
+    pub fn mark_token_unresponsive(&mut self, token_id: ValidAccountId) {
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        let failures = self
+            .token_withdraw_failures
+            .get(token_id.as_ref())
+            .unwrap_or(0);
+        assert!(
+            failures >= MIN_FAILED_WITHDRAWS,
+            "{}",
+            ERR160_NOT_ENOUGH_FAILED_WITHDRAWS
+        );
+        self.unresponsive_tokens.insert(token_id.as_ref());
+        log!(
+            "Token {} marked unresponsive by {} after {} failed withdraws",
+            token_id,
+            env::predecessor_account_id(),
+            failures
+        );
+    }
+
+    /// Lifts quarantine of the token and resets its failed withdraws.
+    /// Only can be called by owner or guardians.
+    pub fn mark_token_responsive(&mut self, token_id: ValidAccountId) {
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        self.unresponsive_tokens.remove(token_id.as_ref());
+        self.token_withdraw_failures.remove(token_id.as_ref());
+        log!(
+            "Token {} marked responsive by {}",
+            token_id,
+            env::predecessor_account_id()
+        );
+    }
+
+    pub fn get_unresponsive_tokens(&self) -> Vec<AccountId> {
+        self.unresponsive_tokens.to_vec()
+    }
+
+    /// Deposits quarantined amount of the responsive token to the caller's account.
+    /// Returns claimed amount.
+    #[payable]
+    pub fn claim_quarantined_tokens(&mut self, token_id: ValidAccountId) -> U128 {
+        assert_one_yocto();
+        self.assert_contract_running();
+        let token_id: AccountId = token_id.into();
+        self.assert_token_responsive(&token_id);
+        let account_id = env::predecessor_account_id();
+        let mut quarantined = self.quarantined_amounts.get(&account_id).unwrap_or_default();
+        let amount = quarantined.remove(&token_id).expect(ERR162_NO_QUARANTINED_AMOUNT);
+        if quarantined.is_empty() {
+            self.quarantined_amounts.remove(&account_id);
+        } else {
+            self.quarantined_amounts.insert(&account_id, &quarantined);
+        }
+        let mut account = self.internal_unwrap_account(&account_id);
+        account.deposit(&token_id, amount);
+        self.internal_save_account(&account_id, account);
+        log!("Account {} claimed quarantined {} {}", account_id, amount, token_id);
+        U128(amount)
+    }
+
+    /// Returns quarantined amounts of the account by token.
+    pub fn get_quarantined_amounts(&self, account_id: ValidAccountId) -> HashMap<AccountId, U128> {
+        self.quarantined_amounts
+            .get(account_id.as_ref())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(token_id, amount)| (token_id, U128(amount)))
+            .collect()
+    }
+
+    /// Returns number of failed withdraws of the token in a row.
+    pub fn get_token_withdraw_failures(&self, token_id: ValidAccountId) -> u32 {
+        self.token_withdraw_failures
+            .get(token_id.as_ref())
+            .unwrap_or(0)
+    }
+}
+
+impl Contract {
+    /// Counts failed withdraws of the token, resets the counter on success.
+    /// Counter records are covered by the exchange.
+    pub(crate) fn internal_record_withdraw_result(&mut self, token_id: &AccountId, success: bool) {
+        if success {
+            if self.token_withdraw_failures.get(token_id).is_some() {
+                self.token_withdraw_failures.remove(token_id);
+            }
+        } else {
+            let failures = self.token_withdraw_failures.get(token_id).unwrap_or(0) + 1;
+            self.token_withdraw_failures.insert(token_id, &failures);
+        }
+    }
+
+    pub(crate) fn assert_token_responsive(&self, token_id: &AccountId) {
+        assert!(
+            !self.unresponsive_tokens.contains(token_id),
+            "{}",
+            ERR161_TOKEN_UNRESPONSIVE
+        );
+    }
+
+    /// Panics if any token of the pool is unresponsive.
+    pub(crate) fn assert_pool_responsive(&self, pool: &Pool) {
+        for token_id in pool.tokens() {
+            self.assert_token_responsive(token_id);
+        }
+    }
+
+    /// Keeps unresponsive tokens' part of liquidity removed by the account as its quarantined
+    /// amounts, returns what the account receives.
+    pub(crate) fn internal_quarantine_removed_liquidity(
+        &mut self,
+        account_id: &AccountId,
+        tokens: &[AccountId],
+        mut amounts: Vec<Balance>,
+    ) -> Vec<Balance> {
+        let mut quarantined = None;
+        for i in 0..tokens.len() {
+            if amounts[i] > 0 && self.unresponsive_tokens.contains(&tokens[i]) {
+                log!(
+                    "Unresponsive token {} amount {} of removed liquidity quarantined for {}",
+                    tokens[i],
+                    amounts[i],
+                    account_id
+                );
+                *quarantined
+                    .get_or_insert_with(|| {
+                        self.quarantined_amounts.get(account_id).unwrap_or_default()
+                    })
+                    .entry(tokens[i].clone())
+                    .or_insert(0) += amounts[i];
+                amounts[i] = 0;
+            }
+        }
+        if let Some(quarantined) = quarantined {
+            self.quarantined_amounts.insert(account_id, &quarantined);
+        }
+        amounts
+    }
+}
@@ -0,0 +1,93 @@
+//! Quotes pinned to the pool state.
+//!
+//! Each pool has a nonce that increases with every change of its reserves or parameters.
+//! A quote id is a hash over the pool nonce and swap params, so a swap action carrying it
+//! is rejected if the pool mutated more than `MAX_QUOTE_POOL_MUTATIONS` times since the quote.
+
+use near_sdk::json_types::U64;
+
+use crate::*;
+
+/// Number of pool mutations after which a quote can not be executed anymore.
+pub const MAX_QUOTE_POOL_MUTATIONS: u64 = 2;
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug))]
+pub struct ReturnQuote {
+    /// Amount of token_out received at the current pool state.
+    pub amount_out: U128,
+    /// Nonce of the pool at the moment of quote.
+    pub pool_nonce: U64,
+    /// Id to pass in the swap action to execute against not too much changed pool.
+    pub quote_id: String,
+}
+
+/// Returns base58 encoded sha256 of the pool nonce and swap params.
+pub(crate) fn compute_quote_id(
+    pool_id: u64,
+    pool_nonce: u64,
+    token_in: &AccountId,
+    amount_in: Balance,
+    token_out: &AccountId,
+) -> String {
+    let data = (pool_id, pool_nonce, token_in, amount_in, token_out)
+        .try_to_vec()
+        .expect("ERR_SERIALIZE_QUOTE");
+    near_sdk::bs58::encode(env::sha256(&data)).into_string()
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Same as `get_return`, but also returns the quote id pinned to the current pool state.
+    pub fn get_return_quote(
+        &self,
+        pool_id: u64,
+        token_in: ValidAccountId,
+        amount_in: U128,
+        token_out: ValidAccountId,
+    ) -> ReturnQuote {
+        let pool_nonce = self.pool_nonces.get(&pool_id).unwrap_or(0);
+        ReturnQuote {
+            amount_out: self.get_return(pool_id, token_in.clone(), amount_in, token_out.clone()),
+            pool_nonce: pool_nonce.into(),
+            quote_id: compute_quote_id(
+                pool_id,
+                pool_nonce,
+                token_in.as_ref(),
+                amount_in.0,
+                token_out.as_ref(),
+            ),
+        }
+    }
+
+    /// Returns number of mutations of given pool.
+    pub fn get_pool_nonce(&self, pool_id: u64) -> U64 {
+        self.pool_nonces.get(&pool_id).unwrap_or(0).into()
+    }
+}
+
+impl Contract {
+    /// Saves changed pool and increases its nonce.
+    pub(crate) fn internal_save_pool(&mut self, pool_id: u64, pool: &Pool) {
+        self.pools.replace(pool_id, pool);
+        let nonce = self.pool_nonces.get(&pool_id).unwrap_or(0);
+        self.pool_nonces.insert(&pool_id, &(nonce + 1));
+    }
+
+    /// Panics if the quote id was not issued for given params within last allowed pool mutations.
+    pub(crate) fn assert_quote(
+        &self,
+        quote_id: &str,
+        pool_id: u64,
+        token_in: &AccountId,
+        amount_in: Balance,
+        token_out: &AccountId,
+    ) {
+        let nonce = self.pool_nonces.get(&pool_id).unwrap_or(0);
+        let valid = (nonce.saturating_sub(MAX_QUOTE_POOL_MUTATIONS)..=nonce).any(|pool_nonce| {
+            compute_quote_id(pool_id, pool_nonce, token_in, amount_in, token_out) == quote_id
+        });
+        assert!(valid, "{}", ERR72_QUOTE_EXPIRED);
+    }
+}
@@ -0,0 +1,113 @@
+//! Registry of referrers with tiered referral fees.
+//!
+//! Referrers register themselves, paying for the storage with attached deposit. Owner sets a
+//! table of tier fees and assigns registered referrers to its tiers, new referrers start at the
+//! first tier. Swaps referred by a registered referrer use the fee of its tier instead of the
+//! default referral fee, other referrals keep the default one. Referral fee tiers of the input
+//! token and referral budgets apply on top as usual.
+
+use crate::utils::FEE_DIVISOR;
+use crate::*;
+
+/// Max number of referral tiers.
+pub const MAX_REFERRAL_TIERS: usize = 10;
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug, PartialEq))]
+pub struct ReferrerInfo {
+    pub tier: u32,
+    /// Referral fee applied to swaps of the referrer.
+    pub referral_fee: u32,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Registers the caller as referrer at the first tier, attached deposit covers its storage.
+    #[payable]
+    pub fn register_referrer(&mut self) {
+        self.assert_contract_running();
+        let prev_storage = env::storage_usage();
+        let sender_id = env::predecessor_account_id();
+        assert!(
+            !self.referrers.contains_key(&sender_id),
+            "{}",
+            ERR392_REFERRER_ALREADY_REGISTERED
+        );
+        self.referrers.insert(&sender_id, &0);
+        self.internal_check_storage(prev_storage);
+        log!("Referrer {} registered", sender_id);
+    }
+
+    /// Replaces referral fees of the tiers, in bps of `FEE_DIVISOR`. Referrers of removed tiers
+    /// get the default referral fee. Only can be called by owner, timelocked.
+    #[payable]
+    pub fn set_referral_tier_fees(&mut self, fees: Vec<u32>) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.assert_not_timelocked();
+        self.internal_set_referral_tier_fees(fees);
+    }
+
+    /// Assigns registered referrer to the tier. Only can be called by owner.
+    #[payable]
+    pub fn set_referrer_tier(&mut self, referrer_id: ValidAccountId, tier: u32) {
+        assert_one_yocto();
+        self.assert_owner();
+        assert!(
+            self.referrers.contains_key(referrer_id.as_ref()),
+            "{}",
+            ERR393_REFERRER_NOT_REGISTERED
+        );
+        assert!(
+            (tier as usize) < self.referral_tier_fees.len(),
+            "{}",
+            ERR394_INVALID_REFERRAL_TIER
+        );
+        self.referrers.insert(referrer_id.as_ref(), &tier);
+        log!("Referrer {} set to tier {}", referrer_id.as_ref(), tier);
+    }
+
+    pub fn get_referral_tier_fees(&self) -> Vec<u32> {
+        self.referral_tier_fees.clone()
+    }
+
+    /// Returns tier of the referrer, None if it's not registered.
+    pub fn get_referrer(&self, referrer_id: ValidAccountId) -> Option<ReferrerInfo> {
+        let tier = self.referrers.get(referrer_id.as_ref())?;
+        Some(ReferrerInfo {
+            tier,
+            referral_fee: self.internal_referral_fee(&Some(referrer_id.into()), self.referral_fee),
+        })
+    }
+}
+
+impl Contract {
+    /// Returns referral fee of the referrer's tier, or the given default one.
+    pub(crate) fn internal_referral_fee(
+        &self,
+        referral_id: &Option<AccountId>,
+        default_fee: u32,
+    ) -> u32 {
+        referral_id
+            .as_ref()
+            .and_then(|referral_id| self.referrers.get(referral_id))
+            .and_then(|tier| self.referral_tier_fees.get(tier as usize).cloned())
+            .unwrap_or(default_fee)
+    }
+
+    /// Replaces referral fees of the tiers, see `set_referral_tier_fees`.
+    pub(crate) fn internal_set_referral_tier_fees(&mut self, fees: Vec<u32>) {
+        assert!(
+            fees.len() <= MAX_REFERRAL_TIERS,
+            "{}",
+            ERR394_INVALID_REFERRAL_TIER
+        );
+        for fee in fees.iter() {
+            assert!(self.exchange_fee + fee <= FEE_DIVISOR, "ERR_ILLEGAL_FEE");
+            self.pool_bounds.assert_admin_fee(self.exchange_fee + fee);
+        }
+        log!("Referral tier fees set to {:?}", fees);
+        self.referral_tier_fees = fees;
+    }
+}
@@ -0,0 +1,142 @@
+//! Per epoch caps on referral fees.
+//!
+//! Owner can cap the referral fees paid on swaps of a given input token within an epoch.
+//! Referral fee of a swap is counted in the input token as `amount_in * pool fee * referral fee`.
+//! Once the cap would be exceeded, referral share of further swaps goes to the exchange until
+//! the next epoch. Records are kept per token and their storage is covered by the exchange.
+
+use near_sdk::json_types::WrappedDuration;
+
+use crate::admin_fee::AdminFees;
+use crate::utils::{FEE_DIVISOR, U256};
+use crate::*;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct ReferralBudget {
+    /// Max referral fees per epoch, in the input token.
+    pub max_per_epoch: U128,
+    pub epoch_duration: WrappedDuration,
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct ReferralBudgetRecord {
+    pub budget: ReferralBudget,
+    pub epoch: u64,
+    pub spent: Balance,
+}
+
+impl ReferralBudgetRecord {
+    fn current_epoch(&self) -> u64 {
+        env::block_timestamp() / self.budget.epoch_duration.0
+    }
+
+    /// Returns referral fees spent in the current epoch.
+    fn current_spent(&self) -> Balance {
+        if self.epoch == self.current_epoch() {
+            self.spent
+        } else {
+            0
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug))]
+pub struct ReferralBudgetInfo {
+    pub max_per_epoch: U128,
+    pub epoch_duration: WrappedDuration,
+    pub epoch: u64,
+    /// Referral fees paid in the current epoch.
+    pub spent: U128,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Sets referral budget of swaps from given token, None removes the cap.
+    /// Only can be called by owner.
+    #[payable]
+    pub fn set_referral_budget(&mut self, token_id: ValidAccountId, budget: Option<ReferralBudget>) {
+        assert_one_yocto();
+        self.assert_owner();
+        match budget {
+            Some(budget) => {
+                assert!(budget.epoch_duration.0 > 0, "{}", ERR240_INVALID_REFERRAL_BUDGET);
+                log!(
+                    "Referral budget of {} set to {} per {}",
+                    token_id.as_ref(),
+                    budget.max_per_epoch.0,
+                    budget.epoch_duration.0
+                );
+                let mut record = ReferralBudgetRecord {
+                    budget,
+                    epoch: 0,
+                    spent: 0,
+                };
+                if let Some(prev) = self.referral_budgets.get(token_id.as_ref()) {
+                    // Keep what was spent in the current epoch of the same duration.
+                    if prev.budget.epoch_duration.0 == record.budget.epoch_duration.0 {
+                        record.epoch = prev.epoch;
+                        record.spent = prev.spent;
+                    }
+                }
+                self.referral_budgets.insert(token_id.as_ref(), &record);
+            }
+            None => {
+                self.referral_budgets.remove(token_id.as_ref());
+                log!("Referral budget of {} removed", token_id.as_ref());
+            }
+        }
+    }
+
+    pub fn get_referral_budget(&self, token_id: ValidAccountId) -> Option<ReferralBudgetInfo> {
+        self.referral_budgets
+            .get(token_id.as_ref())
+            .map(|record| ReferralBudgetInfo {
+                max_per_epoch: record.budget.max_per_epoch,
+                epoch_duration: record.budget.epoch_duration,
+                epoch: record.current_epoch(),
+                spent: U128(record.current_spent()),
+            })
+    }
+}
+
+impl Contract {
+    /// Counts referral fee of the swap against the budget of `token_in`.
+    /// If the budget is exhausted, moves referral fee to the exchange.
+    pub(crate) fn internal_apply_referral_budget(
+        &mut self,
+        mut admin_fee: AdminFees,
+        token_in: &AccountId,
+        amount_in: Balance,
+        pool_fee: u32,
+    ) -> AdminFees {
+        if admin_fee.referral_id.is_none() || admin_fee.referral_fee == 0 {
+            return admin_fee;
+        }
+        let mut record = match self.referral_budgets.get(token_in) {
+            Some(record) => record,
+            None => return admin_fee,
+        };
+        let fee = (U256::from(amount_in) * U256::from(pool_fee) * U256::from(admin_fee.referral_fee)
+            / U256::from(FEE_DIVISOR)
+            / U256::from(FEE_DIVISOR))
+        .as_u128();
+        let spent = record.current_spent() + fee;
+        if spent > record.budget.max_per_epoch.0 {
+            log!(
+                "Referral budget of {} exhausted, referral fee goes to exchange",
+                token_in
+            );
+            admin_fee.exchange_fee += admin_fee.referral_fee;
+            admin_fee.referral_fee = 0;
+            return admin_fee;
+        }
+        record.epoch = record.current_epoch();
+        record.spent = spent;
+        self.referral_budgets.insert(token_in, &record);
+        admin_fee
+    }
+}
@@ -0,0 +1,163 @@
+//! Roles of accounts operating the exchange on behalf of owner.
+//!
+//! Owner grants accounts specific permissions, stored as bit flags of the account, so e.g. a key
+//! managing the whitelist can't pause the exchange. Guardians are marked by their own flag, apart
+//! from the permission flags, so granting every permission doesn't make a guardian and adding a
+//! permission doesn't demote one. Guardians hold all permissions and only they can call the other
+//! methods allowed to guardians. Owner has every permission. Roles are covered by the exchange.
+
+use near_sdk::serde_json;
+
+use crate::*;
+
+/// Permission to call a group of methods.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub enum Permission {
+    /// Change state of the exchange and pause single pools.
+    Pause,
+    /// Extend and remove whitelisted tokens.
+    Whitelist,
+    /// Add stable swap pools.
+    CreateStablePool,
+    /// Ramp amplification of stable swap pools.
+    RampAmp,
+}
+
+/// All permissions, in order of their flags.
+pub const PERMISSIONS: [Permission; 4] = [
+    Permission::Pause,
+    Permission::Whitelist,
+    Permission::CreateStablePool,
+    Permission::RampAmp,
+];
+
+/// Flag of guardians, kept apart from the permission flags.
+pub const GUARDIAN_FLAG: u32 = 1 << 31;
+
+impl Permission {
+    pub fn flag(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+/// Account with its permissions.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug, PartialEq))]
+pub struct RoleInfo {
+    pub account_id: AccountId,
+    pub permissions: Vec<Permission>,
+    pub guardian: bool,
+}
+
+/// Returns permissions held with given flags, guardians hold all of them.
+fn permissions_of(flags: u32) -> Vec<Permission> {
+    PERMISSIONS
+        .iter()
+        .filter(|permission| flags & (GUARDIAN_FLAG | permission.flag()) != 0)
+        .cloned()
+        .collect()
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Grants permissions to the account. Only can be called by owner.
+    #[payable]
+    pub fn grant_permissions(&mut self, account_id: ValidAccountId, permissions: Vec<Permission>) {
+        assert_one_yocto();
+        self.assert_owner();
+        let flags = permissions.iter().fold(
+            self.internal_get_permission_flags(account_id.as_ref()),
+            |flags, permission| flags | permission.flag(),
+        );
+        self.internal_set_permission_flags(account_id.as_ref(), flags);
+    }
+
+    /// Revokes permissions of the account, account without permissions loses its role.
+    /// Only can be called by owner.
+    #[payable]
+    pub fn revoke_permissions(&mut self, account_id: ValidAccountId, permissions: Vec<Permission>) {
+        assert_one_yocto();
+        self.assert_owner();
+        let flags = permissions.iter().fold(
+            self.internal_get_permission_flags(account_id.as_ref()),
+            |flags, permission| flags & !permission.flag(),
+        );
+        self.internal_set_permission_flags(account_id.as_ref(), flags);
+    }
+
+    pub fn get_permissions(&self, account_id: ValidAccountId) -> Vec<Permission> {
+        permissions_of(self.internal_get_permission_flags(account_id.as_ref()))
+    }
+
+    /// Returns `limit` accounts with roles from `from_index`.
+    pub fn get_roles(&self, from_index: u64, limit: u64) -> Vec<RoleInfo> {
+        let keys = self.roles.keys_as_vector();
+        let values = self.roles.values_as_vector();
+        (from_index..std::cmp::min(from_index + limit, keys.len()))
+            .map(|index| {
+                let flags = values.get(index).unwrap();
+                RoleInfo {
+                    account_id: keys.get(index).unwrap(),
+                    permissions: permissions_of(flags),
+                    guardian: flags & GUARDIAN_FLAG != 0,
+                }
+            })
+            .collect()
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_get_permission_flags(&self, account_id: &AccountId) -> u32 {
+        self.roles.get(account_id).unwrap_or(0)
+    }
+
+    /// Sets flags of the account, removing its role if there are none.
+    pub(crate) fn internal_set_permission_flags(&mut self, account_id: &AccountId, flags: u32) {
+        if flags == 0 {
+            self.roles.remove(account_id);
+        } else {
+            self.roles.insert(account_id, &flags);
+        }
+        log!(
+            "Permissions of {} set to {}{}",
+            account_id,
+            serde_json::to_string(&permissions_of(flags)).unwrap(),
+            if flags & GUARDIAN_FLAG != 0 { " as guardian" } else { "" }
+        );
+    }
+
+    /// Sets or clears the guardian flag of the account, keeping its permission flags.
+    pub(crate) fn internal_set_guardian(&mut self, account_id: &AccountId, guardian: bool) {
+        let flags = self.internal_get_permission_flags(account_id);
+        if guardian {
+            self.internal_set_permission_flags(account_id, flags | GUARDIAN_FLAG);
+        } else {
+            self.internal_set_permission_flags(account_id, flags & !GUARDIAN_FLAG);
+        }
+    }
+
+    pub(crate) fn internal_is_guardian(&self, account_id: &AccountId) -> bool {
+        self.internal_get_permission_flags(account_id) & GUARDIAN_FLAG != 0
+    }
+
+    /// Returns accounts with the guardian flag.
+    pub(crate) fn internal_get_guardians(&self) -> Vec<AccountId> {
+        self.roles
+            .iter()
+            .filter(|(_, flags)| flags & GUARDIAN_FLAG != 0)
+            .map(|(account_id, _)| account_id)
+            .collect()
+    }
+
+    /// Returns true if predecessor is owner, guardian or holds the permission.
+    pub(crate) fn is_owner_or_permitted(&self, permission: Permission) -> bool {
+        let predecessor_id = env::predecessor_account_id();
+        predecessor_id == self.owner_id
+            || self.internal_get_permission_flags(&predecessor_id)
+                & (GUARDIAN_FLAG | permission.flag())
+                != 0
+    }
+}
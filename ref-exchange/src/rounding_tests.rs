@@ -0,0 +1,195 @@
+//! Differential tests of pool math against arbitrary precision reference implementations.
+//! Every rounding step is checked to favor the pool (never the trader / LP) and to stay bounded.
+
+use near_sdk::test_utils::{accounts, VMContextBuilder};
+use near_sdk::{testing_env, Balance, MockedBlockchain};
+use num_bigint::{BigInt, BigUint};
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg32;
+
+use crate::admin_fee::AdminFees;
+use crate::simple_pool::SimplePool;
+use crate::stable_swap::math::StableSwap;
+use crate::stable_swap::StableSwapPool;
+use crate::utils::FEE_DIVISOR;
+
+const ROUNDS: usize = 300;
+
+fn big(x: Balance) -> BigUint {
+    BigUint::from(x)
+}
+
+fn setup_simple_pool(rng: &mut Pcg32) -> SimplePool {
+    let mut pool = SimplePool::new(0, vec![accounts(1), accounts(2)], rng.gen_range(0..100), 0, 0);
+    let mut amounts = vec![
+        rng.gen_range(10u128.pow(6)..10u128.pow(30)),
+        rng.gen_range(10u128.pow(6)..10u128.pow(30)),
+    ];
+    pool.add_liquidity(accounts(0).as_ref(), &mut amounts);
+    pool
+}
+
+/// Exact stable swap invariant, rounded down: largest D such that
+/// `(Ann * S + D - Ann * D) * n^n * P >= D^(n+1)`, where `Ann = A * n^n`.
+fn reference_d(amp: u128, c_amounts: &[Balance]) -> BigUint {
+    let n = c_amounts.len() as u32;
+    let nn = BigInt::from(n).pow(n);
+    let ann = BigInt::from(amp) * &nn;
+    let sum: BigInt = c_amounts.iter().map(|x| BigInt::from(*x)).sum();
+    let prod: BigInt = c_amounts.iter().map(|x| BigInt::from(*x)).product();
+    let holds = |d: &BigInt| -> bool {
+        (&ann * &sum + d - &ann * d) * &nn * &prod >= d.pow(n + 1)
+    };
+    // Invariant D is between 0 and sum of balances.
+    let (mut lo, mut hi) = (BigInt::from(0), sum.clone());
+    while lo < hi {
+        let mid: BigInt = (&lo + &hi + 1) / 2;
+        if holds(&mid) {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+    lo.to_biguint().unwrap()
+}
+
+#[test]
+fn test_simple_swap_rounding() {
+    let mut context = VMContextBuilder::new();
+    let mut rng = Pcg32::seed_from_u64(1);
+    for _ in 0..ROUNDS {
+        // Fresh environment every round to stay within log limits.
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        let mut pool = setup_simple_pool(&mut rng);
+        let (in_balance, out_balance) = (pool.amounts[0], pool.amounts[1]);
+        let amount_in = rng.gen_range(1..in_balance);
+        let fee = pool.total_fee;
+        let amount_out = pool.swap(
+            accounts(1).as_ref(),
+            amount_in,
+            accounts(2).as_ref(),
+            0,
+            &AdminFees::zero(),
+        );
+
+        let amount_with_fee = big(amount_in) * big((FEE_DIVISOR - fee) as u128);
+        let numerator = &amount_with_fee * big(out_balance);
+        let denominator = big(FEE_DIVISOR as u128) * big(in_balance) + amount_with_fee;
+        // Trader never receives more than exact output, and loses less than 1 unit.
+        assert!(big(amount_out) * &denominator <= numerator);
+        assert!(big(amount_out + 1) * &denominator > numerator);
+        // Constant product never decreases.
+        assert!(
+            big(pool.amounts[0]) * big(pool.amounts[1]) >= big(in_balance) * big(out_balance)
+        );
+    }
+}
+
+#[test]
+fn test_simple_liquidity_rounding() {
+    let mut context = VMContextBuilder::new();
+    let mut rng = Pcg32::seed_from_u64(2);
+    for _ in 0..ROUNDS {
+        // Fresh environment every round to stay within log limits.
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        let mut pool = setup_simple_pool(&mut rng);
+        let prev_amounts = pool.amounts.clone();
+        let prev_supply = pool.shares_total_supply;
+        let mut amounts = vec![
+            rng.gen_range(prev_amounts[0] / 1000 + 1..prev_amounts[0] * 10),
+            rng.gen_range(prev_amounts[1] / 1000 + 1..prev_amounts[1] * 10),
+        ];
+        let max_amounts = amounts.clone();
+        let shares = pool.add_liquidity(accounts(3).as_ref(), &mut amounts);
+        for i in 0..2 {
+            // LP pays at least the exact value of minted shares, but not more than offered.
+            assert!(big(amounts[i]) * big(prev_supply) >= big(shares) * big(prev_amounts[i]));
+            assert!(
+                big(amounts[i] - 1) * big(prev_supply) < big(shares) * big(prev_amounts[i])
+            );
+            assert!(amounts[i] <= max_amounts[i]);
+        }
+
+        let prev_amounts = pool.amounts.clone();
+        let prev_supply = pool.shares_total_supply;
+        let burn = rng.gen_range(1..=shares);
+        let result = pool.remove_liquidity(accounts(3).as_ref(), burn, vec![0, 0]);
+        for i in 0..2 {
+            // LP receives at most the exact value of burnt shares, rounding loses less than 1 unit.
+            assert!(big(result[i]) * big(prev_supply) <= big(burn) * big(prev_amounts[i]));
+            assert!(big(result[i] + 1) * big(prev_supply) > big(burn) * big(prev_amounts[i]));
+        }
+    }
+}
+
+#[test]
+fn test_stable_invariant_differential() {
+    let mut context = VMContextBuilder::new();
+    let mut rng = Pcg32::seed_from_u64(3);
+    for _ in 0..ROUNDS {
+        // Fresh environment every round to stay within log limits.
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        let amp = rng.gen_range(1..10_000u128);
+        let n_coins = rng.gen_range(2..=3);
+        let c_amounts: Vec<Balance> = (0..n_coins)
+            .map(|_| rng.gen_range(10u128.pow(18)..10u128.pow(30)))
+            .collect();
+        let d = StableSwap::new(amp, amp, 0, 0, 0).compute_d(&c_amounts).unwrap();
+        let expected = reference_d(amp, &c_amounts);
+        let d = big(d.as_u128());
+        let error = if d > expected { &d - &expected } else { &expected - &d };
+        assert!(error <= big(2), "amp {} amounts {:?}", amp, c_amounts);
+    }
+}
+
+#[test]
+fn test_stable_swap_rounding() {
+    let mut context = VMContextBuilder::new();
+    let mut rng = Pcg32::seed_from_u64(4);
+    for _ in 0..ROUNDS {
+        // Fresh environment every round to stay within log limits.
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        let amp = rng.gen_range(1..10_000u128);
+        let mut pool = StableSwapPool::new(
+            0,
+            vec![accounts(1), accounts(2)],
+            vec![18, 18],
+            amp,
+            rng.gen_range(0..100),
+        );
+        let amounts = vec![
+            rng.gen_range(10u128.pow(21)..10u128.pow(30)),
+            rng.gen_range(10u128.pow(21)..10u128.pow(30)),
+        ];
+        pool.add_liquidity(accounts(0).as_ref(), &amounts, 1, &AdminFees::zero());
+        let prev_c_amounts = pool.c_amounts.clone();
+        let amount_in = rng.gen_range(10u128.pow(12)..prev_c_amounts[0] / 10);
+        pool.swap(
+            accounts(1).as_ref(),
+            amount_in,
+            accounts(2).as_ref(),
+            0,
+            &AdminFees::zero(),
+        );
+        // Invariant never decreases because of rounding in the swap. `compute_y` converges
+        // with precision of 1, which is only visible (at most 1 unit) in pools without fee.
+        let d0 = reference_d(amp, &prev_c_amounts);
+        let d1 = reference_d(amp, &pool.c_amounts);
+        let tolerance = if pool.total_fee == 0 { 1u128 } else { 0 };
+        assert!(
+            d1 + big(tolerance) >= d0,
+            "amp {} amounts {:?} amount_in {}",
+            amp,
+            prev_c_amounts,
+            amount_in
+        );
+
+        let prev_c_amounts = pool.c_amounts.clone();
+        let prev_supply = pool.shares_total_supply;
+        let burn = rng.gen_range(1..prev_supply / 2);
+        let result = pool.remove_liquidity_by_shares(accounts(0).as_ref(), burn, vec![0, 0]);
+        for i in 0..2 {
+            assert!(big(result[i]) * big(prev_supply) <= big(burn) * big(prev_c_amounts[i]));
+        }
+    }
+}
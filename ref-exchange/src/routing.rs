@@ -0,0 +1,156 @@
+//! Search and execution of multi-hop routes.
+//!
+//! `get_best_route` tries routes from the input token through route intermediaries set by owner
+//! to the output token, picking for every hop the pool with the largest return, and returns the
+//! route with the largest output. Pools without liquidity, not launched yet or with unresponsive
+//! tokens are skipped, and a pool is used at most once per route. The route can be executed as
+//! `Action::RoutedSwap`, which fails if the output is below its minimum. Referral fee isn't
+//! accounted, like in `get_return`.
+
+use crate::*;
+
+/// Max hops route search tries, bounds gas of the view.
+pub const MAX_ROUTE_SEARCH_HOPS: u32 = 3;
+
+#[derive(Serialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug, PartialEq))]
+pub struct RouteInfo {
+    /// Pools of the route in order of hops.
+    pub pool_ids: Vec<u64>,
+    /// Tokens of the route from input to output, one more than pools.
+    pub tokens: Vec<AccountId>,
+    pub amount_out: U128,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Returns route with the largest return of `token_out` for `amount_in` of `token_in` in at
+    /// most `max_hops` swaps, None if there is no route.
+    pub fn get_best_route(
+        &self,
+        token_in: ValidAccountId,
+        token_out: ValidAccountId,
+        amount_in: U128,
+        max_hops: u32,
+    ) -> Option<RouteInfo> {
+        let max_hops = max_hops
+            .min(self.action_limits.max_route_hops)
+            .min(MAX_ROUTE_SEARCH_HOPS);
+        let pools: Vec<(u64, Pool)> = (0..self.pools.len())
+            .filter_map(|pool_id| {
+                let pool = self.pools.get(pool_id).unwrap();
+                let routable = pool.share_total_balance() > 0
+                    && self.internal_remaining_launch_delay(pool_id).is_none()
+                    && pool
+                        .tokens()
+                        .iter()
+                        .all(|token_id| !self.unresponsive_tokens.contains(token_id));
+                if routable {
+                    Some((pool_id, pool))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        let intermediaries: Vec<AccountId> = self
+            .route_intermediaries
+            .iter()
+            .filter(|token_id| token_id != token_in.as_ref() && token_id != token_out.as_ref())
+            .collect();
+        let route = RouteInfo {
+            pool_ids: vec![],
+            tokens: vec![token_in.into()],
+            amount_out: amount_in,
+        };
+        let mut best = None;
+        self.internal_search_route(
+            &pools,
+            &intermediaries,
+            token_out.as_ref(),
+            route,
+            max_hops,
+            &mut best,
+        );
+        best
+    }
+}
+
+impl Contract {
+    /// Extends the route by hops to `token_out`, directly or through intermediaries not in
+    /// the route yet, and keeps the best complete route in `best`.
+    fn internal_search_route(
+        &self,
+        pools: &[(u64, Pool)],
+        intermediaries: &[AccountId],
+        token_out: &AccountId,
+        route: RouteInfo,
+        hops_left: u32,
+        best: &mut Option<RouteInfo>,
+    ) {
+        if hops_left == 0 {
+            return;
+        }
+        if let Some(route) = self.internal_extend_route(pools, &route, token_out) {
+            if best
+                .as_ref()
+                .filter(|best| best.amount_out.0 >= route.amount_out.0)
+                .is_none()
+            {
+                *best = Some(route);
+            }
+        }
+        if hops_left == 1 {
+            return;
+        }
+        for token_id in intermediaries {
+            if route.tokens.contains(token_id) {
+                continue;
+            }
+            if let Some(route) = self.internal_extend_route(pools, &route, token_id) {
+                self.internal_search_route(
+                    pools,
+                    intermediaries,
+                    token_out,
+                    route,
+                    hops_left - 1,
+                    best,
+                );
+            }
+        }
+    }
+
+    /// Returns the route extended by a swap to `token_id` in the pool with the largest return.
+    fn internal_extend_route(
+        &self,
+        pools: &[(u64, Pool)],
+        route: &RouteInfo,
+        token_id: &AccountId,
+    ) -> Option<RouteInfo> {
+        let token_in = route.tokens.last().unwrap();
+        let (pool_id, amount_out) = pools
+            .iter()
+            .filter(|(pool_id, pool)| {
+                !route.pool_ids.contains(pool_id)
+                    && pool.tokens().contains(token_in)
+                    && pool.tokens().contains(token_id)
+            })
+            .map(|(pool_id, pool)| {
+                let fees = AdminFees::new(self.internal_get_pool_admin_fees(*pool_id).exchange_fee)
+                    .with_fee_side(self.internal_get_fee_side(*pool_id, pool));
+                (
+                    *pool_id,
+                    pool.get_return(token_in, route.amount_out.0, token_id, &fees),
+                )
+            })
+            .max_by_key(|(_, amount_out)| *amount_out)?;
+        if amount_out == 0 {
+            return None;
+        }
+        let mut route = route.clone();
+        route.pool_ids.push(pool_id);
+        route.tokens.push(token_id.clone());
+        route.amount_out = U128(amount_out);
+        Some(route)
+    }
+}
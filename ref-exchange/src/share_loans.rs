@@ -0,0 +1,440 @@
+//! Loans against LP shares of stable pools.
+//!
+//! Owner designates stable pools whose shares can be used as collateral and funds a lending
+//! reserve of tokens. An LP locks shares of such pool and borrows one of the pool tokens from the
+//! reserve, up to `max_ltv` of the locked shares value. Locked shares stay with the LP and keep
+//! earning fees, but can't be transferred or removed until the loan is repaid. Shares under a
+//! time lock can't be used as collateral. Loans don't accrue
+//! interest. Shares are valued conservatively, so a depegged pool token doesn't back loans: only
+//! the balanced part of the pool counts, i.e. the smallest comparable amount of pool tokens
+//! times their number, at par. If all pool tokens have fresh price hints, the value is further
+//! capped at the whole pool taken at the price of its cheapest token.
+//! Once debt exceeds `liquidation_ltv` of the value, anyone can liquidate the loan: locked
+//! shares are burnt, debt worth of the removed tokens goes back to the reserve, `liquidation_bonus`
+//! of the debt worth to the liquidator and the rest to the LP. The bonus pays for keeping the
+//! reserve whole without waiting on the owner.
+
+use crate::account_deposit::log_near_amount_change;
+use crate::stable_swap::{StableSwapPool, TARGET_DECIMAL};
+use crate::utils::{FEE_DIVISOR, U256};
+use crate::*;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct ShareLoanConfig {
+    /// Max debt at borrowing, in bps of `FEE_DIVISOR` of the locked shares value. 0 stops borrowing.
+    pub max_ltv: u32,
+    /// Debt above which the loan can be liquidated, in bps of `FEE_DIVISOR` of the value.
+    pub liquidation_ltv: u32,
+    /// Paid to the liquidator out of the borrower's part, in bps of `FEE_DIVISOR` of the debt.
+    pub liquidation_bonus: u32,
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct ShareLoan {
+    pub shares: Balance,
+    pub token_id: AccountId,
+    pub debt: Balance,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug))]
+pub struct ShareLoanInfo {
+    pub shares: U128,
+    pub token_id: AccountId,
+    pub debt: U128,
+    /// Value of the locked shares in the borrowed token.
+    pub value: U128,
+    /// Debt to value, in bps of `FEE_DIVISOR`.
+    pub ltv: u32,
+}
+
+/// Returns debt to value in bps of `FEE_DIVISOR`, capped at u32.
+fn loan_ltv(debt: Balance, value: Balance) -> u32 {
+    if value == 0 {
+        return u32::MAX;
+    }
+    std::cmp::min(
+        U256::from(debt) * U256::from(FEE_DIVISOR) / U256::from(value),
+        U256::from(u32::MAX),
+    )
+    .as_u32()
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Allows shares of the stable pool to be used as loan collateral with given limits.
+    /// Only can be called by owner.
+    #[payable]
+    pub fn set_share_loan_config(&mut self, pool_id: u64, config: ShareLoanConfig) {
+        assert_one_yocto();
+        self.assert_owner();
+        match self.pools.get(pool_id).expect("ERR_NO_POOL") {
+            Pool::StableSwapPool(_) => (),
+            Pool::SimplePool(_) | Pool::WeightedPool(_) => {
+                env::panic(ERR280_SHARE_LOANS_NOT_ALLOWED.as_bytes())
+            }
+        }
+        assert!(
+            config.max_ltv < config.liquidation_ltv
+                && config.liquidation_ltv < FEE_DIVISOR
+                && config.liquidation_bonus < FEE_DIVISOR,
+            "{}",
+            ERR281_INVALID_SHARE_LOAN_CONFIG
+        );
+        log!(
+            "Share loans of pool {} set to max ltv {}, liquidation ltv {}, liquidation bonus {}",
+            pool_id,
+            config.max_ltv,
+            config.liquidation_ltv,
+            config.liquidation_bonus
+        );
+        self.share_loan_configs.insert(&pool_id, &config);
+    }
+
+    /// Moves owner's deposit of the token to the lending reserve. Only can be called by owner.
+    #[payable]
+    pub fn fund_lending_reserve(&mut self, token_id: ValidAccountId, amount: U128) {
+        assert_one_yocto();
+        self.assert_owner();
+        let owner_id = self.owner_id.clone();
+        let mut account = self.internal_unwrap_account(&owner_id);
+        account.withdraw(token_id.as_ref(), amount.0);
+        self.internal_save_account(&owner_id, account);
+        let reserve = self.lending_reserves.get(token_id.as_ref()).unwrap_or(0);
+        self.lending_reserves
+            .insert(token_id.as_ref(), &(reserve + amount.0));
+        log!("Lending reserve of {} funded with {}", token_id.as_ref(), amount.0);
+    }
+
+    /// Moves the token from the lending reserve to owner's deposit. Only can be called by owner.
+    #[payable]
+    pub fn withdraw_lending_reserve(&mut self, token_id: ValidAccountId, amount: U128) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.internal_take_lending_reserve(token_id.as_ref(), amount.0);
+        let owner_id = self.owner_id.clone();
+        let mut account = self.internal_unwrap_account(&owner_id);
+        account.deposit(token_id.as_ref(), amount.0);
+        self.internal_save_account(&owner_id, account);
+        log!("Lending reserve of {} withdrawn by {}", token_id.as_ref(), amount.0);
+    }
+
+    /// Locks `shares` more of the pool and borrows `amount` more of `token_id` into the inner
+    /// account. Loan of a pool can only be in one token. Loan storage is covered by the caller's
+    /// storage balance and returned to it on repay.
+    #[payable]
+    pub fn borrow_against_shares(
+        &mut self,
+        pool_id: u64,
+        shares: U128,
+        token_id: ValidAccountId,
+        amount: U128,
+    ) {
+        assert_one_yocto();
+        self.assert_contract_running();
+        let prev_storage = env::storage_usage();
+        let sender_id = env::predecessor_account_id();
+        let config = self
+            .share_loan_configs
+            .get(&pool_id)
+            .expect(ERR280_SHARE_LOANS_NOT_ALLOWED);
+        let pool = match self.pools.get(pool_id).expect("ERR_NO_POOL") {
+            Pool::StableSwapPool(pool) => pool,
+            Pool::SimplePool(_) | Pool::WeightedPool(_) => {
+                env::panic(ERR280_SHARE_LOANS_NOT_ALLOWED.as_bytes())
+            }
+        };
+        let token_index = pool
+            .token_account_ids
+            .iter()
+            .position(|id| id == token_id.as_ref())
+            .expect(ERR282_WRONG_LOAN_TOKEN);
+        let key = (sender_id.clone(), pool_id);
+        let mut loan = self.share_loans.get(&key).unwrap_or(ShareLoan {
+            shares: 0,
+            token_id: token_id.clone().into(),
+            debt: 0,
+        });
+        assert_eq!(&loan.token_id, token_id.as_ref(), "{}", ERR282_WRONG_LOAN_TOKEN);
+        loan.shares += shares.0;
+        loan.debt += amount.0;
+        assert!(
+            pool.share_balance_of(&sender_id)
+                >= loan.shares + self.internal_locked_shares(&sender_id, pool_id),
+            "{}",
+            ERR34_INSUFFICIENT_LP_SHARES
+        );
+        let value = self.internal_share_value(&pool, loan.shares, token_index);
+        assert!(
+            loan_ltv(loan.debt, value) <= config.max_ltv,
+            "{}",
+            ERR283_LOAN_TOO_LARGE
+        );
+        self.internal_take_lending_reserve(token_id.as_ref(), amount.0);
+        self.share_loans.insert(&key, &loan);
+        let mut account = self.internal_unwrap_account(&sender_id);
+        account.deposit(token_id.as_ref(), amount.0);
+        if env::storage_usage() > prev_storage {
+            // Charged at the price it is refunded at on repay.
+            let storage_cost =
+                (env::storage_usage() - prev_storage) as Balance * env::storage_byte_cost();
+            assert!(
+                account.storage_available(self.internal_storage_byte_price()) >= storage_cost,
+                "{}",
+                ERR11_INSUFFICIENT_STORAGE
+            );
+            let prev_near_amount = account.near_amount;
+            account.near_amount -= storage_cost;
+            log_near_amount_change(
+                &sender_id,
+                "share_loan_storage",
+                prev_near_amount,
+                account.near_amount,
+            );
+        }
+        self.internal_save_account(&sender_id, account);
+        log!(
+            "{} borrowed {} of {} against {} shares of pool {}",
+            sender_id,
+            amount.0,
+            token_id.as_ref(),
+            loan.shares,
+            pool_id
+        );
+    }
+
+    /// Repays up to `amount` of the loan from the inner account, unlocks shares once debt is zero.
+    /// Returns remaining debt.
+    #[payable]
+    pub fn repay_share_loan(&mut self, pool_id: u64, amount: U128) -> U128 {
+        assert_one_yocto();
+        let prev_storage = env::storage_usage();
+        let sender_id = env::predecessor_account_id();
+        let key = (sender_id.clone(), pool_id);
+        let mut loan = self.share_loans.get(&key).expect(ERR284_NO_SHARE_LOAN);
+        let repaid = std::cmp::min(amount.0, loan.debt);
+        let mut account = self.internal_unwrap_account(&sender_id);
+        account.withdraw(&loan.token_id, repaid);
+        loan.debt -= repaid;
+        let reserve = self.lending_reserves.get(&loan.token_id).unwrap_or(0);
+        self.lending_reserves.insert(&loan.token_id, &(reserve + repaid));
+        if loan.debt == 0 {
+            self.share_loans.remove(&key);
+            log!("Shares of pool {} unlocked for {}", pool_id, sender_id);
+        } else {
+            self.share_loans.insert(&key, &loan);
+        }
+        // Freed up storage of the loan will be returned to near_balance.
+        if prev_storage > env::storage_usage() {
+            let prev_near_amount = account.near_amount;
+            account.near_amount +=
+                (prev_storage - env::storage_usage()) as Balance * env::storage_byte_cost();
+            log_near_amount_change(
+                &sender_id,
+                "share_loan_storage_refund",
+                prev_near_amount,
+                account.near_amount,
+            );
+        }
+        self.internal_save_account(&sender_id, account);
+        log!(
+            "{} repaid {} of {}, remaining debt {}",
+            sender_id,
+            repaid,
+            loan.token_id,
+            loan.debt
+        );
+        U128(loan.debt)
+    }
+
+    /// Burns locked shares of a loan over its liquidation ltv, debt worth of the removed tokens
+    /// goes to the lending reserve, the liquidation bonus to the caller's inner account and the
+    /// rest to the borrower. Can be called by anyone registered.
+    #[payable]
+    pub fn liquidate_share_loan(&mut self, account_id: ValidAccountId, pool_id: u64) {
+        assert_one_yocto();
+        self.assert_contract_running();
+        let liquidator_id = env::predecessor_account_id();
+        let key = (account_id.to_string(), pool_id);
+        let loan = self.share_loans.get(&key).expect(ERR284_NO_SHARE_LOAN);
+        let config = self.share_loan_configs.get(&pool_id).unwrap();
+        let mut pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+        let (tokens, debt_c_amount, bonus_c_amount) = match &pool {
+            Pool::StableSwapPool(pool) => {
+                let token_index = pool
+                    .token_account_ids
+                    .iter()
+                    .position(|id| id == &loan.token_id)
+                    .unwrap();
+                let value = self.internal_share_value(pool, loan.shares, token_index);
+                assert!(
+                    loan_ltv(loan.debt, value) > config.liquidation_ltv,
+                    "{}",
+                    ERR285_LOAN_NOT_LIQUIDATABLE
+                );
+                let debt_c_amount = pool.amount_to_c_amount(loan.debt, token_index);
+                (
+                    pool.token_account_ids.clone(),
+                    debt_c_amount,
+                    (U256::from(debt_c_amount) * U256::from(config.liquidation_bonus)
+                        / U256::from(FEE_DIVISOR))
+                    .as_u128(),
+                )
+            }
+            Pool::SimplePool(_) | Pool::WeightedPool(_) => unreachable!(),
+        };
+        self.share_loans.remove(&key);
+        let amounts = pool.remove_liquidity(
+            account_id.as_ref(),
+            loan.shares,
+            vec![0; tokens.len()],
+        );
+        // Debt and then the bonus are covered by the borrowed token first, then by other tokens
+        // at par.
+        let mut order: Vec<usize> = (0..tokens.len()).collect();
+        order.sort_by_key(|index| tokens[*index] != loan.token_id);
+        let mut left = amounts;
+        let mut debt_amounts = vec![0; tokens.len()];
+        let mut bonus_amounts = vec![0; tokens.len()];
+        for (target, c_amount) in [
+            (&mut debt_amounts, debt_c_amount),
+            (&mut bonus_amounts, bonus_c_amount),
+        ] {
+            let mut remaining_c_amount = c_amount;
+            for index in order.iter().copied() {
+                let (covered, c_covered) = match &pool {
+                    Pool::StableSwapPool(pool) => {
+                        let c_amount = pool.amount_to_c_amount(left[index], index);
+                        if c_amount <= remaining_c_amount {
+                            (left[index], c_amount)
+                        } else {
+                            let covered = pool.c_amount_to_amount(remaining_c_amount, index);
+                            (covered, pool.amount_to_c_amount(covered, index))
+                        }
+                    }
+                    Pool::SimplePool(_) | Pool::WeightedPool(_) => unreachable!(),
+                };
+                remaining_c_amount -= c_covered;
+                left[index] -= covered;
+                target[index] = covered;
+            }
+        }
+        let mut account = self.internal_unwrap_account(account_id.as_ref());
+        for (index, token_id) in tokens.iter().enumerate() {
+            if debt_amounts[index] > 0 {
+                let reserve = self.lending_reserves.get(token_id).unwrap_or(0);
+                self.lending_reserves
+                    .insert(token_id, &(reserve + debt_amounts[index]));
+            }
+            if left[index] > 0 {
+                account.deposit(token_id, left[index]);
+            }
+        }
+        self.internal_save_pool(pool_id, &pool);
+        self.internal_save_account(account_id.as_ref(), account);
+        let mut liquidator = self.internal_unwrap_account(&liquidator_id);
+        for (index, token_id) in tokens.iter().enumerate() {
+            if bonus_amounts[index] > 0 {
+                liquidator.deposit(token_id, bonus_amounts[index]);
+            }
+        }
+        self.internal_save_account(&liquidator_id, liquidator);
+        log!(
+            "Share loan of {} in pool {} liquidated by {}, {} shares burnt, debt {} of {}",
+            account_id.as_ref(),
+            pool_id,
+            liquidator_id,
+            loan.shares,
+            loan.debt,
+            loan.token_id
+        );
+    }
+
+    pub fn get_share_loan_config(&self, pool_id: u64) -> Option<ShareLoanConfig> {
+        self.share_loan_configs.get(&pool_id)
+    }
+
+    pub fn get_lending_reserve(&self, token_id: ValidAccountId) -> U128 {
+        U128(self.lending_reserves.get(token_id.as_ref()).unwrap_or(0))
+    }
+
+    pub fn get_share_loan(&self, account_id: ValidAccountId, pool_id: u64) -> Option<ShareLoanInfo> {
+        let loan = self.share_loans.get(&(account_id.into(), pool_id))?;
+        let value = match self.pools.get(pool_id)? {
+            Pool::StableSwapPool(pool) => {
+                let token_index = pool
+                    .token_account_ids
+                    .iter()
+                    .position(|id| id == &loan.token_id)?;
+                self.internal_share_value(&pool, loan.shares, token_index)
+            }
+            Pool::SimplePool(_) | Pool::WeightedPool(_) => return None,
+        };
+        Some(ShareLoanInfo {
+            shares: U128(loan.shares),
+            token_id: loan.token_id,
+            debt: U128(loan.debt),
+            value: U128(value),
+            ltv: loan_ltv(loan.debt, value),
+        })
+    }
+}
+
+impl Contract {
+    /// Returns conservative value of the shares in token with given index, see module doc.
+    fn internal_share_value(
+        &self,
+        pool: &StableSwapPool,
+        shares: Balance,
+        token_index: usize,
+    ) -> Balance {
+        if pool.shares_total_supply == 0 {
+            return 0;
+        }
+        let n = pool.c_amounts.len() as u128;
+        let mut c_value = U256::from(*pool.c_amounts.iter().min().unwrap()) * U256::from(n);
+        // Prices of a whole comparable unit of each pool token in the reference token.
+        let prices: Option<Vec<Balance>> = pool
+            .token_account_ids
+            .iter()
+            .enumerate()
+            .map(|(index, token_id)| {
+                let unit = pool.c_amount_to_amount(10u128.pow(TARGET_DECIMAL as u32), index);
+                self.internal_value_by_price_hint(token_id, unit)
+            })
+            .collect();
+        if let Some(prices) = prices {
+            if prices[token_index] > 0 {
+                let total = pool
+                    .c_amounts
+                    .iter()
+                    .fold(U256::zero(), |total, c_amount| total + U256::from(*c_amount));
+                let hinted = total * U256::from(*prices.iter().min().unwrap())
+                    / U256::from(prices[token_index]);
+                c_value = std::cmp::min(c_value, hinted);
+            }
+        }
+        let c_value = c_value * U256::from(shares) / U256::from(pool.shares_total_supply);
+        pool.c_amount_to_amount(c_value.as_u128(), token_index)
+    }
+
+    fn internal_take_lending_reserve(&mut self, token_id: &AccountId, amount: Balance) {
+        let reserve = self.lending_reserves.get(token_id).unwrap_or(0);
+        assert!(reserve >= amount, "{}", ERR286_INSUFFICIENT_LENDING_RESERVE);
+        self.lending_reserves.insert(token_id, &(reserve - amount));
+    }
+
+    /// Panics if the account has less shares of the pool than locked by its loan.
+    pub(crate) fn assert_loan_collateral(&self, account_id: &AccountId, pool_id: u64, pool: &Pool) {
+        if let Some(loan) = self.share_loans.get(&(account_id.clone(), pool_id)) {
+            assert!(
+                pool.share_balances(account_id) >= loan.shares,
+                "{}",
+                ERR287_SHARES_LOCKED
+            );
+        }
+    }
+}
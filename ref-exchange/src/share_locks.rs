@@ -0,0 +1,174 @@
+//! Time locks of LP shares.
+//!
+//! An LP locks shares of a pool until a timestamp, e.g. a project proving its launch liquidity
+//! can't be pulled, without moving shares to a third-party locker. Locked shares stay with the LP
+//! and keep earning fees, but can't be transferred, removed or used as loan collateral until the
+//! lock expires. Several locks of a pool vest shares in tranches. Expired locks stop restricting
+//! shares right away, `unlock_shares` removes them and refunds their storage. Locks and unlocks
+//! are logged as standard events.
+
+use near_sdk::json_types::{WrappedDuration, WrappedTimestamp};
+
+use crate::account_deposit::log_near_amount_change;
+use crate::events::Event;
+use crate::*;
+
+/// Max number of locks of an account in a pool.
+pub const MAX_SHARE_LOCKS: usize = 16;
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct ShareLock {
+    pub shares: Balance,
+    pub unlock_at: Timestamp,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug, PartialEq))]
+pub struct ShareLockInfo {
+    pub shares: U128,
+    pub unlock_at: WrappedTimestamp,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug, PartialEq))]
+pub struct ShareLockEvent {
+    pub account_id: AccountId,
+    pub pool_id: u64,
+    pub shares: U128,
+    /// Unlock timestamp of a new lock, None for unlocked shares.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unlock_at: Option<WrappedTimestamp>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Locks `amount` of the caller's shares of the pool for `duration` nanoseconds.
+    /// Attached deposit covers lock storage.
+    #[payable]
+    pub fn lock_shares(&mut self, pool_id: u64, amount: U128, duration: WrappedDuration) {
+        self.assert_contract_running();
+        assert!(amount.0 > 0 && duration.0 > 0, "{}", ERR413_INVALID_SHARE_LOCK);
+        let prev_storage = env::storage_usage();
+        let sender_id = env::predecessor_account_id();
+        let pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+        let key = (sender_id.clone(), pool_id);
+        let mut locks = self.share_locks.get(&key).unwrap_or_default();
+        assert!(locks.len() < MAX_SHARE_LOCKS, "{}", ERR415_TOO_MANY_SHARE_LOCKS);
+        let unlock_at = env::block_timestamp() + duration.0;
+        locks.push(ShareLock {
+            shares: amount.0,
+            unlock_at,
+        });
+        self.share_locks.insert(&key, &locks);
+        self.assert_shares_unlocked(&sender_id, pool_id, &pool);
+        self.internal_check_storage(prev_storage);
+        log!(
+            "{} locked {} shares of pool {} until {}",
+            sender_id,
+            amount.0,
+            pool_id,
+            unlock_at
+        );
+        Event::LockShares(vec![ShareLockEvent {
+            account_id: sender_id,
+            pool_id,
+            shares: amount,
+            unlock_at: Some(unlock_at.into()),
+        }])
+        .emit();
+    }
+
+    /// Removes expired locks of the caller in the pool, freed storage is returned to
+    /// near_balance. Returns amount of unlocked shares.
+    #[payable]
+    pub fn unlock_shares(&mut self, pool_id: u64) -> U128 {
+        assert_one_yocto();
+        let prev_storage = env::storage_usage();
+        let sender_id = env::predecessor_account_id();
+        let key = (sender_id.clone(), pool_id);
+        let (expired, locks): (Vec<ShareLock>, Vec<ShareLock>) = self
+            .share_locks
+            .get(&key)
+            .unwrap_or_default()
+            .into_iter()
+            .partition(|lock| lock.unlock_at <= env::block_timestamp());
+        let unlocked: Balance = expired.iter().map(|lock| lock.shares).sum();
+        if unlocked == 0 {
+            return U128(0);
+        }
+        if locks.is_empty() {
+            self.share_locks.remove(&key);
+        } else {
+            self.share_locks.insert(&key, &locks);
+        }
+        let mut account = self.internal_unwrap_account(&sender_id);
+        if prev_storage > env::storage_usage() {
+            let prev_near_amount = account.near_amount;
+            account.near_amount +=
+                (prev_storage - env::storage_usage()) as Balance * env::storage_byte_cost();
+            log_near_amount_change(
+                &sender_id,
+                "share_lock_storage_refund",
+                prev_near_amount,
+                account.near_amount,
+            );
+        }
+        self.internal_save_account(&sender_id, account);
+        log!("{} unlocked {} shares of pool {}", sender_id, unlocked, pool_id);
+        Event::UnlockShares(vec![ShareLockEvent {
+            account_id: sender_id,
+            pool_id,
+            shares: U128(unlocked),
+            unlock_at: None,
+        }])
+        .emit();
+        U128(unlocked)
+    }
+
+    /// Returns locks of the account in the pool not removed yet, including expired ones.
+    pub fn get_share_locks(&self, account_id: ValidAccountId, pool_id: u64) -> Vec<ShareLockInfo> {
+        self.share_locks
+            .get(&(account_id.into(), pool_id))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|lock| ShareLockInfo {
+                shares: U128(lock.shares),
+                unlock_at: lock.unlock_at.into(),
+            })
+            .collect()
+    }
+
+    /// Returns shares of the account in the pool locked at the moment.
+    pub fn get_locked_shares(&self, account_id: ValidAccountId, pool_id: u64) -> U128 {
+        U128(self.internal_locked_shares(account_id.as_ref(), pool_id))
+    }
+}
+
+impl Contract {
+    /// Returns shares of the account in the pool under locks not expired yet.
+    pub(crate) fn internal_locked_shares(&self, account_id: &AccountId, pool_id: u64) -> Balance {
+        self.share_locks
+            .get(&(account_id.clone(), pool_id))
+            .unwrap_or_default()
+            .iter()
+            .filter(|lock| lock.unlock_at > env::block_timestamp())
+            .map(|lock| lock.shares)
+            .sum()
+    }
+
+    /// Panics if the account has less shares of the pool than locked by it and by its loan.
+    pub(crate) fn assert_shares_unlocked(&self, account_id: &AccountId, pool_id: u64, pool: &Pool) {
+        let loan_shares = self
+            .share_loans
+            .get(&(account_id.clone(), pool_id))
+            .map_or(0, |loan| loan.shares);
+        assert!(
+            pool.share_balances(account_id)
+                >= self.internal_locked_shares(account_id, pool_id) + loan_shares,
+            "{}",
+            ERR414_SHARES_LOCKED
+        );
+    }
+}
@@ -5,16 +5,18 @@ use near_sdk::collections::LookupMap;
 use near_sdk::json_types::ValidAccountId;
 use near_sdk::{env, AccountId, Balance};
 use crate::StorageKey;
-use crate::admin_fee::AdminFees;
+use crate::admin_fee::{AdminFees, FeeSide};
 
 use crate::errors::{
     ERR13_LP_NOT_REGISTERED, ERR14_LP_ALREADY_REGISTERED, ERR31_ZERO_AMOUNT, ERR32_ZERO_SHARES,
+    ERR409_DONATION_TO_EMPTY_POOL, ERR412_NOT_ENOUGH_LIQUIDITY,
 };
 use crate::utils::{
     add_to_collection, integer_sqrt, SwapVolume, FEE_DIVISOR, INIT_SHARES_SUPPLY, U256,
 };
 
-const NUM_TOKENS: usize = 2;
+pub(crate) const MIN_SIMPLE_POOL_TOKENS: usize = 2;
+pub(crate) const MAX_SIMPLE_POOL_TOKENS: usize = 8;
 
 /// Implementation of simple pool, that maintains constant product between balances of all the tokens.
 /// Similar in design to "Uniswap".
@@ -52,7 +54,11 @@ impl SimplePool {
             "ERR_FEE_TOO_LARGE"
         );
         // [AUDIT_10]
-        assert_eq!(token_account_ids.len(), NUM_TOKENS, "ERR_SHOULD_HAVE_2_TOKENS");
+        assert!(
+            token_account_ids.len() >= MIN_SIMPLE_POOL_TOKENS
+                && token_account_ids.len() <= MAX_SIMPLE_POOL_TOKENS,
+            "ERR_WRONG_TOKEN_COUNT"
+        );
         Self {
             token_account_ids: token_account_ids.iter().map(|a| a.clone().into()).collect(),
             amounts: vec![0u128; token_account_ids.len()],
@@ -92,11 +98,22 @@ impl SimplePool {
         self.shares.insert(&receiver_id, &(balance_out + amount));
     }
 
+    pub fn share_is_registered(&self, account_id: &AccountId) -> bool {
+        self.shares.contains_key(account_id)
+    }
+
     /// Returns balance of shares for given user.
     pub fn share_balance_of(&self, account_id: &AccountId) -> Balance {
         self.shares.get(account_id).unwrap_or_default()
     }
 
+    /// Adds amount of the token to reserves without minting shares, all LPs gain from it.
+    pub fn donate(&mut self, token_id: &AccountId, amount: Balance) {
+        assert!(self.shares_total_supply > 0, "{}", ERR409_DONATION_TO_EMPTY_POOL);
+        let index = self.token_index(token_id);
+        self.amounts[index] += amount;
+    }
+
     /// Returns total number of shares in this pool.
     pub fn share_total_balance(&self) -> Balance {
         self.shares_total_supply
@@ -125,10 +142,16 @@ impl SimplePool {
                 );
             }
             for i in 0..self.token_account_ids.len() {
-                let amount = (U256::from(self.amounts[i]) * fair_supply
+                let value = U256::from(self.amounts[i]) * fair_supply;
+                assert!(
+                    value >= U256::from(self.shares_total_supply),
+                    "{}",
+                    ERR31_ZERO_AMOUNT
+                );
+                // Round up, so LP always pays at least the value of minted shares.
+                let amount = ((value + U256::from(self.shares_total_supply - 1))
                     / U256::from(self.shares_total_supply))
                 .as_u128();
-                assert!(amount > 0, "{}", ERR31_ZERO_AMOUNT);
                 self.amounts[i] += amount;
                 amounts[i] = amount;
             }
@@ -166,6 +189,17 @@ impl SimplePool {
     }
 
     /// Removes given number of shares from the pool and returns amounts to the parent.
+    /// Returns amounts of tokens given shares are worth.
+    pub fn predict_remove_liquidity(&self, shares: Balance) -> Vec<Balance> {
+        self.amounts
+            .iter()
+            .map(|amount| {
+                (U256::from(*amount) * U256::from(shares) / U256::from(self.shares_total_supply))
+                    .as_u128()
+            })
+            .collect()
+    }
+
     pub fn remove_liquidity(
         &mut self,
         sender_id: &AccountId,
@@ -226,6 +260,7 @@ impl SimplePool {
         token_in: usize,
         amount_in: Balance,
         token_out: usize,
+        fee_side: FeeSide,
     ) -> Balance {
         let in_balance = U256::from(self.amounts[token_in]);
         let out_balance = U256::from(self.amounts[token_out]);
@@ -236,9 +271,22 @@ impl SimplePool {
                 && amount_in > 0,
             "ERR_INVALID"
         );
-        let amount_with_fee = U256::from(amount_in) * U256::from(FEE_DIVISOR - self.total_fee);
-        (amount_with_fee * out_balance / (U256::from(FEE_DIVISOR) * in_balance + amount_with_fee))
-            .as_u128()
+        match fee_side {
+            FeeSide::Input => {
+                let amount_with_fee =
+                    U256::from(amount_in) * U256::from(FEE_DIVISOR - self.total_fee);
+                (amount_with_fee * out_balance
+                    / (U256::from(FEE_DIVISOR) * in_balance + amount_with_fee))
+                    .as_u128()
+            }
+            FeeSide::Output => {
+                // Fee part of the amount out stays in the pool.
+                let amount_in = U256::from(amount_in);
+                (amount_in * out_balance * U256::from(FEE_DIVISOR - self.total_fee)
+                    / ((in_balance + amount_in) * U256::from(FEE_DIVISOR)))
+                .as_u128()
+            }
+        }
     }
 
     /// Returns how much token you will receive if swap `token_amount_in` of `token_in` for `token_out`.
@@ -247,11 +295,69 @@ impl SimplePool {
         token_in: &AccountId,
         amount_in: Balance,
         token_out: &AccountId,
+        fee_side: FeeSide,
     ) -> Balance {
         self.internal_get_return(
             self.token_index(token_in),
             amount_in,
             self.token_index(token_out),
+            fee_side,
+        )
+    }
+
+    /// Returns smallest amount of `token_in` to swap for at least `amount_out` of `token_out`.
+    pub fn get_input_for_output(
+        &self,
+        token_in: &AccountId,
+        amount_out: Balance,
+        token_out: &AccountId,
+        fee_side: FeeSide,
+    ) -> Balance {
+        let in_idx = self.token_index(token_in);
+        let out_idx = self.token_index(token_out);
+        let in_balance = U256::from(self.amounts[in_idx]);
+        let out_balance = U256::from(self.amounts[out_idx]);
+        assert!(
+            in_balance > U256::zero()
+                && out_balance > U256::zero()
+                && in_idx != out_idx
+                && amount_out > 0,
+            "ERR_INVALID"
+        );
+        let amount_out = U256::from(amount_out);
+        let fee_divisor = U256::from(FEE_DIVISOR);
+        let fee_multiplier = U256::from(FEE_DIVISOR - self.total_fee);
+        // Solves the return of `internal_get_return` for the amount in, rounded up.
+        let denominator = match fee_side {
+            FeeSide::Input => {
+                assert!(amount_out < out_balance, "{}", ERR412_NOT_ENOUGH_LIQUIDITY);
+                fee_multiplier * (out_balance - amount_out)
+            }
+            FeeSide::Output => {
+                assert!(
+                    amount_out * fee_divisor < out_balance * fee_multiplier,
+                    "{}",
+                    ERR412_NOT_ENOUGH_LIQUIDITY
+                );
+                out_balance * fee_multiplier - amount_out * fee_divisor
+            }
+        };
+        ((amount_out * fee_divisor * in_balance + denominator - 1) / denominator).as_u128()
+    }
+
+    /// Returns price of each token in the first one, in their smallest units multiplied by
+    /// `precision`. None if the pool is empty.
+    pub fn spot_prices(&self, precision: u128) -> Option<Vec<U256>> {
+        if self.amounts.contains(&0) {
+            return None;
+        }
+        Some(
+            self.amounts
+                .iter()
+                .map(|amount| {
+                    U256::from(self.amounts[0]) * U256::from(precision) / U256::from(*amount)
+                })
+                .collect(),
         )
     }
 
@@ -278,7 +384,8 @@ impl SimplePool {
         assert_ne!(token_in, token_out, "ERR_SAME_TOKEN_SWAP");
         let in_idx = self.token_index(token_in);
         let out_idx = self.token_index(token_out);
-        let amount_out = self.internal_get_return(in_idx, amount_in, out_idx);
+        let amount_out =
+            self.internal_get_return(in_idx, amount_in, out_idx, admin_fee.fee_side);
         assert!(amount_out >= min_amount_out, "ERR_MIN_AMOUNT");
         env::log(
             format!(
@@ -301,10 +408,14 @@ impl SimplePool {
         // Invariant can not reduce (otherwise loosing balance of the pool and something it broken).
         assert!(new_invariant >= prev_invariant, "ERR_INVARIANT");
         let numerator = (new_invariant - prev_invariant) * U256::from(self.shares_total_supply);
+        // Value of the pool grows with the product of all amounts to the power of 1 / n, while
+        // the invariant of the traded pair is its square root, so growth is scaled by 2 / n.
+        let num_tokens = self.token_account_ids.len() as u32;
 
         // Allocate exchange fee as fraction of total fee by issuing LP shares proportionally.
         if admin_fee.exchange_fee > 0 && numerator > U256::zero() {
-            let denominator = new_invariant * FEE_DIVISOR / admin_fee.exchange_fee;
+            let denominator =
+                new_invariant * FEE_DIVISOR * num_tokens / (2 * admin_fee.exchange_fee);
             self.mint_shares(&admin_fee.exchange_id, (numerator / denominator).as_u128());
         }
 
@@ -314,7 +425,8 @@ impl SimplePool {
                 && numerator > U256::zero()
                 && self.shares.contains_key(referral_id)
             {
-                let denominator = new_invariant * FEE_DIVISOR / admin_fee.referral_fee;
+                let denominator =
+                    new_invariant * FEE_DIVISOR * num_tokens / (2 * admin_fee.referral_fee);
                 self.mint_shares(referral_id, (numerator / denominator).as_u128());
             }
         }
@@ -360,6 +472,7 @@ mod tests {
                 exchange_id: accounts(3).as_ref().clone(),
                 referral_fee: 0,
                 referral_id: None,
+                fee_side: FeeSide::Input,
             },
         );
         assert_eq!(
@@ -410,6 +523,7 @@ mod tests {
                 exchange_id: accounts(3).as_ref().clone(),
                 referral_fee: 0,
                 referral_id: None,
+                fee_side: FeeSide::Input,
             },
         );
         assert_eq!(
@@ -423,6 +537,41 @@ mod tests {
         assert_eq!(liq1[1] + liq2[1], to_yocto("10") - out);
     }
 
+    /// Exchange gets the same share of fees in pools of more tokens.
+    #[test]
+    fn test_multi_token_pool_exchange_fee() {
+        let mut context = VMContextBuilder::new();
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        let admin_fees = AdminFees {
+            exchange_fee: 2000,
+            exchange_id: accounts(3).as_ref().clone(),
+            referral_fee: 0,
+            referral_id: None,
+            fee_side: FeeSide::Input,
+        };
+        let mut exchange_values = vec![];
+        for num_tokens in 2..5 {
+            let tokens: Vec<ValidAccountId> = (1..=num_tokens).map(accounts).collect();
+            let mut pool = SimplePool::new(num_tokens as u32, tokens, 100, 0, 0);
+            let mut amounts = vec![to_yocto("10"); num_tokens];
+            pool.add_liquidity(accounts(0).as_ref(), &mut amounts);
+            pool.swap(
+                accounts(1).as_ref(),
+                to_yocto("1"),
+                accounts(2).as_ref(),
+                1,
+                &admin_fees,
+            );
+            // Pool of n tokens is worth n times one of its reserves.
+            exchange_values
+                .push(pool.share_balance_of(accounts(3).as_ref()) * num_tokens as u128);
+        }
+        for &value in exchange_values.iter() {
+            assert!(value < exchange_values[0] + 5 && exchange_values[0] < value + 5);
+        }
+    }
+
     #[test]
     #[should_panic(expected = "E31: adding zero amount")]
     fn test_rounding() {
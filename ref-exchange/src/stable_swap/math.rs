@@ -36,18 +36,18 @@ impl Fees {
     }
 
     pub fn trade_fee(&self, amount: Balance) -> Balance {
-        amount * (self.trade_fee as u128) / (FEE_DIVISOR as u128)
+        (U256::from(amount) * U256::from(self.trade_fee) / U256::from(FEE_DIVISOR)).as_u128()
     }
 
     pub fn admin_trade_fee(&self, amount: Balance) -> Balance {
-        amount * (self.admin_fee as u128) / (FEE_DIVISOR as u128)
+        (U256::from(amount) * U256::from(self.admin_fee) / U256::from(FEE_DIVISOR)).as_u128()
     }
 
     /// Used to normalize fee applid on difference amount with ideal balance, This logic is from 
     /// https://github.com/saber-hq/stable-swap/blob/5db776fb0a41a0d1a23d46b99ef412ca7ccc5bf6/stable-swap-program/program/src/fees.rs#L73
     pub fn normalized_trade_fee(&self, num_coins: u32, amount: Balance) -> Balance {
         let adjusted_trade_fee = (self.trade_fee * num_coins) / (4 * (num_coins - 1));
-        amount * (adjusted_trade_fee as u128) / (FEE_DIVISOR as u128)
+        (U256::from(amount) * U256::from(adjusted_trade_fee) / U256::from(FEE_DIVISOR)).as_u128()
     }
 }
 
@@ -138,29 +138,31 @@ impl StableSwap {
     /// A * sum(x_i) * n**n + D = A * D * n**n + D**(n+1) / (n**n * prod(x_i))
     pub fn compute_d(&self, c_amounts: &Vec<Balance>) -> Option<U256> {
         let n_coins = c_amounts.len() as u128;
-        let sum_x = c_amounts.iter().fold(0, |sum, i| sum + i);
-        if sum_x == 0 {
+        let sum_x = c_amounts
+            .iter()
+            .fold(U256::zero(), |sum, i| sum + U256::from(*i));
+        if sum_x.is_zero() {
             Some(0.into())
         } else {
             let amp_factor = self.compute_amp_factor()?;
             let mut d_prev: U256;
-            let mut d: U256 = sum_x.into();
+            let mut d: U256 = sum_x;
             for _ in 0..256 {
                 // $ D_{k,prod} = \frac{D_k^{n+1}}{n^n \prod x_{i}} = \frac{D^3}{4xy} $
                 let mut d_prod = d;
                 for c_amount in c_amounts {
                     d_prod = d_prod.checked_mul(d)?
-                    .checked_div((c_amount * n_coins).into())?;
+                    .checked_div(U256::from(*c_amount).checked_mul(n_coins.into())?)?;
                 }
                 d_prev = d;
 
                 let ann = amp_factor.checked_mul(n_coins.checked_pow(n_coins as u32)?.into())?;
-                let leverage = (U256::from(sum_x)).checked_mul(ann.into())?;
+                let leverage = sum_x.checked_mul(ann.into())?;
                 // d = (ann * sum_x + d_prod * n_coins) * d_prev / ((ann - 1) * d_prev + (n_coins + 1) * d_prod)
                 let numerator = d_prev.checked_mul(
                     d_prod
                         .checked_mul(n_coins.into())?
-                        .checked_add(leverage.into())?,
+                        .checked_add(leverage)?,
                 )?;
                 let denominator = d_prev
                     .checked_mul(ann.checked_sub(1)?.into())?
@@ -254,11 +256,11 @@ impl StableSwap {
         let ann = amp_factor.checked_mul(n_coins.checked_pow(n_coins as u32)?.into())?;
         // invariant
         let d = self.compute_d(current_c_amounts)?;
-        let mut s_ = x_c_amount;
+        let mut s_ = U256::from(x_c_amount);
         let mut c = d.checked_mul(d)?.checked_div(x_c_amount.into())?;
         for (idx, c_amount) in current_c_amounts.iter().enumerate() {
             if idx != index_x && idx != index_y {
-                s_ += *c_amount;
+                s_ += U256::from(*c_amount);
                 c = c.checked_mul(d)?
                     .checked_div((*c_amount).into())?;
             }
@@ -267,7 +269,7 @@ impl StableSwap {
             .checked_mul(d)?
             .checked_div(ann.checked_mul((n_coins as u128).checked_pow(n_coins as u32)?.into())?.into())?;
 
-        let b = d.checked_div(ann.into())?.checked_add(s_.into())?; // d will be subtracted later
+        let b = d.checked_div(ann.into())?.checked_add(s_)?; // d will be subtracted later
 
         // Solve for y by approximating: y**2 + b*y = c
         let mut y_prev: U256;
@@ -364,7 +366,7 @@ impl StableSwap {
         fees: &Fees,
     ) -> Option<SwapResult> {
         let y = self.compute_y(
-            token_in_amount + current_c_amounts[token_in_idx], 
+            token_in_amount.checked_add(current_c_amounts[token_in_idx])?,
             current_c_amounts,
             token_in_idx,
             token_out_idx,
@@ -8,13 +8,15 @@ use crate::errors::*;
 use crate::stable_swap::math::{
     Fees, StableSwap, SwapResult, MAX_AMP, MAX_AMP_CHANGE, MIN_AMP, MIN_RAMP_DURATION,
 };
-use crate::utils::{add_to_collection, SwapVolume, FEE_DIVISOR, U256};
+use crate::utils::{add_to_collection, min_amount_in, SwapVolume, FEE_DIVISOR, U256};
 use crate::StorageKey;
 
-mod math;
+pub(crate) mod math;
 
 pub const MIN_DECIMAL: u8 = 1;
-pub const MAX_DECIMAL: u8 = 18;
+pub const MAX_DECIMAL: u8 = 24;
+/// Decimals of comparable amounts. Tokens with more decimals lose the extra precision in them,
+/// e.g. wNEAR with 24 decimals is kept in units of 10^6 yoctoNEAR.
 pub const TARGET_DECIMAL: u8 = 18;
 pub const MIN_RESERVE: u128 = 1_000_000_000_000_000_000;
 
@@ -44,6 +46,40 @@ pub struct StableSwapPool {
     pub stop_amp_time: Timestamp,
 }
 
+/// Converts amount of a token with given decimals to comparable amount, rounding down or up
+/// precision beyond `TARGET_DECIMAL`.
+fn to_c_amount(amount: Balance, decimal: u8, round_up: bool) -> Balance {
+    let c_amount = if decimal <= TARGET_DECIMAL {
+        U256::from(amount) * U256::exp10((TARGET_DECIMAL - decimal) as usize)
+    } else {
+        let factor = U256::exp10((decimal - TARGET_DECIMAL) as usize);
+        let rounding = if round_up { factor - 1 } else { U256::zero() };
+        (U256::from(amount) + rounding) / factor
+    };
+    assert!(c_amount <= U256::from(Balance::MAX), "{}", ERR418_AMOUNT_OVERFLOW);
+    c_amount.as_u128()
+}
+
+/// Converts comparable amount to amount of a token with given decimals, rounding down.
+fn from_c_amount(c_amount: Balance, decimal: u8) -> Balance {
+    let amount = if decimal <= TARGET_DECIMAL {
+        U256::from(c_amount) / U256::exp10((TARGET_DECIMAL - decimal) as usize)
+    } else {
+        U256::from(c_amount) * U256::exp10((decimal - TARGET_DECIMAL) as usize)
+    };
+    assert!(amount <= U256::from(Balance::MAX), "{}", ERR418_AMOUNT_OVERFLOW);
+    amount.as_u128()
+}
+
+/// Checks there is a decimal for each token and all of them are supported.
+fn assert_valid_decimals(token_decimals: &[u8], n_coins: usize) {
+    assert_eq!(token_decimals.len(), n_coins, "{}", ERR64_TOKENS_COUNT_ILLEGAL);
+    for decimal in token_decimals {
+        assert!(*decimal <= MAX_DECIMAL, "{}", ERR60_DECIMAL_ILLEGAL);
+        assert!(*decimal >= MIN_DECIMAL, "{}", ERR60_DECIMAL_ILLEGAL);
+    }
+}
+
 impl StableSwapPool {
     pub fn new(
         id: u32,
@@ -52,10 +88,7 @@ impl StableSwapPool {
         amp_factor: u128,
         total_fee: u32,
     ) -> Self {
-        for decimal in token_decimals.clone().into_iter() {
-            assert!(decimal <= MAX_DECIMAL, "{}", ERR60_DECIMAL_ILLEGAL);
-            assert!(decimal >= MIN_DECIMAL, "{}", ERR60_DECIMAL_ILLEGAL);
-        }
+        assert_valid_decimals(&token_decimals, token_account_ids.len());
         assert!(
             amp_factor >= MIN_AMP && amp_factor <= MAX_AMP,
             "{}",
@@ -78,41 +111,30 @@ impl StableSwapPool {
     }
 
     pub fn get_amounts(&self) ->Vec<u128> {
-        let mut amounts = self.c_amounts.clone();
-        for (index, value) in self.token_decimals.iter().enumerate() {
-            let factor = 10_u128
-                .checked_pow((TARGET_DECIMAL - value) as u32)
-                .unwrap();
-            amounts[index] = amounts[index].checked_div(factor).unwrap();
-        }
-        amounts
+        (0..self.c_amounts.len())
+            .map(|index| self.c_amount_to_amount(self.c_amounts[index], index))
+            .collect()
     }
 
     fn amounts_to_c_amounts(&self, amounts: &Vec<u128>) ->Vec<u128> {
-        let mut c_amounts = amounts.clone();
-        for (index, value) in self.token_decimals.iter().enumerate() {
-            let factor = 10_u128
-                .checked_pow((TARGET_DECIMAL - value) as u32)
-                .unwrap();
-            c_amounts[index] = c_amounts[index].checked_mul(factor).unwrap();
-        }
-        c_amounts
+        (0..amounts.len())
+            .map(|index| self.amount_to_c_amount(amounts[index], index))
+            .collect()
+    }
+
+    /// Converts amounts leaving the pool, rounding up so the pool is never short of them.
+    fn amounts_out_to_c_amounts(&self, amounts: &Vec<u128>) -> Vec<u128> {
+        (0..amounts.len())
+            .map(|index| to_c_amount(amounts[index], self.token_decimals[index], true))
+            .collect()
     }
 
-    fn amount_to_c_amount(&self, amount: u128, index: usize) -> u128 {
-        let value = self.token_decimals.get(index).unwrap();
-        let factor = 10_u128
-                .checked_pow((TARGET_DECIMAL - value) as u32)
-                .unwrap();
-        amount.checked_mul(factor).unwrap()
+    pub(crate) fn amount_to_c_amount(&self, amount: u128, index: usize) -> u128 {
+        to_c_amount(amount, self.token_decimals[index], false)
     }
 
-    fn c_amount_to_amount(&self, c_amount: u128, index: usize) -> u128 {
-        let value = self.token_decimals.get(index).unwrap();
-        let factor = 10_u128
-                .checked_pow((TARGET_DECIMAL - value) as u32)
-                .unwrap();
-        c_amount.checked_div(factor).unwrap()
+    pub(crate) fn c_amount_to_amount(&self, c_amount: u128, index: usize) -> u128 {
+        from_c_amount(c_amount, self.token_decimals[index])
     }
 
     fn assert_min_reserve(&self, balance: u128) {
@@ -141,6 +163,20 @@ impl StableSwapPool {
         )
     }
 
+    /// Replaces mis-specified token decimals, only allowed before pool received any liquidity.
+    pub fn set_token_decimals(&mut self, token_decimals: Vec<u8>) {
+        assert_eq!(self.shares_total_supply, 0, "{}", ERR87_POOL_NOT_EMPTY);
+        assert_valid_decimals(&token_decimals, self.token_account_ids.len());
+        self.token_decimals = token_decimals;
+    }
+
+    /// Adds amount of the token to reserves without minting shares, all LPs gain from it.
+    pub fn donate(&mut self, token_id: &AccountId, amount: Balance) {
+        assert!(self.shares_total_supply > 0, "{}", ERR409_DONATION_TO_EMPTY_POOL);
+        let index = self.token_index(token_id);
+        self.c_amounts[index] += self.amount_to_c_amount(amount, index);
+    }
+
     /// Returns token index for given token account_id.
     fn token_index(&self, token_id: &AccountId) -> usize {
         self.token_account_ids
@@ -159,6 +195,61 @@ impl StableSwapPool {
         self.volumes.clone()
     }
 
+    /// Returns deviation from parity of the marginal price of each token against the first one,
+    /// in bps of `FEE_DIVISOR`. Price is probed without fees with 0.01% of the first token reserve.
+    pub fn get_peg_deviations(&self) -> Vec<u32> {
+        let probe = self.c_amounts[0] / 10_000;
+        if probe == 0 {
+            return vec![];
+        }
+        let invariant = self.get_invariant();
+        (0..self.token_account_ids.len())
+            .map(|index| {
+                if index == 0 {
+                    return 0;
+                }
+                let amount_out = invariant
+                    .swap_to(0, probe, index, &self.c_amounts, &Fees::zero())
+                    .map_or(0, |result| result.amount_swapped);
+                let diff = std::cmp::max(amount_out, probe) - std::cmp::min(amount_out, probe);
+                std::cmp::min(
+                    U256::from(diff) * U256::from(FEE_DIVISOR) / U256::from(probe),
+                    U256::from(FEE_DIVISOR),
+                )
+                .as_u32()
+            })
+            .collect()
+    }
+
+    /// Returns marginal price of each token in the first one, in their smallest units multiplied
+    /// by `precision`. Price is probed without fees with 0.01% of the token reserve.
+    /// None if some price can't be probed.
+    pub fn spot_prices(&self, precision: u128) -> Option<Vec<U256>> {
+        let invariant = self.get_invariant();
+        (0..self.token_account_ids.len())
+            .map(|index| {
+                if index == 0 {
+                    return Some(U256::from(precision));
+                }
+                let probe = self.c_amounts[index] / 10_000;
+                if probe == 0 {
+                    return None;
+                }
+                let c_amount_out = invariant
+                    .swap_to(index, probe, 0, &self.c_amounts, &Fees::zero())?
+                    .amount_swapped;
+                let c_price = U256::from(c_amount_out) * U256::from(precision) / U256::from(probe);
+                // Comparable amounts have the same decimals, scale back to smallest units.
+                let (decimal_in, decimal_out) = (self.token_decimals[index], self.token_decimals[0]);
+                Some(if decimal_in <= decimal_out {
+                    c_price * U256::exp10((decimal_out - decimal_in) as usize)
+                } else {
+                    c_price / U256::exp10((decimal_in - decimal_out) as usize)
+                })
+            })
+            .collect()
+    }
+
     /// Get per lp token price, with 1e8 precision
     pub fn get_share_price(&self) -> u128 {
 
@@ -343,7 +434,7 @@ impl StableSwapPool {
         fees: &AdminFees,
     ) -> Balance {
         let n_coins = self.token_account_ids.len();
-        let c_amounts = self.amounts_to_c_amounts(amounts);
+        let c_amounts = self.amounts_out_to_c_amounts(amounts);
         for i in 0..n_coins {
             self.assert_min_reserve(self.c_amounts[i].checked_sub(c_amounts[i]).unwrap_or(0));
         }
@@ -378,7 +469,7 @@ impl StableSwapPool {
         let prev_shares_amount = self.shares.get(&sender_id).expect(ERR13_LP_NOT_REGISTERED);
 
         // make amounts into comparable-amounts
-        let c_amounts = self.amounts_to_c_amounts(&amounts);
+        let c_amounts = self.amounts_out_to_c_amounts(&amounts);
         for i in 0..n_coins {
             self.assert_min_reserve(self.c_amounts[i].checked_sub(c_amounts[i]).unwrap_or(0));
         }
@@ -481,6 +572,48 @@ impl StableSwapPool {
         self.c_amount_to_amount(c_amount_out, self.token_index(token_out))
     }
 
+    /// Returns smallest amount of `token_in` to swap for at least `amount_out` of `token_out`.
+    /// Starts from the new balance of `token_in` keeping the invariant with `amount_out` and
+    /// trade fee taken from the pool, then corrects rounding against `get_return`.
+    pub fn get_input_for_output(
+        &self,
+        token_in: &AccountId,
+        amount_out: Balance,
+        token_out: &AccountId,
+        fees: &AdminFees,
+    ) -> Balance {
+        assert_ne!(token_in, token_out, "{}", ERR71_SWAP_DUP_TOKENS);
+        assert!(amount_out > 0, "ERR_INVALID");
+        let in_idx = self.token_index(token_in);
+        let out_idx = self.token_index(token_out);
+        let fee_divisor = FEE_DIVISOR as u128;
+        let fee_multiplier = (FEE_DIVISOR - self.total_fee) as u128;
+        let c_amount_out = (U256::from(self.amount_to_c_amount(amount_out, out_idx))
+            * U256::from(fee_divisor)
+            + U256::from(fee_multiplier - 1))
+            / U256::from(fee_multiplier);
+        assert!(
+            c_amount_out < U256::from(self.c_amounts[out_idx]),
+            "{}",
+            ERR412_NOT_ENOUGH_LIQUIDITY
+        );
+        let new_in_c_amount = self
+            .get_invariant()
+            .compute_y(
+                self.c_amounts[out_idx] - c_amount_out.as_u128(),
+                &self.c_amounts,
+                out_idx,
+                in_idx,
+            )
+            .expect(ERR66_INVARIANT_CALC_ERR)
+            .as_u128();
+        let estimate =
+            self.c_amount_to_amount(new_in_c_amount.saturating_sub(self.c_amounts[in_idx]), in_idx);
+        min_amount_in(estimate, amount_out, |amount_in| {
+            self.get_return(token_in, amount_in, token_out, fees)
+        })
+    }
+
     /// Swap `token_amount_in` of `token_in` token into `token_out` and return how much was received.
     /// Assuming that `token_amount_in` was already received from `sender_id`.
     pub fn swap(
@@ -641,6 +774,10 @@ impl StableSwapPool {
         self.shares.insert(&receiver_id, &(balance_out + amount));
     }
 
+    pub fn share_is_registered(&self, account_id: &AccountId) -> bool {
+        self.shares.contains_key(account_id)
+    }
+
     /// Returns balance of shares for given user.
     pub fn share_balance_of(&self, account_id: &AccountId) -> Balance {
         self.shares.get(account_id).unwrap_or_default()
@@ -1012,6 +1149,58 @@ mod tests {
         pool.remove_liquidity_by_shares(accounts(3).as_ref(), num_shares, vec![1, 1]);
     }
 
+    /// Test fixing decimals of an empty pool, then rejecting it once liquidity is added.
+    #[test]
+    #[should_panic(expected = "E87: pool already has liquidity")]
+    fn test_stable_set_decimals() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        let mut pool = StableSwapPool::new(0, vec![accounts(1), accounts(2)], vec![6, 18], 10000, 0);
+        pool.set_token_decimals(vec![6, 6]);
+        let mut amounts = vec![5000000, 10000000];
+        pool.add_liquidity(accounts(0).as_ref(), &mut amounts, 1, &AdminFees::zero());
+        assert_eq!(pool.c_amounts, vec![5 * 10u128.pow(18), 10 * 10u128.pow(18)]);
+        pool.set_token_decimals(vec![6, 18]);
+    }
+
+    /// Test pool of tokens with 24 and 1 decimals at max reserves of the 24 decimals token.
+    #[test]
+    fn test_stable_wide_decimals() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        let mut pool =
+            StableSwapPool::new(0, vec![accounts(1), accounts(2)], vec![24, 1], 10000, 0);
+        let one = 10u128.pow(24);
+        let mut amounts = vec![100_000_000_000_000 * one, 1_000_000_000_000_000];
+        pool.add_liquidity(accounts(0).as_ref(), &mut amounts, 1, &AdminFees::zero());
+        assert_eq!(pool.c_amounts, vec![10u128.pow(32), 10u128.pow(32)]);
+        assert_eq!(pool.get_amounts(), amounts);
+
+        assert_eq!(swap(&mut pool, 2, 10, 1), one);
+        // Precision beyond 18 decimals is not swapped.
+        assert_eq!(swap(&mut pool, 1, one + 999_999, 2), 10);
+        assert_eq!(pool.c_amounts[0], 10u128.pow(32));
+        // Amounts out are rounded up in comparable amounts.
+        let c_amount = pool.c_amounts[0];
+        pool.remove_liquidity_by_tokens(
+            accounts(0).as_ref(),
+            vec![one + 1, 0],
+            u128::MAX,
+            &AdminFees::zero(),
+        );
+        assert_eq!(pool.c_amounts[0], c_amount - 10u128.pow(18) - 1);
+        // 1 unit of the second token is 10^23 units of the first one.
+        let prices = pool.spot_prices(10u128.pow(18)).unwrap();
+        assert_eq!(prices[1] / U256::exp10(38), U256::from(999));
+    }
+
+    #[test]
+    #[should_panic(expected = "E64: illegal tokens count")]
+    fn test_stable_decimals_count() {
+        testing_env!(VMContextBuilder::new().build());
+        StableSwapPool::new(0, vec![accounts(1), accounts(2)], vec![6], 10000, 0);
+    }
+
     /// Test ramping up amplification factor, ramping it even more and then stopping.
     #[test]
     fn test_stable_ramp_amp() {
@@ -1031,4 +1220,20 @@ mod tests {
             .build());
         pool.stop_ramp_amplification();
     }
+
+    #[test]
+    fn test_stable_peg_deviations() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        let fees = AdminFees::zero();
+        let mut pool = StableSwapPool::new(0, vec![accounts(1), accounts(2)], vec![6, 6], 100, 0);
+        assert!(pool.get_peg_deviations().is_empty());
+        let mut amounts = vec![5000000, 5000000];
+        let _ = pool.add_liquidity(accounts(0).as_ref(), &mut amounts, 1, &fees);
+        assert_eq!(pool.get_peg_deviations(), vec![0, 0]);
+        swap(&mut pool, 1, 4000000, 2);
+        let deviations = pool.get_peg_deviations();
+        assert_eq!(deviations[0], 0);
+        assert!(deviations[1] > 100);
+    }
 }
@@ -1,5 +1,78 @@
+use crate::account_deposit::{INIT_ACCOUNT_STORAGE, MEASURED_ACCOUNT_STORAGE};
+use crate::utils::{FEE_DIVISOR, U256};
 use crate::*;
 
+/// Max storage price buffer, 100% in bps.
+pub const MAX_STORAGE_PRICE_BUFFER: u32 = 10_000;
+
+/// Storage deposit settings, owner recalibrates them when account layout or runtime costs change.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct StorageConfig {
+    /// Bytes paid to register an account, minimal storage deposit is derived from it.
+    pub account_bytes: StorageUsage,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            account_bytes: MEASURED_ACCOUNT_STORAGE,
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Updates storage deposit settings. Minimal deposit can't be lower than account storage charged
+    /// by the exchange. Only can be called by owner.
+    #[payable]
+    pub fn set_storage_config(&mut self, config: StorageConfig) {
+        assert_one_yocto();
+        self.assert_owner();
+        assert!(
+            config.account_bytes >= INIT_ACCOUNT_STORAGE,
+            "{}",
+            ERR15_INVALID_STORAGE_CONFIG
+        );
+        log!("Storage account bytes set to {}", config.account_bytes);
+        self.storage_config = config;
+    }
+
+    pub fn get_storage_config(&self) -> StorageConfig {
+        self.storage_config.clone()
+    }
+
+    /// Sets safety buffer over the runtime storage byte cost, in bps of `FEE_DIVISOR`.
+    /// Storage charged to accounts and pool creators is priced with it, so a rise of the runtime
+    /// cost up to the buffer stays covered. Only can be called by owner.
+    #[payable]
+    pub fn modify_storage_price_buffer(&mut self, buffer: u32) {
+        assert_one_yocto();
+        self.assert_owner();
+        assert!(
+            buffer <= MAX_STORAGE_PRICE_BUFFER,
+            "{}",
+            ERR16_INVALID_STORAGE_PRICE_BUFFER
+        );
+        log!("Storage price buffer set to {}", buffer);
+        self.storage_price_buffer = buffer;
+    }
+
+    pub fn get_storage_price_buffer(&self) -> u32 {
+        self.storage_price_buffer
+    }
+}
+
+impl Contract {
+    /// Returns price of storage byte charged by the exchange, runtime cost plus the buffer.
+    pub(crate) fn internal_storage_byte_price(&self) -> Balance {
+        (U256::from(env::storage_byte_cost()) * U256::from(FEE_DIVISOR + self.storage_price_buffer)
+            / U256::from(FEE_DIVISOR))
+        .as_u128()
+    }
+}
+
 /// Implements users storage management for the pool.
 #[near_bindgen]
 impl StorageManagement for Contract {
@@ -66,6 +139,10 @@ impl StorageManagement for Contract {
                 "ERR_STORAGE_UNREGISTER_TOKENS_NOT_EMPTY"
             );
             self.accounts.remove(&account_id);
+            self.account_index.remove(&account_id);
+            self.account_activity.remove(&account_id);
+            self.dust_flags.remove(&account_id);
+            log_near_amount_change(&account_id, "storage_unregister", account_deposit.near_amount, 0);
             Promise::new(account_id.clone()).transfer(account_deposit.near_amount);
             true
         } else {
@@ -75,7 +152,8 @@ impl StorageManagement for Contract {
 
     fn storage_balance_bounds(&self) -> StorageBalanceBounds {
         StorageBalanceBounds {
-            min: Account::min_storage_usage().into(),
+            min: (self.storage_config.account_bytes as Balance * self.internal_storage_byte_price())
+                .into(),
             max: None,
         }
     }
@@ -86,7 +164,7 @@ impl StorageManagement for Contract {
                 { 
                     StorageBalance {
                         total: U128(account.near_amount),
-                        available: U128(account.storage_available()),
+                        available: U128(account.storage_available(self.internal_storage_byte_price())),
                     } 
                 })
     }
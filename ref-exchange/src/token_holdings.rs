@@ -0,0 +1,308 @@
+//! Skimming of token balances not owed to anyone.
+//!
+//! The exchange tracks how much of each token it holds for deposits and pool reserves: incoming
+//! transfers add to it, outgoing ones stay in flight until their callback. Tokens sent to the
+//! exchange directly or airdropped to it are not tracked. Owner or guardians `skim` the token,
+//! comparing its `ft_balance_of` with the tracked amount, and the surplus is set aside as skimmed.
+//! Skims are refused while outgoing transfers are in flight and skipped if holdings change before
+//! the balance arrives. An incoming transfer racing the balance query can still make it look
+//! higher, so a later skim finding a deficit reduces unclaimed skimmed tokens. Owner claims them
+//! to the lostfound account. Holdings are tracked from the start for new contracts, after
+//! migration owner initializes them per token from `ft_balance_of` of the exchange, so tokens
+//! held before can't be skimmed. Tokens in shares mode are reconciled instead.
+//! Anyone can `check_solvency` of a tracked token without changing anything, which reports drift
+//! of the balance from holdings, e.g. for alarms after upgrades. Holdings tracked from the start
+//! are the sum of deposits and reserves. Holdings initialized after migration count what the
+//! exchange held at initialization as owed, so a shortfall from before isn't reported, the report
+//! flags them and auditors sum deposits with `get_accounts` and reserves with `get_pools`.
+
+use near_contract_standards::fungible_token::core_impl::ext_fungible_token;
+use near_sdk::{serde_json, Gas};
+
+use crate::utils::{ext_self, GAS_FOR_FT_BALANCE_OF};
+use crate::*;
+
+/// Amount of gas for comparing the queried balance with tracked holdings.
+pub const GAS_FOR_RESOLVE_SKIM: Gas = 10_000_000_000_000;
+
+/// Amount of gas for initializing holdings with the queried balance.
+pub const GAS_FOR_RESOLVE_INIT_HOLDINGS: Gas = 5_000_000_000_000;
+
+/// Amount of gas for reporting solvency of the token with the queried balance.
+pub const GAS_FOR_RESOLVE_CHECK_SOLVENCY: Gas = 5_000_000_000_000;
+
+#[derive(BorshSerialize, BorshDeserialize, Default)]
+pub struct TokenHoldings {
+    /// Sum of internal amounts of the token, in deposits, reserves and claims.
+    pub internal: Balance,
+    /// Amount of outgoing transfers not resolved yet, still counted in the balance until then.
+    pub in_flight: Balance,
+    /// Surplus found by skims and not claimed yet.
+    pub skimmed: Balance,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug, PartialEq))]
+pub struct TokenHoldingsInfo {
+    pub internal: U128,
+    pub in_flight: U128,
+    pub skimmed: U128,
+}
+
+/// Balance of a token compared with its tracked holdings.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug, PartialEq))]
+pub struct SolvencyInfo {
+    pub balance: U128,
+    /// Internal, in flight and skimmed amounts of the token.
+    pub tracked: U128,
+    /// Balance above tracked holdings, e.g. sent to the exchange directly.
+    pub surplus: U128,
+    /// Tracked holdings not covered by the balance.
+    pub deficit: U128,
+    /// Whether holdings were initialized from the balance after migration rather than tracked
+    /// from the start.
+    pub initialized_from_balance: bool,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Starts tracking holdings of the token not tracked yet with the exchange's balance of it.
+    /// Only can be called by owner.
+    #[payable]
+    pub fn init_token_holdings(&mut self, token_id: ValidAccountId) -> Promise {
+        assert_one_yocto();
+        self.assert_owner();
+        self.assert_token_holdings_not_tracked(token_id.as_ref());
+        ext_fungible_token::ft_balance_of(
+            env::current_account_id(),
+            token_id.as_ref(),
+            0,
+            GAS_FOR_FT_BALANCE_OF,
+        )
+        .then(ext_self::exchange_callback_init_token_holdings(
+            token_id.into(),
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_INIT_HOLDINGS,
+        ))
+    }
+
+    /// Initializes holdings of the token with its reported balance, all of it is owed.
+    #[private]
+    pub fn exchange_callback_init_token_holdings(&mut self, token_id: AccountId) {
+        self.assert_token_holdings_not_tracked(&token_id);
+        let balance = internal_balance_result();
+        self.token_holdings.insert(
+            &token_id,
+            &TokenHoldings {
+                internal: balance.0,
+                ..Default::default()
+            },
+        );
+        log!("Holdings of {} initialized to {}", token_id, balance.0);
+    }
+
+    /// Compares the exchange's balance of the token with tracked holdings and sets the surplus
+    /// aside as skimmed. Only can be called by owner or guardians.
+    pub fn skim(&mut self, token_id: ValidAccountId) -> Promise {
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        let holdings = self
+            .internal_get_token_holdings(token_id.as_ref())
+            .expect(ERR410_TOKEN_HOLDINGS_NOT_TRACKED);
+        assert_eq!(holdings.in_flight, 0, "{}", ERR424_TRANSFERS_IN_FLIGHT);
+        ext_fungible_token::ft_balance_of(
+            env::current_account_id(),
+            token_id.as_ref(),
+            0,
+            GAS_FOR_FT_BALANCE_OF,
+        )
+        .then(ext_self::exchange_callback_skim(
+            token_id.into(),
+            U128(holdings.internal),
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_SKIM,
+        ))
+    }
+
+    /// Updates skimmed amount of the token with its reported balance, returns skimmed amount.
+    /// Skipped if internal amount changed since the skim or transfers are in flight.
+    #[private]
+    pub fn exchange_callback_skim(&mut self, token_id: AccountId, internal: U128) -> U128 {
+        let balance = internal_balance_result();
+        let mut holdings = self.internal_get_token_holdings(&token_id).unwrap();
+        if holdings.internal != internal.0 || holdings.in_flight > 0 {
+            log!("Holdings of {} changed during skim, skipped", token_id);
+            return U128(holdings.skimmed);
+        }
+        let expected = holdings.internal + holdings.in_flight + holdings.skimmed;
+        if balance.0 >= expected {
+            holdings.skimmed += balance.0 - expected;
+        } else {
+            holdings.skimmed = holdings.skimmed.saturating_sub(expected - balance.0);
+        }
+        log!(
+            "Token {} skimmed, balance {}, tracked {}, skimmed {}",
+            token_id,
+            balance.0,
+            holdings.internal + holdings.in_flight,
+            holdings.skimmed
+        );
+        let skimmed = holdings.skimmed;
+        self.token_holdings.insert(&token_id, &holdings);
+        U128(skimmed)
+    }
+
+    /// Compares the exchange's balance of the token with tracked holdings, holdings are not
+    /// changed.
+    pub fn check_solvency(&self, token_id: ValidAccountId) -> Promise {
+        assert!(
+            self.token_shares.get(token_id.as_ref()).is_none(),
+            "{}",
+            ERR120_SHARES_MODE_ENABLED
+        );
+        assert!(
+            self.internal_get_token_holdings(token_id.as_ref()).is_some(),
+            "{}",
+            ERR425_TOKEN_HOLDINGS_NOT_INITIALIZED
+        );
+        ext_fungible_token::ft_balance_of(
+            env::current_account_id(),
+            token_id.as_ref(),
+            0,
+            GAS_FOR_FT_BALANCE_OF,
+        )
+        .then(ext_self::exchange_callback_check_solvency(
+            token_id.into(),
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_CHECK_SOLVENCY,
+        ))
+    }
+
+    /// Returns solvency of the token with its reported balance.
+    #[private]
+    pub fn exchange_callback_check_solvency(&self, token_id: AccountId) -> SolvencyInfo {
+        let balance = internal_balance_result();
+        let holdings = self.internal_get_token_holdings(&token_id).unwrap();
+        let tracked = holdings.internal + holdings.in_flight + holdings.skimmed;
+        let surplus = balance.0.saturating_sub(tracked);
+        let deficit = tracked.saturating_sub(balance.0);
+        if deficit > 0 {
+            log!("Token {} insolvent, balance {}, tracked {}", token_id, balance.0, tracked);
+        }
+        SolvencyInfo {
+            balance,
+            tracked: U128(tracked),
+            surplus: U128(surplus),
+            deficit: U128(deficit),
+            initialized_from_balance: !self.track_all_holdings,
+        }
+    }
+
+    /// Deposits skimmed tokens to the lostfound account of owner, token must be whitelisted.
+    /// Returns claimed amount. Only can be called by owner.
+    #[payable]
+    pub fn claim_skimmed_tokens(&mut self, token_id: ValidAccountId) -> U128 {
+        assert_one_yocto();
+        self.assert_owner();
+        let token_id: AccountId = token_id.into();
+        let mut holdings = self
+            .internal_get_token_holdings(&token_id)
+            .expect(ERR410_TOKEN_HOLDINGS_NOT_TRACKED);
+        let amount = holdings.skimmed;
+        holdings.internal += amount;
+        holdings.skimmed = 0;
+        self.token_holdings.insert(&token_id, &holdings);
+        if amount > 0 {
+            self.internal_lostfound(&token_id, amount);
+            log!("Claimed {} skimmed {}", amount, token_id);
+        }
+        U128(amount)
+    }
+
+    /// Returns tracked holdings of the token, None if they are not tracked.
+    pub fn get_token_holdings(&self, token_id: ValidAccountId) -> Option<TokenHoldingsInfo> {
+        self.internal_get_token_holdings(token_id.as_ref())
+            .map(|holdings| TokenHoldingsInfo {
+                internal: U128(holdings.internal),
+                in_flight: U128(holdings.in_flight),
+                skimmed: U128(holdings.skimmed),
+            })
+    }
+}
+
+/// Returns balance reported by `ft_balance_of` the callback waits for.
+fn internal_balance_result() -> U128 {
+    assert_eq!(env::promise_results_count(), 1, "ERR_EXPECTED_ONE_PROMISE_RESULT");
+    match env::promise_result(0) {
+        PromiseResult::Successful(value) => {
+            serde_json::from_slice(&value).expect("ERR_WRONG_BALANCE_FORMAT")
+        }
+        _ => env::panic(b"ERR_FT_BALANCE_OF_FAILED"),
+    }
+}
+
+impl Contract {
+    fn assert_token_holdings_not_tracked(&self, token_id: &AccountId) {
+        assert!(
+            self.internal_get_token_holdings(token_id).is_none(),
+            "{}",
+            ERR411_TOKEN_HOLDINGS_TRACKED
+        );
+        assert!(
+            self.token_shares.get(token_id).is_none(),
+            "{}",
+            ERR120_SHARES_MODE_ENABLED
+        );
+    }
+
+    /// Returns holdings of the token, None if they are not tracked.
+    pub(crate) fn internal_get_token_holdings(&self, token_id: &AccountId) -> Option<TokenHoldings> {
+        if self.token_shares.get(token_id).is_some() {
+            return None;
+        }
+        self.token_holdings.get(token_id).or_else(|| {
+            if self.track_all_holdings {
+                Some(TokenHoldings::default())
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Applies the change to tracked holdings of the token, if they are tracked.
+    fn internal_update_token_holdings(
+        &mut self,
+        token_id: &AccountId,
+        update: impl FnOnce(&mut TokenHoldings),
+    ) {
+        if let Some(mut holdings) = self.internal_get_token_holdings(token_id) {
+            update(&mut holdings);
+            self.token_holdings.insert(token_id, &holdings);
+        }
+    }
+
+    /// Adds tokens received by the exchange to holdings.
+    pub(crate) fn internal_track_incoming(&mut self, token_id: &AccountId, amount: Balance) {
+        self.internal_update_token_holdings(token_id, |holdings| holdings.internal += amount);
+    }
+
+    /// Moves tokens leaving the exchange from holdings to in flight until the transfer resolves.
+    pub(crate) fn internal_track_outgoing(&mut self, token_id: &AccountId, amount: Balance) {
+        self.internal_update_token_holdings(token_id, |holdings| {
+            holdings.internal = holdings.internal.saturating_sub(amount);
+            holdings.in_flight += amount;
+        });
+    }
+
+    /// Removes resolved outgoing transfer from in flight, failed ones are received back.
+    pub(crate) fn internal_track_resolved(&mut self, token_id: &AccountId, amount: Balance) {
+        self.internal_update_token_holdings(token_id, |holdings| {
+            holdings.in_flight = holdings.in_flight.saturating_sub(amount)
+        });
+    }
+}
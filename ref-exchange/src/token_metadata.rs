@@ -0,0 +1,101 @@
+//! Cached `ft_metadata` of tokens, shown in metadata of LP tokens.
+//!
+//! Owner or guardians fetch `ft_metadata` of tokens and the exchange caches their symbols and
+//! icons, so `mft_metadata` of a pool can name its LP token after the pool tokens, e.g.
+//! `USDC-USDT-LP`, and show an icon without wallets querying every token. Symbols and icons
+//! above the size limits are not cached, the exchange pays for the storage. Pool metadata set by
+//! the creator takes precedence over the derived name and icon.
+
+use near_contract_standards::fungible_token::metadata::FungibleTokenMetadata;
+use near_sdk::{serde_json, Gas};
+
+use crate::utils::{ext_ft_metadata, ext_self, GAS_FOR_FT_METADATA};
+use crate::*;
+
+pub const MAX_TOKEN_SYMBOL_LEN: usize = 32;
+/// Max length of a cached icon, larger data URLs are skipped.
+pub const MAX_TOKEN_ICON_LEN: usize = 4096;
+/// Max number of tokens fetched in one call.
+pub const MAX_TOKEN_METADATA_BATCH: usize = 10;
+
+/// Amount of gas for caching fetched metadata, per token.
+pub const GAS_FOR_RESOLVE_CACHE_METADATA: Gas = 5_000_000_000_000;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug, PartialEq))]
+pub struct CachedTokenMetadata {
+    pub symbol: String,
+    pub icon: Option<String>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Fetches `ft_metadata` of the tokens and caches their symbols and icons.
+    /// Only can be called by owner or guardians.
+    pub fn cache_token_metadata(&mut self, token_ids: Vec<ValidAccountId>) -> Promise {
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        assert!(
+            !token_ids.is_empty() && token_ids.len() <= MAX_TOKEN_METADATA_BATCH,
+            "{}",
+            ERR417_INVALID_TOKEN_METADATA_BATCH
+        );
+        let token_ids: Vec<AccountId> = token_ids.into_iter().map(|token_id| token_id.into()).collect();
+        let promise = token_ids
+            .iter()
+            .map(|token_id| ext_ft_metadata::ft_metadata(token_id, 0, GAS_FOR_FT_METADATA))
+            .reduce(|promise, next| promise.and(next))
+            .unwrap();
+        let gas = GAS_FOR_RESOLVE_CACHE_METADATA * token_ids.len() as Gas;
+        promise.then(ext_self::exchange_callback_cache_token_metadata(
+            token_ids,
+            &env::current_account_id(),
+            0,
+            gas,
+        ))
+    }
+
+    /// Caches metadata returned by the tokens, returns tokens that were cached.
+    #[private]
+    pub fn exchange_callback_cache_token_metadata(
+        &mut self,
+        token_ids: Vec<AccountId>,
+    ) -> Vec<AccountId> {
+        assert_eq!(
+            env::promise_results_count() as usize,
+            token_ids.len(),
+            "ERR_WRONG_PROMISE_RESULTS"
+        );
+        let mut cached = vec![];
+        for (i, token_id) in token_ids.into_iter().enumerate() {
+            let metadata = match env::promise_result(i as u64) {
+                PromiseResult::Successful(value) => {
+                    serde_json::from_slice::<FungibleTokenMetadata>(&value).ok()
+                }
+                _ => None,
+            };
+            match metadata {
+                Some(metadata) if metadata.symbol.len() <= MAX_TOKEN_SYMBOL_LEN => {
+                    self.token_metadata.insert(
+                        &token_id,
+                        &CachedTokenMetadata {
+                            symbol: metadata.symbol,
+                            icon: metadata
+                                .icon
+                                .filter(|icon| icon.len() <= MAX_TOKEN_ICON_LEN),
+                        },
+                    );
+                    cached.push(token_id);
+                }
+                _ => log!("Metadata of {} not cached", token_id),
+            }
+        }
+        log!("Cached metadata of {:?}", cached);
+        cached
+    }
+
+    /// Returns cached metadata of the token.
+    pub fn get_token_metadata(&self, token_id: ValidAccountId) -> Option<CachedTokenMetadata> {
+        self.token_metadata.get(token_id.as_ref())
+    }
+}
@@ -1,30 +1,17 @@
 
 use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
-use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{serde_json, PromiseOrValue};
 
 use crate::*;
 
 pub const VIRTUAL_ACC: &str = "@";
 
-/// Message parameters to receive via token function call.
-#[derive(Serialize, Deserialize)]
-#[serde(crate = "near_sdk::serde")]
-#[serde(untagged)]
-enum TokenReceiverMessage {
-    /// Alternative to deposit + execute actions call.
-    Execute {
-        referral_id: Option<ValidAccountId>,
-        /// List of sequential actions.
-        actions: Vec<Action>,
-    },
-}
-
 impl Contract {
     /// Executes set of actions on virtual account.
     /// Returns amounts to send to the sender directly.
     fn internal_direct_actions(
         &mut self,
+        sender_id: &AccountId,
         token_in: AccountId,
         amount_in: Balance,
         referral_id: Option<AccountId>,
@@ -36,6 +23,7 @@ impl Contract {
 
         account.deposit(&token_in, amount_in);
         let _ = self.internal_execute_actions(
+            sender_id,
             &mut account,
             &referral_id,
             &actions,
@@ -59,6 +47,8 @@ impl Contract {
 impl FungibleTokenReceiver for Contract {
     /// Callback on receiving tokens by this contract.
     /// `msg` format is either "" for deposit or `TokenReceiverMessage`.
+    /// Deposit to another account fails if it's not registered, tokens are returned then.
+    /// Deposits are logged and recorded in receipts of accounts that enabled them.
     #[allow(unreachable_code)]
     fn ft_on_transfer(
         &mut self,
@@ -68,9 +58,12 @@ impl FungibleTokenReceiver for Contract {
     ) -> PromiseOrValue<U128> {
         self.assert_contract_running();
         let token_in = env::predecessor_account_id();
+        self.assert_token_not_banned(&token_in);
+        let amount = U128(self.internal_receive_tokens(&token_in, amount.0));
         if msg.is_empty() {
             // Simple deposit.
             self.internal_deposit(sender_id.as_ref(), &token_in, amount.into());
+            self.internal_record_deposit(sender_id.as_ref(), &token_in, amount.into(), None);
             PromiseOrValue::Value(U128(0))
         } else {
             // instant swap
@@ -83,6 +76,7 @@ impl FungibleTokenReceiver for Contract {
                 } => {
                     let referral_id = referral_id.map(|x| x.to_string());
                     let out_amounts = self.internal_direct_actions(
+                        sender_id.as_ref(),
                         token_in,
                         amount.0,
                         referral_id,
@@ -94,6 +88,33 @@ impl FungibleTokenReceiver for Contract {
                     // Even if send tokens fails, we don't return funds back to sender.
                     PromiseOrValue::Value(U128(0))
                 }
+                TokenReceiverMessage::DepositTo { receiver_id, memo } => {
+                    self.internal_deposit(receiver_id.as_ref(), &token_in, amount.into());
+                    log!(
+                        "{} deposited {} {} to {}",
+                        sender_id.as_ref(),
+                        amount.0,
+                        token_in,
+                        receiver_id.as_ref()
+                    );
+                    self.internal_record_deposit(
+                        receiver_id.as_ref(),
+                        &token_in,
+                        amount.into(),
+                        memo,
+                    );
+                    PromiseOrValue::Value(U128(0))
+                }
+                TokenReceiverMessage::Deposit { memo } => {
+                    self.internal_deposit(sender_id.as_ref(), &token_in, amount.into());
+                    self.internal_record_deposit(
+                        sender_id.as_ref(),
+                        &token_in,
+                        amount.into(),
+                        Some(memo),
+                    );
+                    PromiseOrValue::Value(U128(0))
+                }
             }
         }
     }
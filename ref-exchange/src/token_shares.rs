@@ -0,0 +1,200 @@
+//! Shares mode for interest-bearing / rebasing tokens.
+//!
+//! For a token in shares mode, all internal amounts (account deposits, pool reserves) are shares
+//! of the exchange's total holdings of this token. Incoming tokens mint shares, outgoing burn them.
+//! When token rebases, owner or guardians reconcile tracked holdings with `ft_balance_of` of the
+//! exchange, which changes the value of every share at once instead of breaking the omnibus
+//! accounting. Outgoing transfers stay in flight until their callback, reconciling is refused and
+//! skipped while any are, as the balance may or may not include them yet. An incoming
+//! `ft_transfer_call` is in the balance before `ft_on_transfer` mints its shares, a balance query
+//! racing it counts the amount twice and inflates the shares until the next reconcile, so
+//! reconciling is restricted and should be done when no deposits of the token are pending.
+//! Shares mode is enabled only for tokens with tracked holdings and nothing deposited or reserved.
+
+use near_contract_standards::fungible_token::core_impl::ext_fungible_token;
+use near_sdk::serde_json;
+
+use crate::utils::{ext_self, U256, GAS_FOR_FT_BALANCE_OF, GAS_FOR_RESOLVE_RECONCILE};
+use crate::*;
+
+/// Tracked holdings of a token in shares mode.
+#[derive(BorshSerialize, BorshDeserialize, Default, Clone)]
+pub struct TokenShares {
+    /// Total shares, i.e. sum of all internal amounts of this token.
+    pub total_shares: Balance,
+    /// Amount of tokens the exchange holds for all the shares.
+    pub total_balance: Balance,
+    /// Amount of tokens of outgoing transfers not resolved yet.
+    pub in_flight: Balance,
+}
+
+impl TokenShares {
+    /// Returns shares for given token amount, rounded down.
+    pub fn amount_to_shares(&self, amount: Balance) -> Balance {
+        if self.total_shares == 0 || self.total_balance == 0 {
+            amount
+        } else {
+            (U256::from(amount) * U256::from(self.total_shares) / U256::from(self.total_balance))
+                .as_u128()
+        }
+    }
+
+    /// Returns token amount for given shares, rounded down.
+    pub fn shares_to_amount(&self, shares: Balance) -> Balance {
+        if self.total_shares == 0 {
+            0
+        } else {
+            (U256::from(shares) * U256::from(self.total_balance) / U256::from(self.total_shares))
+                .as_u128()
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug, PartialEq))]
+pub struct TokenSharesInfo {
+    pub total_shares: U128,
+    pub total_balance: U128,
+    pub in_flight: U128,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Switches given token to shares mode. Only can be called by owner.
+    /// Must be done before the token is deposited first time, can not be reverted.
+    /// Holdings of the token must be tracked, see `init_token_holdings`.
+    #[payable]
+    pub fn enable_token_shares_mode(&mut self, token_id: ValidAccountId) {
+        assert_one_yocto();
+        self.assert_owner();
+        assert!(
+            self.token_shares.get(token_id.as_ref()).is_none(),
+            "{}",
+            ERR120_SHARES_MODE_ENABLED
+        );
+        let holdings = self
+            .internal_get_token_holdings(token_id.as_ref())
+            .expect(ERR425_TOKEN_HOLDINGS_NOT_INITIALIZED);
+        assert!(
+            holdings.internal == 0 && holdings.in_flight == 0 && holdings.skimmed == 0,
+            "{}",
+            ERR123_TOKEN_HAS_DEPOSITS
+        );
+        self.token_holdings.remove(token_id.as_ref());
+        self.token_shares
+            .insert(token_id.as_ref(), &TokenShares::default());
+        log!("Token {} switched to shares mode", token_id);
+    }
+
+    /// Updates tracked holdings of the token in shares mode with the exchange's actual balance.
+    /// Only can be called by owner or guardians.
+    pub fn reconcile_token_shares(&mut self, token_id: ValidAccountId) -> Promise {
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        let token_shares = self
+            .token_shares
+            .get(token_id.as_ref())
+            .expect(ERR122_NOT_SHARES_MODE);
+        assert_eq!(token_shares.in_flight, 0, "{}", ERR424_TRANSFERS_IN_FLIGHT);
+        ext_fungible_token::ft_balance_of(
+            env::current_account_id(),
+            token_id.as_ref(),
+            0,
+            GAS_FOR_FT_BALANCE_OF,
+        )
+        .then(ext_self::exchange_callback_reconcile_shares(
+            token_id.into(),
+            U128(token_shares.total_balance),
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_RECONCILE,
+        ))
+    }
+
+    /// Sets tracked holdings to the reported balance, adjusted by the movements
+    /// that happened between the balance request and this callback.
+    /// Skipped if outgoing transfers are in flight.
+    #[private]
+    pub fn exchange_callback_reconcile_shares(&mut self, token_id: AccountId, tracked_balance: U128) {
+        assert_eq!(env::promise_results_count(), 1, "ERR_EXPECTED_ONE_PROMISE_RESULT");
+        let balance: U128 = match env::promise_result(0) {
+            PromiseResult::Successful(value) => {
+                serde_json::from_slice(&value).expect("ERR_WRONG_BALANCE_FORMAT")
+            }
+            _ => env::panic(b"ERR_FT_BALANCE_OF_FAILED"),
+        };
+        let mut token_shares = self.token_shares.get(&token_id).unwrap();
+        if token_shares.in_flight > 0 {
+            log!("Token {} transfers in flight during reconcile, skipped", token_id);
+            return;
+        }
+        let prev_balance = token_shares.total_balance;
+        token_shares.total_balance = (balance.0 + token_shares.total_balance)
+            .saturating_sub(tracked_balance.0);
+        self.token_shares.insert(&token_id, &token_shares);
+        log!(
+            "Token {} shares reconciled, balance {} -> {}, total shares {}",
+            token_id,
+            prev_balance,
+            token_shares.total_balance,
+            token_shares.total_shares
+        );
+    }
+
+    /// Returns tracked holdings of the token, None if the token is not in shares mode.
+    pub fn get_token_shares(&self, token_id: ValidAccountId) -> Option<TokenSharesInfo> {
+        self.token_shares
+            .get(token_id.as_ref())
+            .map(|token_shares| TokenSharesInfo {
+                total_shares: U128(token_shares.total_shares),
+                total_balance: U128(token_shares.total_balance),
+                in_flight: U128(token_shares.in_flight),
+            })
+    }
+}
+
+impl Contract {
+    /// Converts amount of tokens received by the exchange into internal amount.
+    /// For tokens in shares mode mints shares, otherwise returns the same amount.
+    pub(crate) fn internal_receive_tokens(&mut self, token_id: &AccountId, amount: Balance) -> Balance {
+        if let Some(mut token_shares) = self.token_shares.get(token_id) {
+            // Dust worth less than a share is left to all share holders.
+            let shares = token_shares.amount_to_shares(amount);
+            token_shares.total_shares += shares;
+            token_shares.total_balance += amount;
+            self.token_shares.insert(token_id, &token_shares);
+            shares
+        } else {
+            self.internal_track_incoming(token_id, amount);
+            amount
+        }
+    }
+
+    /// Converts internal amount leaving the exchange into amount of tokens to transfer.
+    /// For tokens in shares mode burns shares, otherwise returns the same amount.
+    pub(crate) fn internal_release_tokens(&mut self, token_id: &AccountId, amount: Balance) -> Balance {
+        if let Some(mut token_shares) = self.token_shares.get(token_id) {
+            let token_amount = token_shares.shares_to_amount(amount);
+            assert!(token_amount > 0, "{}", ERR121_SHARES_AMOUNT_TOO_SMALL);
+            token_shares.total_shares -= amount;
+            token_shares.total_balance -= token_amount;
+            token_shares.in_flight += token_amount;
+            self.token_shares.insert(token_id, &token_shares);
+            token_amount
+        } else {
+            self.internal_track_outgoing(token_id, amount);
+            amount
+        }
+    }
+
+    /// Removes resolved outgoing transfer of given amount of tokens from in flight.
+    /// Failed transfers are received back separately.
+    pub(crate) fn internal_resolve_tokens(&mut self, token_id: &AccountId, amount: Balance) {
+        if let Some(mut token_shares) = self.token_shares.get(token_id) {
+            token_shares.in_flight = token_shares.in_flight.saturating_sub(amount);
+            self.token_shares.insert(token_id, &token_shares);
+        } else {
+            self.internal_track_resolved(token_id, amount);
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! Time weighted average prices of pools.
+//!
+//! Like in Uniswap V2, every swap first adds the spot prices of the pool, taken as its prices
+//! since the previous swap, multiplied by the time elapsed to cumulative prices. Spot prices are
+//! fee-less prices of the other pool tokens in the first one and of the first token in them, in
+//! smallest token units. A snapshot of cumulative prices is kept at most once per
+//! `TWAP_OBSERVATION_INTERVAL`, the last `MAX_TWAP_OBSERVATIONS` of them, and the average over a
+//! window is the difference to the snapshot before its start divided by the time in between.
+//! Cumulative prices are covered by the exchange.
+
+use near_sdk::json_types::WrappedTimestamp;
+use near_sdk::Timestamp;
+
+use crate::utils::U256;
+use crate::*;
+
+/// Precision of spot and average prices.
+pub const TWAP_PRICE_PRECISION: u128 = 1_000_000_000_000_000_000_000_000_000_000_000_000;
+
+/// Min time between observations of a pool, 30 minutes in nano sec.
+pub const TWAP_OBSERVATION_INTERVAL: Timestamp = 30 * 60 * 1_000_000_000;
+
+/// Number of observations kept per pool, covers windows up to a day.
+pub const MAX_TWAP_OBSERVATIONS: usize = 48;
+
+#[derive(BorshSerialize, BorshDeserialize, Clone)]
+pub struct PriceObservation {
+    pub timestamp: Timestamp,
+    /// Cumulative prices of the other tokens in the first one, followed by cumulative prices of
+    /// the first token in them, as words of U256. They wrap on overflow.
+    pub cumulative_prices: Vec<[u64; 4]>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct PoolTwap {
+    /// Cumulative prices as of the last swap.
+    pub latest: PriceObservation,
+    pub observations: Vec<PriceObservation>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug))]
+pub struct TwapInfo {
+    /// Observation the average is computed from.
+    pub from_timestamp: WrappedTimestamp,
+    pub to_timestamp: WrappedTimestamp,
+    pub tokens: Vec<AccountId>,
+    /// Average price of the token in each pool token, in their smallest units multiplied by
+    /// `TWAP_PRICE_PRECISION`. Decimal strings, as prices may not fit into U128.
+    pub prices: Vec<String>,
+}
+
+/// Returns spot prices of the other tokens in the first one, followed by prices of the first
+/// token in them. None for empty pool.
+fn twap_prices(pool: &Pool) -> Option<Vec<U256>> {
+    let spot_prices = pool.spot_prices(TWAP_PRICE_PRECISION)?;
+    if spot_prices.iter().any(|price| price.is_zero()) {
+        return None;
+    }
+    let precision = U256::from(TWAP_PRICE_PRECISION);
+    let inverse_prices: Vec<U256> = spot_prices[1..]
+        .iter()
+        .map(|price| precision * precision / *price)
+        .collect();
+    Some([&spot_prices[1..], &inverse_prices[..]].concat())
+}
+
+impl PriceObservation {
+    /// Returns the observation with given prices accumulated from its timestamp until now.
+    fn accumulate(&self, prices: &[U256]) -> Self {
+        let elapsed = U256::from(env::block_timestamp() - self.timestamp);
+        PriceObservation {
+            timestamp: env::block_timestamp(),
+            cumulative_prices: self
+                .cumulative_prices
+                .iter()
+                .zip(prices.iter())
+                .map(|(cumulative, price)| {
+                    let cumulative =
+                        U256(*cumulative).overflowing_add(price.overflowing_mul(elapsed).0);
+                    (cumulative.0).0
+                })
+                .collect(),
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Returns average prices of the token in the pool since the latest observation at least
+    /// `window_sec` seconds old. None if the pool has no such observation yet.
+    /// Prices between two tokens other than the first one are derived from their average prices
+    /// in the first token.
+    pub fn get_twap(
+        &self,
+        pool_id: u64,
+        token_id: ValidAccountId,
+        window_sec: u64,
+    ) -> Option<TwapInfo> {
+        let pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+        let tokens = pool.tokens();
+        let token_index = tokens
+            .iter()
+            .position(|id| id == token_id.as_ref())
+            .expect(ERR63_MISSING_TOKEN);
+        let twap = self.pool_twaps.get(&pool_id)?;
+        let current = match twap_prices(&pool) {
+            Some(prices) => twap.latest.accumulate(&prices),
+            None => twap.latest.clone(),
+        };
+        let window_start =
+            env::block_timestamp().checked_sub(window_sec.checked_mul(1_000_000_000)?)?;
+        let from = twap
+            .observations
+            .iter()
+            .rev()
+            .find(|observation| observation.timestamp <= window_start)?;
+        let elapsed = current.timestamp - from.timestamp;
+        if elapsed == 0 {
+            return None;
+        }
+        let averages: Vec<U256> = current
+            .cumulative_prices
+            .iter()
+            .zip(from.cumulative_prices.iter())
+            .map(|(current, from)| {
+                U256(*current).overflowing_sub(U256(*from)).0 / U256::from(elapsed)
+            })
+            .collect();
+        let (in_first, of_first) = averages.split_at(tokens.len() - 1);
+        let precision = U256::from(TWAP_PRICE_PRECISION);
+        let prices = (0..tokens.len())
+            .map(|index| {
+                let price = if index == token_index {
+                    precision
+                } else if index == 0 {
+                    in_first[token_index - 1]
+                } else if token_index == 0 {
+                    of_first[index - 1]
+                } else {
+                    let (price, other_price) = (in_first[token_index - 1], of_first[index - 1]);
+                    price
+                        .checked_mul(other_price)
+                        .map(|product| product / precision)
+                        .unwrap_or_else(|| price / precision * other_price)
+                };
+                price.to_string()
+            })
+            .collect();
+        Some(TwapInfo {
+            from_timestamp: from.timestamp.into(),
+            to_timestamp: current.timestamp.into(),
+            tokens: tokens.to_vec(),
+            prices,
+        })
+    }
+}
+
+impl Contract {
+    /// Accumulates spot prices of the pool since the previous swap and takes an observation if
+    /// the latest one is older than `TWAP_OBSERVATION_INTERVAL`. Must be called before the swap.
+    pub(crate) fn internal_accumulate_prices(&mut self, pool_id: u64, pool: &Pool) {
+        let prices = match twap_prices(pool) {
+            Some(prices) => prices,
+            None => return,
+        };
+        let mut twap = match self.pool_twaps.get(&pool_id) {
+            Some(mut twap) => {
+                twap.latest = twap.latest.accumulate(&prices);
+                twap
+            }
+            None => PoolTwap {
+                latest: PriceObservation {
+                    timestamp: env::block_timestamp(),
+                    cumulative_prices: vec![[0; 4]; prices.len()],
+                },
+                observations: vec![],
+            },
+        };
+        if twap
+            .observations
+            .last()
+            .filter(|last| env::block_timestamp() < last.timestamp + TWAP_OBSERVATION_INTERVAL)
+            .is_none()
+        {
+            twap.observations.push(twap.latest.clone());
+            if twap.observations.len() > MAX_TWAP_OBSERVATIONS {
+                twap.observations.remove(0);
+            }
+        }
+        self.pool_twaps.insert(&pool_id, &twap);
+    }
+}
@@ -2,11 +2,14 @@ use std::collections::HashSet;
 
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::collections::LookupMap;
-use near_sdk::json_types::{ValidAccountId, U128};
+use near_sdk::json_types::{ValidAccountId, WrappedTimestamp, U128};
 use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::{ext_contract, AccountId, Balance, Gas};
+use near_sdk::{env, ext_contract, AccountId, Balance, Gas};
 use uint::construct_uint;
 
+use crate::action::SwapAction;
+use crate::errors::ERR390_DEADLINE_PASSED;
+
 /// Attach no deposit.
 pub const NO_DEPOSIT: u128 = 0;
 /// hotfix_insuffient_gas_for_mft_resolve_transfer.
@@ -17,9 +20,33 @@ pub const GAS_FOR_FT_TRANSFER_CALL: Gas = 25_000_000_000_000 + GAS_FOR_RESOLVE_T
 /// Amount of gas for fungible token transfers, increased to 20T to support AS token contracts.
 pub const GAS_FOR_FT_TRANSFER: Gas = 20_000_000_000_000;
 
+/// Precision of spot prices compared by price impact guards of swaps.
+pub const PRICE_IMPACT_PRECISION: u128 = 1_000_000_000_000_000_000;
+
+/// Amount of gas for querying exchange's balance of a token.
+pub const GAS_FOR_FT_BALANCE_OF: Gas = 10_000_000_000_000;
+
+/// Amount of gas for reconciling token shares with the queried balance.
+pub const GAS_FOR_RESOLVE_RECONCILE: Gas = 10_000_000_000_000;
+
+/// Amount of gas for fetching metadata of a token.
+pub const GAS_FOR_FT_METADATA: Gas = 10_000_000_000_000;
+
+/// Amount of gas for checking stable pool decimals against fetched metadata.
+pub const GAS_FOR_RESOLVE_CHECK_DECIMALS: Gas = 20_000_000_000_000;
+
+/// Amount of gas for registering an account on a token.
+pub const GAS_FOR_STORAGE_DEPOSIT: Gas = 10_000_000_000_000;
+
 /// Fee divisor, allowing to provide fee in bps.
 pub const FEE_DIVISOR: u32 = 10_000;
 
+/// Max number of intermediary tokens for route search, bounds its gas.
+pub const MAX_ROUTE_INTERMEDIARIES: u64 = 8;
+
+/// Max number of referral fee tiers per token.
+pub const MAX_REFERRAL_FEE_TIERS: usize = 10;
+
 /// Initial shares supply on deposit of liquidity.
 pub const INIT_SHARES_SUPPLY: u128 = 1_000_000_000_000_000_000_000_000;
 
@@ -53,6 +80,41 @@ pub trait RefExchange {
         sender_id: AccountId,
         amount: U128,
     );
+    fn exchange_callback_reconcile_shares(&mut self, token_id: AccountId, tracked_balance: U128);
+    fn exchange_callback_check_decimals(&mut self, pool_id: u64);
+    fn exchange_callback_near_deposit(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128,
+        actions: Vec<SwapAction>,
+        referral_id: Option<AccountId>,
+    );
+    fn exchange_callback_swap_near(
+        &mut self,
+        sender_id: AccountId,
+        actions: Vec<SwapAction>,
+        referral_id: Option<AccountId>,
+    );
+    fn exchange_callback_near_withdraw(&mut self, sender_id: AccountId, amount: U128);
+    fn exchange_callback_flash_loan(&mut self, sender_id: AccountId) -> U128;
+    fn exchange_callback_skim(&mut self, token_id: AccountId, internal: U128) -> U128;
+    fn exchange_callback_init_token_holdings(&mut self, token_id: AccountId);
+    fn exchange_callback_check_solvency(&self, token_id: AccountId);
+    fn exchange_callback_cache_token_metadata(&mut self, token_ids: Vec<AccountId>);
+}
+
+#[ext_contract(ext_ft_metadata)]
+pub trait FungibleTokenMetadataProvider {
+    fn ft_metadata(&self) -> near_contract_standards::fungible_token::metadata::FungibleTokenMetadata;
+}
+
+#[ext_contract(ext_ft_storage)]
+pub trait FungibleTokenStorage {
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> near_contract_standards::storage_management::StorageBalance;
 }
 
 /// Adds given value to item stored in the given key in the LookupMap collection.
@@ -67,6 +129,13 @@ pub fn check_token_duplicates(tokens: &[ValidAccountId]) {
     assert_eq!(token_set.len(), tokens.len(), "ERR_TOKEN_DUPLICATES");
 }
 
+/// Checks that the block is not past the given deadline, if any.
+pub fn assert_deadline(deadline: Option<WrappedTimestamp>) {
+    if let Some(deadline) = deadline {
+        assert!(env::block_timestamp() <= deadline.0, "{}", ERR390_DEADLINE_PASSED);
+    }
+}
+
 /// Newton's method of integer square root.
 pub fn integer_sqrt(value: U256) -> U256 {
     let mut guess: U256 = (value + U256::one()) >> 1;
@@ -78,6 +147,43 @@ pub fn integer_sqrt(value: U256) -> U256 {
     res
 }
 
+/// Returns smallest amount in with `get_return` of at least `amount_out`, searching around
+/// `estimate`. `get_return` must not decrease with the amount in.
+pub fn min_amount_in(
+    estimate: Balance,
+    amount_out: Balance,
+    get_return: impl Fn(Balance) -> Balance,
+) -> Balance {
+    // Amounts in up to `low` return less than amount out, `high` returns enough.
+    let mut high = std::cmp::max(estimate, 1);
+    let mut step = 1;
+    let mut low = if get_return(high) >= amount_out {
+        while high > step && get_return(high - step) >= amount_out {
+            high -= step;
+            step *= 2;
+        }
+        high.saturating_sub(step)
+    } else {
+        loop {
+            let low = high;
+            high = low.checked_add(step).expect("ERR_AMOUNT_IN_OVERFLOW");
+            if get_return(high) >= amount_out {
+                break low;
+            }
+            step *= 2;
+        }
+    };
+    while high - low > 1 {
+        let middle = low + (high - low) / 2;
+        if get_return(middle) >= amount_out {
+            high = middle;
+        } else {
+            low = middle;
+        }
+    }
+    high
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -91,4 +197,12 @@ mod tests {
             U256::from(1_231_323)
         );
     }
+
+    #[test]
+    fn test_min_amount_in() {
+        let get_return = |amount_in: Balance| amount_in * 2 / 3;
+        for estimate in [0, 1, 149, 150, 151, 1_000_000].iter() {
+            assert_eq!(min_amount_in(*estimate, 100, get_return), 150);
+        }
+    }
 }
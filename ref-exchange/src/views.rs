@@ -2,13 +2,24 @@
 
 use std::collections::HashMap;
 
-use near_sdk::json_types::{ValidAccountId, U128};
+use near_sdk::json_types::{ValidAccountId, U128, U64};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{near_bindgen, AccountId};
 
-use crate::utils::SwapVolume;
+use crate::account_deposit::{INIT_ACCOUNT_STORAGE, LEGACY_TOKEN_STORAGE, TOKEN_STORAGE};
+use crate::admin_fee::FeeSide;
+use crate::decimals_check::DecimalsStatus;
+use crate::pagination::{Cursor, Page};
+use crate::pool_metadata::PoolMetadata;
+use crate::simple_pool::{MAX_SIMPLE_POOL_TOKENS, MIN_SIMPLE_POOL_TOKENS};
+use crate::stable_swap::math::{MAX_AMP, MIN_AMP};
+use crate::utils::{SwapVolume, FEE_DIVISOR, U256};
+use crate::weighted_pool::{MAX_WEIGHTED_POOL_TOKENS, MIN_WEIGHTED_POOL_TOKENS};
 use crate::*;
 
+/// Precision of spot prices used for price impact of simulated swaps.
+const SIMULATION_PRICE_PRECISION: u128 = 1_000_000_000_000_000_000;
+
 #[derive(Serialize)]
 #[serde(crate = "near_sdk::serde")]
 #[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug))]
@@ -20,6 +31,48 @@ pub struct ContractMetadata {
     pub state: RunningState,
     pub exchange_fee: u32,
     pub referral_fee: u32,
+    /// Limits of actions per call and tokens per pool.
+    pub action_limits: ActionLimits,
+}
+
+/// Parameters of a pool type supported by the exchange.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug))]
+pub struct PoolTypeInfo {
+    /// Same as `pool_kind` in `PoolInfo`.
+    pub pool_kind: String,
+    /// Method that creates a pool of this type.
+    pub create_method: String,
+    /// If false, only owner or guardians can create pools of this type.
+    pub permissionless: bool,
+    pub min_tokens: u32,
+    /// None if the number of tokens is not limited.
+    pub max_tokens: Option<u32>,
+    /// Total fee of the pool must be less than this, in bps of `fee_divisor`.
+    pub max_total_fee: u32,
+    pub min_amp: Option<u64>,
+    pub max_amp: Option<u64>,
+}
+
+/// Self-description of the exchange for aggregators and routers.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Deserialize, Debug))]
+pub struct ExchangeInfo {
+    pub protocol: String,
+    pub version: String,
+    pub fee_divisor: u32,
+    /// Part of the pool fee going to the exchange, in bps of `fee_divisor`.
+    pub exchange_fee: u32,
+    /// Part of the pool fee going to the referral, in bps of `fee_divisor`.
+    pub referral_fee: u32,
+    pub pool_types: Vec<PoolTypeInfo>,
+    /// Format of token ids in `mft_*` methods.
+    pub mft_token_id_scheme: String,
+    /// Formats of `msg` accepted in `ft_on_transfer`.
+    pub receiver_messages: Vec<String>,
+    pub state: RunningState,
 }
 
 #[derive(Serialize, Deserialize, PartialEq)]
@@ -30,6 +83,26 @@ pub struct RefStorageState {
     pub usage: U128,
 }
 
+/// Storage bytes charged to the account by component.
+#[derive(Serialize, Deserialize, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct StorageUsageBreakdown {
+    /// Account record itself: id, storage balance and collections headers.
+    pub account_bytes: u64,
+    /// Number of registered tokens and bytes they take.
+    pub tokens: u64,
+    pub token_bytes: u64,
+    /// Number of tokens in the legacy map left from older account version and bytes they take.
+    pub legacy_tokens: u64,
+    pub legacy_token_bytes: u64,
+    pub total_bytes: u64,
+    pub storage_byte_cost: U128,
+    /// Storage deposit of the account and the part of it locked by used bytes.
+    pub deposit: U128,
+    pub usage: U128,
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 #[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
@@ -45,6 +118,20 @@ pub struct PoolInfo {
     /// Total number of shares.
     pub shares_total_supply: U128,
     pub amp: u64,
+    /// Result of decimals check of a stable pool, absent if it was never checked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decimals_status: Option<DecimalsStatus>,
+    /// Token of the swap the fee is taken in.
+    pub fee_side: FeeSide,
+    /// Blocks left until swaps of a newly seeded pool open, absent if swaps are open.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub launch_delay: Option<u64>,
+    /// Display metadata set by the pool creator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<PoolMetadata>,
+    /// Weights of the tokens of a weighted pool, in parts of 10000.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weights: Option<Vec<u32>>,
 }
 
 impl From<Pool> for PoolInfo {
@@ -58,6 +145,11 @@ impl From<Pool> for PoolInfo {
                 amounts: pool.amounts.into_iter().map(|a| U128(a)).collect(),
                 total_fee: pool.total_fee,
                 shares_total_supply: U128(pool.shares_total_supply),
+                decimals_status: None,
+                fee_side: FeeSide::Input,
+                launch_delay: None,
+                metadata: None,
+                weights: None,
             },
             Pool::StableSwapPool(pool) => Self {
                 pool_kind,
@@ -66,11 +158,63 @@ impl From<Pool> for PoolInfo {
                 token_account_ids: pool.token_account_ids,
                 total_fee: pool.total_fee,
                 shares_total_supply: U128(pool.shares_total_supply),
+                decimals_status: None,
+                fee_side: FeeSide::Output,
+                launch_delay: None,
+                metadata: None,
+                weights: None,
+            },
+            Pool::WeightedPool(pool) => Self {
+                pool_kind,
+                amp: 0,
+                token_account_ids: pool.token_account_ids,
+                amounts: pool.amounts.into_iter().map(U128).collect(),
+                total_fee: pool.total_fee,
+                shares_total_supply: U128(pool.shares_total_supply),
+                decimals_status: None,
+                fee_side: FeeSide::Input,
+                launch_delay: None,
+                metadata: None,
+                weights: Some(pool.weights),
             },
         }
     }
 }
 
+/// Fee parameters applied to swaps in a pool.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct PoolFees {
+    /// Fee charged for swap, in bps of `fee_divisor`.
+    pub total_fee: u32,
+    /// Parts of the total fee going to the exchange and the referral, in bps of `fee_divisor`.
+    pub exchange_fee: u32,
+    pub referral_fee: u32,
+    /// Referral fee tiers of pool tokens that have them, by input token.
+    pub referral_fee_tiers: Vec<(AccountId, Vec<ReferralFeeTier>)>,
+    pub fee_side: FeeSide,
+    /// False if exchange fee shares are skimmed from the pool.
+    pub compounding: bool,
+}
+
+/// Cheap liveness summary for uptime monitors.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct HealthCheck {
+    pub state: RunningState,
+    pub block_height: U64,
+    pub timestamp: U64,
+    /// Number of tokens quarantined by guardians, swaps through them are paused.
+    pub quarantined_tokens: u64,
+    /// Blocks swaps of newly seeded pools are blocked for.
+    pub pool_launch_delay: u64,
+    pub storage_usage: U64,
+    /// Balance of the contract not locked for its storage.
+    pub storage_headroom: U128,
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 #[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
@@ -89,10 +233,71 @@ pub struct StablePoolInfo {
     pub amp: u64,
 }
 
+/// Ramp of the stable pool's amp factor, amp moves linearly from initial to target factor
+/// between the two timestamps and stays at target factor after.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct AmpRampInfo {
+    pub amp: u64,
+    pub init_amp_factor: u64,
+    pub init_amp_time: U64,
+    pub target_amp_factor: u64,
+    pub stop_amp_time: U64,
+}
+
+/// Result of a simulated swap through one pool.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct SwapSimulation {
+    pub pool_id: u64,
+    pub token_in: AccountId,
+    pub amount_in: U128,
+    pub token_out: AccountId,
+    pub amount_out: U128,
+    /// Total fee of the pool charged on the swap, in the input token.
+    pub fee: U128,
+    /// Loss against the spot price before the swap, fee included, in bps of `fee_divisor`.
+    pub price_impact: u32,
+}
+
+/// Result of a simulated action, routed swaps have a swap per hop.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct ActionSimulation {
+    pub amount_in: U128,
+    pub amount_out: U128,
+    /// Combined price impact of the swaps, in bps of `fee_divisor`.
+    pub price_impact: u32,
+    pub swaps: Vec<SwapSimulation>,
+}
+
+/// Shares of an account in a pool with the amounts of pool tokens they are redeemable for.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct LiquidityPosition {
+    pub pool_id: u64,
+    pub shares: U128,
+    pub token_account_ids: Vec<AccountId>,
+    pub amounts: Vec<U128>,
+}
+
+/// Deposits and liquidity positions of an account.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct AccountPortfolio {
+    pub deposits: HashMap<AccountId, U128>,
+    pub positions: Vec<LiquidityPosition>,
+}
+
 impl From<Pool> for StablePoolInfo {
     fn from(pool: Pool) -> Self {
         match pool {
-            Pool::SimplePool(_) => unimplemented!(),
+            Pool::SimplePool(_) | Pool::WeightedPool(_) => unimplemented!(),
             Pool::StableSwapPool(pool) => Self {
                 amp: pool.get_amp(),
                 amounts: pool.get_amounts().into_iter().map(|a| U128(a)).collect(),
@@ -114,17 +319,93 @@ impl Contract {
         ContractMetadata {
             version: env!("CARGO_PKG_VERSION").to_string(),
             owner: self.owner_id.clone(),
-            guardians: self.guardians.to_vec(),
+            guardians: self.internal_get_guardians(),
             pool_count: self.pools.len(),
             state: self.state.clone(),
             exchange_fee: self.exchange_fee,
             referral_fee: self.referral_fee,
+            action_limits: self.action_limits.clone(),
+        }
+    }
+
+    /// Returns standardized description of this exchange, so integrations don't need per deployment adapters.
+    pub fn get_exchange_info(&self) -> ExchangeInfo {
+        ExchangeInfo {
+            protocol: "jumbo-exchange".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            fee_divisor: FEE_DIVISOR,
+            exchange_fee: self.exchange_fee,
+            referral_fee: self.referral_fee,
+            pool_types: vec![
+                PoolTypeInfo {
+                    pool_kind: "SIMPLE_POOL".to_string(),
+                    create_method: "add_simple_pool".to_string(),
+                    permissionless: true,
+                    min_tokens: MIN_SIMPLE_POOL_TOKENS as u32,
+                    max_tokens: Some(std::cmp::min(
+                        MAX_SIMPLE_POOL_TOKENS as u32,
+                        self.action_limits.max_pool_tokens,
+                    )),
+                    max_total_fee: FEE_DIVISOR,
+                    min_amp: None,
+                    max_amp: None,
+                },
+                PoolTypeInfo {
+                    pool_kind: "STABLE_SWAP".to_string(),
+                    create_method: "add_stable_swap_pool".to_string(),
+                    permissionless: false,
+                    min_tokens: 2,
+                    max_tokens: Some(self.action_limits.max_pool_tokens),
+                    max_total_fee: FEE_DIVISOR,
+                    min_amp: Some(MIN_AMP as u64),
+                    max_amp: Some(MAX_AMP as u64),
+                },
+                PoolTypeInfo {
+                    pool_kind: "WEIGHTED_POOL".to_string(),
+                    create_method: "add_weighted_pool".to_string(),
+                    permissionless: true,
+                    min_tokens: MIN_WEIGHTED_POOL_TOKENS as u32,
+                    max_tokens: Some(std::cmp::min(
+                        MAX_WEIGHTED_POOL_TOKENS as u32,
+                        self.action_limits.max_pool_tokens,
+                    )),
+                    max_total_fee: FEE_DIVISOR,
+                    min_amp: None,
+                    max_amp: None,
+                },
+            ],
+            mft_token_id_scheme: "<token account id> for deposits, :<pool id> for pool shares"
+                .to_string(),
+            receiver_messages: vec![
+                "\"\" deposits tokens to the sender's account".to_string(),
+                concat!(
+                    "{\"referral_id\": <optional account id>, \"actions\": [<SwapAction>, ...]} ",
+                    "swaps and transfers the output back to the sender"
+                )
+                .to_string(),
+            ],
+            state: self.state.clone(),
+        }
+    }
+
+    /// Returns running state and storage headroom of the contract.
+    /// Only reads fixed size fields, so it can be polled every block.
+    pub fn health_check(&self) -> HealthCheck {
+        let storage_cost = env::storage_usage() as Balance * env::storage_byte_cost();
+        HealthCheck {
+            state: self.state.clone(),
+            block_height: U64(env::block_index()),
+            timestamp: U64(env::block_timestamp()),
+            quarantined_tokens: self.unresponsive_tokens.len(),
+            pool_launch_delay: self.pool_launch_delay,
+            storage_usage: U64(env::storage_usage()),
+            storage_headroom: U128(env::account_balance().saturating_sub(storage_cost)),
         }
     }
 
     /// Only get guardians info
     pub fn get_guardians(&self) -> Vec<AccountId> {
-        self.guardians.to_vec()
+        self.internal_get_guardians()
     }
     
     /// Returns semver of this contract.
@@ -144,9 +425,21 @@ impl Contract {
             .collect()
     }
 
+    /// Returns page of `limit` pools after the cursor.
+    pub fn get_pools_page(&self, cursor: Option<String>, limit: u64) -> Page<PoolInfo> {
+        Page::from_index(cursor, limit, self.pools.len(), |index| self.get_pool(index))
+    }
+
     /// Returns information about specified pool.
     pub fn get_pool(&self, pool_id: u64) -> PoolInfo {
-        self.pools.get(pool_id).expect("ERR_NO_POOL").into()
+        let pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+        let fee_side = self.internal_get_fee_side(pool_id, &pool);
+        let mut pool_info: PoolInfo = pool.into();
+        pool_info.decimals_status = self.pool_decimals_status.get(&pool_id);
+        pool_info.fee_side = fee_side;
+        pool_info.launch_delay = self.internal_remaining_launch_delay(pool_id);
+        pool_info.metadata = self.pool_metadata.get(&pool_id);
+        pool_info
     }
 
     /// Returns stable pool information about specified pool.
@@ -154,11 +447,56 @@ impl Contract {
         self.pools.get(pool_id).expect("ERR_NO_POOL").into()
     }
 
+    /// Returns ramp schedule of the stable pool's amp factor.
+    pub fn get_amp_ramp(&self, pool_id: u64) -> AmpRampInfo {
+        match self.pools.get(pool_id).expect("ERR_NO_POOL") {
+            Pool::StableSwapPool(pool) => AmpRampInfo {
+                amp: pool.get_amp(),
+                init_amp_factor: pool.init_amp_factor as u64,
+                init_amp_time: U64(pool.init_amp_time),
+                target_amp_factor: pool.target_amp_factor as u64,
+                stop_amp_time: U64(pool.stop_amp_time),
+            },
+            _ => env::panic(b"ERR_NOT_STABLE_POOL"),
+        }
+    }
+
     /// Return total fee of the given pool.
     pub fn get_pool_fee(&self, pool_id: u64) -> u32 {
         self.pools.get(pool_id).expect("ERR_NO_POOL").get_fee()
     }
 
+    /// Returns fee parameters of given pools, in the same order.
+    pub fn get_fees_by_pool_ids(&self, pool_ids: Vec<u64>) -> Vec<PoolFees> {
+        pool_ids
+            .into_iter()
+            .map(|pool_id| {
+                let pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+                let admin_fees = self.internal_get_pool_admin_fees(pool_id);
+                PoolFees {
+                    total_fee: pool.get_fee(),
+                    exchange_fee: admin_fees.exchange_fee,
+                    referral_fee: admin_fees.referral_fee,
+                    referral_fee_tiers: pool
+                        .tokens()
+                        .iter()
+                        .filter_map(|token_id| {
+                            self.referral_fee_tiers
+                                .get(token_id)
+                                .map(|tiers| (token_id.clone(), tiers))
+                        })
+                        .collect(),
+                    fee_side: self.internal_get_fee_side(pool_id, &pool),
+                    compounding: self
+                        .pool_fee_skims
+                        .get(&pool_id)
+                        .filter(|fee_skim| fee_skim.enabled)
+                        .is_none(),
+                }
+            })
+            .collect()
+    }
+
     /// Return volumes of the given pool.
     pub fn get_pool_volumes(&self, pool_id: u64) -> Vec<SwapVolume> {
         self.pools.get(pool_id).expect("ERR_NO_POOL").get_volumes()
@@ -200,6 +538,84 @@ impl Contract {
         }
     }
 
+    /// Returns page of `limit` deposits of given user after the cursor, in order of token ids.
+    pub fn get_deposits_page(
+        &self,
+        account_id: ValidAccountId,
+        cursor: Option<String>,
+        limit: u64,
+    ) -> Page<(AccountId, U128)> {
+        let after = match cursor.map(|cursor| Cursor::decode(&cursor)) {
+            None => None,
+            Some(Cursor::After(token_id)) => Some(token_id),
+            Some(Cursor::Index(_)) => env::panic(ERR290_INVALID_CURSOR.as_bytes()),
+        };
+        let account = match self.internal_get_account(account_id.as_ref()) {
+            Some(account) => account,
+            None => {
+                return Page {
+                    items: vec![],
+                    next_cursor: None,
+                    has_more: false,
+                }
+            }
+        };
+        let mut tokens: Vec<AccountId> = account
+            .get_tokens()
+            .into_iter()
+            .filter(|token_id| match &after {
+                Some(after) => token_id > after,
+                None => true,
+            })
+            .collect();
+        tokens.sort();
+        let has_more = tokens.len() as u64 > limit;
+        tokens.truncate(limit as usize);
+        let next_cursor = match tokens.last() {
+            Some(token_id) if has_more => Some(Cursor::After(token_id.clone()).encode()),
+            _ => None,
+        };
+        Page {
+            items: tokens
+                .into_iter()
+                .map(|token_id| {
+                    let amount = account.get_balance(&token_id).unwrap();
+                    (token_id, U128(amount))
+                })
+                .collect(),
+            next_cursor,
+            has_more,
+        }
+    }
+
+    /// Returns deposits of given user and its shares of every pool, valued at the amounts of
+    /// pool tokens that removing the liquidity would return now.
+    pub fn get_account_portfolio(&self, account_id: ValidAccountId) -> AccountPortfolio {
+        let positions = (0..self.pools.len())
+            .filter_map(|pool_id| {
+                let pool = self.pools.get(pool_id).unwrap();
+                let shares = pool.share_balances(account_id.as_ref());
+                if shares == 0 {
+                    return None;
+                }
+                Some(LiquidityPosition {
+                    pool_id,
+                    shares: U128(shares),
+                    token_account_ids: pool.tokens().to_vec(),
+                    amounts: pool
+                        .predict_remove_liquidity(shares)
+                        .into_iter()
+                        .map(U128)
+                        .collect(),
+                })
+            })
+            .collect();
+        AccountPortfolio {
+            deposits: self.get_deposits(account_id),
+            positions,
+        }
+    }
+
     /// Returns balance of the deposit for given user outside of any pools.
     pub fn get_deposit(&self, account_id: ValidAccountId, token_id: ValidAccountId) -> U128 {
         self.internal_get_deposit(account_id.as_ref(), token_id.as_ref())
@@ -215,15 +631,121 @@ impl Contract {
         token_out: ValidAccountId,
     ) -> U128 {
         let pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
-        pool.get_return(token_in.as_ref(), amount_in.into(), token_out.as_ref(), &AdminFees::new(self.exchange_fee))
+        let fees = AdminFees::new(self.internal_get_pool_admin_fees(pool_id).exchange_fee)
+            .with_fee_side(self.internal_get_fee_side(pool_id, &pool));
+        pool.get_return(token_in.as_ref(), amount_in.into(), token_out.as_ref(), &fees)
+            .into()
+    }
+
+    /// Given specific pool, returns smallest amount of token_in to swap for at least
+    /// `amount_out` of token_out.
+    pub fn get_input_for_output(
+        &self,
+        pool_id: u64,
+        token_in: ValidAccountId,
+        token_out: ValidAccountId,
+        amount_out: U128,
+    ) -> U128 {
+        let pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
+        let fees = AdminFees::new(self.internal_get_pool_admin_fees(pool_id).exchange_fee)
+            .with_fee_side(self.internal_get_fee_side(pool_id, &pool));
+        pool.get_input_for_output(token_in.as_ref(), amount_out.into(), token_out.as_ref(), &fees)
             .into()
     }
 
+    /// Returns amount out of the last swap of the route. Swaps without amount in take the output
+    /// of the previous one, as in `swap`.
+    pub fn get_return_by_route(&self, route: Vec<SwapAction>) -> U128 {
+        self.simulate_actions(route.into_iter().map(Action::Swap).collect(), None)
+            .pop()
+            .expect(ERR370_INVALID_ROUTE)
+            .amount_out
+    }
+
+    /// Runs actions with the math of `execute_actions` on copies of the pools, without changing
+    /// state, and returns amounts, fees and price impact of each action. Pools used by several
+    /// swaps see reserves changed by the previous ones. If `sender_id` is given, input amounts
+    /// are taken from its deposits and fail like execution if they are not enough. Min amounts
+    /// and deadlines aren't checked, so they can be derived from the result.
+    pub fn simulate_actions(
+        &self,
+        actions: Vec<Action>,
+        sender_id: Option<ValidAccountId>,
+    ) -> Vec<ActionSimulation> {
+        let mut pools = HashMap::new();
+        let mut deposits = sender_id.map(|sender_id| (sender_id.into(), HashMap::new()));
+        let mut prev_amount = None;
+        let mut results = vec![];
+        for action in actions {
+            let (pool_ids, tokens, amount_in) = match action {
+                Action::Swap(swap_action) => (
+                    vec![swap_action.pool_id],
+                    vec![swap_action.token_in, swap_action.token_out],
+                    swap_action.amount_in,
+                ),
+                Action::RoutedSwap(routed_swap_action) => {
+                    assert_eq!(
+                        routed_swap_action.tokens.len(),
+                        routed_swap_action.pool_ids.len() + 1,
+                        "{}",
+                        ERR370_INVALID_ROUTE
+                    );
+                    (
+                        routed_swap_action.pool_ids,
+                        routed_swap_action.tokens,
+                        routed_swap_action.amount_in,
+                    )
+                }
+            };
+            let amount_in = amount_in
+                .map(|amount_in| amount_in.0)
+                .or(prev_amount)
+                .expect(ERR41_WRONG_ACTION_RESULT);
+            let mut amount = amount_in;
+            let mut remaining = FEE_DIVISOR as u128;
+            let mut swaps = vec![];
+            for (hop, pool_id) in pool_ids.into_iter().enumerate() {
+                let swap = self.internal_simulate_swap(
+                    &mut pools,
+                    &mut deposits,
+                    pool_id,
+                    &tokens[hop],
+                    amount,
+                    &tokens[hop + 1],
+                );
+                amount = swap.amount_out.0;
+                remaining = remaining * (FEE_DIVISOR - swap.price_impact) as u128
+                    / FEE_DIVISOR as u128;
+                swaps.push(swap);
+            }
+            prev_amount = Some(amount);
+            results.push(ActionSimulation {
+                amount_in: U128(amount_in),
+                amount_out: U128(amount),
+                price_impact: FEE_DIVISOR - remaining as u32,
+                swaps,
+            });
+        }
+        results
+    }
+
     /// Get contract level whitelisted tokens.
     pub fn get_whitelisted_tokens(&self) -> Vec<AccountId> {
         self.whitelisted_tokens.to_vec()
     }
 
+    /// Get tokens that route search may use as intermediary hops.
+    pub fn get_route_intermediaries(&self) -> Vec<AccountId> {
+        self.route_intermediaries.to_vec()
+    }
+
+    /// Get referral fee tiers for swaps of given input token.
+    pub fn get_referral_fee_tiers(&self, token_id: ValidAccountId) -> Vec<ReferralFeeTier> {
+        self.referral_fee_tiers
+            .get(token_id.as_ref())
+            .unwrap_or_default()
+    }
+
     /// Get specific user whitelisted tokens.
     pub fn get_user_whitelisted_tokens(&self, account_id: ValidAccountId) -> Vec<AccountId> {
         self.internal_get_account(account_id.as_ref())
@@ -238,7 +760,7 @@ impl Contract {
             Some(
                 RefStorageState {
                     deposit: U128(account.near_amount),
-                    usage: U128(account.storage_usage()),
+                    usage: U128(account.storage_usage(self.internal_storage_byte_price())),
                 }
             )           
         } else {
@@ -246,13 +768,38 @@ impl Contract {
         }
     }
 
+    /// Get bytes per component of user's storage usage, to show what the storage deposit pays for.
+    pub fn get_storage_usage_breakdown(
+        &self,
+        account_id: ValidAccountId,
+    ) -> Option<StorageUsageBreakdown> {
+        self.internal_get_account(account_id.as_ref())
+            .map(|account| {
+                let tokens = account.tokens.len();
+                let legacy_tokens = account.legacy_tokens.len() as u64;
+                let token_bytes = tokens * TOKEN_STORAGE;
+                let legacy_token_bytes = legacy_tokens * LEGACY_TOKEN_STORAGE;
+                StorageUsageBreakdown {
+                    account_bytes: INIT_ACCOUNT_STORAGE,
+                    tokens,
+                    token_bytes,
+                    legacy_tokens,
+                    legacy_token_bytes,
+                    total_bytes: INIT_ACCOUNT_STORAGE + token_bytes + legacy_token_bytes,
+                    storage_byte_cost: U128(self.internal_storage_byte_price()),
+                    deposit: U128(account.near_amount),
+                    usage: U128(account.storage_usage(self.internal_storage_byte_price())),
+                }
+            })
+    }
+
     pub fn predict_add_stable_liquidity(
         &self,
         pool_id: u64,
         amounts: &Vec<U128>,
     ) -> U128 {
         let pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
-        pool.predict_add_stable_liquidity(&amounts.into_iter().map(|x| x.0).collect(), &AdminFees::new(self.exchange_fee))
+        pool.predict_add_stable_liquidity(&amounts.into_iter().map(|x| x.0).collect(), &AdminFees::new(self.internal_get_pool_admin_fees(pool_id).exchange_fee))
             .into()
     }
 
@@ -271,7 +818,76 @@ impl Contract {
         amounts: &Vec<U128>,
     ) -> U128 {
         let pool = self.pools.get(pool_id).expect("ERR_NO_POOL");
-        pool.predict_remove_liquidity_by_tokens(&amounts.into_iter().map(|x| x.0).collect(), &AdminFees::new(self.exchange_fee))
+        pool.predict_remove_liquidity_by_tokens(&amounts.into_iter().map(|x| x.0).collect(), &AdminFees::new(self.internal_get_pool_admin_fees(pool_id).exchange_fee))
             .into()
     }
 }
+
+impl Contract {
+    /// Swaps on the copy of the pool and moves amounts of the simulated sender's deposits.
+    /// Admin fee shares are left out, so no state is written.
+    fn internal_simulate_swap(
+        &self,
+        pools: &mut HashMap<u64, Pool>,
+        deposits: &mut Option<(AccountId, HashMap<AccountId, Balance>)>,
+        pool_id: u64,
+        token_in: &AccountId,
+        amount_in: Balance,
+        token_out: &AccountId,
+    ) -> SwapSimulation {
+        self.assert_token_responsive(token_in);
+        self.assert_token_responsive(token_out);
+        self.assert_pool_launched(pool_id);
+        self.assert_pool_not_paused(pool_id);
+        self.assert_pool_not_flash_loaned(pool_id);
+        if let Some((sender_id, balances)) = deposits {
+            let balance = balances
+                .entry(token_in.clone())
+                .or_insert_with(|| self.internal_get_deposit(sender_id, token_in));
+            assert!(*balance >= amount_in, "{}", ERR22_NOT_ENOUGH_TOKENS);
+            *balance -= amount_in;
+        }
+        let pool = pools
+            .entry(pool_id)
+            .or_insert_with(|| self.pools.get(pool_id).expect("ERR_NO_POOL"));
+        let fee_side = self.internal_get_fee_side(pool_id, pool);
+        let spot_prices = pool.spot_prices(SIMULATION_PRICE_PRECISION);
+        let amount_out = pool.swap(
+            token_in,
+            amount_in,
+            token_out,
+            0,
+            AdminFees::zero().with_fee_side(fee_side),
+        );
+        if let Some((sender_id, balances)) = deposits {
+            *balances
+                .entry(token_out.clone())
+                .or_insert_with(|| self.internal_get_deposit(sender_id, token_out)) += amount_out;
+        }
+        let fee = U256::from(amount_in) * U256::from(pool.get_fee()) / U256::from(FEE_DIVISOR);
+        let price_impact = spot_prices
+            .map(|prices| {
+                let tokens = pool.tokens();
+                let index = |token_id: &AccountId| {
+                    tokens.iter().position(|id| id == token_id).unwrap()
+                };
+                let value_in = U256::from(amount_in) * prices[index(token_in)];
+                let value_out = U256::from(amount_out) * prices[index(token_out)];
+                if value_in.is_zero() || value_out >= value_in {
+                    0
+                } else {
+                    ((value_in - value_out) * U256::from(FEE_DIVISOR) / value_in).as_u32()
+                }
+            })
+            .unwrap_or(FEE_DIVISOR);
+        SwapSimulation {
+            pool_id,
+            token_in: token_in.clone(),
+            amount_in: U128(amount_in),
+            token_out: token_out.clone(),
+            amount_out: U128(amount_out),
+            fee: U128(fee.as_u128()),
+            price_impact,
+        }
+    }
+}
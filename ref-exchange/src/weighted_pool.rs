@@ -0,0 +1,653 @@
+use std::cmp::min;
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupMap;
+use near_sdk::json_types::ValidAccountId;
+use near_sdk::{env, AccountId, Balance};
+
+use crate::admin_fee::AdminFees;
+use crate::errors::{
+    ERR13_LP_NOT_REGISTERED, ERR14_LP_ALREADY_REGISTERED, ERR31_ZERO_AMOUNT, ERR32_ZERO_SHARES,
+    ERR380_INVALID_WEIGHTS, ERR381_MAX_IN_RATIO, ERR382_EMPTY_RESERVE, ERR383_MAX_OUT_RATIO,
+    ERR384_AMOUNT_IN_OVERFLOW, ERR409_DONATION_TO_EMPTY_POOL,
+};
+use crate::utils::{
+    add_to_collection, min_amount_in, SwapVolume, FEE_DIVISOR, INIT_SHARES_SUPPLY, U256,
+};
+use crate::StorageKey;
+
+pub const MIN_WEIGHTED_POOL_TOKENS: usize = 2;
+pub const MAX_WEIGHTED_POOL_TOKENS: usize = 8;
+
+/// Weights of pool tokens are parts of `WEIGHT_DIVISOR` and sum up to it.
+pub const WEIGHT_DIVISOR: u32 = 10_000;
+/// Min weight of a token, 1%.
+pub const MIN_WEIGHT: u32 = 100;
+
+/// Max part of the input token reserve swapped at once, in bps of `FEE_DIVISOR`.
+pub const MAX_IN_RATIO: u32 = 5_000;
+/// Max part of the output token reserve asked for at once, in bps of `FEE_DIVISOR`.
+pub const MAX_OUT_RATIO: u32 = 3_000;
+
+/// Fixed point one of the swap math.
+const ONE: u128 = 1_000_000_000_000_000_000;
+/// Precision of the fractional power approximation.
+const POW_PRECISION: u128 = ONE / 10_000_000_000;
+/// Bound of the rounding error of the power, in fixed point of `ONE`.
+const POW_ROUNDING: u128 = 100;
+
+fn mul_down(a: U256, b: U256) -> U256 {
+    a * b / U256::from(ONE)
+}
+
+fn div_down(a: U256, b: U256) -> U256 {
+    a * U256::from(ONE) / b
+}
+
+fn div_up(a: U256, b: U256) -> U256 {
+    (a * U256::from(ONE) + b - 1) / b
+}
+
+/// Returns `base^exp` for integer `exp`.
+fn pow_int(mut base: U256, mut exp: U256) -> U256 {
+    let mut result = U256::from(ONE);
+    while !exp.is_zero() {
+        if exp.low_u32() & 1 == 1 {
+            result = mul_down(result, base);
+        }
+        base = mul_down(base, base);
+        exp >>= 1;
+    }
+    result
+}
+
+/// Returns `base^exp` for `exp` below one by its binomial series, `base` in (0, 2).
+fn pow_frac(base: U256, exp: U256) -> U256 {
+    let one = U256::from(ONE);
+    let (x, x_neg) = if base >= one {
+        (base - one, false)
+    } else {
+        (one - base, true)
+    };
+    let mut term = one;
+    let mut sum = one;
+    let mut negative = false;
+    let mut i = 1u128;
+    while term >= U256::from(POW_PRECISION) {
+        let big_k = U256::from(i * ONE);
+        let (c, c_neg) = if exp >= big_k - one {
+            (exp - (big_k - one), false)
+        } else {
+            ((big_k - one) - exp, true)
+        };
+        term = mul_down(term, mul_down(c, x));
+        term = div_down(term, big_k);
+        if term.is_zero() {
+            break;
+        }
+        if x_neg {
+            negative = !negative;
+        }
+        if c_neg {
+            negative = !negative;
+        }
+        if negative {
+            sum = sum.saturating_sub(term);
+        } else {
+            sum += term;
+        }
+        i += 1;
+    }
+    sum
+}
+
+/// Returns `base^exp` in fixed point of `ONE`, `base` in (0, 2).
+fn pow(base: U256, exp: U256) -> U256 {
+    let one = U256::from(ONE);
+    let whole = exp / one;
+    let remain = exp - whole * one;
+    let whole_pow = pow_int(base, whole);
+    if remain.is_zero() {
+        return whole_pow;
+    }
+    mul_down(whole_pow, pow_frac(base, remain))
+}
+
+/// Implementation of weighted pool, that maintains constant weighted product of balances of all
+/// the tokens, each balance raised to the weight of its token. Similar in design to "Balancer".
+/// Liquidity providers deposit and withdraw tokens in proportion of the pool balances.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct WeightedPool {
+    /// List of tokens in the pool.
+    pub token_account_ids: Vec<AccountId>,
+    /// Weights of the tokens, in parts of `WEIGHT_DIVISOR`.
+    pub weights: Vec<u32>,
+    /// How much of each token the pool has.
+    pub amounts: Vec<Balance>,
+    /// Volumes accumulated by this pool.
+    pub volumes: Vec<SwapVolume>,
+    /// Fee charged for swap (gets divided by FEE_DIVISOR).
+    pub total_fee: u32,
+    /// Shares of the pool by liquidity providers.
+    pub shares: LookupMap<AccountId, Balance>,
+    /// Total number of shares.
+    pub shares_total_supply: Balance,
+}
+
+impl WeightedPool {
+    pub fn new(
+        id: u32,
+        token_account_ids: Vec<ValidAccountId>,
+        weights: Vec<u32>,
+        total_fee: u32,
+    ) -> Self {
+        assert!(total_fee < FEE_DIVISOR, "ERR_FEE_TOO_LARGE");
+        assert!(
+            token_account_ids.len() >= MIN_WEIGHTED_POOL_TOKENS
+                && token_account_ids.len() <= MAX_WEIGHTED_POOL_TOKENS,
+            "ERR_WRONG_TOKEN_COUNT"
+        );
+        assert!(
+            weights.len() == token_account_ids.len()
+                && weights.iter().all(|weight| *weight >= MIN_WEIGHT)
+                && weights.iter().map(|weight| *weight as u64).sum::<u64>()
+                    == WEIGHT_DIVISOR as u64,
+            "{}",
+            ERR380_INVALID_WEIGHTS
+        );
+        Self {
+            token_account_ids: token_account_ids.iter().map(|a| a.clone().into()).collect(),
+            weights,
+            amounts: vec![0u128; token_account_ids.len()],
+            volumes: vec![SwapVolume::default(); token_account_ids.len()],
+            total_fee,
+            shares: LookupMap::new(StorageKey::Shares { pool_id: id }),
+            shares_total_supply: 0,
+        }
+    }
+
+    /// Register given account with 0 balance in shares.
+    /// Storage payment should be checked by caller.
+    pub fn share_register(&mut self, account_id: &AccountId) {
+        if self.shares.contains_key(account_id) {
+            env::panic(ERR14_LP_ALREADY_REGISTERED.as_bytes());
+        }
+        self.shares.insert(account_id, &0);
+    }
+
+    /// Transfers shares from predecessor to receiver.
+    pub fn share_transfer(&mut self, sender_id: &AccountId, receiver_id: &AccountId, amount: u128) {
+        let balance = self.shares.get(sender_id).expect("ERR_NO_SHARES");
+        if let Some(new_balance) = balance.checked_sub(amount) {
+            self.shares.insert(sender_id, &new_balance);
+        } else {
+            env::panic(b"ERR_NOT_ENOUGH_SHARES");
+        }
+        let balance_out = self.shares.get(receiver_id).expect(ERR13_LP_NOT_REGISTERED);
+        self.shares.insert(receiver_id, &(balance_out + amount));
+    }
+
+    pub fn share_is_registered(&self, account_id: &AccountId) -> bool {
+        self.shares.contains_key(account_id)
+    }
+
+    /// Returns balance of shares for given user.
+    pub fn share_balance_of(&self, account_id: &AccountId) -> Balance {
+        self.shares.get(account_id).unwrap_or_default()
+    }
+
+    /// Adds amount of the token to reserves without minting shares, all LPs gain from it.
+    pub fn donate(&mut self, token_id: &AccountId, amount: Balance) {
+        assert!(self.shares_total_supply > 0, "{}", ERR409_DONATION_TO_EMPTY_POOL);
+        let index = self.token_index(token_id);
+        self.amounts[index] += amount;
+    }
+
+    /// Returns total number of shares in this pool.
+    pub fn share_total_balance(&self) -> Balance {
+        self.shares_total_supply
+    }
+
+    /// Returns list of tokens in this pool.
+    pub fn tokens(&self) -> &[AccountId] {
+        &self.token_account_ids
+    }
+
+    /// Adds the amounts of tokens to liquidity pool and returns number of shares that this user receives.
+    /// The first deposit sets prices of the tokens, later ones are taken in proportion of the pool balances.
+    /// Updates amount to amount kept in the pool.
+    pub fn add_liquidity(&mut self, sender_id: &AccountId, amounts: &mut [Balance]) -> Balance {
+        assert_eq!(
+            amounts.len(),
+            self.token_account_ids.len(),
+            "ERR_WRONG_TOKEN_COUNT"
+        );
+        let shares = if self.shares_total_supply > 0 {
+            let mut fair_supply = U256::max_value();
+            for (amount, pool_amount) in amounts.iter().zip(self.amounts.iter()) {
+                assert!(*amount > 0, "{}", ERR31_ZERO_AMOUNT);
+                assert!(*pool_amount > 0, "{}", ERR382_EMPTY_RESERVE);
+                fair_supply = min(
+                    fair_supply,
+                    U256::from(*amount) * U256::from(self.shares_total_supply) / *pool_amount,
+                );
+            }
+            for (amount, pool_amount) in amounts.iter_mut().zip(self.amounts.iter_mut()) {
+                let value = U256::from(*pool_amount) * fair_supply;
+                // Round up, so LP always pays at least the value of minted shares.
+                let paid = ((value + U256::from(self.shares_total_supply - 1))
+                    / U256::from(self.shares_total_supply))
+                .as_u128();
+                assert!(paid > 0, "{}", ERR31_ZERO_AMOUNT);
+                *pool_amount += paid;
+                *amount = paid;
+            }
+            fair_supply.as_u128()
+        } else {
+            for (amount, pool_amount) in amounts.iter().zip(self.amounts.iter_mut()) {
+                assert!(*amount > 0, "{}", ERR31_ZERO_AMOUNT);
+                *pool_amount += *amount;
+            }
+            INIT_SHARES_SUPPLY
+        };
+        self.mint_shares(sender_id, shares);
+        assert!(shares > 0, "{}", ERR32_ZERO_SHARES);
+        env::log(
+            format!(
+                "Liquidity added {:?}, minted {} shares",
+                amounts
+                    .iter()
+                    .zip(self.token_account_ids.iter())
+                    .map(|(amount, token_id)| format!("{} {}", amount, token_id))
+                    .collect::<Vec<String>>(),
+                shares
+            )
+            .as_bytes(),
+        );
+        shares
+    }
+
+    /// Mint new shares for given user.
+    fn mint_shares(&mut self, account_id: &AccountId, shares: Balance) {
+        if shares == 0 {
+            return;
+        }
+        self.shares_total_supply += shares;
+        add_to_collection(&mut self.shares, account_id, shares);
+    }
+
+    /// Returns amounts of tokens given shares are worth.
+    pub fn predict_remove_liquidity(&self, shares: Balance) -> Vec<Balance> {
+        self.amounts
+            .iter()
+            .map(|amount| {
+                (U256::from(*amount) * U256::from(shares) / U256::from(self.shares_total_supply))
+                    .as_u128()
+            })
+            .collect()
+    }
+
+    /// Burns given number of shares and returns amounts of tokens they are worth.
+    pub fn remove_liquidity(
+        &mut self,
+        sender_id: &AccountId,
+        shares: Balance,
+        min_amounts: Vec<Balance>,
+    ) -> Vec<Balance> {
+        assert_eq!(
+            min_amounts.len(),
+            self.token_account_ids.len(),
+            "ERR_WRONG_TOKEN_COUNT"
+        );
+        let prev_shares_amount = self.shares.get(sender_id).expect("ERR_NO_SHARES");
+        assert!(prev_shares_amount >= shares, "ERR_NOT_ENOUGH_SHARES");
+        let result = self.predict_remove_liquidity(shares);
+        for i in 0..self.token_account_ids.len() {
+            assert!(result[i] >= min_amounts[i], "ERR_MIN_AMOUNT");
+            self.amounts[i] -= result[i];
+        }
+        // Never unregister a LP when he removed all his liquidity.
+        self.shares
+            .insert(sender_id, &(prev_shares_amount - shares));
+        env::log(
+            format!(
+                "{} shares of liquidity removed: receive back {:?}",
+                shares,
+                result
+                    .iter()
+                    .zip(self.token_account_ids.iter())
+                    .map(|(amount, token_id)| format!("{} {}", amount, token_id))
+                    .collect::<Vec<String>>(),
+            )
+            .as_bytes(),
+        );
+        self.shares_total_supply -= shares;
+        result
+    }
+
+    /// Returns token index for given pool.
+    fn token_index(&self, token_id: &AccountId) -> usize {
+        self.token_account_ids
+            .iter()
+            .position(|id| id == token_id)
+            .expect("ERR_MISSING_TOKEN")
+    }
+
+    /// Returns number of tokens in outcome, given amount.
+    /// Tokens are provided as indexes into token list for given pool.
+    /// `amount_out = balance_out * (1 - (balance_in / (balance_in + amount_in)) ^ (weight_in / weight_out))`
+    /// with fee taken from the amount in. Power is approximated, the approximation error is
+    /// deducted from the amount out so the pool never loses on it. The error of the series is
+    /// below `POW_PRECISION` of `1 - base`, so it scales down with the swap.
+    fn internal_get_return(
+        &self,
+        token_in: usize,
+        amount_in: Balance,
+        token_out: usize,
+    ) -> Balance {
+        let in_balance = U256::from(self.amounts[token_in]);
+        let out_balance = U256::from(self.amounts[token_out]);
+        assert!(
+            in_balance > U256::zero()
+                && out_balance > U256::zero()
+                && token_in != token_out
+                && amount_in > 0,
+            "ERR_INVALID"
+        );
+        assert!(
+            U256::from(amount_in) * U256::from(FEE_DIVISOR)
+                <= in_balance * U256::from(MAX_IN_RATIO),
+            "{}",
+            ERR381_MAX_IN_RATIO
+        );
+        let amount_with_fee = U256::from(amount_in) * U256::from(FEE_DIVISOR - self.total_fee)
+            / U256::from(FEE_DIVISOR);
+        let base = div_up(in_balance, in_balance + amount_with_fee);
+        let exp = div_down(
+            U256::from(self.weights[token_in]),
+            U256::from(self.weights[token_out]),
+        );
+        let error = mul_down(U256::from(ONE) - base, U256::from(POW_PRECISION))
+            + U256::from(POW_ROUNDING);
+        let ratio = U256::from(ONE)
+            .saturating_sub(pow(base, exp))
+            .saturating_sub(error);
+        mul_down(out_balance, ratio).as_u128()
+    }
+
+    /// Returns how much token you will receive if swap `token_amount_in` of `token_in` for `token_out`.
+    pub fn get_return(
+        &self,
+        token_in: &AccountId,
+        amount_in: Balance,
+        token_out: &AccountId,
+    ) -> Balance {
+        self.internal_get_return(
+            self.token_index(token_in),
+            amount_in,
+            self.token_index(token_out),
+        )
+    }
+
+    /// Returns smallest amount of `token_in` to swap for at least `amount_out` of `token_out`.
+    /// Starts from the inverse of the return formula
+    /// `amount_in = balance_in * ((balance_out / (balance_out - amount_out)) ^ (weight_out / weight_in) - 1)`
+    /// with fee added to it, then corrects its approximation error against `get_return`.
+    /// `amount_out` is capped at `MAX_OUT_RATIO` of the reserve, where the power stays accurate.
+    pub fn get_input_for_output(
+        &self,
+        token_in: &AccountId,
+        amount_out: Balance,
+        token_out: &AccountId,
+    ) -> Balance {
+        let in_idx = self.token_index(token_in);
+        let out_idx = self.token_index(token_out);
+        let in_balance = U256::from(self.amounts[in_idx]);
+        let out_balance = U256::from(self.amounts[out_idx]);
+        assert!(
+            in_balance > U256::zero()
+                && out_balance > U256::zero()
+                && in_idx != out_idx
+                && amount_out > 0,
+            "ERR_INVALID"
+        );
+        assert!(
+            U256::from(amount_out) * U256::from(FEE_DIVISOR)
+                <= out_balance * U256::from(MAX_OUT_RATIO),
+            "{}",
+            ERR383_MAX_OUT_RATIO
+        );
+        let base = div_down(out_balance - U256::from(amount_out), out_balance);
+        let exp = div_down(
+            U256::from(self.weights[out_idx]),
+            U256::from(self.weights[in_idx]),
+        );
+        let power = std::cmp::max(pow(base, exp), U256::one());
+        let amount_with_fee = in_balance * (U256::from(ONE).saturating_sub(power)) / power;
+        let estimate = amount_with_fee * U256::from(FEE_DIVISOR)
+            / U256::from(FEE_DIVISOR - self.total_fee);
+        assert!(estimate <= U256::from(Balance::MAX), "{}", ERR384_AMOUNT_IN_OVERFLOW);
+        let estimate = estimate.as_u128();
+        min_amount_in(estimate, amount_out, |amount_in| {
+            self.internal_get_return(in_idx, amount_in, out_idx)
+        })
+    }
+
+    /// Returns price of each token in the first one, in their smallest units multiplied by
+    /// `precision`. None if the pool is empty.
+    pub fn spot_prices(&self, precision: u128) -> Option<Vec<U256>> {
+        if self.amounts.contains(&0) {
+            return None;
+        }
+        Some(
+            self.amounts
+                .iter()
+                .zip(self.weights.iter())
+                .map(|(amount, weight)| {
+                    U256::from(self.amounts[0]) * U256::from(*weight) * U256::from(precision)
+                        / (U256::from(*amount) * U256::from(self.weights[0]))
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns given pool's total fee.
+    pub fn get_fee(&self) -> u32 {
+        self.total_fee
+    }
+
+    /// Returns volumes of the given pool.
+    pub fn get_volumes(&self) -> Vec<SwapVolume> {
+        self.volumes.clone()
+    }
+
+    /// Swap `token_amount_in` of `token_in` token into `token_out` and return how much was received.
+    /// Assuming that `token_amount_in` was already received from `sender_id`.
+    pub fn swap(
+        &mut self,
+        token_in: &AccountId,
+        amount_in: Balance,
+        token_out: &AccountId,
+        min_amount_out: Balance,
+        admin_fee: &AdminFees,
+    ) -> Balance {
+        assert_ne!(token_in, token_out, "ERR_SAME_TOKEN_SWAP");
+        let in_idx = self.token_index(token_in);
+        let out_idx = self.token_index(token_out);
+        let amount_out = self.internal_get_return(in_idx, amount_in, out_idx);
+        assert!(amount_out >= min_amount_out, "ERR_MIN_AMOUNT");
+        env::log(
+            format!(
+                "Swapped {} {} for {} {}",
+                amount_in, token_in, amount_out, token_out
+            )
+            .as_bytes(),
+        );
+
+        self.amounts[in_idx] += amount_in;
+        self.amounts[out_idx] -= amount_out;
+
+        // Fee stays in the pool, parts of it are allocated to exchange and referral by issuing
+        // shares worth them. Shares of a single sided deposit are bounded by its weighted part of
+        // the pool, so issued shares never exceed the fee.
+        let fee = U256::from(amount_in) * U256::from(self.total_fee) / U256::from(FEE_DIVISOR);
+        let fee_shares = |fee_part: u32| {
+            (fee * U256::from(fee_part)
+                * U256::from(self.weights[in_idx])
+                * U256::from(self.shares_total_supply)
+                / (U256::from(FEE_DIVISOR)
+                    * U256::from(WEIGHT_DIVISOR)
+                    * U256::from(self.amounts[in_idx])))
+            .as_u128()
+        };
+        let exchange_shares = fee_shares(admin_fee.exchange_fee);
+        let referral_shares = fee_shares(admin_fee.referral_fee);
+        self.mint_shares(&admin_fee.exchange_id, exchange_shares);
+
+        // If there is referral provided and the account already registered LP, allocate it % of LP rewards.
+        if let Some(referral_id) = &admin_fee.referral_id {
+            if self.shares.contains_key(referral_id) {
+                self.mint_shares(referral_id, referral_shares);
+            }
+        }
+
+        // Keeping track of volume per each input traded separately.
+        self.volumes[in_idx].input.0 += amount_in;
+        self.volumes[in_idx].output.0 += amount_out;
+
+        amount_out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::{testing_env, MockedBlockchain};
+    use near_sdk_sim::to_yocto;
+
+    use super::*;
+    use crate::admin_fee::FeeSide;
+
+    fn admin_fee() -> AdminFees {
+        AdminFees {
+            exchange_fee: 0,
+            exchange_id: accounts(3).as_ref().clone(),
+            referral_fee: 0,
+            referral_id: None,
+            fee_side: FeeSide::Input,
+        }
+    }
+
+    #[test]
+    fn test_pow() {
+        let one = U256::from(ONE);
+        // 0.5^2 = 0.25
+        assert_eq!(pow(one / 2, one * 2), one / 4);
+        // 0.25^0.5 = 0.5
+        let root = pow(one / 4, one / 2);
+        let error = if root > one / 2 {
+            root - one / 2
+        } else {
+            one / 2 - root
+        };
+        assert!(error < U256::from(POW_PRECISION) * 10);
+    }
+
+    #[test]
+    fn test_weighted_pool_swap() {
+        let mut context = VMContextBuilder::new();
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        let mut pool = WeightedPool::new(0, vec![accounts(1), accounts(2)], vec![8000, 2000], 0);
+        let mut amounts = vec![to_yocto("80"), to_yocto("5")];
+        let shares = pool.add_liquidity(accounts(0).as_ref(), &mut amounts);
+        assert_eq!(shares, INIT_SHARES_SUPPLY);
+        // 80/20 pool prices the first token at 80 / 0.8 per 5 / 0.2 of the second.
+        let prices = pool.spot_prices(ONE).unwrap();
+        assert_eq!(prices[1], U256::from(ONE) * 4);
+
+        // Selling 1% of the first token reserve gets a bit less than 4% of the second.
+        let out = pool.swap(
+            accounts(1).as_ref(),
+            to_yocto("0.8"),
+            accounts(2).as_ref(),
+            1,
+            &admin_fee(),
+        );
+        let expected = 5.0 * (1.0 - (80.0f64 / 80.8).powf(4.0));
+        let out_f = out as f64 / 1e24;
+        assert!(out_f < expected && expected - out_f < 1e-6, "{}", out_f);
+        assert_eq!(pool.amounts, vec![to_yocto("80.8"), to_yocto("5") - out]);
+
+        // Swapping the output back returns a bit less than the input.
+        let back = pool.swap(
+            accounts(2).as_ref(),
+            out,
+            accounts(1).as_ref(),
+            1,
+            &admin_fee(),
+        );
+        assert!(back < to_yocto("0.8") && back > to_yocto("0.7999"));
+    }
+
+    #[test]
+    fn test_weighted_pool_small_swap() {
+        let mut context = VMContextBuilder::new();
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        let mut pool = WeightedPool::new(0, vec![accounts(1), accounts(2)], vec![8000, 2000], 0);
+        let mut amounts = vec![to_yocto("80"), to_yocto("5")];
+        pool.add_liquidity(accounts(0).as_ref(), &mut amounts);
+        // Swap of 1e-12 of the reserve is far below the precision of the power.
+        let out = pool.swap(
+            accounts(1).as_ref(),
+            to_yocto("80") / 1_000_000_000_000,
+            accounts(2).as_ref(),
+            1,
+            &admin_fee(),
+        );
+        let expected = to_yocto("5") * 4 / 1_000_000_000_000;
+        assert!(out < expected && out > expected / 10_000 * 9_999, "{}", out);
+    }
+
+    #[test]
+    #[should_panic(expected = "E382: pool has empty reserve")]
+    fn test_weighted_pool_empty_reserve() {
+        let mut context = VMContextBuilder::new();
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        let mut pool = WeightedPool::new(0, vec![accounts(1), accounts(2)], vec![5000, 5000], 0);
+        let mut amounts = vec![to_yocto("1"), to_yocto("1")];
+        pool.add_liquidity(accounts(0).as_ref(), &mut amounts);
+        pool.amounts[1] = 0;
+        let mut amounts = vec![to_yocto("1"), to_yocto("1")];
+        pool.add_liquidity(accounts(0).as_ref(), &mut amounts);
+    }
+
+    #[test]
+    fn test_weighted_pool_liquidity() {
+        let mut context = VMContextBuilder::new();
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        let tokens = vec![accounts(1), accounts(2), accounts(3)];
+        let mut pool = WeightedPool::new(0, tokens, vec![5000, 3000, 2000], 30);
+        let mut amounts = vec![to_yocto("50"), to_yocto("30"), to_yocto("20")];
+        pool.add_liquidity(accounts(0).as_ref(), &mut amounts);
+        pool.share_register(accounts(4).as_ref());
+        let mut amounts = vec![to_yocto("5"), to_yocto("5"), to_yocto("5")];
+        let shares = pool.add_liquidity(accounts(4).as_ref(), &mut amounts);
+        assert_eq!(shares, INIT_SHARES_SUPPLY / 10);
+        assert_eq!(amounts, vec![to_yocto("5"), to_yocto("3"), to_yocto("2")]);
+        let amounts = pool.remove_liquidity(accounts(4).as_ref(), shares, vec![0; 3]);
+        assert_eq!(amounts, vec![to_yocto("5"), to_yocto("3"), to_yocto("2")]);
+        assert_eq!(pool.share_balance_of(accounts(4).as_ref()), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "E380: invalid pool weights")]
+    fn test_weighted_pool_invalid_weights() {
+        let context = VMContextBuilder::new();
+        testing_env!(context.build());
+        WeightedPool::new(0, vec![accounts(1), accounts(2)], vec![8000, 1000], 30);
+    }
+}
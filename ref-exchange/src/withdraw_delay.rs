@@ -0,0 +1,257 @@
+//! Two-phase withdrawals for accounts opted into a withdraw delay.
+//!
+//! With a delay set, `withdraw` locks the amount in a pending withdrawal that the account can
+//! execute only after the delay. Until then the account or its guardian can cancel it, returning
+//! the amount to the deposits, so a leaked key can't move funds out before the owner reacts.
+//! Transfers of tokens and LP shares to other accounts, directly or by permits, are disabled
+//! while the delay is set, otherwise the receiver could withdraw them right away.
+//! Changes weakening the protection (shorter delay, other guardian) take effect after the current delay.
+
+use near_sdk::json_types::{WrappedDuration, WrappedTimestamp};
+use near_sdk::{Duration, PromiseOrValue};
+
+use crate::*;
+
+/// Max withdraw delay an account can choose, 30 days in nano sec.
+pub const MAX_WITHDRAW_DELAY: Duration = 30 * 24 * 3600 * 1_000_000_000;
+
+/// Max number of pending withdrawals per account, bounds storage covered by the exchange.
+pub const MAX_PENDING_WITHDRAWALS: usize = 10;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct WithdrawDelayChange {
+    pub delay: WrappedDuration,
+    pub guardian_id: Option<AccountId>,
+    pub effective_at: WrappedTimestamp,
+}
+
+/// Withdraw delay settings of an account.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct WithdrawDelay {
+    pub delay: WrappedDuration,
+    /// Account allowed to cancel pending withdrawals besides the account itself.
+    pub guardian_id: Option<AccountId>,
+    /// Weakening change waiting for the current delay to pass.
+    pub pending_change: Option<WithdrawDelayChange>,
+}
+
+impl WithdrawDelay {
+    /// Returns settings with pending change applied if it's effective already.
+    fn current(mut self) -> Self {
+        if let Some(change) = self.pending_change.clone() {
+            if env::block_timestamp() >= change.effective_at.0 {
+                self.delay = change.delay;
+                self.guardian_id = change.guardian_id;
+                self.pending_change = None;
+            }
+        }
+        self
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct PendingWithdrawal {
+    pub id: u64,
+    pub token_id: AccountId,
+    pub amount: U128,
+    pub executable_at: WrappedTimestamp,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Sets withdraw delay of the caller and the guardian that can cancel pending withdrawals.
+    /// Longer delay with the same guardian applies immediately, other changes after the current delay.
+    #[payable]
+    pub fn set_withdraw_delay(&mut self, delay: WrappedDuration, guardian_id: Option<ValidAccountId>) {
+        assert_one_yocto();
+        self.assert_contract_running();
+        assert!(delay.0 <= MAX_WITHDRAW_DELAY, "{}", ERR180_WITHDRAW_DELAY_TOO_LONG);
+        let account_id = env::predecessor_account_id();
+        self.internal_unwrap_account(&account_id);
+        let guardian_id: Option<AccountId> = guardian_id.map(|guardian_id| guardian_id.into());
+        let settings = match self.internal_get_withdraw_delay(&account_id) {
+            Some(current)
+                if current.delay.0 > 0
+                    && (delay.0 < current.delay.0 || guardian_id != current.guardian_id) =>
+            {
+                let effective_at = env::block_timestamp() + current.delay.0;
+                WithdrawDelay {
+                    pending_change: Some(WithdrawDelayChange {
+                        delay,
+                        guardian_id,
+                        effective_at: effective_at.into(),
+                    }),
+                    ..current
+                }
+            }
+            _ => WithdrawDelay {
+                delay,
+                guardian_id,
+                pending_change: None,
+            },
+        };
+        log!(
+            "Account {} withdraw delay set to {} with guardian {:?}, effective at {}",
+            account_id,
+            delay.0,
+            settings
+                .pending_change
+                .as_ref()
+                .map_or(&settings.guardian_id, |change| &change.guardian_id),
+            settings
+                .pending_change
+                .as_ref()
+                .map_or(env::block_timestamp(), |change| change.effective_at.0)
+        );
+        self.withdraw_delays.insert(&account_id, &settings);
+    }
+
+    pub fn get_withdraw_delay(&self, account_id: ValidAccountId) -> Option<WithdrawDelay> {
+        self.internal_get_withdraw_delay(account_id.as_ref())
+    }
+
+    pub fn get_pending_withdrawals(&self, account_id: ValidAccountId) -> Vec<PendingWithdrawal> {
+        self.pending_withdrawals
+            .get(account_id.as_ref())
+            .unwrap_or_default()
+    }
+
+    /// Sends tokens of caller's pending withdrawal once its delay has passed.
+    #[payable]
+    pub fn execute_withdrawal(&mut self, withdrawal_id: u64) -> Promise {
+        assert_one_yocto();
+        self.assert_contract_running();
+        let account_id = env::predecessor_account_id();
+        let withdrawal = self.internal_take_pending_withdrawal(&account_id, withdrawal_id);
+        assert!(
+            env::block_timestamp() >= withdrawal.executable_at.0,
+            "{}",
+            ERR181_WITHDRAWAL_NOT_READY
+        );
+        log!(
+            "Account {} executes withdrawal {} of {} {}",
+            account_id,
+            withdrawal_id,
+            withdrawal.amount.0,
+            withdrawal.token_id
+        );
+        self.internal_send_tokens(&account_id, &withdrawal.token_id, withdrawal.amount.0)
+    }
+
+    /// Cancels pending withdrawal of the account, returning the amount to its deposits.
+    /// Only can be called by the account or its guardian.
+    #[payable]
+    pub fn cancel_withdrawal(&mut self, account_id: ValidAccountId, withdrawal_id: u64) {
+        assert_one_yocto();
+        let account_id: AccountId = account_id.into();
+        let guardian_id = self
+            .internal_get_withdraw_delay(&account_id)
+            .and_then(|settings| settings.guardian_id);
+        assert!(
+            env::predecessor_account_id() == account_id
+                || Some(env::predecessor_account_id()) == guardian_id,
+            "{}",
+            ERR100_NOT_ALLOWED
+        );
+        let withdrawal = self.internal_take_pending_withdrawal(&account_id, withdrawal_id);
+        let mut account = self.internal_unwrap_account(&account_id);
+        account.deposit(&withdrawal.token_id, withdrawal.amount.0);
+        self.internal_save_account(&account_id, account);
+        log!(
+            "Withdrawal {} of account {} cancelled by {}",
+            withdrawal_id,
+            account_id,
+            env::predecessor_account_id()
+        );
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_get_withdraw_delay(&self, account_id: &AccountId) -> Option<WithdrawDelay> {
+        self.withdraw_delays
+            .get(account_id)
+            .map(|settings| settings.current())
+    }
+
+    /// Returns whether withdraws of the account are delayed.
+    pub(crate) fn is_withdraw_delayed(&self, account_id: &AccountId) -> bool {
+        self.internal_get_withdraw_delay(account_id)
+            .map_or(0, |settings| settings.delay.0)
+            > 0
+    }
+
+    /// Panics if the account has withdraw delay, so its funds can't move to other accounts.
+    pub(crate) fn assert_transfer_not_delayed(&self, account_id: &AccountId) {
+        assert!(
+            !self.is_withdraw_delayed(account_id),
+            "{}",
+            ERR185_TRANSFER_WITH_WITHDRAW_DELAY
+        );
+    }
+
+    /// Withdraws tokens of the account, delayed if the account opted in.
+    /// Tokens must already be subtracted from internal balance.
+    pub(crate) fn internal_withdraw_tokens(
+        &mut self,
+        account_id: &AccountId,
+        token_id: &AccountId,
+        amount: Balance,
+    ) -> PromiseOrValue<()> {
+        self.assert_no_flash_loan(account_id);
+        let delay = self
+            .internal_get_withdraw_delay(account_id)
+            .map_or(0, |settings| settings.delay.0);
+        if delay == 0 {
+            return self.internal_send_tokens(account_id, token_id, amount).into();
+        }
+        let mut withdrawals = self.pending_withdrawals.get(account_id).unwrap_or_default();
+        assert!(
+            withdrawals.len() < MAX_PENDING_WITHDRAWALS,
+            "{}",
+            ERR182_TOO_MANY_PENDING_WITHDRAWALS
+        );
+        let id = withdrawals.last().map_or(0, |withdrawal| withdrawal.id + 1);
+        let executable_at = env::block_timestamp() + delay;
+        withdrawals.push(PendingWithdrawal {
+            id,
+            token_id: token_id.clone(),
+            amount: U128(amount),
+            executable_at: executable_at.into(),
+        });
+        self.pending_withdrawals.insert(account_id, &withdrawals);
+        log!(
+            "Account {} requested withdrawal {} of {} {}, executable at {}",
+            account_id,
+            id,
+            amount,
+            token_id,
+            executable_at
+        );
+        PromiseOrValue::Value(())
+    }
+
+    fn internal_take_pending_withdrawal(
+        &mut self,
+        account_id: &AccountId,
+        withdrawal_id: u64,
+    ) -> PendingWithdrawal {
+        let mut withdrawals = self.pending_withdrawals.get(account_id).unwrap_or_default();
+        let index = withdrawals
+            .iter()
+            .position(|withdrawal| withdrawal.id == withdrawal_id)
+            .expect(ERR183_NO_PENDING_WITHDRAWAL);
+        let withdrawal = withdrawals.remove(index);
+        if withdrawals.is_empty() {
+            self.pending_withdrawals.remove(account_id);
+        } else {
+            self.pending_withdrawals.insert(account_id, &withdrawals);
+        }
+        withdrawal
+    }
+}
@@ -0,0 +1,224 @@
+//! Swaps of native NEAR through wNEAR.
+//!
+//! Owner sets the wNEAR contract. `swap_near` wraps attached NEAR into wNEAR deposited to the
+//! caller's account and then runs the swap actions, the first one taking the wrapped amount if
+//! it has no amount given. Swaps run in a separate callback, so if they fail the wrapped wNEAR
+//! stays in the account. `withdraw_near` unwraps wNEAR of the account and sends native NEAR.
+//! For accounts with a withdraw delay it's a pending withdrawal of wNEAR instead, sent as wNEAR
+//! once the delay passes.
+
+use near_sdk::{ext_contract, Gas, PromiseOrValue};
+
+use crate::utils::ext_self;
+use crate::*;
+
+/// Amount of gas for wrapping and unwrapping NEAR on the wNEAR contract.
+pub const GAS_FOR_NEAR_DEPOSIT: Gas = 10_000_000_000_000;
+pub const GAS_FOR_NEAR_WITHDRAW: Gas = 10_000_000_000_000;
+
+/// Amount of gas for resolving unwrapped NEAR, including the transfer to the account.
+pub const GAS_FOR_RESOLVE_NEAR_WITHDRAW: Gas = 20_000_000_000_000;
+
+/// Amount of gas kept by `swap_near` and its wrap callback for their own execution.
+pub const GAS_FOR_SWAP_NEAR_CALL: Gas = 20_000_000_000_000;
+
+#[ext_contract(ext_wnear)]
+pub trait WrappedNear {
+    fn near_deposit(&mut self);
+    fn near_withdraw(&mut self, amount: U128);
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Sets wNEAR contract used by `swap_near` and `withdraw_near`, None disables them.
+    /// Only can be called by owner.
+    #[payable]
+    pub fn set_wnear_account_id(&mut self, wnear_account_id: Option<ValidAccountId>) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.wnear_account_id = wnear_account_id.map(|account_id| account_id.into());
+        match &self.wnear_account_id {
+            Some(account_id) => log!("wNEAR contract set to {}", account_id),
+            None => log!("wNEAR contract removed"),
+        }
+    }
+
+    pub fn get_wnear_account_id(&self) -> Option<AccountId> {
+        self.wnear_account_id.clone()
+    }
+
+    /// Wraps attached NEAR into wNEAR of the caller's account and executes the swap actions.
+    /// Caller must be registered with wNEAR registered or whitelisted.
+    /// If referrer provided, pays referral_fee to it.
+    #[payable]
+    pub fn swap_near(
+        &mut self,
+        actions: Vec<SwapAction>,
+        referral_id: Option<ValidAccountId>,
+    ) -> Promise {
+        self.assert_contract_running();
+        assert_ne!(actions.len(), 0, "ERR_AT_LEAST_ONE_SWAP");
+        let wnear_id = self.wnear_account_id.clone().expect(ERR395_WNEAR_NOT_SET);
+        let amount = env::attached_deposit();
+        assert!(amount > 0, "{}", ERR29_ILLEGAL_WITHDRAW_AMOUNT);
+        let sender_id = env::predecessor_account_id();
+        let account = self.internal_unwrap_account(&sender_id);
+        assert!(
+            self.whitelisted_tokens.contains(&wnear_id) || account.get_balance(&wnear_id).is_some(),
+            "{}",
+            ERR12_TOKEN_NOT_WHITELISTED
+        );
+        let mut actions = actions;
+        if actions[0].amount_in.is_none() {
+            actions[0].amount_in = Some(U128(amount));
+        }
+        ext_wnear::near_deposit(&wnear_id, amount, GAS_FOR_NEAR_DEPOSIT).then(
+            ext_self::exchange_callback_near_deposit(
+                sender_id,
+                U128(amount),
+                actions,
+                referral_id.map(|referral_id| referral_id.into()),
+                &env::current_account_id(),
+                0,
+                env::prepaid_gas()
+                    - env::used_gas()
+                    - GAS_FOR_NEAR_DEPOSIT
+                    - GAS_FOR_SWAP_NEAR_CALL,
+            ),
+        )
+    }
+
+    /// Deposits wrapped NEAR to the account and schedules the swap, or refunds NEAR if wrapping
+    /// failed.
+    #[private]
+    pub fn exchange_callback_near_deposit(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128,
+        actions: Vec<SwapAction>,
+        referral_id: Option<AccountId>,
+    ) -> PromiseOrValue<U128> {
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "{}",
+            ERR25_CALLBACK_POST_WITHDRAW_INVALID
+        );
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_) => {
+                let wnear_id = self.wnear_account_id.clone().expect(ERR395_WNEAR_NOT_SET);
+                let shares = self.internal_receive_tokens(&wnear_id, amount.0);
+                let byte_price = self.internal_storage_byte_price();
+                let account = self
+                    .internal_get_account(&sender_id)
+                    .and_then(|mut account| {
+                        if account.deposit_with_storage_check(&wnear_id, shares, byte_price) {
+                            Some(account)
+                        } else {
+                            None
+                        }
+                    });
+                match account {
+                    Some(account) => self.internal_save_account(&sender_id, account),
+                    None => {
+                        log!("Account {} can't receive wrapped NEAR.", sender_id);
+                        self.internal_add_lost_tokens(&sender_id, &wnear_id, shares);
+                        return PromiseOrValue::Value(U128(0));
+                    }
+                }
+                log!("Wrapped {} NEAR for {}", amount.0, sender_id);
+                ext_self::exchange_callback_swap_near(
+                    sender_id,
+                    actions,
+                    referral_id,
+                    &env::current_account_id(),
+                    0,
+                    env::prepaid_gas() - env::used_gas() - GAS_FOR_SWAP_NEAR_CALL,
+                )
+                .into()
+            }
+            PromiseResult::Failed => {
+                log!("Failed to wrap {} NEAR, refunding {}", amount.0, sender_id);
+                Promise::new(sender_id).transfer(amount.0);
+                PromiseOrValue::Value(U128(0))
+            }
+        }
+    }
+
+    /// Executes swap actions of `swap_near` on behalf of the account and returns amount out.
+    #[private]
+    pub fn exchange_callback_swap_near(
+        &mut self,
+        sender_id: AccountId,
+        actions: Vec<SwapAction>,
+        referral_id: Option<AccountId>,
+    ) -> U128 {
+        self.assert_contract_running();
+        let mut account = self.internal_unwrap_account(&sender_id);
+        let actions: Vec<Action> = actions.into_iter().map(Action::Swap).collect();
+        let result = self.internal_execute_actions(
+            &sender_id,
+            &mut account,
+            &referral_id,
+            &actions,
+            ActionResult::None,
+        );
+        self.internal_save_account(&sender_id, account);
+        U128(result.to_amount())
+    }
+
+    /// Unwraps given amount of the caller's wNEAR and sends it as native NEAR.
+    /// Failed unwrap is returned like failed withdrawals.
+    #[payable]
+    pub fn withdraw_near(&mut self, amount: U128) -> PromiseOrValue<()> {
+        assert_one_yocto();
+        self.assert_contract_running();
+        let wnear_id = self.wnear_account_id.clone().expect(ERR395_WNEAR_NOT_SET);
+        assert!(amount.0 > 0, "{}", ERR29_ILLEGAL_WITHDRAW_AMOUNT);
+        self.assert_min_withdrawal(&wnear_id, amount.0);
+        let sender_id = env::predecessor_account_id();
+        self.assert_no_flash_loan(&sender_id);
+        let mut account = self.internal_unwrap_account(&sender_id);
+        account.withdraw(&wnear_id, amount.0);
+        self.internal_save_account(&sender_id, account);
+        if self.is_withdraw_delayed(&sender_id) {
+            return self.internal_withdraw_tokens(&sender_id, &wnear_id, amount.0);
+        }
+        let amount = self.internal_release_tokens(&wnear_id, amount.0);
+        ext_wnear::near_withdraw(U128(amount), &wnear_id, 1, GAS_FOR_NEAR_WITHDRAW)
+            .then(ext_self::exchange_callback_near_withdraw(
+                sender_id,
+                U128(amount),
+                &env::current_account_id(),
+                0,
+                GAS_FOR_RESOLVE_NEAR_WITHDRAW,
+            ))
+            .into()
+    }
+
+    /// Sends unwrapped NEAR to the account, or returns wNEAR if unwrapping failed.
+    #[private]
+    pub fn exchange_callback_near_withdraw(&mut self, sender_id: AccountId, amount: U128) {
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "{}",
+            ERR25_CALLBACK_POST_WITHDRAW_INVALID
+        );
+        let wnear_id = self.wnear_account_id.clone().expect(ERR395_WNEAR_NOT_SET);
+        self.internal_resolve_tokens(&wnear_id, amount.0);
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_) => {
+                self.internal_record_withdraw_result(&wnear_id, true);
+                log!("Unwrapped {} NEAR for {}", amount.0, sender_id);
+                Promise::new(sender_id).transfer(amount.0);
+            }
+            PromiseResult::Failed => {
+                self.internal_record_withdraw_result(&wnear_id, false);
+                self.internal_return_failed_withdrawal(&wnear_id, &sender_id, amount.0);
+            }
+        }
+    }
+}
@@ -147,6 +147,20 @@ pub fn get_deposits(
     view!(pool.get_deposits(account_id)).unwrap_json::<HashMap<String, U128>>()
 }
 
+pub fn get_failed_withdrawals(
+    pool: &ContractAccount<Exchange>,
+    account_id: ValidAccountId
+) -> HashMap<String, U128> {
+    view!(pool.get_failed_withdrawals(account_id)).unwrap_json::<HashMap<String, U128>>()
+}
+
+pub fn get_lost_tokens(
+    pool: &ContractAccount<Exchange>,
+    account_id: ValidAccountId
+) -> HashMap<String, U128> {
+    view!(pool.get_lost_tokens(account_id)).unwrap_json::<HashMap<String, U128>>()
+}
+
 /// get ref-exchange's whitelisted tokens
 pub fn get_whitelist(pool: &ContractAccount<Exchange>) -> Vec<String> {
     view!(pool.get_whitelisted_tokens()).unwrap_json::<Vec<String>>()
@@ -315,19 +329,19 @@ pub fn setup_pool_with_liquidity() -> (
     .assert_success();
     call!(
         root,
-        pool.add_liquidity(0, vec![U128(to_yocto("10")), U128(to_yocto("20"))], None),
+        pool.add_liquidity(0, vec![U128(to_yocto("10")), U128(to_yocto("20"))], None, None),
         deposit = to_yocto("0.0007")
     )
     .assert_success();
     call!(
         root,
-        pool.add_liquidity(1, vec![U128(to_yocto("20")), U128(to_yocto("10"))], None),
+        pool.add_liquidity(1, vec![U128(to_yocto("20")), U128(to_yocto("10"))], None, None),
         deposit = to_yocto("0.0007")
     )
     .assert_success();
     call!(
         root,
-        pool.add_liquidity(2, vec![U128(to_yocto("10")), U128(to_yocto("10"))], None),
+        pool.add_liquidity(2, vec![U128(to_yocto("10")), U128(to_yocto("10"))], None, None),
         deposit = to_yocto("0.0007")
     )
     .assert_success();
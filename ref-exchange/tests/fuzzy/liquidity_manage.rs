@@ -25,7 +25,7 @@ construct_uint! {
 pub fn add_liquidity_action(pool :&ContractAccount<Exchange>, operator: &Operator, simple_pool_id: u64, liquidity1: u128, liquidity2: u128) -> ExecutionResult {
     call!(
         &operator.user,
-        pool.add_liquidity(simple_pool_id, vec![U128(liquidity1), U128(liquidity2)], None),
+        pool.add_liquidity(simple_pool_id, vec![U128(liquidity1), U128(liquidity2)], None, None),
         deposit = to_yocto("0.0009")// < 0.0009 ERR_STORAGE_DEPOSIT
     )
 }
@@ -257,7 +257,7 @@ pub fn do_stable_remove_liquidity_by_shares(token_contracts: &Vec<ContractAccoun
 
     let out_come = call!(
         operator.user,
-        pool.remove_liquidity(0, U128(remove_lp_num), min_amounts),
+        pool.remove_liquidity(0, U128(remove_lp_num), min_amounts, None, None),
         deposit = 1 
     );
 
@@ -314,7 +314,7 @@ pub fn do_stable_remove_liquidity_by_token(token_contracts: &Vec<ContractAccount
 
     let out_come = call!(
         operator.user,
-        pool.remove_liquidity_by_tokens(0, remove_amounts.clone(), U128(max_burn_shares)),
+        pool.remove_liquidity_by_tokens(0, remove_amounts.clone(), U128(max_burn_shares), None),
         deposit = 1 
     );
 
@@ -25,7 +25,10 @@ pub fn swap_action(pool :&ContractAccount<Exchange>, operator: &Operator, token_
                 token_in: token_in,
                 amount_in: Some(U128(amount_in)),
                 token_out: token_out,
-                min_amount_out: U128(1)
+                min_amount_out: U128(1),
+                quote_id: None,
+                deadline: None,
+                max_price_impact_bps: None,
             }],
             None
         ),
@@ -221,7 +224,10 @@ pub fn do_stable_pool_swap(token_contracts: &Vec<ContractAccount<TestToken>>, rn
                 token_in: token_in.clone(),
                 amount_in: Some(U128(amount_in)),
                 token_out: token_out.clone(),
-                min_amount_out: U128(1)
+                min_amount_out: U128(1),
+                quote_id: None,
+                deadline: None,
+                max_price_impact_bps: None,
             }],
             None
         ),
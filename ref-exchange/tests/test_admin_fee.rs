@@ -5,7 +5,7 @@ use near_sdk_sim::{
     call, view, to_yocto,
 };
 
-use ref_exchange::{PoolInfo, SwapAction};
+use ref_exchange::{FeeSide, PoolInfo, SwapAction};
 
 use crate::common::utils::*;
 pub mod common;
@@ -37,6 +37,11 @@ fn modify_admin_fee() {
             amounts: vec![to_yocto("10").into(), to_yocto("20").into()],
             total_fee: 25,
             shares_total_supply: to_yocto("1").into(),
+            decimals_status: None,
+            fee_side: FeeSide::Input,
+            launch_delay: None,
+            metadata: None,
+            weights: None,
         }
     );
 
@@ -61,7 +66,10 @@ fn modify_admin_fee() {
                 token_in: dai(),
                 amount_in: Some(U128(to_yocto("1"))),
                 token_out: eth(),
-                min_amount_out: U128(1)
+                min_amount_out: U128(1),
+                quote_id: None,
+                deadline: None,
+                max_price_impact_bps: None,
             }],
             None
         ),
@@ -108,7 +116,10 @@ fn modify_admin_fee() {
                 token_in: usdt(),
                 amount_in: Some(U128(to_yocto("1"))),
                 token_out: eth(),
-                min_amount_out: U128(1)
+                min_amount_out: U128(1),
+                quote_id: None,
+                deadline: None,
+                max_price_impact_bps: None,
             }],
             None
         ),
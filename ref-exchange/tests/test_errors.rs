@@ -148,7 +148,10 @@ fn sim_stable_e63 () {
                 token_in: token3.account_id(),
                 amount_in: Some(U128(100)),
                 token_out: usdt(),
-                min_amount_out: U128(1)
+                min_amount_out: U128(1),
+                quote_id: None,
+                deadline: None,
+                max_price_impact_bps: None,
             }],
             None
         ),
@@ -200,26 +203,26 @@ fn sim_stable_e64 () {
 
     let outcome = call!(
         root,
-        ex.remove_liquidity(0, U128(1), vec![U128(1), U128(1), U128(1)]),
+        ex.remove_liquidity(0, U128(1), vec![U128(1), U128(1), U128(1)], None, None),
         deposit = 1
     );
     assert_failure(outcome, "E64: illegal tokens count");
     let outcome = call!(
         root,
-        ex.remove_liquidity(0, U128(1), vec![U128(1)]),
+        ex.remove_liquidity(0, U128(1), vec![U128(1)], None, None),
         deposit = 1
     );
     assert_failure(outcome, "E64: illegal tokens count");
 
     let outcome = call!(
         root,
-        ex.remove_liquidity_by_tokens(0, vec![U128(1), U128(1), U128(1)], U128(1)),
+        ex.remove_liquidity_by_tokens(0, vec![U128(1), U128(1), U128(1)], U128(1), None),
         deposit = 1
     );
     assert_failure(outcome, "E64: illegal tokens count");
     let outcome = call!(
         root,
-        ex.remove_liquidity_by_tokens(0, vec![U128(1)], U128(1)),
+        ex.remove_liquidity_by_tokens(0, vec![U128(1)], U128(1), None),
         deposit = 1
     );
     assert_failure(outcome, "E64: illegal tokens count");
@@ -286,14 +289,14 @@ fn sim_stable_e13 () {
 
     let outcome = call!(
         user,
-        ex.remove_liquidity(0, U128(1), vec![U128(1), U128(1)]),
+        ex.remove_liquidity(0, U128(1), vec![U128(1), U128(1)], None, None),
         deposit = 1
     );
     assert_failure(outcome, "E13: LP not registered");
 
     let outcome = call!(
         user,
-        ex.remove_liquidity_by_tokens(0, vec![U128(1), U128(1)], U128(1)),
+        ex.remove_liquidity_by_tokens(0, vec![U128(1), U128(1)], U128(1), None),
         deposit = 1
     );
     assert_failure(outcome, "E13: LP not registered");
@@ -339,7 +342,7 @@ fn sim_stable_e34 () {
 
     let outcome = call!(
         root,
-        ex.remove_liquidity(0, U128(lp_shares + 1), vec![U128(1), U128(1)]),
+        ex.remove_liquidity(0, U128(lp_shares + 1), vec![U128(1), U128(1)], None, None),
         deposit = 1
     );
     assert_failure(outcome, "E34: insufficient lp shares");
@@ -361,7 +364,7 @@ fn sim_stable_e34 () {
 
     let outcome = call!(
         root,
-        ex.remove_liquidity_by_tokens(0, vec![U128(1*ONE_DAI), U128(1*ONE_USDT)], U128(1)),
+        ex.remove_liquidity_by_tokens(0, vec![U128(1*ONE_DAI), U128(1*ONE_USDT)], U128(1), None),
         deposit = 1
     );
     assert_failure(outcome, "E34: insufficient lp shares");
@@ -402,14 +405,14 @@ fn sim_stable_e68 () {
 
     let outcome = call!(
         root,
-        ex.remove_liquidity(0, U128(100*ONE_LPT), vec![U128(51*ONE_DAI), U128(50*ONE_USDT)]),
+        ex.remove_liquidity(0, U128(100*ONE_LPT), vec![U128(51*ONE_DAI), U128(50*ONE_USDT)], None, None),
         deposit = 1
     );
     assert_failure(outcome, "E68: slippage error");
 
     let outcome = call!(
         root,
-        ex.remove_liquidity_by_tokens(0, vec![U128(50*ONE_DAI), U128(50*ONE_USDT)], U128(99*ONE_LPT)),
+        ex.remove_liquidity_by_tokens(0, vec![U128(50*ONE_DAI), U128(50*ONE_USDT)], U128(99*ONE_LPT), None),
         deposit = 1
     );
     assert_failure(outcome, "E68: slippage error");
@@ -422,7 +425,10 @@ fn sim_stable_e68 () {
                 token_in: dai(),
                 amount_in: Some(U128(ONE_DAI)),
                 token_out: usdt(),
-                min_amount_out: U128(2 * ONE_USDT)
+                min_amount_out: U128(2 * ONE_USDT),
+                quote_id: None,
+                deadline: None,
+                max_price_impact_bps: None,
             }],
             None
         ),
@@ -460,14 +466,14 @@ fn sim_stable_e69 () {
     // try to withdraw all from pool
     let outcome = call!(
         root,
-        ex.remove_liquidity(0, U128(200*ONE_LPT), vec![U128(1), U128(1)]),
+        ex.remove_liquidity(0, U128(200*ONE_LPT), vec![U128(1), U128(1)], None, None),
         deposit = 1
     );
     assert_failure(outcome, "E69: pool reserved token balance less than MIN_RESERVE");
 
     let outcome = call!(
         root,
-        ex.remove_liquidity_by_tokens(0, vec![U128(100*ONE_DAI), U128(100*ONE_USDT)], U128(200*ONE_LPT)),
+        ex.remove_liquidity_by_tokens(0, vec![U128(100*ONE_DAI), U128(100*ONE_USDT)], U128(200*ONE_LPT), None),
         deposit = 1
     );
     assert_failure(outcome, "E69: pool reserved token balance less than MIN_RESERVE");
@@ -475,7 +481,7 @@ fn sim_stable_e69 () {
     // remove liquidity so that the pool is small enough
     call!(
         root,
-        ex.remove_liquidity_by_tokens(0, vec![U128(99*ONE_DAI), U128(99*ONE_USDT)], U128(200*ONE_LPT)),
+        ex.remove_liquidity_by_tokens(0, vec![U128(99*ONE_DAI), U128(99*ONE_USDT)], U128(200*ONE_LPT), None),
         deposit = 1
     )
     .assert_success();
@@ -488,7 +494,10 @@ fn sim_stable_e69 () {
                 token_in: dai(),
                 amount_in: Some(U128(99*ONE_DAI)),
                 token_out: usdt(),
-                min_amount_out: U128(1)
+                min_amount_out: U128(1),
+                quote_id: None,
+                deadline: None,
+                max_price_impact_bps: None,
             }],
             None
         ),
@@ -531,7 +540,10 @@ fn sim_stable_e71 () {
                 token_in: dai(),
                 amount_in: Some(U128(1)),
                 token_out: dai(),
-                min_amount_out: U128(1)
+                min_amount_out: U128(1),
+                quote_id: None,
+                deadline: None,
+                max_price_impact_bps: None,
             }],
             None
         ),
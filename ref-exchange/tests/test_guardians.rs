@@ -145,7 +145,7 @@ fn guardians_scenario_01() {
     // add liquidity would fail
     let out_come = call!(
         root,
-        pool.add_liquidity(0, vec![U128(to_yocto("10")), U128(to_yocto("20"))], None),
+        pool.add_liquidity(0, vec![U128(to_yocto("10")), U128(to_yocto("20"))], None, None),
         deposit = to_yocto("0.0007")
     );
     assert!(!out_come.is_ok());
@@ -161,7 +161,10 @@ fn guardians_scenario_01() {
                 token_in: dai(),
                 amount_in: Some(U128(to_yocto("1"))),
                 token_out: eth(),
-                min_amount_out: U128(1)
+                min_amount_out: U128(1),
+                quote_id: None,
+                deadline: None,
+                max_price_impact_bps: None,
             }],
             None
         ),
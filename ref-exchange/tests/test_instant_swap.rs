@@ -47,7 +47,7 @@ fn direct_swap(
 
 #[test]
 fn instant_swap_scenario_01() {
-    let (root, owner, pool, token1, token2, _) = setup_pool_with_liquidity();
+    let (root, _owner, pool, token1, token2, _) = setup_pool_with_liquidity();
     let new_user = root.create_user("new_user".to_string(), to_yocto("100"));
     call!(
         new_user,
@@ -94,7 +94,7 @@ fn instant_swap_scenario_01() {
     assert!(get_storage_balance(&pool, new_user.valid_account_id()).is_none());
     assert_eq!(balance_of(&token1, &new_user.account_id), to_yocto("9"));
     assert!(
-        get_deposits(&pool, owner.valid_account_id())
+        get_lost_tokens(&pool, new_user.valid_account_id())
             .get(&token2.account_id())
             .unwrap()
             .0
@@ -122,7 +122,7 @@ fn instant_swap_scenario_01() {
 
 #[test]
 fn instant_swap_scenario_02() {
-    let (root, owner, pool, token1, token2, token3) = setup_pool_with_liquidity();
+    let (root, _owner, pool, token1, token2, token3) = setup_pool_with_liquidity();
     let new_user = root.create_user("new_user".to_string(), to_yocto("100"));
     call!(
         new_user,
@@ -161,7 +161,9 @@ fn instant_swap_scenario_02() {
     assert!(get_error_status(&out_come)
         .contains("Smart contract panicked: The account new_user is not registered"));
     // println!("total logs: {:#?}", get_logs(&out_come));
-    assert!(get_logs(&out_come)[2].contains("Account new_user has not enough storage. Depositing to owner."));
+    assert!(get_logs(&out_come)
+        .iter()
+        .any(|log| log.contains("failed, it can be claimed")));
     assert_eq!(
         get_storage_balance(&pool, new_user.valid_account_id())
             .unwrap()
@@ -178,7 +180,7 @@ fn instant_swap_scenario_02() {
     );
     assert_eq!(balance_of(&token1, &new_user.account_id), to_yocto("9"));
     assert!(
-        get_deposits(&pool, owner.valid_account_id())
+        get_failed_withdrawals(&pool, new_user.valid_account_id())
             .get(&token2.account_id())
             .unwrap()
             .0
@@ -306,7 +308,7 @@ fn instant_swap_scenario_02() {
 
 #[test]
 fn instant_swap_scenario_03() {
-    let (root, owner, pool, token1, token2, token3) = setup_pool_with_liquidity();
+    let (root, _owner, pool, token1, token2, token3) = setup_pool_with_liquidity();
     let new_user = root.create_user("new_user".to_string(), to_yocto("100"));
     call!(
         new_user,
@@ -384,7 +386,7 @@ fn instant_swap_scenario_03() {
     
     assert_eq!(balance_of(&token1, &new_user.account_id), to_yocto("2"));
     assert!(
-        get_deposits(&pool, owner.valid_account_id())
+        get_lost_tokens(&pool, new_user.valid_account_id())
             .get(&token2.account_id())
             .unwrap()
             .0 
@@ -417,7 +419,7 @@ fn instant_swap_scenario_03() {
         .contains("Smart contract panicked: The account new_user is not registered"));
 
     assert!(
-        get_deposits(&pool, new_user.valid_account_id())
+        get_failed_withdrawals(&pool, new_user.valid_account_id())
             .get(&token3.account_id())
             .unwrap()
             .0
@@ -448,7 +450,7 @@ fn instant_swap_scenario_04() {
     const ONE_DAI: u128 = 1000000000000000000;
     const ONE_USDT: u128 = 1000000;
     const ONE_USDC: u128 = 1000000;
-    let (root, owner, pool, tokens) = 
+    let (root, _owner, pool, tokens) = 
         setup_stable_pool_with_liquidity(
             vec![dai(), usdt(), usdc()],
             vec![100000*ONE_DAI, 100000*ONE_USDT, 100000*ONE_USDC],
@@ -473,7 +475,7 @@ fn instant_swap_scenario_04() {
     assert_eq!(balance_of(&tokens[0], &user.account_id), 9*ONE_DAI);
 
     assert_eq!(
-        get_deposits(&pool, owner.valid_account_id())
+        get_lost_tokens(&pool, user.valid_account_id())
             .get(&token_out.account_id())
             .unwrap()
             .0,
@@ -7,7 +7,7 @@ use near_sdk_sim::{
     call, view, to_yocto
 };
 
-use ref_exchange::{PoolInfo, SwapAction};
+use ref_exchange::{FeeSide, PoolInfo, SwapAction};
 use crate::common::utils::*;
 pub mod common;
 
@@ -38,6 +38,11 @@ fn sim_stable_swap() {
             amounts: vec![U128(100000*ONE_DAI), U128(100000*ONE_USDT), U128(100000*ONE_USDC)],
             total_fee: 25,
             shares_total_supply: U128(300000*ONE_LPT),
+            decimals_status: None,
+            fee_side: FeeSide::Output,
+            launch_delay: None,
+            metadata: None,
+            weights: None,
         }
     );
     assert_eq!(
@@ -73,7 +78,10 @@ fn sim_stable_swap() {
                 token_in: dai(),
                 amount_in: Some(U128(ONE_DAI)),
                 token_out: usdc(),
-                min_amount_out: U128(1)
+                min_amount_out: U128(1),
+                quote_id: None,
+                deadline: None,
+                max_price_impact_bps: None,
             }],
             None
         ),
@@ -90,7 +98,10 @@ fn sim_stable_swap() {
                 token_in: dai(),
                 amount_in: Some(U128(ONE_DAI)),
                 token_out: usdt(),
-                min_amount_out: U128(1)
+                min_amount_out: U128(1),
+                quote_id: None,
+                deadline: None,
+                max_price_impact_bps: None,
             }],
             None
         ),
@@ -114,6 +125,11 @@ fn sim_stable_swap() {
             amounts: vec![U128(100002*ONE_DAI), U128(99999*ONE_USDT+2500), U128(99999*ONE_USDC+2500)],
             total_fee: 25,
             shares_total_supply: U128(300000*ONE_LPT + 499999996666583 + 499999993277742),
+            decimals_status: None,
+            fee_side: FeeSide::Output,
+            launch_delay: None,
+            metadata: None,
+            weights: None,
         }
     );
 }
@@ -151,7 +167,7 @@ fn sim_stable_lp() {
     // remove by shares
     let out_come = call!(
         user1,
-        pool.remove_liquidity(0, U128(300*ONE_LPT), vec![U128(1*ONE_DAI), U128(1*ONE_USDT), U128(1*ONE_USDC)]),
+        pool.remove_liquidity(0, U128(300*ONE_LPT), vec![U128(1*ONE_DAI), U128(1*ONE_USDT), U128(1*ONE_USDC)], None, None),
         deposit = 1 
     );
     out_come.assert_success();
@@ -188,6 +204,11 @@ fn sim_stable_lp() {
             amounts: vec![U128(100500*ONE_DAI), U128(100600*ONE_USDT), U128(100800*ONE_USDC)],
             total_fee: 25,
             shares_total_supply: U128(301200*ONE_LPT+699699997426210330025+47999999735823255),
+            decimals_status: None,
+            fee_side: FeeSide::Output,
+            launch_delay: None,
+            metadata: None,
+            weights: None,
         }
     );
     assert_eq!(mft_balance_of(&pool, ":0", &user1.account_id()), 1200*ONE_LPT);
@@ -200,7 +221,7 @@ fn sim_stable_lp() {
     // remove by tokens
     let out_come = call!(
         user1,
-        pool.remove_liquidity_by_tokens(0, vec![U128(1*ONE_DAI), U128(500*ONE_USDT), U128(1*ONE_USDC)], U128(550*ONE_LPT)),
+        pool.remove_liquidity_by_tokens(0, vec![U128(1*ONE_DAI), U128(500*ONE_USDT), U128(1*ONE_USDC)], U128(550*ONE_LPT), None),
         deposit = 1 
     );
     out_come.assert_success();
@@ -220,6 +241,11 @@ fn sim_stable_lp() {
             amounts: vec![U128(100499*ONE_DAI), U128(100100*ONE_USDT), U128(100799*ONE_USDC)],
             total_fee: 25,
             shares_total_supply: U128(last_lpt_supply-502598491280079770545+95823884420348155),
+            decimals_status: None,
+            fee_side: FeeSide::Output,
+            launch_delay: None,
+            metadata: None,
+            weights: None,
         }
     );
     assert_eq!(mft_balance_of(&pool, ":0", &user1.account_id()), 1200*ONE_LPT-502598491280079770545);
@@ -244,7 +270,7 @@ fn sim_stable_lp() {
     // other remove by shares trigger slippage
     let out_come = call!(
         user2,
-        pool.remove_liquidity(0, U128(300*ONE_LPT), vec![U128(1*ONE_DAI), U128(298*ONE_USDT), U128(1*ONE_USDC)]),
+        pool.remove_liquidity(0, U128(300*ONE_LPT), vec![U128(1*ONE_DAI), U128(298*ONE_USDT), U128(1*ONE_USDC)], None, None),
         deposit = 1 
     );
     assert!(!out_come.is_ok());
@@ -257,7 +283,7 @@ fn sim_stable_lp() {
     // other remove by tokens trigger slippage
     let out_come = call!(
         user2,
-        pool.remove_liquidity_by_tokens(0, vec![U128(1*ONE_DAI), U128(298*ONE_USDT), U128(1*ONE_USDC)], U128(300*ONE_LPT)),
+        pool.remove_liquidity_by_tokens(0, vec![U128(1*ONE_DAI), U128(298*ONE_USDT), U128(1*ONE_USDC)], U128(300*ONE_LPT), None),
         deposit = 1 
     );
     assert!(!out_come.is_ok());
@@ -271,7 +297,7 @@ fn sim_stable_lp() {
     assert_eq!(mft_balance_of(&pool, ":0", &user2.account_id()), 799699997426210330025);
     let out_come = call!(
         user2,
-        pool.remove_liquidity(0, U128(300*ONE_LPT), vec![U128(1*ONE_DAI), U128(1*ONE_USDT), U128(1*ONE_USDC)]),
+        pool.remove_liquidity(0, U128(300*ONE_LPT), vec![U128(1*ONE_DAI), U128(1*ONE_USDT), U128(1*ONE_USDC)], None, None),
         deposit = 1 
     );
     out_come.assert_success();
@@ -285,7 +311,7 @@ fn sim_stable_lp() {
     // user2 remove by tokens
     let out_come = call!(
         user2,
-        pool.remove_liquidity_by_tokens(0, vec![U128(498*ONE_DAI), U128(0*ONE_USDT), U128(0*ONE_USDC)], U128(499*ONE_LPT)),
+        pool.remove_liquidity_by_tokens(0, vec![U128(498*ONE_DAI), U128(0*ONE_USDT), U128(0*ONE_USDC)], U128(499*ONE_LPT), None),
         deposit = 1 
     );
     out_come.assert_success();
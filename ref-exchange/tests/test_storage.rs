@@ -171,7 +171,7 @@ fn storage_scenario_02() {
     println!("Storage Case 0203: remove liquidity by share");
     let out_come = call!(
         new_user,
-        pool.remove_liquidity(0, U128(10*ONE_LPT), vec![U128(3*ONE_DAI), U128(3*ONE_USDT), U128(3*ONE_USDC)]),
+        pool.remove_liquidity(0, U128(10*ONE_LPT), vec![U128(3*ONE_DAI), U128(3*ONE_USDT), U128(3*ONE_USDC)], None, None),
         deposit = 1
     );
     out_come.assert_success();
@@ -182,7 +182,7 @@ fn storage_scenario_02() {
     println!("Storage Case 0204: remove liquidity by token");
     let out_come = call!(
         new_user,
-        pool.remove_liquidity_by_tokens(0, vec![U128(10*ONE_DAI), U128(1*ONE_USDT), U128(1*ONE_USDC)], U128(13*ONE_LPT)),
+        pool.remove_liquidity_by_tokens(0, vec![U128(10*ONE_DAI), U128(1*ONE_USDT), U128(1*ONE_USDC)], U128(13*ONE_LPT), None),
         deposit = 1
     );
     out_come.assert_success();
@@ -199,7 +199,10 @@ fn storage_scenario_02() {
                 token_in: dai(),
                 amount_in: Some(U128(ONE_DAI)),
                 token_out: usdc(),
-                min_amount_out: U128(1)
+                min_amount_out: U128(1),
+                quote_id: None,
+                deadline: None,
+                max_price_impact_bps: None,
             }],
             None
         ),
@@ -232,7 +235,10 @@ fn storage_scenario_02() {
                 token_in: dai(),
                 amount_in: Some(U128(ONE_DAI)),
                 token_out: usdc(),
-                min_amount_out: U128(1)
+                min_amount_out: U128(1),
+                quote_id: None,
+                deadline: None,
+                max_price_impact_bps: None,
             }],
             None
         ),
@@ -261,7 +267,10 @@ fn storage_scenario_02() {
                 token_in: dai(),
                 amount_in: Some(U128(ONE_DAI)),
                 token_out: usdc(),
-                min_amount_out: U128(1)
+                min_amount_out: U128(1),
+                quote_id: None,
+                deadline: None,
+                max_price_impact_bps: None,
             }],
             None
         ),
@@ -298,7 +307,7 @@ fn storage_scenario_02() {
     out_come.assert_success();
     let out_come = call!(
         user3,
-        pool.remove_liquidity(0, U128(5*ONE_LPT), vec![U128(1*ONE_DAI), U128(1*ONE_USDT), U128(1*ONE_USDC)]),
+        pool.remove_liquidity(0, U128(5*ONE_LPT), vec![U128(1*ONE_DAI), U128(1*ONE_USDT), U128(1*ONE_USDC)], None, None),
         deposit = 1
     );
     assert!(!out_come.is_ok());
@@ -315,7 +324,7 @@ fn storage_scenario_02() {
 
     let out_come = call!(
         user3,
-        pool.remove_liquidity(0, U128(5*ONE_LPT), vec![U128(1*ONE_DAI), U128(1*ONE_USDT), U128(1*ONE_USDC)]),
+        pool.remove_liquidity(0, U128(5*ONE_LPT), vec![U128(1*ONE_DAI), U128(1*ONE_USDT), U128(1*ONE_USDC)], None, None),
         deposit = 1
     );
     out_come.assert_success();
@@ -9,7 +9,7 @@ use near_sdk_sim::{
     call, deploy, init_simulator, to_yocto, view, ContractAccount, ExecutionResult, UserAccount,
 };
 
-use ref_exchange::{ContractContract as Exchange, PoolInfo, SwapAction};
+use ref_exchange::{ContractContract as Exchange, FeeSide, PoolInfo, SwapAction};
 use test_token::ContractContract as TestToken;
 
 near_sdk_sim::lazy_static_include::lazy_static_include_bytes! {
@@ -148,7 +148,7 @@ fn setup_pool_with_liquidity() -> (
     .assert_success();
     call!(
         root,
-        pool.add_liquidity(0, vec![U128(to_yocto("5")), U128(to_yocto("10"))], None),
+        pool.add_liquidity(0, vec![U128(to_yocto("5")), U128(to_yocto("10"))], None, None),
         deposit = to_yocto("0.0007")
     )
     .assert_success();
@@ -167,6 +167,11 @@ fn test_swap() {
             amounts: vec![to_yocto("5").into(), to_yocto("10").into()],
             total_fee: 25,
             shares_total_supply: to_yocto("1").into(),
+            decimals_status: None,
+            fee_side: FeeSide::Input,
+            launch_delay: None,
+            metadata: None,
+            weights: None,
         }
     );
     assert_eq!(
@@ -194,7 +199,10 @@ fn test_swap() {
                 token_in: dai(),
                 amount_in: Some(U128(to_yocto("1"))),
                 token_out: eth(),
-                min_amount_out: U128(1)
+                min_amount_out: U128(1),
+                quote_id: None,
+                deadline: None,
+                max_price_impact_bps: None,
             }],
             None
         ),